@@ -0,0 +1,34 @@
+//! Overhead of `counters_only()` on the hot path of `ok()`.
+//!
+//! Run with `cargo bench --bench counters`.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use futures::sink::Sink;
+use futures_test_sink::{ok, task::noop_context};
+use std::pin::Pin;
+
+fn send(c: &mut Criterion) {
+    let mut group = c.benchmark_group("send through ok()");
+    let mut cx = noop_context();
+
+    let mut plain = ok::<u64>();
+    group.bench_function("plain", |b| {
+        b.iter(|| {
+            let _ = Pin::new(&mut plain).poll_ready(&mut cx);
+            let _ = Pin::new(&mut plain).start_send(black_box(1));
+        })
+    });
+
+    let mut counted = ok::<u64>();
+    counted.counters_only();
+    group.bench_function("counters_only", |b| {
+        b.iter(|| {
+            let _ = Pin::new(&mut counted).poll_ready(&mut cx);
+            let _ = Pin::new(&mut counted).start_send(black_box(1));
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, send);
+criterion_main!(benches);
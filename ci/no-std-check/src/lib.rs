@@ -0,0 +1,16 @@
+//! Compile-only check that `futures-test-sink` works in `#![no_std]` crates.
+#![no_std]
+
+use core::iter;
+use core::task::Poll;
+use futures_test_sink::{from_iter, fuse_last::IteratorExt, ok, SinkMock};
+
+pub fn sinks() {
+    let _ok = ok::<u8>();
+    let _feedback = from_iter::<u8, _, _, ()>(
+        iter::once(Poll::Ready(Ok(()))).fuse_last(),
+        iter::repeat(Ok(())),
+    );
+    let _mock: SinkMock<_, _, _, u8, ()> =
+        SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok(()))));
+}
@@ -0,0 +1,141 @@
+use crate::{SinkFeedback, SinkMock};
+use futures::sink::Sink;
+use pin_project_lite::pin_project;
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+pin_project! {
+    /// Wrapper converting errors of the inner sink into [anyhow::Error].
+    ///
+    /// It behaves like [sink_map_err()](futures::sink::SinkExt::sink_map_err) with
+    /// `anyhow::Error::from`, but the type can be named. Create it with
+    /// [SinkFeedback::into_anyhow()] or [SinkMock::into_anyhow()].
+    ///
+    /// Requires `anyhow` feature.
+    pub struct AnyhowSink<S> {
+        #[pin]
+        inner: S,
+    }
+}
+
+impl<S> AnyhowSink<S> {
+    /// Wrap `inner` sink.
+    pub fn new(inner: S) -> Self {
+        Self { inner }
+    }
+
+    inner_accessors!(S, "the inner sink");
+}
+
+impl<S, Item> Sink<Item> for AnyhowSink<S>
+where
+    S: Sink<Item>,
+    S::Error: Into<anyhow::Error>,
+{
+    type Error = anyhow::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.project().inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Item) -> Result<(), Self::Error> {
+        self.project().inner.start_send(item).map_err(Into::into)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.project().inner.poll_flush(cx).map_err(Into::into)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.project().inner.poll_close(cx).map_err(Into::into)
+    }
+}
+
+impl<E, FI, SSI, Item> SinkFeedback<E, FI, SSI, Item> {
+    /// Convert scripted errors into [anyhow::Error].
+    ///
+    /// Requires `anyhow` feature.
+    ///
+    /// # Examples
+    /// ```
+    /// use futures::{executor::block_on, SinkExt};
+    /// use futures_test_sink::from_iter;
+    /// use std::{io, iter, task::Poll};
+    ///
+    /// fn app_send<S: futures::Sink<u8, Error = anyhow::Error> + Unpin>(
+    ///     sink: &mut S,
+    /// ) -> anyhow::Result<()> {
+    ///     block_on(sink.send(1))
+    /// }
+    ///
+    /// let poll_fallback = iter::once(Poll::Ready(Err(io::Error::from(io::ErrorKind::BrokenPipe))));
+    /// let mut sink = from_iter(poll_fallback, iter::empty()).into_anyhow();
+    /// let err = app_send(&mut sink).unwrap_err();
+    /// assert_eq!(
+    ///     err.downcast_ref::<io::Error>().unwrap().kind(),
+    ///     io::ErrorKind::BrokenPipe
+    /// );
+    /// ```
+    pub fn into_anyhow(self) -> AnyhowSink<Self> {
+        AnyhowSink::new(self)
+    }
+}
+
+impl<FlushI, ReadyI, SendI, Item, E> SinkMock<FlushI, ReadyI, SendI, Item, E> {
+    /// Convert errors of this mock into [anyhow::Error].
+    ///
+    /// Requires `anyhow` feature.
+    pub fn into_anyhow(self) -> AnyhowSink<Self> {
+        AnyhowSink::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{
+        executor::block_on,
+        stream::{self, StreamExt},
+    };
+    use std::{fmt, iter};
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Broken(u8);
+
+    impl fmt::Display for Broken {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "broken {}", self.0)
+        }
+    }
+
+    impl std::error::Error for Broken {}
+
+    #[test]
+    fn errors_are_wrapped() {
+        let feedback = vec![
+            Poll::Ready(Ok(())),
+            Poll::Pending,
+            Poll::Ready(Err(Broken(3))),
+        ]
+        .into_iter()
+        .cycle();
+        let sink = SinkMock::with_flush_feedback(feedback).into_anyhow();
+        let items = stream::iter(vec![Ok(5u8), Ok(7), Ok(9), Ok(77), Ok(79)]);
+        let err = block_on(items.forward(sink)).unwrap_err();
+        assert_eq!(err.to_string(), "broken 3");
+        assert_eq!(err.downcast::<Broken>().unwrap(), Broken(3));
+    }
+
+    #[test]
+    fn ok_is_unchanged() {
+        let mut sink = crate::from_iter(
+            iter::repeat(Poll::Ready(Ok::<_, Broken>(()))),
+            iter::repeat(Ok(())),
+        )
+        .into_anyhow();
+        let items = stream::iter(vec![Ok(5u8), Ok(7)]);
+        assert!(block_on(items.forward(&mut sink)).is_ok());
+    }
+}
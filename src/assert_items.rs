@@ -0,0 +1,191 @@
+use alloc::format;
+use alloc::string::String;
+use core::fmt::Debug;
+use core::pin::Pin;
+use futures::sink::Sink;
+use pin_project_lite::pin_project;
+
+/// Check of a single item used by [AssertItems].
+///
+/// It's implemented for every `FnMut(&Item) -> Result<(), String>`. The error is the message
+/// included in the panic.
+pub trait Validate<Item> {
+    /// Check `item`.
+    fn validate(&mut self, item: &Item) -> Result<(), String>;
+}
+
+impl<F, Item> Validate<Item> for F
+where
+    F: FnMut(&Item) -> Result<(), String>,
+{
+    fn validate(&mut self, item: &Item) -> Result<(), String> {
+        self(item)
+    }
+}
+
+/// [Validate] implementation created by [AssertItems::ordered_by()].
+pub struct OrderedBy<KF, K> {
+    key: KF,
+    last: Option<K>,
+}
+
+impl<KF, K, Item> Validate<Item> for OrderedBy<KF, K>
+where
+    KF: FnMut(&Item) -> K,
+    K: Ord + Debug,
+{
+    fn validate(&mut self, item: &Item) -> Result<(), String> {
+        let key = (self.key)(item);
+        let res = match &self.last {
+            Some(last) if key < *last => Err(format!("key {:?} is less than {:?}", key, last)),
+            _ => Ok(()),
+        };
+        self.last = Some(key);
+        res
+    }
+}
+
+pin_project! {
+    /// Wrapper that validates every item before passing it to the inner sink.
+    ///
+    /// Unlike mocks in this crate it wraps an arbitrary sink, so items still reach their real
+    /// destination. Every item passed to `start_send()` is checked first. When the check fails it
+    /// panics with the index of the item (counting from 0) and the message. Other methods are
+    /// forwarded unchanged.
+    ///
+    /// # Examples
+    /// ```
+    /// use futures::{executor::block_on, stream, StreamExt};
+    /// use futures_test_sink::AssertItems;
+    ///
+    /// let mut sink = AssertItems::new(Vec::new(), |item: &u32| {
+    ///     if *item < 100 {
+    ///         Ok(())
+    ///     } else {
+    ///         Err(format!("{} is too big", item))
+    ///     }
+    /// });
+    /// let items = stream::iter(vec![Ok(5), Ok(7), Ok(9)]);
+    /// assert_eq!(Ok(()), block_on(items.forward(&mut sink)));
+    /// assert_eq!(sink.validated_count(), 3);
+    /// assert_eq!(sink.into_inner(), vec![5, 7, 9]);
+    /// ```
+    pub struct AssertItems<S, F> {
+        #[pin]
+        inner: S,
+        check: F,
+        validated_cnt: usize,
+    }
+}
+
+impl<S, F> AssertItems<S, F> {
+    /// Wrap `inner` sink checking every item with `check`.
+    pub fn new<Item>(inner: S, check: F) -> Self
+    where
+        F: Validate<Item>,
+    {
+        Self {
+            inner,
+            check,
+            validated_cnt: 0,
+        }
+    }
+}
+
+impl<S, KF, K> AssertItems<S, OrderedBy<KF, K>> {
+    /// Wrap `inner` sink checking that items are sent in non-decreasing order of `key`.
+    ///
+    /// Every key is compared with the key of the previous item, like in
+    /// [ordering_sink()](crate::ordering_sink).
+    pub fn ordered_by<Item>(inner: S, key: KF) -> Self
+    where
+        KF: FnMut(&Item) -> K,
+        K: Ord + Debug,
+    {
+        Self::new(inner, OrderedBy { key, last: None })
+    }
+}
+
+impl<S, F> AssertItems<S, F> {
+    /// How many items passed the check.
+    pub fn validated_count(&self) -> usize {
+        self.validated_cnt
+    }
+
+    inner_accessors!(S, "the inner sink");
+}
+
+impl<S, F, Item> Sink<Item> for AssertItems<S, F>
+where
+    S: Sink<Item>,
+    F: Validate<Item>,
+{
+    type Error = S::Error;
+
+    forward_sink!("assert_items", poll_ready, poll_flush, poll_close);
+
+    fn start_send(self: Pin<&mut Self>, item: Item) -> Result<(), Self::Error> {
+        let this = self.project();
+        if let Err(msg) = this.check.validate(&item) {
+            panic!(
+                "AssertItems: item #{} is invalid: {}",
+                this.validated_cnt, msg
+            );
+        }
+        *this.validated_cnt += 1;
+        let res = this.inner.start_send(item);
+        wrapper_event!("assert_items", "start_send", &res);
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    use futures::{
+        executor::block_on,
+        stream::{self, StreamExt},
+    };
+
+    fn non_empty(item: &&str) -> Result<(), String> {
+        if item.is_empty() {
+            Err(String::from("empty item"))
+        } else {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn pass() {
+        let mut sink = AssertItems::new(Vec::new(), non_empty);
+        let items = stream::iter(vec![Ok("a"), Ok("b")]);
+        assert_eq!(Ok(()), block_on(items.forward(&mut sink)));
+        assert_eq!(sink.validated_count(), 2);
+        assert_eq!(sink.get_ref(), &vec!["a", "b"]);
+    }
+
+    #[test]
+    #[should_panic(expected = "AssertItems: item #2 is invalid: empty item")]
+    fn fail() {
+        let mut sink = AssertItems::new(Vec::new(), non_empty);
+        let items = stream::iter(vec![Ok("a"), Ok("b"), Ok(""), Ok("c")]);
+        let _ = block_on(items.forward(&mut sink));
+    }
+
+    #[test]
+    fn ordered_by_pass() {
+        let mut sink = AssertItems::ordered_by(Vec::new(), |(seq, _): &(u32, &str)| *seq);
+        let items = stream::iter(vec![Ok((1, "z")), Ok((1, "a")), Ok((2, "b"))]);
+        assert_eq!(Ok(()), block_on(items.forward(&mut sink)));
+        assert_eq!(sink.into_inner().len(), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "AssertItems: item #3 is invalid: key 2 is less than 3")]
+    fn ordered_by_fail() {
+        let mut sink = AssertItems::ordered_by(Vec::new(), |seq: &u32| *seq);
+        let items = stream::iter(vec![Ok(1), Ok(2), Ok(3), Ok(2)]);
+        let _ = block_on(items.forward(&mut sink));
+    }
+}
@@ -0,0 +1,386 @@
+/// Assert that `poll_ready()` of a sink returns `Poll::Ready(Ok(()))`.
+///
+/// Takes a sink by name and a [Context](core::task::Context) or a `&mut Context`, e.g. the
+/// argument of a `poll` function, pins the sink with `Pin::new()` and polls it. Works for any
+/// `Sink + Unpin` whose error is `Debug`. On failure it panics with the returned result and, if
+/// the sink is `Debug` like [SinkMock](crate::SinkMock), the sink.
+///
+/// A sink accepting more item types, or one whose item type isn't inferred yet, takes the item
+/// type as the last argument `Item = T`.
+///
+/// # Examples
+/// ```
+/// use futures::sink::drain;
+/// use futures_test_sink::{
+///     assert_flush_pending, assert_sink_ready, assert_start_send_ok, interleave_pending,
+///     task::noop_context,
+/// };
+/// use std::task::Context;
+///
+/// let mut cx = noop_context();
+/// let mut s = interleave_pending();
+/// assert_sink_ready!(s, cx);
+/// assert_start_send_ok!(s, 1);
+/// assert_flush_pending!(s, cx);
+///
+/// fn poll_drain(cx: &mut Context<'_>) {
+///     let mut s = drain();
+///     assert_sink_ready!(s, cx, Item = u8);
+/// }
+/// poll_drain(&mut cx);
+/// ```
+#[macro_export]
+macro_rules! assert_sink_ready {
+    ($sink:expr, $cx:expr, Item = $item:ty $(,)?) => {
+        $crate::__assert_poll!(
+            poll_ready,
+            $item,
+            $sink,
+            $cx,
+            ::core::task::Poll::Ready(::core::result::Result::Ok(())),
+            ::core::format_args!("Ready(Ok(()))")
+        )
+    };
+    ($sink:expr, $cx:expr $(,)?) => {
+        $crate::assert_sink_ready!($sink, $cx, Item = _)
+    };
+}
+
+/// Assert that `poll_ready()` of a sink returns `Poll::Pending`.
+///
+/// See [assert_sink_ready!](crate::assert_sink_ready).
+#[macro_export]
+macro_rules! assert_sink_pending {
+    ($sink:expr, $cx:expr, Item = $item:ty $(,)?) => {
+        $crate::__assert_poll!(
+            poll_ready,
+            $item,
+            $sink,
+            $cx,
+            ::core::task::Poll::Pending,
+            ::core::format_args!("Pending")
+        )
+    };
+    ($sink:expr, $cx:expr $(,)?) => {
+        $crate::assert_sink_pending!($sink, $cx, Item = _)
+    };
+}
+
+/// Assert that `poll_ready()` of a sink returns `Poll::Ready(Err(expected))`.
+///
+/// The error must be `PartialEq`. See [assert_sink_ready!](crate::assert_sink_ready).
+///
+/// # Examples
+/// ```
+/// use futures_test_sink::{assert_sink_err, task::noop_context, SinkMock};
+/// use std::iter;
+///
+/// let mut cx = noop_context();
+/// let mut s = SinkMock::<_, _, _, u8, _>::new(
+///     iter::empty(),
+///     iter::once("broken pipe"),
+///     iter::empty(),
+///     1,
+///     1,
+/// );
+/// assert_sink_err!(s, cx, "broken pipe");
+/// ```
+#[macro_export]
+macro_rules! assert_sink_err {
+    ($sink:expr, $cx:expr, $err:expr, Item = $item:ty $(,)?) => {{
+        let expected = $err;
+        $crate::__assert_poll!(
+            poll_ready,
+            $item,
+            $sink,
+            $cx,
+            ::core::task::Poll::Ready(::core::result::Result::Err(ref err)) if *err == expected,
+            ::core::format_args!("Ready(Err({:?}))", expected)
+        )
+    }};
+    ($sink:expr, $cx:expr, $err:expr $(,)?) => {
+        $crate::assert_sink_err!($sink, $cx, $err, Item = _)
+    };
+}
+
+/// Assert that `poll_flush()` of a sink returns `Poll::Ready(Ok(()))`.
+///
+/// See [assert_sink_ready!](crate::assert_sink_ready).
+#[macro_export]
+macro_rules! assert_flush_ready {
+    ($sink:expr, $cx:expr, Item = $item:ty $(,)?) => {
+        $crate::__assert_poll!(
+            poll_flush,
+            $item,
+            $sink,
+            $cx,
+            ::core::task::Poll::Ready(::core::result::Result::Ok(())),
+            ::core::format_args!("Ready(Ok(()))")
+        )
+    };
+    ($sink:expr, $cx:expr $(,)?) => {
+        $crate::assert_flush_ready!($sink, $cx, Item = _)
+    };
+}
+
+/// Assert that `poll_flush()` of a sink returns `Poll::Pending`.
+///
+/// See [assert_sink_ready!](crate::assert_sink_ready).
+#[macro_export]
+macro_rules! assert_flush_pending {
+    ($sink:expr, $cx:expr, Item = $item:ty $(,)?) => {
+        $crate::__assert_poll!(
+            poll_flush,
+            $item,
+            $sink,
+            $cx,
+            ::core::task::Poll::Pending,
+            ::core::format_args!("Pending")
+        )
+    };
+    ($sink:expr, $cx:expr $(,)?) => {
+        $crate::assert_flush_pending!($sink, $cx, Item = _)
+    };
+}
+
+/// Assert that `poll_close()` of a sink returns `Poll::Ready(Ok(()))`.
+///
+/// See [assert_sink_ready!](crate::assert_sink_ready).
+#[macro_export]
+macro_rules! assert_close_ready {
+    ($sink:expr, $cx:expr, Item = $item:ty $(,)?) => {
+        $crate::__assert_poll!(
+            poll_close,
+            $item,
+            $sink,
+            $cx,
+            ::core::task::Poll::Ready(::core::result::Result::Ok(())),
+            ::core::format_args!("Ready(Ok(()))")
+        )
+    };
+    ($sink:expr, $cx:expr $(,)?) => {
+        $crate::assert_close_ready!($sink, $cx, Item = _)
+    };
+}
+
+/// Assert that `start_send()` of a sink returns `Ok(())`.
+///
+/// See [assert_sink_ready!](crate::assert_sink_ready).
+#[macro_export]
+macro_rules! assert_start_send_ok {
+    ($sink:expr, $item:expr $(,)?) => {{
+        #[allow(unused_imports)]
+        use $crate::__private::{DebugState as _, NoState as _};
+        let sink = &mut $sink;
+        let res = $crate::__private::start_send(sink, $item);
+        if res.is_err() {
+            $crate::__private::failed(
+                "start_send",
+                &::core::format_args!("Ok(())"),
+                &res,
+                (&$crate::__private::State(&*sink)).state(),
+            );
+        }
+    }};
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __assert_poll {
+    (
+        $method:ident,
+        $item:ty,
+        $sink:expr,
+        $cx:expr,
+        $pattern:pat $(if $guard:expr)?,
+        $expected:expr
+    ) => {{
+        #[allow(unused_imports)]
+        use $crate::__private::{DebugState as _, NoState as _, Reborrow as _};
+        let sink = &mut $sink;
+        let poll = $crate::__private::$method::<_, $item>(sink, $cx.reborrow());
+        if !::core::matches!(poll, $pattern $(if $guard)?) {
+            $crate::__private::failed(
+                ::core::stringify!($method),
+                &$expected,
+                &poll,
+                (&$crate::__private::State(&*sink)).state(),
+            );
+        }
+    }};
+}
+
+#[doc(hidden)]
+pub mod __private {
+    use alloc::format;
+    use alloc::string::String;
+    use core::fmt::Debug;
+    use core::pin::Pin;
+    use core::task::{Context, Poll};
+    use futures::sink::Sink;
+
+    pub fn poll_ready<S, Item>(sink: &mut S, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>>
+    where
+        S: Sink<Item> + Unpin,
+    {
+        Pin::new(sink).poll_ready(cx)
+    }
+
+    pub fn poll_flush<S, Item>(sink: &mut S, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>>
+    where
+        S: Sink<Item> + Unpin,
+    {
+        Pin::new(sink).poll_flush(cx)
+    }
+
+    pub fn poll_close<S, Item>(sink: &mut S, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>>
+    where
+        S: Sink<Item> + Unpin,
+    {
+        Pin::new(sink).poll_close(cx)
+    }
+
+    pub fn start_send<S, Item>(sink: &mut S, item: Item) -> Result<(), S::Error>
+    where
+        S: Sink<Item> + Unpin,
+    {
+        Pin::new(sink).start_send(item)
+    }
+
+    // `Context` or `&mut Context`, the latter reborrowed even from an immutable binding
+    pub trait Reborrow<'a> {
+        fn reborrow(&mut self) -> &mut Context<'a>;
+    }
+
+    impl<'a> Reborrow<'a> for Context<'a> {
+        fn reborrow(&mut self) -> &mut Context<'a> {
+            self
+        }
+    }
+
+    #[track_caller]
+    pub fn failed(
+        method: &str,
+        expected: &dyn Debug,
+        actual: &dyn Debug,
+        state: Option<String>,
+    ) -> ! {
+        let state = state.map_or_else(String::new, |state| format!("\n sink: {}", state));
+        panic!(
+            "assertion failed: `{}()` returned {:?}, expected {:?}{}",
+            method, actual, expected, state
+        )
+    }
+
+    // the sink, `Debug` picked by auto-ref when the sink implements it
+    pub struct State<'a, S>(pub &'a S);
+
+    pub trait DebugState {
+        fn state(&self) -> Option<String>;
+    }
+
+    impl<S: Debug> DebugState for State<'_, S> {
+        fn state(&self) -> Option<String> {
+            Some(format!("{:?}", self.0))
+        }
+    }
+
+    pub trait NoState {
+        fn state(&self) -> Option<String> {
+            None
+        }
+    }
+
+    impl<S> NoState for &State<'_, S> {}
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::task::noop_context;
+    use crate::{interleave_pending, ok, SinkMock};
+    use futures::sink::drain;
+    use std::iter;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::string::String;
+    use std::task::{Context, Poll};
+
+    fn panic_message(f: impl FnOnce()) -> String {
+        let err = catch_unwind(AssertUnwindSafe(f)).unwrap_err();
+        *err.downcast::<String>().unwrap()
+    }
+
+    #[test]
+    fn any_unpin_sink() {
+        let mut cx = noop_context();
+        let mut s = drain::<u8>();
+        assert_sink_ready!(s, cx);
+        assert_start_send_ok!(s, 1);
+        assert_flush_ready!(s, cx);
+        assert_close_ready!(&mut s, cx);
+        assert_eq!(
+            panic_message(|| assert_sink_pending!(s, cx)),
+            "assertion failed: `poll_ready()` returned Ready(Ok(())), expected Pending\n \
+             sink: Drain { marker: PhantomData<u8> }"
+        );
+
+        // not `Debug`
+        let mut s = interleave_pending::<u8>();
+        assert_sink_ready!(s, cx);
+        assert_start_send_ok!(s, 1);
+        assert_eq!(
+            panic_message(|| assert_flush_ready!(s, cx)),
+            "assertion failed: `poll_flush()` returned Pending, expected Ready(Ok(()))"
+        );
+    }
+
+    #[test]
+    fn item_type_and_borrowed_context() {
+        fn poll(cx: &mut Context<'_>) {
+            let mut s = drain();
+            assert_sink_ready!(s, cx, Item = u8);
+            assert_flush_ready!(s, cx, Item = u8);
+            assert_close_ready!(s, &mut *cx, Item = u8,);
+        }
+        poll(&mut noop_context());
+
+        let mut cx = noop_context();
+        let mut s = SinkMock::<_, _, _, u8, _>::new(
+            iter::empty(),
+            iter::once("broken pipe"),
+            iter::empty(),
+            1,
+            1,
+        );
+        assert_sink_err!(s, &mut cx, "broken pipe", Item = u8);
+        assert_sink_ready!(s, &mut cx);
+    }
+
+    #[test]
+    fn mock_state_in_message() {
+        let mut cx = noop_context();
+        let mut s = ok::<u8>();
+        s.named("ok");
+        assert_eq!(
+            panic_message(|| assert_flush_pending!(s, cx)),
+            "assertion failed: `poll_flush()` returned Ready(Ok(())), expected Pending\n \
+             sink: SinkFeedback { name: Some(\"ok\"), polls: 1 }"
+        );
+
+        let mut s = SinkMock::<_, _, _, u8, _>::new(
+            iter::repeat(Poll::Ready(Ok(()))),
+            iter::once("full"),
+            iter::once("oversized"),
+            1,
+            1,
+        );
+        assert_eq!(
+            panic_message(|| assert_sink_err!(s, cx, "broken")),
+            "assertion failed: `poll_ready()` returned Ready(Err(\"full\")), expected \
+             Ready(Err(\"broken\"))\n sink: SinkMock { name: None, buffered: 0, max_item: 1, \
+             accepted: 0, can_start_send: false, is_closed: false, errored: false }"
+        );
+        assert_sink_ready!(s, cx);
+        assert!(panic_message(|| assert_start_send_ok!(s, 1))
+            .contains("`start_send()` returned Err(\"oversized\"), expected Ok(())"));
+    }
+}
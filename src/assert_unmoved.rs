@@ -0,0 +1,122 @@
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures::sink::Sink;
+use pin_project_lite::pin_project;
+
+pin_project! {
+    /// Wrapper that panics if it was moved between `poll_ready()` and the following
+    /// `start_send()`.
+    ///
+    /// The wrapper is `Unpin` when the inner sink is, like the mocks, so moving it is allowed and
+    /// `Pin::new()` works. But an adapter that stores the mock and moves it, e.g. into a new state
+    /// of its state machine, after `poll_ready()` returned `Poll::Ready(Ok(()))` and before
+    /// `start_send()`, handles pinning incorrectly: with a `!Unpin` sink the same code would be
+    /// unsound. This wrapper compares the address of `Pin<&mut Self>` of both calls to catch that
+    /// with `Unpin` mocks.
+    ///
+    /// # Panics
+    /// When `start_send()` is called at another address than the last successful `poll_ready()`.
+    ///
+    /// # Examples
+    /// ```
+    /// use futures::{executor::block_on, stream, StreamExt};
+    /// use futures_test_sink::{ok, AssertUnmoved};
+    ///
+    /// let mut sink = AssertUnmoved::new(ok());
+    /// let items = stream::iter(vec![Ok(1u8), Ok(2)]);
+    /// assert_eq!(Ok(()), block_on(items.forward(&mut sink)));
+    /// ```
+    pub struct AssertUnmoved<S> {
+        #[pin]
+        inner: S,
+        ready_at: Option<usize>,
+    }
+}
+
+impl<S> AssertUnmoved<S> {
+    /// Wrap `inner` sink.
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            ready_at: None,
+        }
+    }
+
+    inner_accessors!(S, "the inner sink");
+
+    fn address(self: Pin<&mut Self>) -> usize {
+        self.as_ref().get_ref() as *const Self as usize
+    }
+}
+
+impl<S, Item> Sink<Item> for AssertUnmoved<S>
+where
+    S: Sink<Item>,
+{
+    type Error = S::Error;
+
+    forward_sink!("assert_unmoved", poll_flush, poll_close);
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let address = self.as_mut().address();
+        let this = self.project();
+        let poll = this.inner.poll_ready(cx);
+        if let Poll::Ready(Ok(())) = poll {
+            *this.ready_at = Some(address);
+        }
+        wrapper_event!("assert_unmoved", "poll_ready", &poll);
+        poll
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Item) -> Result<(), Self::Error> {
+        let address = self.as_mut().address();
+        let this = self.project();
+        if let Some(ready_at) = this.ready_at.take() {
+            if ready_at != address {
+                panic!(
+                    "sink moved between `poll_ready()` at {:#x} and `start_send()` at {:#x}",
+                    ready_at, address
+                );
+            }
+        }
+        let res = this.inner.start_send(item);
+        wrapper_event!("assert_unmoved", "start_send", &res);
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ok, task::noop_context};
+    use alloc::boxed::Box;
+
+    #[test]
+    fn unmoved_sink_is_fine() {
+        let mut cx = noop_context();
+        let mut sink = AssertUnmoved::new(ok::<u8>());
+        for item in 0..3 {
+            assert_eq!(Pin::new(&mut sink).poll_ready(&mut cx), Poll::Ready(Ok(())));
+            Pin::new(&mut sink).start_send(item).unwrap();
+        }
+        // moving between sends is allowed
+        let mut sink = Box::new(sink);
+        assert_eq!(
+            Pin::new(&mut *sink).poll_ready(&mut cx),
+            Poll::Ready(Ok(()))
+        );
+        Pin::new(&mut *sink).start_send(3).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "sink moved between `poll_ready()` at")]
+    fn moved_after_ready() {
+        let mut cx = noop_context();
+        let mut sink = AssertUnmoved::new(ok::<u8>());
+        assert_eq!(Pin::new(&mut sink).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        let mut moved = Box::new(sink);
+        let _ = Pin::new(&mut *moved).start_send(1);
+    }
+}
@@ -0,0 +1,164 @@
+use crate::{from_iter, interleave_pending, ok, SinkFeedback, SinkMock};
+use alloc::boxed::Box;
+use core::{pin::Pin, task::Poll};
+use futures::{never::Never, sink::Sink};
+
+/// Type-erased sink that can be sent between threads.
+///
+/// It allows to keep differently configured mocks in one collection and run the same scenario
+/// against each of them. Take introspection handles (e.g. [ItemsHandle](crate::ItemsHandle) from
+/// [tee()](crate::tee)) before boxing, because the concrete type can't be accessed afterwards.
+///
+/// # Examples
+/// ```
+/// use futures::{executor::block_on, never::Never, stream, StreamExt};
+/// use futures_test_sink::{
+///     from_iter_boxed, interleave_pending_boxed, ok_boxed, tee, BoxSink, SinkMock,
+/// };
+/// use std::{iter, task::Poll};
+///
+/// let (teed, items) = tee(SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok(())))));
+/// let sinks: Vec<BoxSink<u8, Never>> = vec![
+///     ok_boxed(),
+///     interleave_pending_boxed(),
+///     from_iter_boxed(iter::repeat(Poll::Ready(Ok(()))), iter::repeat(Ok(()))),
+///     SinkMock::with_flush_feedback(vec![Poll::Pending, Poll::Ready(Ok(()))].into_iter().cycle())
+///         .boxed(),
+///     Box::pin(teed),
+/// ];
+///
+/// for sink in sinks {
+///     let data = stream::iter(vec![Ok(5u8), Ok(7), Ok(9)]);
+///     assert_eq!(Ok(()), block_on(data.forward(sink)));
+/// }
+/// assert_eq!(items.items(), vec![5, 7, 9]);
+/// ```
+pub type BoxSink<Item, E> = Pin<Box<dyn Sink<Item, Error = E> + Send + 'static>>;
+
+/// Type-erased sink that doesn't have to be `Send`. See [BoxSink].
+pub type LocalBoxSink<Item, E> = Pin<Box<dyn Sink<Item, Error = E> + 'static>>;
+
+/// [ok()] as [BoxSink].
+pub fn ok_boxed<Item: 'static>() -> BoxSink<Item, Never> {
+    Box::pin(ok())
+}
+
+/// [interleave_pending()] as [BoxSink].
+pub fn interleave_pending_boxed<Item: 'static>() -> BoxSink<Item, Never> {
+    Box::pin(interleave_pending())
+}
+
+/// [from_iter()] as [BoxSink].
+pub fn from_iter_boxed<Item, FI, SSI, E>(
+    poll_fallback: FI,
+    start_send_fallback: SSI,
+) -> BoxSink<Item, E>
+where
+    Item: 'static,
+    FI: Iterator<Item = Poll<Result<(), E>>> + Unpin + Send + 'static,
+    SSI: Iterator<Item = Result<(), E>> + Unpin + Send + 'static,
+    E: Unpin + Send + 'static,
+{
+    Box::pin(from_iter(poll_fallback, start_send_fallback))
+}
+
+/// [from_iter()] as [LocalBoxSink] for iterators that aren't `Send`.
+pub fn from_iter_boxed_local<Item, FI, SSI, E>(
+    poll_fallback: FI,
+    start_send_fallback: SSI,
+) -> LocalBoxSink<Item, E>
+where
+    Item: 'static,
+    FI: Iterator<Item = Poll<Result<(), E>>> + Unpin + 'static,
+    SSI: Iterator<Item = Result<(), E>> + Unpin + 'static,
+    E: Unpin + 'static,
+{
+    Box::pin(from_iter(poll_fallback, start_send_fallback))
+}
+
+impl<FlushI, ReadyI, SendI, Item, E> SinkMock<FlushI, ReadyI, SendI, Item, E>
+where
+    FlushI: Iterator<Item = Poll<Result<(), E>>> + 'static,
+    ReadyI: Iterator<Item = E> + 'static,
+    SendI: Iterator<Item = E> + 'static,
+    Item: 'static,
+    E: 'static,
+{
+    /// Erase the type of this mock. Read counters like
+    /// [flush_feedback_consumed()](SinkMock::flush_feedback_consumed) before, they can't be
+    /// accessed afterwards.
+    pub fn boxed(self) -> BoxSink<Item, E>
+    where
+        FlushI: Send,
+        ReadyI: Send,
+        SendI: Send,
+        Item: Send,
+        E: Send,
+    {
+        Box::pin(self)
+    }
+
+    /// Erase the type of this mock that isn't `Send`.
+    pub fn boxed_local(self) -> LocalBoxSink<Item, E> {
+        Box::pin(self)
+    }
+}
+
+impl<E, FI, SSI, Item> SinkFeedback<E, FI, SSI, Item>
+where
+    FI: Iterator<Item = Poll<Result<(), E>>> + Unpin + 'static,
+    SSI: Iterator<Item = Result<(), E>> + Unpin + 'static,
+    Item: 'static,
+    E: Unpin + 'static,
+{
+    /// Erase the type of this mock.
+    pub fn boxed(self) -> BoxSink<Item, E>
+    where
+        FI: Send,
+        SSI: Send,
+        E: Send,
+    {
+        Box::pin(self)
+    }
+
+    /// Erase the type of this mock that isn't `Send`.
+    pub fn boxed_local(self) -> LocalBoxSink<Item, E> {
+        Box::pin(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{
+        executor::block_on,
+        stream::{self, StreamExt},
+    };
+    use std::{iter, rc::Rc, thread};
+
+    #[test]
+    fn boxed_sink_is_send() {
+        let sink = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, Never>(()))));
+        let sink = sink.boxed();
+        let res = thread::spawn(move || {
+            let items = stream::iter(vec![Ok(5u8), Ok(7), Ok(9)]);
+            block_on(items.forward(sink))
+        });
+        assert_eq!(Ok(()), res.join().unwrap());
+    }
+
+    #[test]
+    fn local_boxed() {
+        // `Rc` makes the iterator `!Send`
+        let shared = Rc::new(Poll::Ready(Ok::<_, ()>(())));
+        let polls = iter::repeat_with(move || *shared);
+        let sinks: Vec<LocalBoxSink<u8, ()>> = vec![
+            from_iter_boxed_local(polls.clone(), iter::repeat(Ok(()))),
+            SinkMock::with_flush_feedback(polls).boxed_local(),
+        ];
+        for sink in sinks {
+            let items = stream::iter(vec![Ok(5u8), Ok(7), Ok(9)]);
+            assert_eq!(Ok(()), block_on(items.forward(sink)));
+        }
+    }
+}
@@ -0,0 +1,27 @@
+/// How [SinkMock](crate::SinkMock) treats buffered items on `poll_close()`.
+///
+/// Set it with [SinkMock::close_mode()](crate::SinkMock::close_mode).
+///
+/// # Examples
+/// ```
+/// use futures::{executor::block_on, SinkExt};
+/// use futures_test_sink::{CloseMode, SinkMock};
+/// use std::{iter, task::Poll};
+///
+/// let mut sink = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, ()>(()))));
+/// sink.close_mode(CloseMode::Abrupt);
+/// block_on(sink.feed(1u8)).unwrap();
+/// block_on(sink.feed(2u8)).unwrap();
+/// block_on(sink.close()).unwrap();
+/// assert_eq!(sink.items_dropped_on_close(), 2);
+/// assert_eq!(sink.flush_feedback_consumed(), 0);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CloseMode {
+    /// Flush buffered items as `poll_flush()` does, then close.
+    #[default]
+    Graceful,
+    /// Drop buffered items and close right away without consulting `flush_feedback`, like a
+    /// connection reset.
+    Abrupt,
+}
@@ -0,0 +1,198 @@
+//! Adapters between `futures::io` mocks of this crate and `tokio::io` traits.
+//!
+//! [TokioCompat] implements [tokio::io::AsyncRead] and [tokio::io::AsyncWrite] for a type
+//! implementing the `futures::io` counterparts, so the same mocks can be used with the tokio
+//! ecosystem (e.g. `tokio_util::codec::Framed`). The wrapped mock stays accessible with
+//! [get_ref()](TokioCompat::get_ref) and [get_mut()](TokioCompat::get_mut).
+//!
+//! Methods are mapped one to one:
+//! - `poll_read()` fills the unfilled part of `ReadBuf`.
+//! - `poll_write()` and `poll_flush()` are forwarded unchanged.
+//! - `poll_shutdown()` calls `poll_close()` once per call.
+//!
+//! Requires `tokio-compat` feature.
+//!
+//! # Examples
+//! ```
+//! use futures_test_sink::io_duplex;
+//! use tokio::io::{AsyncReadExt, AsyncWriteExt};
+//!
+//! # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+//! let (client, server) = io_duplex(16);
+//! let mut client = client.into_tokio_write();
+//! let mut server = server.into_tokio_read();
+//! client.write_all(b"ping").await.unwrap();
+//! client.shutdown().await.unwrap();
+//! let mut received = String::new();
+//! server.read_to_string(&mut received).await.unwrap();
+//! assert_eq!(received, "ping");
+//! assert_eq!(client.get_ref().close_count(), 1);
+//! # });
+//! ```
+
+use crate::MockIo;
+use futures::io::{AsyncRead, AsyncWrite};
+use pin_project_lite::pin_project;
+use std::io;
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::io::ReadBuf;
+
+pin_project! {
+    /// Wrapper implementing `tokio::io` traits for `futures::io` mocks. See [module](self) docs.
+    #[derive(Debug)]
+    pub struct TokioCompat<T> {
+        #[pin]
+        inner: T,
+    }
+}
+
+impl<T> TokioCompat<T> {
+    /// Wrap `inner`.
+    pub fn new(inner: T) -> Self {
+        Self { inner }
+    }
+
+    inner_accessors!(T, "the wrapped mock");
+}
+
+impl<T> tokio::io::AsyncRead for TokioCompat<T>
+where
+    T: AsyncRead,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let n = futures::ready!(self
+            .project()
+            .inner
+            .poll_read(cx, buf.initialize_unfilled()))?;
+        buf.advance(n);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<T> tokio::io::AsyncWrite for TokioCompat<T>
+where
+    T: AsyncWrite,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.project().inner.poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().inner.poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().inner.poll_close(cx)
+    }
+}
+
+impl MockIo {
+    /// Use this half as [tokio::io::AsyncWrite]. See [compat](self) module.
+    ///
+    /// Requires `tokio-compat` feature.
+    pub fn into_tokio_write(self) -> TokioCompat<Self> {
+        TokioCompat::new(self)
+    }
+
+    /// Use this half as [tokio::io::AsyncRead]. See [compat](self) module.
+    ///
+    /// Requires `tokio-compat` feature.
+    pub fn into_tokio_read(self) -> TokioCompat<Self> {
+        TokioCompat::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io_duplex;
+    use futures::{SinkExt, StreamExt};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_util::codec::{Framed, LinesCodec};
+
+    #[tokio::test]
+    async fn shutdown_consumes_close_script_once() {
+        let (mut client, server) = io_duplex(8);
+        client.set_close_script(vec![
+            Poll::Ready(Err(io::ErrorKind::TimedOut.into())),
+            Poll::Ready(Ok(())),
+        ]);
+        let mut client = client.into_tokio_write();
+        let mut server = server.into_tokio_read();
+        client.write_all(b"abc").await.unwrap();
+        let err = client.shutdown().await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(client.get_ref().close_count(), 1);
+        client.shutdown().await.unwrap();
+        assert_eq!(client.get_ref().close_count(), 2);
+
+        let mut received = Vec::new();
+        server.read_to_end(&mut received).await.unwrap();
+        assert_eq!(received, b"abc");
+    }
+
+    #[tokio::test]
+    async fn short_reads_into_read_buf() {
+        let (mut client, mut server) = io_duplex(8);
+        server.set_read_script(vec![Poll::Pending, Poll::Ready(Ok(1)), Poll::Ready(Ok(2))]);
+        futures::AsyncWriteExt::write_all(&mut client, b"abcd")
+            .await
+            .unwrap();
+        let mut server = server.into_tokio_read();
+        let mut buf = [0u8; 8];
+        assert_eq!(server.read(&mut buf).await.unwrap(), 1);
+        assert_eq!(server.read(&mut buf[1..]).await.unwrap(), 2);
+        assert_eq!(server.get_ref().readable(), 1);
+        assert_eq!(server.read(&mut buf[3..]).await.unwrap(), 1);
+        assert_eq!(&buf[..4], b"abcd");
+    }
+
+    #[tokio::test]
+    async fn framed_lines() {
+        let (mut client, mut server) = io_duplex(4);
+        // partial writes and read stalls in both directions
+        for half in [&mut client, &mut server] {
+            half.set_write_script((0..).map(|i| match i % 3 {
+                0 => Poll::Ready(Ok(1)),
+                1 => Poll::Pending,
+                _ => Poll::Ready(Ok(3)),
+            }));
+            half.set_read_script((0..).map(|i| match i % 2 {
+                0 => Poll::Pending,
+                _ => Poll::Ready(Ok(2)),
+            }));
+        }
+        let mut client = Framed::new(client.into_tokio_write(), LinesCodec::new());
+        let mut server = Framed::new(server.into_tokio_read(), LinesCodec::new());
+
+        let echo = async {
+            while let Some(line) = server.next().await {
+                server.send(line.unwrap().to_uppercase()).await.unwrap();
+            }
+            SinkExt::<String>::close(&mut server).await.unwrap();
+        };
+        let talk = async {
+            let mut answers = Vec::new();
+            for line in ["hello", "framed", "world"] {
+                client.send(line).await.unwrap();
+                answers.push(client.next().await.unwrap().unwrap());
+            }
+            SinkExt::<&str>::close(&mut client).await.unwrap();
+            assert!(client.next().await.is_none());
+            answers
+        };
+        let (_, answers) = tokio::join!(echo, talk);
+        assert_eq!(answers, ["HELLO", "FRAMED", "WORLD"]);
+    }
+}
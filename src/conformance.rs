@@ -0,0 +1,331 @@
+/// Generate the `#[test]` functions every sink wrapper should pass.
+///
+/// The argument builds the wrapper around the inner sink, e.g. a closure
+/// `|inner| MyWrapper::new(inner)`. The inner sink is a [BoxSink](crate::BoxSink) of `u8` items
+/// with `&'static str` errors, a boxed [SinkMock](crate::SinkMock) panicking on misuse. The
+/// wrapper must be a `Sink<u8>` with a `Debug` error. Every test forwards a few items to the
+/// wrapper and closes it, polling by hand, so a task that returns `Poll::Pending` without being
+/// woken fails instead of hanging. The generated tests are:
+///
+/// - `forwards_over_ready_sink` - forwarding completes over an always ready inner sink,
+/// - `forwards_over_pending_sink` - forwarding completes over an inner sink returning
+///   `Poll::Pending` from every second flush,
+/// - `surfaces_inner_error` - forwarding fails with an error of the inner sink,
+/// - `closes_inner_once` - closing the wrapper closes the inner sink and doesn't poll it after,
+/// - `polls_ready_before_start_send` - `start_send()` of the inner sink is called only after
+///   `poll_ready()` returned `Poll::Ready(Ok(()))`.
+///
+/// A failure names the property that broke, e.g.
+/// `conformance property `closes_inner_once` broke: the inner sink wasn't closed`, followed by
+/// the [TestRegistry](crate::TestRegistry) report of the inner sink registered as `inner`.
+/// Invoke the macro once per module, the names of the tests are fixed.
+///
+/// Requires `std` feature.
+///
+/// # Examples
+/// ```
+/// mod poll_gap {
+///     use futures_test_sink::{sink_conformance_tests, PollGapSink};
+///
+///     sink_conformance_tests!(PollGapSink::new);
+/// }
+/// ```
+#[macro_export]
+macro_rules! sink_conformance_tests {
+    ($factory:expr $(,)?) => {
+        #[test]
+        fn forwards_over_ready_sink() {
+            $crate::__conformance::forwards_over_ready_sink($factory);
+        }
+
+        #[test]
+        fn forwards_over_pending_sink() {
+            $crate::__conformance::forwards_over_pending_sink($factory);
+        }
+
+        #[test]
+        fn surfaces_inner_error() {
+            $crate::__conformance::surfaces_inner_error($factory);
+        }
+
+        #[test]
+        fn closes_inner_once() {
+            $crate::__conformance::closes_inner_once($factory);
+        }
+
+        #[test]
+        fn polls_ready_before_start_send() {
+            $crate::__conformance::polls_ready_before_start_send($factory);
+        }
+    };
+}
+
+#[doc(hidden)]
+pub mod __conformance {
+    use crate::task::count_waker;
+    use crate::{BoxSink, SinkMock, TestRegistry};
+    use core::fmt::Debug;
+    use futures::sink::Sink;
+    use futures::stream::{self, StreamExt};
+    use futures::FutureExt;
+    use std::boxed::Box;
+    use std::iter;
+    use std::panic::{catch_unwind, resume_unwind, AssertUnwindSafe};
+    use std::string::String;
+    use std::task::{Context, Poll};
+
+    const ITEMS: u8 = 5;
+    const MAX_POLLS: usize = 10_000;
+
+    type Inner = BoxSink<u8, &'static str>;
+
+    pub fn forwards_over_ready_sink<W>(factory: impl FnOnce(Inner) -> W)
+    where
+        W: Sink<u8>,
+        W::Error: Debug,
+    {
+        let property = "forwards_over_ready_sink";
+        let mut inner =
+            SinkMock::<_, _, _, u8, &str>::with_flush_feedback(iter::repeat(Poll::Ready(Ok(()))));
+        let registry = registered(&mut inner);
+        registry.run(|| {
+            if let Err(e) = forward(property, factory(Box::pin(inner))) {
+                broke(property, format_args!("forwarding failed with {:?}", e));
+            }
+        });
+    }
+
+    pub fn forwards_over_pending_sink<W>(factory: impl FnOnce(Inner) -> W)
+    where
+        W: Sink<u8>,
+        W::Error: Debug,
+    {
+        let property = "forwards_over_pending_sink";
+        let feedback = vec![Poll::Ready(Ok(())), Poll::Pending];
+        let mut inner =
+            SinkMock::<_, _, _, u8, &str>::with_flush_feedback(feedback.into_iter().cycle());
+        let registry = registered(&mut inner);
+        registry.run(|| {
+            if let Err(e) = forward(property, factory(Box::pin(inner))) {
+                broke(property, format_args!("forwarding failed with {:?}", e));
+            }
+        });
+    }
+
+    pub fn surfaces_inner_error<W>(factory: impl FnOnce(Inner) -> W)
+    where
+        W: Sink<u8>,
+        W::Error: Debug,
+    {
+        let property = "surfaces_inner_error";
+        let feedback = vec![
+            Poll::Ready(Ok(())),
+            Poll::Pending,
+            Poll::Ready(Err("inner error")),
+        ];
+        let mut inner =
+            SinkMock::<_, _, _, u8, &str>::with_flush_feedback(feedback.into_iter().cycle());
+        let registry = registered(&mut inner);
+        registry.run(|| {
+            if forward(property, factory(Box::pin(inner))).is_ok() {
+                broke(
+                    property,
+                    "forwarding completed with `Ok(())` although the inner sink returned an error",
+                );
+            }
+        });
+    }
+
+    pub fn closes_inner_once<W>(factory: impl FnOnce(Inner) -> W)
+    where
+        W: Sink<u8>,
+        W::Error: Debug,
+    {
+        let property = "closes_inner_once";
+        // the mock panics when it's polled after it was closed
+        let mut inner =
+            SinkMock::<_, _, _, u8, &str>::with_flush_feedback(iter::repeat(Poll::Ready(Ok(()))));
+        let counters = inner.counters();
+        let registry = registered(&mut inner);
+        registry.run(|| {
+            if let Err(e) = forward(property, factory(Box::pin(inner))) {
+                broke(property, format_args!("forwarding failed with {:?}", e));
+            }
+            if !counters.is_closed() {
+                broke(property, "the inner sink wasn't closed");
+            }
+        });
+    }
+
+    pub fn polls_ready_before_start_send<W>(factory: impl FnOnce(Inner) -> W)
+    where
+        W: Sink<u8>,
+        W::Error: Debug,
+    {
+        let property = "polls_ready_before_start_send";
+        // the mock panics on `start_send()` without `Poll::Ready(Ok(()))` from `poll_ready()`,
+        // which is `Poll::Pending` on every second full buffer
+        let feedback = vec![Poll::Pending, Poll::Ready(Ok(()))];
+        let mut inner = SinkMock::<_, _, _, u8, &str>::new(
+            feedback.into_iter().cycle(),
+            iter::empty(),
+            iter::empty(),
+            1,
+            1,
+        );
+        let registry = registered(&mut inner);
+        registry.run(|| {
+            if let Err(e) = forward(property, factory(Box::pin(inner))) {
+                broke(property, format_args!("forwarding failed with {:?}", e));
+            }
+        });
+    }
+
+    // Registry with the inner sink, its report ends the message of a broken property.
+    fn registered<FI, RI, SI>(inner: &mut SinkMock<FI, RI, SI, u8, &'static str>) -> TestRegistry {
+        let registry = TestRegistry::new();
+        inner.register(&registry, "inner");
+        registry
+    }
+
+    // Forward the items to the sink and close it, polling by hand.
+    fn forward<W>(property: &str, sink: W) -> Result<(), W::Error>
+    where
+        W: Sink<u8>,
+    {
+        let items = stream::iter((0..ITEMS).map(Ok));
+        let mut forward = items.forward(Box::pin(sink));
+        let (waker, wakes) = count_waker();
+        let mut cx = Context::from_waker(&waker);
+        for _ in 0..MAX_POLLS {
+            let woken = wakes.get();
+            let poll = catch_unwind(AssertUnwindSafe(|| forward.poll_unpin(&mut cx)))
+                .unwrap_or_else(|payload| match panic_message(&*payload) {
+                    Some(message) => broke(property, format_args!("panicked: {}", message)),
+                    None => resume_unwind(payload),
+                });
+            match poll {
+                Poll::Ready(res) => return res,
+                Poll::Pending if wakes.get() == woken => broke(
+                    property,
+                    "returned `Poll::Pending` without waking the task, a wake of the inner sink \
+                     was lost",
+                ),
+                Poll::Pending => (),
+            }
+        }
+        broke(
+            property,
+            format_args!("forwarding didn't complete in {} polls", MAX_POLLS),
+        )
+    }
+
+    fn panic_message(payload: &(dyn std::any::Any + Send)) -> Option<String> {
+        payload
+            .downcast_ref::<String>()
+            .cloned()
+            .or_else(|| payload.downcast_ref::<&str>().map(|s| String::from(*s)))
+    }
+
+    #[track_caller]
+    fn broke(property: &str, reason: impl core::fmt::Display) -> ! {
+        panic!("conformance property `{}` broke: {}", property, reason)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::__conformance;
+    use crate::{BoxSink, PollGapSink, ThreadTrackingSink};
+    use core::pin::Pin;
+    use core::task::{Context, Poll};
+    use futures::sink::Sink;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::string::String;
+
+    mod identity {
+        sink_conformance_tests!(|inner| inner);
+    }
+
+    mod poll_gap {
+        use super::*;
+
+        sink_conformance_tests!(PollGapSink::new);
+    }
+
+    mod thread_tracking {
+        use super::*;
+
+        sink_conformance_tests!(ThreadTrackingSink::new);
+    }
+
+    // forgets to close the inner sink
+    struct NoClose(BoxSink<u8, &'static str>);
+
+    impl Sink<u8> for NoClose {
+        type Error = &'static str;
+
+        fn poll_ready(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            self.0.as_mut().poll_ready(cx)
+        }
+
+        fn start_send(mut self: Pin<&mut Self>, item: u8) -> Result<(), Self::Error> {
+            self.0.as_mut().start_send(item)
+        }
+
+        fn poll_flush(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            self.0.as_mut().poll_flush(cx)
+        }
+
+        fn poll_close(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<Result<(), Self::Error>> {
+            // flushes only and swallows errors
+            match self.0.as_mut().poll_flush(cx) {
+                Poll::Pending => Poll::Pending,
+                Poll::Ready(_) => Poll::Ready(Ok(())),
+            }
+        }
+    }
+
+    fn panic_message(f: impl FnOnce()) -> String {
+        let err = catch_unwind(AssertUnwindSafe(f)).unwrap_err();
+        *err.downcast::<String>().unwrap()
+    }
+
+    #[test]
+    fn failure_names_the_property() {
+        __conformance::forwards_over_ready_sink(NoClose);
+        assert_eq!(
+            panic_message(|| __conformance::closes_inner_once(NoClose)),
+            "conformance property `closes_inner_once` broke: the inner sink wasn't closed\n\n\
+             mocks:\n\
+             name   accepted  buffered  errors  pendings  closed\n\
+             inner         5         0       0         0  no"
+        );
+        // sends without polling ready
+        let message = panic_message(|| {
+            __conformance::polls_ready_before_start_send(|inner| {
+                futures::sink::unfold(inner, |mut inner, item: u8| async move {
+                    inner.as_mut().start_send(item)?;
+                    Ok::<_, &'static str>(inner)
+                })
+            })
+        });
+        assert!(
+            message.starts_with(
+                "conformance property `polls_ready_before_start_send` broke: panicked: \
+                 `start_send()` called without correct call of `poll_ready()`"
+            ),
+            "{}",
+            message
+        );
+    }
+}
@@ -0,0 +1,210 @@
+use core::marker::PhantomData;
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures::{sink::Sink, stream::Stream};
+
+use crate::wake_policy::Waking;
+
+/// Message that steers [ControlSink].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Control<E> {
+    /// Current `poll_*()` returns `Poll::Ready(Ok(()))`.
+    Ready,
+    /// Current `poll_*()` returns `Poll::Pending`. The waker is woken before it's returned.
+    Pend,
+    /// Current `poll_*()` returns `Poll::Ready(Err(e))`.
+    Fail(E),
+    /// Decide if the next item passed to `start_send()` is accepted (`true`) or dropped
+    /// (`false`). The next message is read to answer the current `poll_*()`.
+    AcceptNext(bool),
+}
+
+/// Sink which responses follow messages from a control stream.
+///
+/// For details see [from_control_stream()].
+///
+/// [from_control_stream()]:from_control_stream
+pub struct ControlSink<S, Item> {
+    ctrl: S,
+    accept_next: bool,
+    accepted_cnt: usize,
+    dropped_cnt: usize,
+    waking: Waking,
+    item_type: PhantomData<fn(Item)>,
+}
+
+/// Create a sink steered by a stream of [Control] messages.
+///
+/// Every `poll_ready()`, `poll_flush()` and `poll_close()` polls `ctrl` and the next message
+/// dictates the response. While `ctrl` has no message the sink returns `Poll::Pending`. The waker
+/// is registered with `ctrl`, so a message sent later (e.g. from other task through a channel)
+/// wakes the task using the sink. After [Control::Pend] the waker is woken as set by
+/// [wake_policy()](ControlSink::wake_policy).
+///
+/// `start_send()` never fails. It accepts or drops the item as decided by the last
+/// [Control::AcceptNext] (accepts by default).
+///
+/// # Panics
+/// When `ctrl` ends.
+///
+/// # Examples
+/// ```
+/// use futures::{channel::mpsc, executor::block_on, future, SinkExt};
+/// use futures_test_sink::{from_control_stream, Control};
+///
+/// let (mut ctrl, rx) = mpsc::unbounded();
+/// let mut sink = from_control_stream(rx);
+///
+/// let controller = async move {
+///     ctrl.send(Control::Ready).await.unwrap(); // poll_ready
+///     ctrl.send(Control::Pend).await.unwrap(); // poll_flush
+///     ctrl.send(Control::Fail("disk full")).await.unwrap(); // poll_flush
+/// };
+/// let (_, res) = block_on(future::join(controller, sink.send(1u8)));
+/// assert_eq!(res, Err("disk full"));
+/// assert_eq!(sink.accepted_count(), 1);
+/// ```
+pub fn from_control_stream<S, Item, E>(ctrl: S) -> ControlSink<S, Item>
+where
+    S: Stream<Item = Control<E>> + Unpin,
+{
+    ControlSink {
+        ctrl,
+        accept_next: true,
+        accepted_cnt: 0,
+        dropped_cnt: 0,
+        waking: Waking::default(),
+        item_type: Default::default(),
+    }
+}
+
+impl<S: Unpin, Item> Unpin for ControlSink<S, Item> {}
+
+impl<S, Item> ControlSink<S, Item> {
+    /// How many items were accepted by `start_send()`.
+    pub fn accepted_count(&self) -> usize {
+        self.accepted_cnt
+    }
+
+    /// How many items were dropped by `start_send()` because of [Control::AcceptNext(false)].
+    ///
+    /// [Control::AcceptNext(false)]: Control::AcceptNext
+    pub fn dropped_count(&self) -> usize {
+        self.dropped_cnt
+    }
+
+    waking_setters! {
+        /// Set how the waker is woken before returning `Poll::Pending`.
+    }
+
+    /// Consume the sink returning the control stream.
+    pub fn into_inner(self) -> S {
+        self.ctrl
+    }
+}
+
+impl<S, Item, E> ControlSink<S, Item>
+where
+    S: Stream<Item = Control<E>> + Unpin,
+{
+    fn poll_control(&mut self, method: &str, cx: &mut Context<'_>) -> Poll<Result<(), E>> {
+        self.waking.polled();
+        loop {
+            match Pin::new(&mut self.ctrl).poll_next(cx) {
+                // `ctrl` registered the waker
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(Some(Control::Ready)) => {
+                    self.waking.ready(cx.waker());
+                    return Poll::Ready(Ok(()));
+                }
+                Poll::Ready(Some(Control::Pend)) => {
+                    self.waking.pending(cx.waker());
+                    return Poll::Pending;
+                }
+                Poll::Ready(Some(Control::Fail(e))) => {
+                    self.waking.ready(cx.waker());
+                    return Poll::Ready(Err(e));
+                }
+                Poll::Ready(Some(Control::AcceptNext(accept))) => self.accept_next = accept,
+                Poll::Ready(None) => panic!("Control stream ended, but `{}` was called", method),
+            }
+        }
+    }
+}
+
+impl<S, Item, E> Sink<Item> for ControlSink<S, Item>
+where
+    S: Stream<Item = Control<E>> + Unpin,
+{
+    type Error = E;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::into_inner(self).poll_control("poll_ready", cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, _item: Item) -> Result<(), Self::Error> {
+        let this = Pin::into_inner(self);
+        if this.accept_next {
+            this.accepted_cnt += 1;
+        } else {
+            this.dropped_cnt += 1;
+        }
+        this.accept_next = true;
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::into_inner(self).poll_control("poll_flush", cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::into_inner(self).poll_control("poll_close", cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{
+        channel::{mpsc, oneshot},
+        executor::block_on,
+        future, stream, SinkExt,
+    };
+
+    #[test]
+    fn controlled_from_companion_task() {
+        let (mut ctrl, rx) = mpsc::unbounded();
+        let (sent_tx, sent_rx) = oneshot::channel();
+        let mut sink = from_control_stream(rx);
+
+        let producer = async {
+            sink.feed(1u8).await?;
+            sink.feed(2).await?;
+            sent_tx.send(()).unwrap();
+            sink.flush().await
+        };
+        let controller = async move {
+            ctrl.send(Control::AcceptNext(false)).await.unwrap();
+            ctrl.send(Control::Ready).await.unwrap();
+            ctrl.send(Control::Ready).await.unwrap();
+            // producer waits for `poll_flush()` response until both items were sent
+            sent_rx.await.unwrap();
+            ctrl.send(Control::Pend).await.unwrap();
+            ctrl.send(Control::Ready).await.unwrap();
+        };
+        let (res, ()) = block_on(future::join(producer, controller));
+        assert_eq!(res, Ok::<_, ()>(()));
+        assert_eq!(sink.accepted_count(), 1);
+        assert_eq!(sink.dropped_count(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Control stream ended, but `poll_close` was called")]
+    fn control_stream_ends() {
+        let mut sink = from_control_stream(stream::iter(vec![Control::<()>::Ready]));
+        let _ = block_on(sink.flush());
+        let _ = block_on(SinkExt::<u8>::close(&mut sink));
+    }
+}
@@ -0,0 +1,151 @@
+use alloc::vec::Vec;
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures::sink::Sink;
+
+/// What [CountdownSink] does after it accepted all items.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CountdownEnd<E> {
+    /// `poll_ready()` returns `Poll::Ready(Err(e))` forever.
+    Error(E),
+    /// `poll_ready()` returns `Poll::Pending` forever. The waker is never woken.
+    PendForever,
+}
+
+/// Sink that accepts exactly `n` items.
+///
+/// For details see [countdown()].
+///
+/// [countdown()]:countdown
+pub struct CountdownSink<Item, E> {
+    left: usize,
+    then: CountdownEnd<E>,
+    items: Vec<Item>,
+}
+
+/// Create a sink that accepts exactly `n` items and then refuses further sends for good.
+///
+/// After `n` items were accepted every `poll_ready()` behaves as `then` says. `poll_flush()` and
+/// `poll_close()` always return `Poll::Ready(Ok(()))`. Accepted items are kept and can be
+/// inspected with [items()](CountdownSink::items).
+///
+/// This is a zero-configuration sink for quick tests of producer termination logic.
+///
+/// # Examples
+/// ```
+/// use futures::{executor::block_on, stream, StreamExt};
+/// use futures_test_sink::{countdown, CountdownEnd};
+///
+/// let mut sink = countdown(2, CountdownEnd::Error("full"));
+/// let items = stream::iter(vec![Ok(1u8), Ok(2), Ok(3)]);
+/// assert_eq!(Err("full"), block_on(items.forward(&mut sink)));
+/// assert_eq!(sink.items(), &[1, 2]);
+/// ```
+pub fn countdown<Item, E: Clone>(n: usize, then: CountdownEnd<E>) -> CountdownSink<Item, E> {
+    CountdownSink {
+        left: n,
+        then,
+        items: Vec::new(),
+    }
+}
+
+impl<Item, E> Unpin for CountdownSink<Item, E> {}
+
+impl<Item, E> CountdownSink<Item, E> {
+    /// Accepted items.
+    pub fn items(&self) -> &[Item] {
+        &self.items
+    }
+
+    /// Consume the sink returning accepted items.
+    pub fn into_items(self) -> Vec<Item> {
+        self.items
+    }
+
+    /// How many items will be accepted yet.
+    pub fn left(&self) -> usize {
+        self.left
+    }
+}
+
+impl<Item, E: Clone> Sink<Item> for CountdownSink<Item, E> {
+    type Error = E;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        if self.left > 0 {
+            return Poll::Ready(Ok(()));
+        }
+        match &self.then {
+            CountdownEnd::Error(e) => Poll::Ready(Err(e.clone())),
+            CountdownEnd::PendForever => Poll::Pending,
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Item) -> Result<(), Self::Error> {
+        let this = Pin::into_inner(self);
+        if this.left == 0 {
+            panic!("`start_send()` called after all items were accepted");
+        }
+        this.left -= 1;
+        this.items.push(item);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{
+        executor::block_on,
+        poll,
+        stream::{self, StreamExt},
+    };
+
+    #[test]
+    fn forward_error() {
+        let mut sink = countdown(3, CountdownEnd::Error(String::from("full")));
+        let items = stream::iter((0..10u8).map(Ok));
+        assert_eq!(
+            Err(String::from("full")),
+            block_on(items.forward(&mut sink))
+        );
+        assert_eq!(sink.left(), 0);
+        // error is permanent
+        let items = stream::iter(vec![Ok(10)]);
+        assert_eq!(
+            Err(String::from("full")),
+            block_on(items.forward(&mut sink))
+        );
+        assert_eq!(sink.into_items(), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn forward_pend_forever() {
+        let mut sink = countdown::<u8, ()>(2, CountdownEnd::PendForever);
+        block_on(async {
+            let mut forward = stream::iter((0..10).map(Ok)).forward(&mut sink);
+            for _ in 0..3 {
+                assert!(poll!(&mut forward).is_pending());
+            }
+        });
+        assert_eq!(sink.items(), &[0, 1]);
+    }
+
+    #[test]
+    fn fewer_items_than_n() {
+        let mut sink = countdown::<_, ()>(3, CountdownEnd::PendForever);
+        let items = stream::iter(vec![Ok(1u8), Ok(2)]);
+        assert_eq!(Ok(()), block_on(items.forward(&mut sink)));
+        assert_eq!(sink.left(), 1);
+    }
+}
@@ -0,0 +1,494 @@
+use alloc::sync::Arc;
+#[cfg(not(feature = "std"))]
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use crate::task::{check_wakes, ExpectedWakes};
+
+/// Method of a sink that is being called.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Method {
+    Ready,
+    Send,
+    Flush,
+    Close,
+}
+
+/// Lock for values that must be updated together.
+///
+/// A [Mutex](std::sync::Mutex) with `std` feature. A panic while it's held doesn't poison it,
+/// the values are plain counters and wakers. Critical sections only copy or take values, so
+/// nothing is formatted or woken under the lock.
+#[cfg(feature = "std")]
+pub(crate) struct Lock<T>(std::sync::Mutex<T>);
+
+#[cfg(feature = "std")]
+impl<T> Lock<T> {
+    pub(crate) fn new(value: T) -> Self {
+        Self(std::sync::Mutex::new(value))
+    }
+
+    pub(crate) fn with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut self
+            .0
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner))
+    }
+}
+
+/// Lock for values that must be updated together.
+///
+/// `core` has no lock, so without `std` feature it spins on a flag. Critical sections only copy
+/// or take values, so spinning is short.
+#[cfg(not(feature = "std"))]
+pub(crate) struct Lock<T> {
+    locked: AtomicBool,
+    value: UnsafeCell<T>,
+}
+
+// SAFETY: the value is accessed only by the thread holding the lock.
+#[cfg(not(feature = "std"))]
+unsafe impl<T: Send> Sync for Lock<T> {}
+
+#[cfg(not(feature = "std"))]
+struct Unlock<'a>(&'a AtomicBool);
+
+#[cfg(not(feature = "std"))]
+impl Drop for Unlock<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl<T> Lock<T> {
+    pub(crate) fn new(value: T) -> Self {
+        Self {
+            locked: AtomicBool::new(false),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    pub(crate) fn with<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            core::hint::spin_loop();
+        }
+        // unlocks even if `f` panics
+        let _unlock = Unlock(&self.locked);
+        // SAFETY: the lock is held until `_unlock` is dropped.
+        f(unsafe { &mut *self.value.get() })
+    }
+}
+
+struct Errors<E> {
+    from_ready: usize,
+    from_send: usize,
+    from_flush: usize,
+    from_close: usize,
+    last: Option<LastError<E>>,
+}
+
+// last error with the function cloning it for a snapshot
+pub(crate) type LastError<E> = (E, fn(&E) -> E);
+
+struct Counters<E> {
+    wakes_from_ready: AtomicUsize,
+    wakes_from_flush: AtomicUsize,
+    wakes_from_close: AtomicUsize,
+    accepted: AtomicUsize,
+    buffered: AtomicUsize,
+    pendings: AtomicUsize,
+    closed: AtomicBool,
+    errors: Lock<Errors<E>>,
+}
+
+/// State of a mock shown in a row of [TestRegistry](crate::TestRegistry) report.
+#[cfg(feature = "std")]
+pub(crate) struct Row {
+    pub(crate) accepted: usize,
+    pub(crate) buffered: usize,
+    pub(crate) errors: usize,
+    pub(crate) pendings: usize,
+    pub(crate) closed: bool,
+}
+
+/// Counters of any mock regardless of its error type.
+#[cfg(feature = "std")]
+pub(crate) trait Stats {
+    fn row(&self) -> Row;
+}
+
+#[cfg(feature = "std")]
+impl<E> Stats for Counters<E> {
+    fn row(&self) -> Row {
+        let errors = self.errors.with(|errors| {
+            errors.from_ready + errors.from_send + errors.from_flush + errors.from_close
+        });
+        Row {
+            accepted: self.accepted.load(Ordering::SeqCst),
+            buffered: self.buffered.load(Ordering::SeqCst),
+            errors,
+            pendings: self.pendings.load(Ordering::SeqCst),
+            closed: self.closed.load(Ordering::SeqCst),
+        }
+    }
+}
+
+/// Counters of a [SinkMock](crate::SinkMock) that stay readable after the mock was moved.
+///
+/// Get it with [SinkMock::counters()](crate::SinkMock::counters). Clones share the same counters.
+/// `Display` prints all counters at once, which is handy in failure messages of long tests.
+///
+/// # Examples
+/// ```
+/// use futures::{executor::block_on, stream, StreamExt};
+/// use futures_test_sink::SinkMock;
+/// use std::task::Poll;
+///
+/// let feedback = vec![Poll::Ready(Ok::<_, ()>(())), Poll::Pending].into_iter().cycle();
+/// let sink = SinkMock::with_flush_feedback(feedback);
+/// let counters = sink.counters();
+/// let items = stream::iter(vec![Ok(5u8), Ok(7), Ok(9), Ok(77), Ok(79)]);
+/// // the sink is moved into `forward()`
+/// block_on(items.forward(sink)).unwrap();
+/// counters.expect_wakes(2);
+/// assert_eq!(counters.wakes_from_ready(), 1);
+/// assert_eq!(counters.wakes_from_close(), 1);
+/// assert_eq!(
+///     counters.to_string(),
+///     "wakes from ready: 1, flush: 0, close: 1; errors from ready: 0, send: 0, flush: 0, close: 0"
+/// );
+/// ```
+pub struct MockCounters<E> {
+    shared: Arc<Counters<E>>,
+}
+
+impl<E> Clone for MockCounters<E> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<E> MockCounters<E> {
+    pub(crate) fn new() -> Self {
+        Self {
+            shared: Arc::new(Counters {
+                wakes_from_ready: AtomicUsize::new(0),
+                wakes_from_flush: AtomicUsize::new(0),
+                wakes_from_close: AtomicUsize::new(0),
+                accepted: AtomicUsize::new(0),
+                buffered: AtomicUsize::new(0),
+                pendings: AtomicUsize::new(0),
+                closed: AtomicBool::new(false),
+                errors: Lock::new(Errors {
+                    from_ready: 0,
+                    from_send: 0,
+                    from_flush: 0,
+                    from_close: 0,
+                    last: None,
+                }),
+            }),
+        }
+    }
+
+    /// How many times the mock woke the waker from `poll_ready()`.
+    ///
+    /// It includes flushes done by `poll_ready()` when the buffer is full and
+    /// [spurious wakes](crate::SinkMock::spurious_wakes).
+    pub fn wakes_from_ready(&self) -> usize {
+        self.shared.wakes_from_ready.load(Ordering::SeqCst)
+    }
+
+    /// How many times the mock woke the waker from `poll_flush()`.
+    pub fn wakes_from_flush(&self) -> usize {
+        self.shared.wakes_from_flush.load(Ordering::SeqCst)
+    }
+
+    /// How many times the mock woke the waker from `poll_close()`.
+    pub fn wakes_from_close(&self) -> usize {
+        self.shared.wakes_from_close.load(Ordering::SeqCst)
+    }
+
+    /// Panic unless the mock woke the waker exactly `n` times from all methods.
+    ///
+    /// The message lists the methods the wakes came from, e.g.
+    /// `expected 3 wakes, observed 5; wake sources: flush×4, ready×1`.
+    #[track_caller]
+    pub fn expect_wakes(&self, n: usize) {
+        self.check_wakes(ExpectedWakes::Exactly(n));
+    }
+
+    /// Panic unless the mock woke the waker at least `n` times.
+    #[track_caller]
+    pub fn expect_at_least(&self, n: usize) {
+        self.check_wakes(ExpectedWakes::AtLeast(n));
+    }
+
+    /// Panic if the mock woke the waker.
+    #[track_caller]
+    pub fn expect_none(&self) {
+        self.check_wakes(ExpectedWakes::Exactly(0));
+    }
+
+    #[track_caller]
+    fn check_wakes(&self, expected: ExpectedWakes) {
+        let mut sources = [
+            ("ready", self.wakes_from_ready()),
+            ("flush", self.wakes_from_flush()),
+            ("close", self.wakes_from_close()),
+        ];
+        // the most frequent source first
+        sources.sort_by_key(|&(_, n)| core::cmp::Reverse(n));
+        let observed = sources.iter().map(|&(_, n)| n).sum();
+        let sources = sources
+            .iter()
+            .filter(|&&(_, n)| n > 0)
+            .map(|(method, n)| alloc::format!("{}×{}", method, n))
+            .collect::<alloc::vec::Vec<_>>()
+            .join(", ");
+        let sources = if sources.is_empty() {
+            "none".into()
+        } else {
+            sources
+        };
+        check_wakes(expected, observed, Some(sources));
+    }
+
+    /// How many items `start_send()` accepted.
+    pub fn accepted(&self) -> usize {
+        self.shared.accepted.load(Ordering::SeqCst)
+    }
+
+    /// How many items are buffered now.
+    pub fn buffered(&self) -> usize {
+        self.shared.buffered.load(Ordering::SeqCst)
+    }
+
+    /// How many times `Poll::Pending` was returned by any method.
+    pub fn pendings(&self) -> usize {
+        self.shared.pendings.load(Ordering::SeqCst)
+    }
+
+    /// `true` after `poll_close()` succeeded.
+    pub fn is_closed(&self) -> bool {
+        self.shared.closed.load(Ordering::SeqCst)
+    }
+
+    /// How many errors `poll_ready()` returned.
+    pub fn errors_from_ready(&self) -> usize {
+        self.shared.errors.with(|errors| errors.from_ready)
+    }
+
+    /// How many errors `start_send()` returned.
+    pub fn errors_from_send(&self) -> usize {
+        self.shared.errors.with(|errors| errors.from_send)
+    }
+
+    /// How many errors `poll_flush()` returned.
+    pub fn errors_from_flush(&self) -> usize {
+        self.shared.errors.with(|errors| errors.from_flush)
+    }
+
+    /// How many errors `poll_close()` returned.
+    pub fn errors_from_close(&self) -> usize {
+        self.shared.errors.with(|errors| errors.from_close)
+    }
+
+    /// The last error returned by any method.
+    ///
+    /// It's `None` unless [track_last_error()](crate::SinkMock::track_last_error) was set. The
+    /// error is replaced together with the error counts, so `Display` of this handle always shows
+    /// the last error matching the counts.
+    pub fn last_error(&self) -> Option<E>
+    where
+        E: Clone,
+    {
+        self.shared
+            .errors
+            .with(|errors| errors.last.as_ref().map(|(last, _)| last.clone()))
+    }
+
+    /// Update the state of the mock after a call.
+    pub(crate) fn progress(&self, accepted: usize, buffered: usize, closed: bool) {
+        self.shared.accepted.store(accepted, Ordering::SeqCst);
+        self.shared.buffered.store(buffered, Ordering::SeqCst);
+        self.shared.closed.store(closed, Ordering::SeqCst);
+    }
+
+    /// Count `Poll::Pending` returned by a method.
+    pub(crate) fn pending(&self) {
+        self.shared.pendings.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// The counters with the error type erased.
+    #[cfg(feature = "std")]
+    pub(crate) fn stats(&self) -> Arc<dyn Stats>
+    where
+        E: 'static,
+    {
+        self.shared.clone()
+    }
+
+    /// Count `n` wakes done by `method` without waking.
+    pub(crate) fn woke(&self, method: Method, n: usize) {
+        let counter = match method {
+            Method::Ready => &self.shared.wakes_from_ready,
+            Method::Flush => &self.shared.wakes_from_flush,
+            Method::Close => &self.shared.wakes_from_close,
+            Method::Send => unreachable!("`start_send()` has no context to wake"),
+        };
+        counter.fetch_add(n, Ordering::SeqCst);
+    }
+
+    /// Count an error returned by `method`, replacing the last error with `last` if it's tracked.
+    pub(crate) fn error(&self, method: Method, last: Option<LastError<E>>) {
+        self.shared.errors.with(|errors| {
+            let counter = match method {
+                Method::Ready => &mut errors.from_ready,
+                Method::Send => &mut errors.from_send,
+                Method::Flush => &mut errors.from_flush,
+                Method::Close => &mut errors.from_close,
+            };
+            *counter += 1;
+            if last.is_some() {
+                errors.last = last;
+            }
+        });
+    }
+}
+
+impl<E: fmt::Debug> fmt::Display for MockCounters<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "wakes from ready: {}, flush: {}, close: {}; ",
+            self.wakes_from_ready(),
+            self.wakes_from_flush(),
+            self.wakes_from_close()
+        )?;
+        // a snapshot, so the last error matches the counts and `Debug` of it runs unlocked
+        let (counts, last) = self.shared.errors.with(|errors| {
+            let counts = [
+                errors.from_ready,
+                errors.from_send,
+                errors.from_flush,
+                errors.from_close,
+            ];
+            (
+                counts,
+                errors.last.as_ref().map(|(last, clone)| clone(last)),
+            )
+        });
+        write!(
+            f,
+            "errors from ready: {}, send: {}, flush: {}, close: {}",
+            counts[0], counts[1], counts[2], counts[3]
+        )?;
+        match last {
+            Some(last) => write!(f, "; last error: {:?}", last),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<E: fmt::Debug> fmt::Debug for MockCounters<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MockCounters({})", self)
+    }
+}
+
+struct Calls {
+    ready: AtomicUsize,
+    send: AtomicUsize,
+    flush: AtomicUsize,
+    close: AtomicUsize,
+}
+
+/// Call counts of a [SinkFeedback](crate::SinkFeedback) in
+/// [counters_only()](crate::SinkFeedback::counters_only) mode.
+///
+/// Unlike handles collecting items or errors it has no lock and no buffer, so counting adds
+/// little noise to microbenchmarks of sink wrappers: every call does a single relaxed
+/// `fetch_add`. Relaxed is enough, because each counter is independent and nothing else is
+/// published through it. Counts read while the sink is polled on another thread may lag behind,
+/// but after the task completed and was joined, which synchronizes with the polling thread, they
+/// are exact. Clones share the same counters.
+///
+/// # Examples
+/// ```
+/// use futures::{executor::block_on, stream, StreamExt};
+/// use futures_test_sink::ok;
+///
+/// let mut sink = ok();
+/// sink.counters_only();
+/// let calls = sink.call_counters().unwrap();
+/// block_on(stream::iter(vec![Ok(1u8), Ok(2)]).forward(sink)).unwrap();
+/// assert_eq!(calls.send_calls(), 2);
+/// assert_eq!(calls.close_calls(), 1);
+/// ```
+#[derive(Clone)]
+pub struct CallCounters {
+    shared: Arc<Calls>,
+}
+
+impl CallCounters {
+    pub(crate) fn new() -> Self {
+        Self {
+            shared: Arc::new(Calls {
+                ready: AtomicUsize::new(0),
+                send: AtomicUsize::new(0),
+                flush: AtomicUsize::new(0),
+                close: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    /// Count a call of `method`.
+    pub(crate) fn count(&self, method: Method) {
+        let counter = match method {
+            Method::Ready => &self.shared.ready,
+            Method::Send => &self.shared.send,
+            Method::Flush => &self.shared.flush,
+            Method::Close => &self.shared.close,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// How many times `poll_ready()` was called.
+    pub fn ready_calls(&self) -> usize {
+        self.shared.ready.load(Ordering::Relaxed)
+    }
+
+    /// How many times `start_send()` was called.
+    pub fn send_calls(&self) -> usize {
+        self.shared.send.load(Ordering::Relaxed)
+    }
+
+    /// How many times `poll_flush()` was called.
+    pub fn flush_calls(&self) -> usize {
+        self.shared.flush.load(Ordering::Relaxed)
+    }
+
+    /// How many times `poll_close()` was called.
+    pub fn close_calls(&self) -> usize {
+        self.shared.close.load(Ordering::Relaxed)
+    }
+}
+
+impl fmt::Debug for CallCounters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CallCounters")
+            .field("ready", &self.ready_calls())
+            .field("send", &self.send_calls())
+            .field("flush", &self.flush_calls())
+            .field("close", &self.close_calls())
+            .finish()
+    }
+}
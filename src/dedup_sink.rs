@@ -0,0 +1,123 @@
+use alloc::vec::Vec;
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures::{never::Never, sink::Sink};
+
+/// Sink that drops an item equal to the item received just before it.
+///
+/// For details see [dedup_mock()].
+///
+/// [dedup_mock()]:dedup_mock
+pub struct DedupSink<Item> {
+    retained: Vec<Item>,
+    received_cnt: usize,
+}
+
+/// Create a sink that coalesces adjacent equal items.
+///
+/// Every item passed to `start_send` is compared with the last retained item. If they are equal
+/// the new item is dropped (coalesced), otherwise it's retained. Only adjacent items are
+/// compared, so `1, 1, 2, 1` retains `1, 2, 1`.
+///
+/// Every method returns `Poll::Ready(Ok(()))` like [ok()](crate::ok).
+///
+/// # Examples
+/// ```
+/// use futures::{executor::block_on, stream, StreamExt};
+/// use futures_test_sink::dedup_mock;
+///
+/// let mut sink = dedup_mock();
+/// let items = stream::iter(vec![Ok("a"), Ok("a"), Ok("b"), Ok("a"), Ok("a")]);
+/// assert_eq!(Ok(()), block_on(items.forward(&mut sink)));
+///
+/// assert_eq!(sink.received_count(), 5);
+/// assert_eq!(sink.retained_count(), 3);
+/// assert_eq!(sink.coalesced_count(), 2);
+/// assert_eq!(sink.retained(), &["a", "b", "a"]);
+/// ```
+pub fn dedup_mock<Item: PartialEq>() -> DedupSink<Item> {
+    DedupSink {
+        retained: Vec::new(),
+        received_cnt: 0,
+    }
+}
+
+impl<Item> Unpin for DedupSink<Item> {}
+
+impl<Item> DedupSink<Item> {
+    /// How many items were passed to `start_send`.
+    pub fn received_count(&self) -> usize {
+        self.received_cnt
+    }
+
+    /// How many items were retained.
+    pub fn retained_count(&self) -> usize {
+        self.retained.len()
+    }
+
+    /// How many items were dropped because they were equal to the previous one.
+    pub fn coalesced_count(&self) -> usize {
+        self.received_cnt - self.retained.len()
+    }
+
+    /// Retained items in order they were received.
+    pub fn retained(&self) -> &[Item] {
+        &self.retained
+    }
+}
+
+impl<Item: PartialEq> Sink<Item> for DedupSink<Item> {
+    type Error = Never;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Item) -> Result<(), Self::Error> {
+        let this = Pin::into_inner(self);
+        this.received_cnt += 1;
+        if this.retained.last() != Some(&item) {
+            this.retained.push(item);
+        }
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{
+        executor::block_on,
+        stream::{self, StreamExt},
+    };
+
+    #[test]
+    fn coalesces_adjacent_only() {
+        let table = vec![
+            (vec![], vec![]),
+            (vec![1u8, 2, 3], vec![1, 2, 3]),
+            (vec![1, 1, 1], vec![1]),
+            (vec![1, 1, 2, 1], vec![1, 2, 1]),
+            (vec![1, 2, 1, 2], vec![1, 2, 1, 2]),
+        ];
+        for (items, expected) in table {
+            let mut sink = dedup_mock();
+            let received = items.len();
+            let items = stream::iter(items.into_iter().map(Ok));
+            assert_eq!(Ok(()), block_on(items.forward(&mut sink)));
+            assert_eq!(sink.retained(), &expected[..]);
+            assert_eq!(sink.received_count(), received);
+            assert_eq!(sink.coalesced_count(), received - expected.len());
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use futures::{never::Never, sink::Sink};
+use std::marker::PhantomData;
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::time::{sleep, sleep_until, Instant, Sleep};
+
+/// Sink that becomes ready and flushes according to tokio time.
+///
+/// For details see [delayed_ready()].
+///
+/// [delayed_ready()]:delayed_ready
+pub struct DelayedReady<Item> {
+    ready_after: Duration,
+    flush_every: Duration,
+    ready_sleep: Option<Pin<Box<Sleep>>>,
+    flush_sleep: Option<Pin<Box<Sleep>>>,
+    last_flush: Option<Instant>,
+    unflushed: usize,
+    send_cnt: usize,
+    item_type: PhantomData<fn(Item)>,
+}
+
+/// Create a sink whose `Poll::Pending` periods are driven by [tokio::time] instead of poll counts.
+///
+/// - `poll_ready` returns `Poll::Pending` until `ready_after` elapsed since the first `poll_ready`
+///   after the previous item was sent (or since the first `poll_ready` at all).
+/// - `poll_flush` with unflushed items returns `Poll::Pending` until `flush_every` elapsed since
+///   the previous completed flush (or since the sink was first polled). Without unflushed items it
+///   returns `Poll::Ready(Ok(()))` immediately.
+/// - `poll_close` flushes like `poll_flush`.
+///
+/// Waiting is done with [tokio::time::sleep], so the waker is woken by the tokio timer. In a test
+/// with paused clock (`#[tokio::test(start_paused = true)]`) [tokio::time::advance] decides when
+/// the sink becomes ready. This is useful to test code that mixes timeouts with sink readiness.
+///
+/// The sink must be polled inside tokio runtime. Requires `tokio` feature.
+///
+/// # Examples
+/// ```
+/// use futures::SinkExt;
+/// use futures_test_sink::delayed_ready;
+/// use std::time::Duration;
+///
+/// #[tokio::main(flavor = "current_thread", start_paused = true)]
+/// async fn main() {
+///     let mut sink = delayed_ready(Duration::from_secs(1), Duration::from_secs(5));
+///     let send = sink.send(1u8);
+///     // sink is ready only after one second, so timeout of 500ms expires first
+///     let timeout = tokio::time::timeout(Duration::from_millis(500), send);
+///     assert!(timeout.await.is_err());
+/// }
+/// ```
+pub fn delayed_ready<Item>(ready_after: Duration, flush_every: Duration) -> DelayedReady<Item> {
+    DelayedReady {
+        ready_after,
+        flush_every,
+        ready_sleep: None,
+        flush_sleep: None,
+        last_flush: None,
+        unflushed: 0,
+        send_cnt: 0,
+        item_type: Default::default(),
+    }
+}
+
+impl<Item> Unpin for DelayedReady<Item> {}
+
+impl<Item> DelayedReady<Item> {
+    /// How many items were sent.
+    pub fn send_count(&self) -> usize {
+        self.send_cnt
+    }
+
+    /// How many sent items were not flushed yet.
+    pub fn unflushed_count(&self) -> usize {
+        self.unflushed
+    }
+
+    fn poll_flush_inner(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Never>> {
+        let last_flush = *self.last_flush.get_or_insert_with(Instant::now);
+        if self.unflushed == 0 {
+            return Poll::Ready(Ok(()));
+        }
+        let flush_every = self.flush_every;
+        let flush_sleep = self
+            .flush_sleep
+            .get_or_insert_with(|| Box::pin(sleep_until(last_flush + flush_every)));
+        match flush_sleep.as_mut().poll(cx) {
+            Poll::Ready(()) => {
+                self.flush_sleep = None;
+                self.last_flush = Some(Instant::now());
+                self.unflushed = 0;
+                Poll::Ready(Ok(()))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<Item> Sink<Item> for DelayedReady<Item> {
+    type Error = Never;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = Pin::into_inner(self);
+        this.last_flush.get_or_insert_with(Instant::now);
+        let ready_after = this.ready_after;
+        let ready_sleep = this
+            .ready_sleep
+            .get_or_insert_with(|| Box::pin(sleep(ready_after)));
+        ready_sleep.as_mut().poll(cx).map(Ok)
+    }
+
+    fn start_send(self: Pin<&mut Self>, _item: Item) -> Result<(), Self::Error> {
+        let this = Pin::into_inner(self);
+        match &this.ready_sleep {
+            Some(ready_sleep) if ready_sleep.is_elapsed() => (),
+            _ => panic!("`start_send()` called before `poll_ready()` returned `Poll::Ready`"),
+        }
+        this.ready_sleep = None;
+        this.unflushed += 1;
+        this.send_cnt += 1;
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::into_inner(self).poll_flush_inner(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::into_inner(self).poll_flush_inner(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{poll, SinkExt};
+    use tokio::time::advance;
+
+    #[tokio::test(start_paused = true)]
+    async fn send_completes_after_advance() {
+        let mut sink = delayed_ready(Duration::from_secs(1), Duration::from_secs(3));
+        let mut send = sink.send(1u8);
+        assert!(poll!(&mut send).is_pending());
+        advance(Duration::from_millis(999)).await;
+        assert!(poll!(&mut send).is_pending());
+        advance(Duration::from_millis(1)).await;
+        // ready, but flush waits for `flush_every` since first poll
+        assert!(poll!(&mut send).is_pending());
+        advance(Duration::from_secs(2)).await;
+        assert_eq!(poll!(&mut send), Poll::Ready(Ok(())));
+        drop(send);
+        assert_eq!(sink.send_count(), 1);
+        assert_eq!(sink.unflushed_count(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn ready_delay_restarts_after_send() {
+        let mut sink = delayed_ready(Duration::from_secs(2), Duration::from_secs(10));
+        let start = Instant::now();
+        sink.feed(1u8).await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_secs(2));
+        sink.feed(2u8).await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_secs(4));
+        assert_eq!(sink.unflushed_count(), 2);
+        sink.flush().await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_secs(10));
+        // nothing to flush
+        sink.flush().await.unwrap();
+        assert_eq!(start.elapsed(), Duration::from_secs(10));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn timeout_wins_over_readiness() {
+        let mut sink = delayed_ready(Duration::from_secs(5), Duration::ZERO);
+        let send = sink.send(1u8);
+        let res = tokio::time::timeout(Duration::from_secs(1), send).await;
+        assert!(res.is_err());
+        let send = sink.send(1u8);
+        let res = tokio::time::timeout(Duration::from_secs(10), send).await;
+        assert_eq!(res, Ok(Ok(())));
+    }
+}
@@ -0,0 +1,425 @@
+use crate::trace_diff::{rendered_step, RenderedStep};
+use crate::{Event, Response, Script};
+use alloc::sync::Arc;
+use alloc::task::Wake;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::task::Waker;
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures::sink::Sink;
+
+/// Differential sink that drives two sinks with identical operations and compares their
+/// observable behavior.
+///
+/// Every operation is performed on `left` and then on `right`. After each operation the returned
+/// values and the number of wakes the sinks made while being polled are compared. On first
+/// divergence `DiffSink` panics with the operation index, both results and the
+/// [diff](Script::diff) of the operations both sinks went through.
+///
+/// Items are cloned for the `left` sink and moved to the `right` one. They must be `Debug` for the
+/// report.
+///
+/// # Pending tolerance
+/// Two correct sinks may legally differ in when they return `Poll::Pending`. By default this is
+/// reported as divergence. After [set_tolerate_pending(true)] the side that returned
+/// `Poll::Ready` keeps its result until the other side is ready too and only results are compared.
+/// The pending side is responsible for waking the task. Wake counts are not compared in this mode.
+///
+/// While one side is still pending, calling a different method panics.
+///
+/// # Examples
+/// ```
+/// use futures::{
+///     executor::block_on,
+///     never::Never,
+///     stream::{self, StreamExt},
+/// };
+/// use futures_test_sink::{DiffSink, SinkMock};
+/// use std::{iter, task::Poll};
+///
+/// let old = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, Never>(()))));
+/// let new = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok(()))));
+/// let sink = DiffSink::new(old, new);
+///
+/// let stream = stream::iter(vec![Ok::<u8, Never>(5u8), Ok(7), Ok(9), Ok(77), Ok(79)]);
+/// assert_eq!(Ok(()), block_on(stream.forward(sink)));
+/// ```
+///
+/// [set_tolerate_pending(true)]: DiffSink::set_tolerate_pending
+pub struct DiffSink<A, B, E> {
+    left: A,
+    right: B,
+    op_index: usize,
+    tolerate_pending: bool,
+
+    // results kept while waiting for the other side (pending tolerance)
+    pending_op: Option<&'static str>,
+    left_result: Option<Result<(), E>>,
+    right_result: Option<Result<(), E>>,
+
+    // every poll and send of both sides for the report
+    left_trace: Vec<RenderedStep>,
+    right_trace: Vec<RenderedStep>,
+}
+
+// `None` is `Poll::Pending`
+fn step<Item: Debug, E: Debug>(event: &Event<Item>, res: Option<&Result<(), E>>) -> RenderedStep {
+    let response = match res {
+        Some(Ok(())) => Response::Ok,
+        Some(Err(e)) => Response::Err(e),
+        None => Response::Pending,
+    };
+    rendered_step(event, &response)
+}
+
+fn ready<T>(poll: &Poll<T>) -> Option<&T> {
+    match poll {
+        Poll::Ready(t) => Some(t),
+        Poll::Pending => None,
+    }
+}
+
+fn event(op: &str) -> Event<()> {
+    match op {
+        "poll_ready" => Event::Ready,
+        "poll_flush" => Event::Flush,
+        _ => Event::Close,
+    }
+}
+
+impl<A, B, E> Unpin for DiffSink<A, B, E> {}
+
+impl<A, B, E> DiffSink<A, B, E> {
+    /// Create a new `DiffSink` comparing `left` with `right`.
+    pub fn new(left: A, right: B) -> Self {
+        Self {
+            left,
+            right,
+            op_index: 0,
+            tolerate_pending: false,
+            pending_op: None,
+            left_result: None,
+            right_result: None,
+            left_trace: Vec::new(),
+            right_trace: Vec::new(),
+        }
+    }
+
+    /// Tolerate one sink returning `Poll::Pending` while the other one returns `Poll::Ready`.
+    pub fn set_tolerate_pending(&mut self, tolerate: bool) -> &mut Self {
+        self.tolerate_pending = tolerate;
+        self
+    }
+
+    /// How many operations were performed on both sinks.
+    pub fn op_count(&self) -> usize {
+        self.op_index
+    }
+
+    /// Consume `DiffSink` returning both inner sinks.
+    pub fn into_inner(self) -> (A, B) {
+        (self.left, self.right)
+    }
+}
+
+/// Waker that counts wakes and forwards them to the wrapped waker.
+struct CountingWaker {
+    inner: Waker,
+    count: AtomicUsize,
+}
+
+impl Wake for CountingWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.count.fetch_add(1, Ordering::SeqCst);
+        self.inner.wake_by_ref();
+    }
+}
+
+/// Call `f` with a context that counts wakes made during the call.
+fn count_wakes<T>(cx: &mut Context<'_>, f: impl FnOnce(&mut Context<'_>) -> T) -> (T, usize) {
+    let counting = Arc::new(CountingWaker {
+        inner: cx.waker().clone(),
+        count: AtomicUsize::new(0),
+    });
+    let waker = Waker::from(counting.clone());
+    let result = f(&mut Context::from_waker(&waker));
+    (result, counting.count.load(Ordering::SeqCst))
+}
+
+impl<A, B, E> DiffSink<A, B, E>
+where
+    E: PartialEq + Debug,
+{
+    fn diverged(&self, op: &str, left: &dyn Debug, right: &dyn Debug) -> ! {
+        let left_trace = Script::new(self.left_trace.clone());
+        let right_trace = Script::new(self.right_trace.clone());
+        panic!(
+            "DiffSink divergence at operation #{} (`{}`): left = {:?}, right = {:?}\n{}",
+            self.op_index,
+            op,
+            left,
+            right,
+            left_trace.diff(&right_trace)
+        );
+    }
+
+    fn poll_both(
+        &mut self,
+        op: &'static str,
+        cx: &mut Context<'_>,
+        poll_left: impl FnOnce(&mut A, &mut Context<'_>) -> Poll<Result<(), E>>,
+        poll_right: impl FnOnce(&mut B, &mut Context<'_>) -> Poll<Result<(), E>>,
+    ) -> Poll<Result<(), E>> {
+        if let Some(pending_op) = self.pending_op {
+            if pending_op != op {
+                panic!(
+                    "DiffSink: `{}` called while `{}` (operation #{}) is still pending on one side",
+                    op, pending_op, self.op_index
+                );
+            }
+        }
+
+        let event = event(op);
+        if !self.tolerate_pending {
+            let left = count_wakes(cx, |cx| poll_left(&mut self.left, cx));
+            let right = count_wakes(cx, |cx| poll_right(&mut self.right, cx));
+            self.left_trace.push(step(&event, ready(&left.0)));
+            self.right_trace.push(step(&event, ready(&right.0)));
+            if left != right {
+                self.diverged(
+                    op,
+                    &format_args!("{:?} ({} wakes)", left.0, left.1),
+                    &format_args!("{:?} ({} wakes)", right.0, right.1),
+                );
+            }
+            if left.0.is_ready() {
+                self.op_index += 1;
+            }
+            return left.0;
+        }
+
+        if self.left_result.is_none() {
+            let poll = poll_left(&mut self.left, cx);
+            self.left_trace.push(step(&event, ready(&poll)));
+            if let Poll::Ready(r) = poll {
+                self.left_result = Some(r);
+            }
+        }
+        if self.right_result.is_none() {
+            let poll = poll_right(&mut self.right, cx);
+            self.right_trace.push(step(&event, ready(&poll)));
+            if let Poll::Ready(r) = poll {
+                self.right_result = Some(r);
+            }
+        }
+
+        match (self.left_result.take(), self.right_result.take()) {
+            (Some(left), Some(right)) => {
+                if left != right {
+                    self.diverged(op, &left, &right);
+                }
+                self.pending_op = None;
+                self.op_index += 1;
+                Poll::Ready(left)
+            }
+            (left, right) => {
+                self.pending_op = Some(op);
+                self.left_result = left;
+                self.right_result = right;
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<A, B, E, Item> Sink<Item> for DiffSink<A, B, E>
+where
+    A: Sink<Item, Error = E> + Unpin,
+    B: Sink<Item, Error = E> + Unpin,
+    E: PartialEq + Debug,
+    Item: Clone + Debug,
+{
+    type Error = E;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::into_inner(self).poll_both(
+            "poll_ready",
+            cx,
+            |a, cx| Pin::new(a).poll_ready(cx),
+            |b, cx| Pin::new(b).poll_ready(cx),
+        )
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Item) -> Result<(), Self::Error> {
+        let this = Pin::into_inner(self);
+        if let Some(pending_op) = this.pending_op {
+            panic!(
+                "DiffSink: `start_send` called while `{}` (operation #{}) is still pending on one side",
+                pending_op, this.op_index
+            );
+        }
+
+        let event = Event::Send(item.clone());
+        let left = Pin::new(&mut this.left).start_send(item.clone());
+        let right = Pin::new(&mut this.right).start_send(item);
+        this.left_trace.push(step(&event, Some(&left)));
+        this.right_trace.push(step(&event, Some(&right)));
+        if left != right {
+            this.diverged("start_send", &left, &right);
+        }
+        this.op_index += 1;
+        left
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::into_inner(self).poll_both(
+            "poll_flush",
+            cx,
+            |a, cx| Pin::new(a).poll_flush(cx),
+            |b, cx| Pin::new(b).poll_flush(cx),
+        )
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::into_inner(self).poll_both(
+            "poll_close",
+            cx,
+            |a, cx| Pin::new(a).poll_close(cx),
+            |b, cx| Pin::new(b).poll_close(cx),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SinkMock;
+    use futures::{
+        executor::block_on,
+        never::Never,
+        stream::{self, StreamExt},
+        SinkExt,
+    };
+    use std::iter;
+
+    fn items() -> impl futures::Stream<Item = Result<u8, Never>> {
+        stream::iter(vec![Ok(5u8), Ok(7), Ok(9), Ok(77), Ok(79)])
+    }
+
+    #[test]
+    fn same_behavior() {
+        let left = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, Never>(()))));
+        let right = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok(()))));
+        let mut sink = DiffSink::new(left, right);
+        assert_eq!(Ok(()), block_on(items().forward(&mut sink)));
+        // 5 x (ready + send) + 1 close
+        assert_eq!(sink.op_count(), 11);
+        let (_left, _right) = sink.into_inner();
+    }
+
+    #[test]
+    fn same_pending_behavior() {
+        let feedback = || {
+            vec![Poll::Ready(Ok::<_, Never>(())), Poll::Pending]
+                .into_iter()
+                .cycle()
+        };
+        let sink = DiffSink::new(
+            SinkMock::with_flush_feedback(feedback()),
+            SinkMock::with_flush_feedback(feedback()),
+        );
+        assert_eq!(Ok(()), block_on(items().forward(sink)));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "DiffSink divergence at operation #6 (`poll_ready`): left = Ready(Ok(())) (0 wakes), right = Pending (1 wakes)"
+    )]
+    fn pending_divergence() {
+        let left = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, Never>(()))));
+        let right = SinkMock::with_flush_feedback(
+            vec![Poll::Ready(Ok(())), Poll::Pending].into_iter().cycle(),
+        );
+        let _ = block_on(items().forward(DiffSink::new(left, right)));
+    }
+
+    #[test]
+    fn tolerate_pending() {
+        let left = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, Never>(()))));
+        let right = SinkMock::with_flush_feedback(
+            vec![Poll::Ready(Ok(())), Poll::Pending].into_iter().cycle(),
+        );
+        let mut sink = DiffSink::new(left, right);
+        sink.set_tolerate_pending(true);
+        assert_eq!(Ok(()), block_on(items().forward(sink)));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "DiffSink divergence at operation #6 (`poll_close`): left = Ok(()), right = Err(())"
+    )]
+    fn tolerate_pending_reports_error() {
+        let left = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok(()))));
+        let right = SinkMock::with_flush_feedback(
+            vec![Poll::Ready(Ok(())), Poll::Pending, Poll::Ready(Err(()))].into_iter(),
+        );
+        let mut sink = DiffSink::new(left, right);
+        sink.set_tolerate_pending(true);
+        let stream = stream::iter(vec![Ok::<u8, ()>(5u8), Ok(7), Ok(9)]);
+        let _ = block_on(stream.forward(sink));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "DiffSink divergence at operation #1 (`start_send`): left = Ok(()), right = Err(7)"
+    )]
+    fn start_send_divergence() {
+        let waker = async_task::waker_fn(|| {});
+        let mut cx = Context::from_waker(&waker);
+        let left = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, u32>(()))));
+        let right = SinkMock::new(
+            iter::repeat(Poll::Ready(Ok(()))),
+            iter::empty(),
+            iter::once(7),
+            3,
+            2,
+        );
+        let mut sink = DiffSink::new(left, right);
+        assert_eq!(Pin::new(&mut sink).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        let _ = Pin::new(&mut sink).start_send(1);
+    }
+
+    #[test]
+    fn divergence_report_diffs_traces() {
+        let left = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, u32>(()))));
+        let right = SinkMock::new(
+            iter::repeat(Poll::Ready(Ok(()))),
+            iter::empty(),
+            vec![7].into_iter(),
+            3,
+            2,
+        );
+        let mut sink = DiffSink::new(left, right);
+        let message = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _ = block_on(sink.send(1u8));
+        }))
+        .unwrap_err();
+        let message = message.downcast::<String>().unwrap();
+        assert_eq!(
+            *message,
+            "DiffSink divergence at operation #1 (`start_send`): left = Ok(()), right = Err(7)
+traces differ at step 1: 1 removed, 1 added
+    0  ready ✔
+-   1  send item#0 1 ✔
++   1  send item#0 1 ✘ 7"
+        );
+    }
+}
@@ -0,0 +1,148 @@
+//! `tracing` events emitted by mocks and wrappers.
+//!
+//! Every macro here expands to nothing without `tracing` feature, so arguments are not even
+//! evaluated then.
+
+#[cfg(feature = "tracing")]
+use core::task::Poll;
+
+/// Emit a debug level `tracing` event.
+macro_rules! debug_event {
+    ($($arg:tt)+) => {
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($arg)+);
+    };
+}
+
+/// Emit an event of a wrapper with target `<wrapper>::<method>` and the result of the call.
+macro_rules! wrapper_event {
+    ($wrapper:literal, "start_send", $result:expr) => {
+        debug_event!(
+            target: concat!($wrapper, "::start_send"),
+            result = crate::events::send_outcome($result)
+        )
+    };
+    ($wrapper:literal, $method:literal, $result:expr) => {
+        debug_event!(
+            target: concat!($wrapper, "::", $method),
+            result = crate::events::outcome($result)
+        )
+    };
+}
+
+/// Short description of a result, so errors don't need to implement `Debug`.
+///
+/// Names match [Response](crate::Response) variants.
+#[cfg(feature = "tracing")]
+pub(crate) fn outcome<E>(poll: &Poll<Result<(), E>>) -> &'static str {
+    match poll {
+        Poll::Ready(Ok(())) => "ok",
+        Poll::Ready(Err(_)) => "err",
+        Poll::Pending => "pending",
+    }
+}
+
+/// Short description of a result of `start_send()`.
+#[cfg(feature = "tracing")]
+pub(crate) fn send_outcome<E>(res: &Result<(), E>) -> &'static str {
+    match res {
+        Ok(()) => "ok",
+        Err(_) => "err",
+    }
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use crate::{from_iter, InspectSink, SinkMock};
+    use futures::{executor::block_on, SinkExt};
+    use std::io;
+    use std::sync::{Arc, Mutex};
+    use std::task::Poll;
+    use tracing_subscriber::fmt::{MakeWriter, Subscriber};
+
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for Captured {
+        type Writer = Self;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    fn capture(f: impl FnOnce()) -> Vec<String> {
+        let captured = Captured::default();
+        let subscriber = Subscriber::builder()
+            .with_writer(captured.clone())
+            .with_max_level(tracing::Level::DEBUG)
+            .with_ansi(false)
+            .without_time()
+            .finish();
+        tracing::subscriber::with_default(subscriber, f);
+        let output = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        output.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn sink_mock_events() {
+        let lines = capture(|| {
+            let feedback = vec![Poll::Pending, Poll::Ready(Ok::<_, ()>(()))].into_iter();
+            let mut sink = SinkMock::with_flush_feedback(feedback);
+            sink.named("db");
+            block_on(sink.send(1u8)).unwrap();
+        });
+        assert_eq!(
+            lines,
+            vec![
+                "DEBUG sink_mock::poll_ready: mock=\"db\" result=\"ok\" buffered=0 script_index=0",
+                "DEBUG sink_mock::start_send: mock=\"db\" result=\"ok\" buffered=1",
+                "DEBUG sink_mock::poll_flush: mock=\"db\" result=\"pending\" buffered=1 script_index=1",
+                "DEBUG sink_mock::poll_flush: mock=\"db\" result=\"ok\" buffered=0 script_index=2",
+            ]
+        );
+    }
+
+    #[test]
+    fn sink_feedback_and_wrapper_events() {
+        let lines = capture(|| {
+            let mut feedback = from_iter(
+                vec![Poll::Ready(Err("reset"))].into_iter(),
+                Vec::new().into_iter(),
+            );
+            feedback.named("fallback");
+            let mut sink = InspectSink::new(feedback, |_: &u8| ());
+            assert_eq!(block_on(sink.send(1u8)), Err("reset"));
+        });
+        assert_eq!(
+            lines,
+            vec![
+                "DEBUG sink_feedback::poll_ready: mock=\"fallback\" result=\"err\" script_index=1",
+                "DEBUG inspect_sink::poll_ready: result=\"err\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn unnamed_mock_has_no_name_field() {
+        let lines = capture(|| {
+            let mut sink = crate::ok();
+            block_on(SinkExt::<u8>::flush(&mut sink)).unwrap();
+        });
+        assert_eq!(
+            lines,
+            vec!["DEBUG sink_feedback::poll_flush: result=\"ok\" script_index=1"]
+        );
+    }
+}
@@ -0,0 +1,164 @@
+use futures::sink::Sink;
+use pin_project_lite::pin_project;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::pin::Pin;
+
+pin_project! {
+    /// Wrapper that detects items delivered more than once.
+    ///
+    /// For details see [exactly_once()].
+    ///
+    /// [exactly_once()]:exactly_once
+    pub struct ExactlyOnce<S, Item> {
+        #[pin]
+        inner: S,
+        seen: HashMap<Item, usize>,
+        // items in order they were sent the second time
+        duplicates: Vec<Item>,
+    }
+}
+
+/// Wrap `inner` sink recording every item it accepted to find duplicates.
+///
+/// This checks that a producer delivers every item exactly once, e.g. that it doesn't resend
+/// items after a retry that in fact succeeded. Tracking is observational only: every method is
+/// delegated to `inner` unchanged, so its buffering and backpressure stay intact. Only items
+/// accepted by `start_send()` of the inner sink are recorded, an item rejected with an error
+/// can be retried without being reported.
+///
+/// # Memory
+/// A clone of every distinct item is kept for the lifetime of the wrapper, so memory grows
+/// with the number of distinct items. For long runs with large items send a small unique key
+/// (like a sequence number) instead of the whole item.
+///
+/// Requires `std` feature.
+///
+/// # Examples
+/// ```
+/// use futures::{executor::block_on, stream, StreamExt};
+/// use futures_test_sink::{exactly_once, ok};
+///
+/// let mut sink = exactly_once(ok());
+/// let items = stream::iter(vec![Ok(1u32), Ok(2), Ok(1), Ok(3), Ok(1), Ok(2)]);
+/// assert_eq!(Ok(()), block_on(items.forward(&mut sink)));
+/// assert_eq!(sink.duplicates(), vec![1, 2]);
+/// assert_eq!(sink.times_sent(&1), 3);
+/// assert!(!sink.is_exactly_once());
+/// ```
+pub fn exactly_once<S, Item>(inner: S) -> ExactlyOnce<S, Item>
+where
+    Item: Hash + Eq + Clone,
+{
+    ExactlyOnce {
+        inner,
+        seen: HashMap::new(),
+        duplicates: Vec::new(),
+    }
+}
+
+impl<S, Item> ExactlyOnce<S, Item>
+where
+    Item: Hash + Eq + Clone,
+{
+    /// Items accepted more than once, each listed once in order they were sent the second time.
+    pub fn duplicates(&self) -> Vec<Item> {
+        self.duplicates.clone()
+    }
+
+    /// `true` if no item was accepted more than once.
+    pub fn is_exactly_once(&self) -> bool {
+        self.duplicates.is_empty()
+    }
+
+    /// How many times `item` was accepted.
+    pub fn times_sent(&self, item: &Item) -> usize {
+        self.seen.get(item).copied().unwrap_or(0)
+    }
+
+    /// How many distinct items were accepted.
+    pub fn distinct_count(&self) -> usize {
+        self.seen.len()
+    }
+}
+
+impl<S, Item> ExactlyOnce<S, Item> {
+    inner_accessors!(S, "the inner sink");
+}
+
+impl<S, Item> Sink<Item> for ExactlyOnce<S, Item>
+where
+    S: Sink<Item>,
+    Item: Hash + Eq + Clone,
+{
+    type Error = S::Error;
+
+    forward_sink!("exactly_once", poll_ready, poll_flush, poll_close);
+
+    fn start_send(self: Pin<&mut Self>, item: Item) -> Result<(), Self::Error> {
+        let this = self.project();
+        let key = item.clone();
+        let res = this.inner.start_send(item);
+        wrapper_event!("exactly_once", "start_send", &res);
+        res?;
+        match this.seen.get_mut(&key) {
+            Some(times) => {
+                *times += 1;
+                if *times == 2 {
+                    this.duplicates.push(key);
+                }
+            }
+            None => {
+                this.seen.insert(key, 1);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{from_iter, SinkMock};
+    use futures::{executor::block_on, never::Never, SinkExt};
+    use std::iter;
+    use std::task::Poll;
+
+    #[test]
+    fn rejected_items_are_not_recorded() {
+        let inner = from_iter(
+            iter::repeat(Poll::Ready(Ok(()))),
+            vec![Ok(()), Err("busy"), Ok(()), Ok(())].into_iter(),
+        );
+        let mut sink = exactly_once(inner);
+        block_on(async {
+            sink.send(1u8).await.unwrap();
+            assert_eq!(sink.send(2).await, Err("busy"));
+            // retry after an error isn't a duplicate
+            sink.send(2).await.unwrap();
+            sink.send(3).await.unwrap();
+        });
+        assert!(sink.is_exactly_once());
+        assert_eq!(sink.distinct_count(), 3);
+        assert_eq!(sink.times_sent(&4), 0);
+    }
+
+    #[test]
+    fn buffering_of_inner_sink_is_kept() {
+        let feedback = vec![Poll::Ready(Ok::<_, Never>(())), Poll::Pending]
+            .into_iter()
+            .cycle();
+        let mut sink = exactly_once(SinkMock::with_flush_feedback(feedback));
+        block_on(async {
+            for item in [1u8, 2, 3, 2, 1, 2] {
+                sink.feed(item).await.unwrap();
+            }
+            SinkExt::<u8>::close(&mut sink).await.unwrap();
+        });
+        assert_eq!(sink.duplicates(), vec![2, 1]);
+        assert_eq!(sink.times_sent(&2), 3);
+        // the mock buffered up to 3 items like without the wrapper
+        assert_eq!(sink.get_mut().high_water_mark(), 3);
+        assert_eq!(sink.into_inner().flush_feedback_consumed(), 5);
+    }
+}
@@ -0,0 +1,217 @@
+//! Global registry of named faults.
+//!
+//! In larger integration tests a mock can be buried several constructors deep, and threading a
+//! handle to it through is invasive. Name the mock with [SinkMock::failpoint()] and then change
+//! its behavior from anywhere in the test binary with [set()], [clear()] or [scoped()].
+//!
+//! Named mocks consult the registry on every call before their own scripts (`flush_feedback`,
+//! `ready_fallback`, ...). A fault stays active until it's cleared.
+//!
+//! The registry is shared by all tests in the binary and tests run in parallel by default. Use
+//! unique names in every test and prefer [scoped()] guards, so a fault doesn't leak to other tests
+//! even when the test panics.
+//!
+//! Requires `failpoints` feature.
+//!
+//! # Examples
+//! ```
+//! use futures::{executor::block_on, never::Never, SinkExt};
+//! use futures_test_sink::{failpoints::{self, Fault}, SinkMock};
+//! use std::{iter, task::Poll};
+//!
+//! // somewhere deep in the code under test
+//! fn make_db_sink() -> impl futures::Sink<u8, Error = &'static str> + Unpin {
+//!     let mut sink = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok(()))));
+//!     sink.failpoint("docs::db_sink");
+//!     sink
+//! }
+//!
+//! let mut sink = make_db_sink();
+//! assert_eq!(block_on(sink.send(1)), Ok(()));
+//! {
+//!     let _guard = failpoints::scoped("docs::db_sink", Fault::FlushErr("disk full"));
+//!     assert_eq!(block_on(sink.send(2)), Err("disk full"));
+//! }
+//! assert_eq!(block_on(sink.send(3)), Ok(()));
+//! ```
+//!
+//! [SinkMock::failpoint()]: crate::SinkMock::failpoint
+
+use std::any::Any;
+use std::sync::Mutex;
+
+/// Fault injected into a named mock.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Fault<E> {
+    /// `poll_ready()` returns `Poll::Ready(Err(e))`.
+    ReadyErr(E),
+    /// `start_send()` returns `Err(e)`. The item is discarded.
+    SendErr(E),
+    /// `poll_flush()` returns `Poll::Ready(Err(e))`.
+    FlushErr(E),
+    /// `poll_close()` returns `Poll::Ready(Err(e))`.
+    CloseErr(E),
+    /// `poll_ready()`, `poll_flush()` and `poll_close()` wake the waker and return
+    /// `Poll::Pending`.
+    Pending,
+}
+
+type Registry = Vec<(String, Box<dyn Any + Send>)>;
+
+static REGISTRY: Mutex<Registry> = Mutex::new(Vec::new());
+
+fn registry() -> std::sync::MutexGuard<'static, Registry> {
+    // a panicking test must not break other tests
+    REGISTRY.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Activate `fault` for mocks named `name`, replacing the previous fault.
+pub fn set<E: Clone + Send + 'static>(name: &str, fault: Fault<E>) {
+    let mut registry = registry();
+    registry.retain(|(n, _)| n != name);
+    registry.push((name.to_owned(), Box::new(fault)));
+}
+
+/// Deactivate fault for mocks named `name`.
+pub fn clear(name: &str) {
+    registry().retain(|(n, _)| n != name);
+}
+
+/// Activate `fault` for mocks named `name` until the returned guard is dropped.
+pub fn scoped<E: Clone + Send + 'static>(name: &str, fault: Fault<E>) -> FailGuard {
+    set(name, fault);
+    FailGuard {
+        name: name.to_owned(),
+    }
+}
+
+/// Guard that clears a fault when dropped. Returned by [scoped()].
+#[must_use = "the fault is cleared when the guard is dropped"]
+#[derive(Debug)]
+pub struct FailGuard {
+    name: String,
+}
+
+impl Drop for FailGuard {
+    fn drop(&mut self) {
+        clear(&self.name);
+    }
+}
+
+/// Function that gets the active fault with error type `E`.
+pub(crate) type Lookup<E> = fn(&str) -> Option<Fault<E>>;
+
+/// Get the active fault for `name`.
+///
+/// # Panics
+/// When the fault was set with a different error type.
+pub(crate) fn get<E: Clone + 'static>(name: &str) -> Option<Fault<E>> {
+    let registry = registry();
+    let (_, fault) = registry.iter().find(|(n, _)| n == name)?;
+    match fault.downcast_ref::<Fault<E>>() {
+        Some(fault) => Some(fault.clone()),
+        None => panic!(
+            "Failpoint `{}` was set with a different error type than `{}`",
+            name,
+            std::any::type_name::<E>()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SinkMock;
+    use futures::{executor::block_on, stream, FutureExt, SinkExt, StreamExt};
+    use std::{iter, task::Poll, thread};
+
+    type Mock = SinkMock<
+        iter::Repeat<Poll<Result<(), String>>>,
+        iter::Empty<String>,
+        iter::Empty<String>,
+        u8,
+        String,
+    >;
+
+    // code under test that hides the mock
+    struct Client {
+        transport: Transport,
+    }
+
+    struct Transport {
+        sink: Mock,
+    }
+
+    impl Client {
+        fn connect(name: &str) -> Self {
+            let mut sink = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok(()))));
+            sink.failpoint(name);
+            Client {
+                transport: Transport { sink },
+            }
+        }
+
+        fn request(&mut self, item: u8) -> Result<(), String> {
+            block_on(self.transport.sink.send(item))
+        }
+    }
+
+    #[test]
+    fn nested_mock_flipped_from_test() {
+        let mut client = Client::connect("tests::nested");
+        assert_eq!(client.request(1), Ok(()));
+
+        set("tests::nested", Fault::FlushErr(String::from("flush")));
+        assert_eq!(client.request(2), Err(String::from("flush")));
+        // persistent until cleared
+        assert_eq!(client.request(3), Err(String::from("flush")));
+
+        set("tests::nested", Fault::SendErr(String::from("send")));
+        assert_eq!(client.request(4), Err(String::from("send")));
+
+        clear("tests::nested");
+        assert_eq!(client.request(5), Ok(()));
+    }
+
+    #[test]
+    fn scoped_guard_clears_on_drop() {
+        let mut client = Client::connect("tests::scoped");
+        let guard = scoped("tests::scoped", Fault::ReadyErr(String::from("ready")));
+        assert_eq!(client.request(1), Err(String::from("ready")));
+        drop(guard);
+        assert_eq!(client.request(2), Ok(()));
+
+        let res = std::panic::catch_unwind(|| {
+            let _guard = scoped("tests::scoped", Fault::CloseErr(String::from("close")));
+            panic!("test failed");
+        });
+        assert!(res.is_err());
+        assert!(get::<String>("tests::scoped").is_none());
+    }
+
+    #[test]
+    fn set_from_other_thread() {
+        let mut client = Client::connect("tests::thread");
+        thread::spawn(|| set("tests::thread", Fault::<String>::Pending))
+            .join()
+            .unwrap();
+        let mut ready = client.transport.sink.send(1);
+        // `Fault::Pending` wakes the task, so poll it a few times by hand
+        let waker = futures::task::noop_waker();
+        let mut cx = std::task::Context::from_waker(&waker);
+        for _ in 0..3 {
+            assert!(ready.poll_unpin(&mut cx).is_pending());
+        }
+        drop(ready);
+        clear("tests::thread");
+        let items = stream::iter(vec![Ok(1), Ok(2)]);
+        assert_eq!(Ok(()), block_on(items.forward(&mut client.transport.sink)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Failpoint `tests::type` was set with a different error type")]
+    fn different_error_type() {
+        let _guard = scoped("tests::type", Fault::FlushErr(1u32));
+        let _ = get::<String>("tests::type");
+    }
+}
@@ -0,0 +1,278 @@
+use alloc::{vec, vec::Vec};
+use core::fmt;
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures::sink::Sink;
+
+/// Error of [Fanout] with the index of the inner sink that returned it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FanoutError<E> {
+    /// Index of the failing sink in the `Vec` passed to [fanout()].
+    pub index: usize,
+    /// Error returned by the sink.
+    pub error: E,
+}
+
+impl<E: fmt::Display> fmt::Display for FanoutError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "sink {} failed: {}", self.index, self.error)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<E> std::error::Error for FanoutError<E>
+where
+    E: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Ready,
+    Flush,
+    Close,
+}
+
+/// Sink broadcasting every item to all inner sinks.
+///
+/// For details see [fanout()].
+///
+/// [fanout()]:fanout
+pub struct Fanout<S> {
+    sinks: Vec<S>,
+    // sinks that completed the operation in progress
+    op: Option<Op>,
+    done: Vec<bool>,
+}
+
+/// Create a sink that sends every item to all `sinks`.
+///
+/// # Readiness
+/// `poll_ready()`, `poll_flush()` and `poll_close()` poll inner sinks in index order and return
+/// `Poll::Ready(Ok(()))` only when all of them completed. Every not yet completed sink is polled,
+/// so all of them register the waker. A sink that already completed the operation isn't polled
+/// again until the whole operation completes or another operation is started.
+///
+/// # Ordering
+/// `start_send()` gives the item to sinks in index order, clones for all but the last one. Since
+/// all sinks must be ready before an item is sent, every sink receives the same items in the same
+/// order.
+///
+/// # Errors
+/// The first error in index order is returned as [FanoutError] with the index of the failing
+/// sink. Sinks after it aren't polled, nor given the item, by that call.
+///
+/// # Examples
+/// ```
+/// use futures::{executor::block_on, stream, StreamExt};
+/// use futures_test_sink::{fanout, interleave_pending, tee};
+///
+/// let sinks = vec![Box::pin(interleave_pending()), Box::pin(interleave_pending())];
+/// let items = stream::iter(vec![Ok(1u8), Ok(2), Ok(3)]);
+/// assert_eq!(Ok(()), block_on(items.forward(fanout(sinks))));
+///
+/// let (sink, items) = tee(Vec::new());
+/// let mut sinks = fanout(vec![sink]);
+/// assert_eq!(Ok(()), block_on(stream::iter(vec![Ok(5u8)]).forward(&mut sinks)));
+/// assert_eq!(items.items(), vec![5]);
+/// ```
+pub fn fanout<S>(sinks: Vec<S>) -> Fanout<S> {
+    Fanout {
+        done: vec![false; sinks.len()],
+        sinks,
+        op: None,
+    }
+}
+
+impl<S> Unpin for Fanout<S> {}
+
+impl<S> Fanout<S> {
+    /// Get a reference to the inner sinks.
+    pub fn get_ref(&self) -> &[S] {
+        &self.sinks
+    }
+
+    /// Get a mutable reference to the inner sinks.
+    pub fn get_mut(&mut self) -> &mut [S] {
+        &mut self.sinks
+    }
+
+    /// Consume the fanout returning the inner sinks.
+    pub fn into_inner(self) -> Vec<S> {
+        self.sinks
+    }
+
+    fn reset(&mut self, op: Option<Op>) {
+        self.op = op;
+        for done in &mut self.done {
+            *done = false;
+        }
+    }
+
+    fn poll_all<E, F>(
+        &mut self,
+        op: Op,
+        cx: &mut Context<'_>,
+        mut f: F,
+    ) -> Poll<Result<(), FanoutError<E>>>
+    where
+        F: FnMut(Pin<&mut S>, &mut Context<'_>) -> Poll<Result<(), E>>,
+        S: Unpin,
+    {
+        if self.op != Some(op) {
+            self.reset(Some(op));
+        }
+        let mut pending = false;
+        for (index, sink) in self.sinks.iter_mut().enumerate() {
+            if self.done[index] {
+                continue;
+            }
+            match f(Pin::new(sink), cx) {
+                Poll::Ready(Ok(())) => self.done[index] = true,
+                Poll::Ready(Err(error)) => {
+                    self.reset(None);
+                    return Poll::Ready(Err(FanoutError { index, error }));
+                }
+                Poll::Pending => pending = true,
+            }
+        }
+        if pending {
+            return Poll::Pending;
+        }
+        self.reset(None);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S, Item> Sink<Item> for Fanout<S>
+where
+    S: Sink<Item> + Unpin,
+    Item: Clone,
+{
+    type Error = FanoutError<S::Error>;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::into_inner(self).poll_all(Op::Ready, cx, |sink, cx| sink.poll_ready(cx))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Item) -> Result<(), Self::Error> {
+        let this = Pin::into_inner(self);
+        this.reset(None);
+        let last = this.sinks.len().saturating_sub(1);
+        let mut item = Some(item);
+        for (index, sink) in this.sinks.iter_mut().enumerate() {
+            let item = if index == last {
+                item.take()
+            } else {
+                item.clone()
+            };
+            if let Some(item) = item {
+                Pin::new(sink)
+                    .start_send(item)
+                    .map_err(|error| FanoutError { index, error })?;
+            }
+        }
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::into_inner(self).poll_all(Op::Flush, cx, |sink, cx| sink.poll_flush(cx))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::into_inner(self).poll_all(Op::Close, cx, |sink, cx| sink.poll_close(cx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{from_iter, SinkMock};
+    use futures::{
+        executor::block_on,
+        never::Never,
+        stream::{self, StreamExt},
+    };
+    use std::iter;
+
+    #[test]
+    fn every_sink_gets_every_item() {
+        let sinks: Vec<Vec<u8>> = vec![Vec::new(); 4];
+        let mut sink = fanout(sinks);
+        let items = stream::iter((0..5u8).map(Ok));
+        assert_eq!(Ok(()), block_on(items.forward(&mut sink)));
+        for inner in sink.into_inner() {
+            assert_eq!(inner, vec![0, 1, 2, 3, 4]);
+        }
+    }
+
+    #[test]
+    fn ready_when_all_ready() {
+        let (waker, wakes) = crate::task::count_waker();
+        let mut cx = Context::from_waker(&waker);
+        let ok = Poll::Ready(Ok::<_, Never>(()));
+        // scripts end, so polling a sink more than expected panics
+        let mut sink = fanout(vec![
+            from_iter(vec![ok, ok].into_iter(), iter::repeat(Ok(()))),
+            from_iter(
+                vec![Poll::Pending, Poll::Pending, ok, ok].into_iter(),
+                iter::repeat(Ok(())),
+            ),
+        ]);
+        assert_eq!(Pin::new(&mut sink).poll_ready(&mut cx), Poll::Pending);
+        assert_eq!(Pin::new(&mut sink).poll_ready(&mut cx), Poll::Pending);
+        assert_eq!(wakes.get(), 2);
+        assert_eq!(Pin::new(&mut sink).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(Pin::new(&mut sink).start_send(1u8), Ok(()));
+        assert_eq!(Pin::new(&mut sink).poll_flush(&mut cx), Poll::Ready(Ok(())));
+    }
+
+    #[test]
+    fn first_error_with_index() {
+        let mock = |script: Vec<Poll<Result<(), &'static str>>>| {
+            SinkMock::with_flush_feedback(script.into_iter().cycle())
+        };
+        let sinks = vec![
+            mock(vec![Poll::Ready(Ok(()))]),
+            mock(vec![Poll::Ready(Ok(())), Poll::Ready(Err("broken"))]),
+            // fails at the same time, but it's after the first failing sink
+            mock(vec![Poll::Ready(Err("ignored"))]),
+        ];
+        let mut sink = fanout(sinks);
+        let items = stream::iter((0..5u8).map(Ok));
+        let err = block_on(items.forward(&mut sink)).unwrap_err();
+        assert_eq!(
+            err,
+            FanoutError {
+                index: 1,
+                error: "broken"
+            }
+        );
+        assert_eq!(err.to_string(), "sink 1 failed: broken");
+    }
+
+    #[test]
+    fn send_error_stops_broadcast() {
+        let sinks = vec![
+            from_iter(iter::repeat(Poll::Ready(Ok(()))), vec![Ok(())].into_iter()),
+            from_iter(
+                iter::repeat(Poll::Ready(Ok(()))),
+                vec![Err(7u8)].into_iter(),
+            ),
+            // would panic if given the item
+            from_iter(iter::repeat(Poll::Ready(Ok(()))), Vec::new().into_iter()),
+        ];
+        let mut sink = fanout(sinks);
+        let mut cx = crate::task::noop_context();
+        assert_eq!(Pin::new(&mut sink).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(
+            Pin::new(&mut sink).start_send(1u8),
+            Err(FanoutError { index: 1, error: 7 })
+        );
+    }
+}
@@ -0,0 +1,96 @@
+use core::task::Poll;
+
+/// `flush_feedback` of [SinkMock::flaky()](crate::SinkMock::flaky).
+///
+/// Yields `Poll::Ready(Err(err))` with the given probability and `Poll::Ready(Ok(()))`
+/// otherwise. Draws come from a small deterministic generator (SplitMix64), so the same seed
+/// gives the same sequence on every platform and in every run.
+#[derive(Debug, Clone)]
+pub struct Flaky<E> {
+    state: u64,
+    error_prob: f64,
+    err: E,
+    error_cnt: usize,
+}
+
+impl<E> Flaky<E> {
+    /// # Panics
+    /// If `error_prob` isn't in `0.0..=1.0`.
+    pub(crate) fn new(seed: u64, error_prob: f64, err: E) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&error_prob),
+            "error probability {} is not in 0.0..=1.0",
+            error_prob
+        );
+        Self {
+            state: seed,
+            error_prob,
+            err,
+            error_cnt: 0,
+        }
+    }
+
+    /// How many errors were yielded.
+    pub fn error_count(&self) -> usize {
+        self.error_cnt
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform number in `0.0..1.0`.
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl<E: Clone> Iterator for Flaky<E> {
+    type Item = Poll<Result<(), E>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next_f64() < self.error_prob {
+            self.error_cnt += 1;
+            Some(Poll::Ready(Err(self.err.clone())))
+        } else {
+            Some(Poll::Ready(Ok(())))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors(seed: u64, error_prob: f64) -> Vec<bool> {
+        Flaky::new(seed, error_prob, ())
+            .take(64)
+            .map(|poll| matches!(poll, Poll::Ready(Err(()))))
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_same_sequence() {
+        assert_eq!(errors(7, 0.3), errors(7, 0.3));
+        assert_ne!(errors(7, 0.3), errors(8, 0.3));
+        assert!(errors(7, 0.0).iter().all(|e| !e));
+        assert!(errors(7, 1.0).iter().all(|e| *e));
+    }
+
+    #[test]
+    fn error_rate_follows_probability() {
+        let mut flaky = Flaky::new(42, 0.25, ());
+        flaky.by_ref().take(10_000).for_each(drop);
+        assert!((2_300..2_700).contains(&flaky.error_count()));
+    }
+
+    #[test]
+    #[should_panic(expected = "error probability 1.5 is not in 0.0..=1.0")]
+    fn invalid_probability() {
+        Flaky::new(0, 1.5, ());
+    }
+}
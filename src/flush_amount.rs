@@ -0,0 +1,79 @@
+use alloc::boxed::Box;
+use core::fmt;
+use core::num::NonZeroUsize;
+
+/// How many buffered items [SinkMock](crate::SinkMock) discards when `flush_feedback` yield
+/// `Poll::Ready(Ok(()))`.
+///
+/// `usize` and `NonZeroUsize` convert to [FlushAmount::Fixed], converting `0` panics. A flush
+/// always discards at least one buffered item, so `0` returned by [FlushAmount::PerEvent] is
+/// taken as `1`.
+///
+/// # Examples
+/// ```
+/// use futures::never::Never;
+/// use futures_test_sink::{FlushAmount, SinkMock};
+/// use std::{iter, num::NonZeroUsize, task::Poll};
+///
+/// let mut sink = SinkMock::<_, _, _, u8, Never>::with_flush_feedback(iter::repeat(Poll::Ready(Ok(()))));
+/// sink.set_flush_at_once(4);
+/// sink.set_flush_at_once(FlushAmount::Fixed(NonZeroUsize::new(4).unwrap()));
+/// sink.set_flush_at_once(FlushAmount::All);
+/// let mut amounts = vec![1, 3].into_iter().cycle();
+/// sink.set_flush_at_once(FlushAmount::PerEvent(Box::new(move || amounts.next().unwrap())));
+/// ```
+pub enum FlushAmount {
+    /// Discard the same number of items every time.
+    Fixed(NonZeroUsize),
+    /// Discard all buffered items.
+    All,
+    /// Ask the function every time, `0` is taken as `1`. It's `Send + Sync` like the mock.
+    PerEvent(Box<dyn FnMut() -> usize + Send + Sync>),
+}
+
+impl FlushAmount {
+    /// The number of items for [FlushAmount::Fixed], `None` otherwise.
+    pub fn fixed(&self) -> Option<usize> {
+        match self {
+            FlushAmount::Fixed(n) => Some(n.get()),
+            _ => None,
+        }
+    }
+
+    /// How many items to discard now when `buffered` items are in the buffer.
+    pub(crate) fn next(&mut self, buffered: usize) -> usize {
+        match self {
+            FlushAmount::Fixed(n) => n.get(),
+            FlushAmount::All => buffered,
+            // zero would never drain the buffer and `poll_flush()` would loop forever
+            FlushAmount::PerEvent(f) => f().max(1),
+        }
+    }
+}
+
+impl fmt::Debug for FlushAmount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FlushAmount::Fixed(n) => f.debug_tuple("Fixed").field(n).finish(),
+            FlushAmount::All => f.write_str("All"),
+            FlushAmount::PerEvent(_) => f.write_str("PerEvent(..)"),
+        }
+    }
+}
+
+impl From<usize> for FlushAmount {
+    /// # Panics
+    /// If `n` is `0`.
+    fn from(n: usize) -> Self {
+        match NonZeroUsize::new(n) {
+            Some(n) => FlushAmount::Fixed(n),
+            None => panic!("`flush_at_once` must be greater than 0"),
+        }
+    }
+}
+
+impl From<NonZeroUsize> for FlushAmount {
+    fn from(n: NonZeroUsize) -> Self {
+        FlushAmount::Fixed(n)
+    }
+}
@@ -1,7 +1,7 @@
 //! This is like [cycle](std::iter::Cycle) but after an iterator end the last element is returned
 //! infinitely.
 
-use std::iter::Fuse;
+use core::iter::Fuse;
 
 /// Extension trait for Iterator that allow use `FuseLast` in chain-like manner.
 pub trait IteratorExt: Iterator {
@@ -0,0 +1,239 @@
+use futures::{never::Never, sink::Sink};
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+use std::{
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+struct State {
+    is_open: bool,
+    wakers: Vec<Waker>,
+}
+
+type Shared = Arc<Mutex<State>>;
+
+/// Sink that is ready only when its [Gate] is open.
+///
+/// For details see [gated()].
+///
+/// [gated()]:gated
+pub struct GateSink<Item> {
+    state: Shared,
+    send_cnt: usize,
+    item_type: PhantomData<fn(Item)>,
+}
+
+/// Handle that opens and closes [GateSink].
+///
+/// It can be cloned and moved to other threads.
+#[derive(Clone)]
+pub struct Gate {
+    state: Shared,
+}
+
+/// Create a sink controlled by a gate.
+///
+/// While the gate is closed `poll_ready`, `poll_flush` and `poll_close` return `Poll::Pending`.
+/// The waker is stored and **not** woken. [Gate::open()] wakes all stored wakers (also from
+/// other thread) and from then on every operation returns `Poll::Ready(Ok(()))`. [Gate::close()]
+/// shuts the gate again. The gate is closed at the beginning.
+///
+/// This allows tests like "start the send, check it's not completed, open the gate, check it
+/// completes" without counting polls.
+///
+/// Requires `std` feature.
+///
+/// # Examples
+/// ```
+/// use futures::{executor::block_on, poll, SinkExt};
+/// use futures_test_sink::gated;
+///
+/// let (mut sink, gate) = gated();
+/// block_on(async {
+///     let mut send = sink.send(1u8);
+///     assert!(poll!(&mut send).is_pending());
+///     gate.open();
+///     assert!(poll!(&mut send).is_ready());
+/// });
+/// assert_eq!(sink.send_count(), 1);
+/// ```
+pub fn gated<Item>() -> (GateSink<Item>, Gate) {
+    let state = Arc::new(Mutex::new(State {
+        is_open: false,
+        wakers: Vec::new(),
+    }));
+    (
+        GateSink {
+            state: state.clone(),
+            send_cnt: 0,
+            item_type: Default::default(),
+        },
+        Gate { state },
+    )
+}
+
+impl Gate {
+    /// Open the gate and wake all tasks waiting for it.
+    pub fn open(&self) {
+        let wakers = {
+            let mut state = self.state.lock().unwrap();
+            state.is_open = true;
+            std::mem::take(&mut state.wakers)
+        };
+        // wake outside the lock, a waker could poll the sink inline
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    /// Close the gate. Following `poll_ready`, `poll_flush` and `poll_close` will be pending.
+    pub fn close(&self) {
+        self.state.lock().unwrap().is_open = false;
+    }
+
+    /// `true` if the gate is open.
+    pub fn is_open(&self) -> bool {
+        self.state.lock().unwrap().is_open
+    }
+
+    /// How many tasks are waiting for the gate.
+    pub fn waiting(&self) -> usize {
+        self.state.lock().unwrap().wakers.len()
+    }
+}
+
+impl<Item> GateSink<Item> {
+    /// How many items were sent.
+    pub fn send_count(&self) -> usize {
+        self.send_cnt
+    }
+
+    fn poll_gate(&self, cx: &mut Context<'_>) -> Poll<Result<(), Never>> {
+        let mut state = self.state.lock().unwrap();
+        if state.is_open {
+            return Poll::Ready(Ok(()));
+        }
+        if !state.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+            state.wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
+
+impl<Item> Unpin for GateSink<Item> {}
+
+impl<Item> Sink<Item> for GateSink<Item> {
+    type Error = Never;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.poll_gate(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, _item: Item) -> Result<(), Self::Error> {
+        Pin::into_inner(self).send_cnt += 1;
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.poll_gate(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.poll_gate(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_task::waker_fn;
+    use futures::{executor::block_on, future, SinkExt};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+
+    #[test]
+    fn pending_until_open() {
+        // create a Context
+        let wake_cnt = Arc::new(AtomicUsize::new(0));
+        let cnt = wake_cnt.clone();
+        let waker = waker_fn(move || {
+            wake_cnt.fetch_add(1, Ordering::SeqCst);
+        });
+        let mut cx = Context::from_waker(&waker);
+        // actual test
+        let (mut sink, gate) = gated::<u8>();
+        assert_eq!(Pin::new(&mut sink).poll_ready(&mut cx), Poll::Pending);
+        assert_eq!(Pin::new(&mut sink).poll_ready(&mut cx), Poll::Pending);
+        // not woken and stored once
+        assert_eq!(0, cnt.load(Ordering::SeqCst));
+        assert_eq!(gate.waiting(), 1);
+
+        gate.open();
+        assert_eq!(1, cnt.load(Ordering::SeqCst));
+        assert_eq!(Pin::new(&mut sink).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(Pin::new(&mut sink).start_send(1), Ok(()));
+        assert_eq!(Pin::new(&mut sink).poll_flush(&mut cx), Poll::Ready(Ok(())));
+
+        gate.close();
+        assert!(!gate.is_open());
+        assert_eq!(Pin::new(&mut sink).poll_flush(&mut cx), Poll::Pending);
+        assert_eq!(Pin::new(&mut sink).poll_close(&mut cx), Poll::Pending);
+        gate.open();
+        assert_eq!(2, cnt.load(Ordering::SeqCst));
+        assert_eq!(Pin::new(&mut sink).poll_close(&mut cx), Poll::Ready(Ok(())));
+    }
+
+    #[test]
+    fn open_from_other_thread() {
+        let (mut sink, gate) = gated();
+        let opener = thread::spawn(move || {
+            // wait until the sink is polled
+            while gate.waiting() == 0 {
+                thread::yield_now();
+            }
+            gate.open();
+        });
+        assert_eq!(Ok(()), block_on(sink.send(5u8)));
+        opener.join().unwrap();
+        assert_eq!(sink.send_count(), 1);
+    }
+
+    #[test]
+    fn wakes_all_waiting_tasks() {
+        let (sink, gate) = gated::<u8>();
+        let sink = Arc::new(Mutex::new(sink));
+        let (s1, s2) = (sink.clone(), sink.clone());
+        let ready_task = thread::spawn(move || {
+            block_on(future::poll_fn(|cx| {
+                Pin::new(&mut *s1.lock().unwrap()).poll_ready(cx)
+            }))
+        });
+        let flush_task = thread::spawn(move || {
+            block_on(future::poll_fn(|cx| {
+                Pin::new(&mut *s2.lock().unwrap()).poll_flush(cx)
+            }))
+        });
+        while gate.waiting() < 2 {
+            thread::yield_now();
+        }
+        gate.open();
+        assert_eq!(Ok(()), ready_task.join().unwrap());
+        assert_eq!(Ok(()), flush_task.join().unwrap());
+    }
+
+    #[test]
+    fn stored_wakers_are_woken() {
+        let (mut cx, stats) = crate::task::tracking_context();
+        let (mut sink, gate) = gated::<u8>();
+        assert_eq!(Pin::new(&mut sink).poll_ready(&mut cx), Poll::Pending);
+        assert_eq!(stats.live_clones(), 1);
+        gate.open();
+        assert_eq!(stats.wakes(), 1);
+        assert_eq!(stats.dropped_unwoken(), 0);
+        assert_eq!(stats.live_clones(), 0);
+        assert_eq!(Pin::new(&mut sink).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        drop(sink);
+        assert_eq!(stats.live_clones(), 0);
+    }
+}
@@ -0,0 +1,154 @@
+use alloc::sync::Arc;
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures::{never::Never, sink::Sink, task::AtomicWaker};
+
+/// Sink that is ready only when a shared flag is `true`.
+///
+/// For details see [gated_by()].
+///
+/// [gated_by()]:gated_by
+pub struct GatedBy<Item> {
+    flag: Arc<AtomicBool>,
+    waker: Arc<AtomicWaker>,
+    send_cnt: usize,
+    item_type: PhantomData<fn(Item)>,
+}
+
+/// Create a sink gated by `flag`.
+///
+/// While `flag` is `false` `poll_ready()`, `poll_flush()` and `poll_close()` store the waker
+/// (without waking it) and return `Poll::Pending`. When it's `true` they return
+/// `Poll::Ready(Ok(()))`. The test flips the flag and then wakes the stored waker, available from
+/// [waker()](GatedBy::waker), to let the items through.
+///
+/// # Examples
+/// ```
+/// use futures::{executor::block_on, poll, SinkExt};
+/// use futures_test_sink::gated_by;
+/// use std::sync::{atomic::{AtomicBool, Ordering}, Arc};
+///
+/// let flag = Arc::new(AtomicBool::new(false));
+/// let mut sink = gated_by(flag.clone());
+/// let waker = sink.waker();
+/// block_on(async {
+///     let mut send = sink.send(1u8);
+///     assert!(poll!(&mut send).is_pending());
+///     flag.store(true, Ordering::SeqCst);
+///     waker.wake();
+///     assert!(poll!(&mut send).is_ready());
+/// });
+/// ```
+pub fn gated_by<Item>(flag: Arc<AtomicBool>) -> GatedBy<Item> {
+    GatedBy {
+        flag,
+        waker: Arc::new(AtomicWaker::new()),
+        send_cnt: 0,
+        item_type: Default::default(),
+    }
+}
+
+impl<Item> Unpin for GatedBy<Item> {}
+
+impl<Item> GatedBy<Item> {
+    /// The waker stored by the last pending call. It can be woken from other thread.
+    pub fn waker(&self) -> Arc<AtomicWaker> {
+        self.waker.clone()
+    }
+
+    /// How many items were sent.
+    pub fn send_count(&self) -> usize {
+        self.send_cnt
+    }
+
+    fn poll_flag(&self, cx: &mut Context<'_>) -> Poll<Result<(), Never>> {
+        if self.flag.load(Ordering::SeqCst) {
+            return Poll::Ready(Ok(()));
+        }
+        self.waker.register(cx.waker());
+        // the flag could be flipped before the waker was registered
+        if self.flag.load(Ordering::SeqCst) {
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+impl<Item> Sink<Item> for GatedBy<Item> {
+    type Error = Never;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.poll_flag(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, _item: Item) -> Result<(), Self::Error> {
+        Pin::into_inner(self).send_cnt += 1;
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.poll_flag(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.poll_flag(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_task::waker_fn;
+    use futures::{
+        executor::block_on,
+        stream::{self, StreamExt},
+    };
+    use std::sync::atomic::AtomicUsize;
+    use std::thread;
+
+    #[test]
+    fn flag_and_stored_waker() {
+        // create a Context
+        let wake_cnt = Arc::new(AtomicUsize::new(0));
+        let cnt = wake_cnt.clone();
+        let waker = waker_fn(move || {
+            wake_cnt.fetch_add(1, Ordering::SeqCst);
+        });
+        let mut cx = Context::from_waker(&waker);
+        // actual test
+        let flag = Arc::new(AtomicBool::new(false));
+        let mut s = gated_by::<u8>(flag.clone());
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Pending);
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Pending);
+        // not woken by the sink
+        assert_eq!(0, cnt.load(Ordering::SeqCst));
+
+        flag.store(true, Ordering::SeqCst);
+        s.waker().wake();
+        assert_eq!(1, cnt.load(Ordering::SeqCst));
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(Pin::new(&mut s).start_send(1), Ok(()));
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Ready(Ok(())));
+    }
+
+    #[test]
+    fn flipped_from_other_thread() {
+        let flag = Arc::new(AtomicBool::new(false));
+        let mut sink = gated_by(flag.clone());
+        let waker = sink.waker();
+        let event = thread::spawn(move || {
+            thread::sleep(std::time::Duration::from_millis(10));
+            flag.store(true, Ordering::SeqCst);
+            waker.wake();
+        });
+        let items = stream::iter(vec![Ok(1u8), Ok(2)]);
+        assert_eq!(Ok(()), block_on(items.forward(&mut sink)));
+        event.join().unwrap();
+        assert_eq!(sink.send_count(), 2);
+    }
+}
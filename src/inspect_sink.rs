@@ -0,0 +1,207 @@
+use core::pin::Pin;
+use futures::sink::Sink;
+use pin_project_lite::pin_project;
+#[cfg(feature = "std")]
+use std::sync::{Arc, Mutex};
+
+/// Observer of items passing through [InspectSink].
+///
+/// It's implemented for every `FnMut(&Item)` and for [ItemsHandle].
+pub trait Inspect<Item> {
+    /// Observe `item` before it's passed to the inner sink.
+    fn inspect(&mut self, item: &Item);
+}
+
+impl<F, Item> Inspect<Item> for F
+where
+    F: FnMut(&Item),
+{
+    fn inspect(&mut self, item: &Item) {
+        self(item)
+    }
+}
+
+pin_project! {
+    /// Wrapper that observes every item before passing it to the inner sink.
+    ///
+    /// It keeps a real sink in the loop while making items observable. Every item passed to
+    /// `start_send()` is shown to the observer and then delegated. `poll_ready()`, `poll_flush()`
+    /// and `poll_close()` are delegated untouched and errors pass through unchanged.
+    ///
+    /// See also [tee()].
+    ///
+    /// # Examples
+    /// ```
+    /// use futures::{executor::block_on, stream, StreamExt};
+    /// use futures_test_sink::InspectSink;
+    ///
+    /// let mut total = 0;
+    /// let mut sink = InspectSink::new(Vec::new(), |item: &u32| total += item);
+    /// let items = stream::iter(vec![Ok(5), Ok(7), Ok(9)]);
+    /// assert_eq!(Ok(()), block_on(items.forward(&mut sink)));
+    /// assert_eq!(sink.into_inner(), vec![5, 7, 9]);
+    /// assert_eq!(total, 21);
+    /// ```
+    pub struct InspectSink<S, F> {
+        #[pin]
+        inner: S,
+        f: F,
+    }
+}
+
+impl<S, F> InspectSink<S, F> {
+    /// Wrap `inner` sink showing every item to `f`.
+    pub fn new<Item>(inner: S, f: F) -> Self
+    where
+        F: Inspect<Item>,
+    {
+        Self { inner, f }
+    }
+
+    inner_accessors!(S, "the inner sink");
+}
+
+impl<S, F, Item> Sink<Item> for InspectSink<S, F>
+where
+    S: Sink<Item>,
+    F: Inspect<Item>,
+{
+    type Error = S::Error;
+
+    forward_sink!("inspect_sink", poll_ready, poll_flush, poll_close);
+
+    fn start_send(self: Pin<&mut Self>, item: Item) -> Result<(), Self::Error> {
+        let this = self.project();
+        this.f.inspect(&item);
+        let res = this.inner.start_send(item);
+        wrapper_event!("inspect_sink", "start_send", &res);
+        res
+    }
+}
+
+/// Shared list of items observed by [tee()].
+///
+/// Requires `std` feature.
+#[cfg(feature = "std")]
+pub struct ItemsHandle<Item> {
+    items: Arc<Mutex<Vec<Item>>>,
+}
+
+#[cfg(feature = "std")]
+impl<Item> Clone for ItemsHandle<Item> {
+    fn clone(&self) -> Self {
+        Self {
+            items: self.items.clone(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<Item: Clone> ItemsHandle<Item> {
+    /// Copy of items in order they were passed to `start_send()`.
+    pub fn items(&self) -> Vec<Item> {
+        self.items.lock().unwrap().clone()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<Item> ItemsHandle<Item> {
+    /// How many items were observed.
+    pub fn len(&self) -> usize {
+        self.items.lock().unwrap().len()
+    }
+
+    /// `true` if no item was observed.
+    pub fn is_empty(&self) -> bool {
+        self.items.lock().unwrap().is_empty()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<Item: Clone> Inspect<Item> for ItemsHandle<Item> {
+    fn inspect(&mut self, item: &Item) {
+        self.items.lock().unwrap().push(item.clone());
+    }
+}
+
+/// Wrap `inner` sink cloning every item into a shared list before passing it on.
+///
+/// The returned [ItemsHandle] reflects items in order they were accepted by `start_send()` even
+/// after the sink was moved.
+///
+/// Requires `std` feature.
+///
+/// # Examples
+/// ```
+/// use futures::{executor::block_on, stream, StreamExt};
+/// use futures_test_sink::tee;
+///
+/// let (sink, items) = tee(Vec::new());
+/// let stream = stream::iter(vec![Ok(5u8), Ok(7), Ok(9)]);
+/// assert_eq!(Ok(()), block_on(stream.forward(sink)));
+/// assert_eq!(items.items(), vec![5, 7, 9]);
+/// ```
+#[cfg(feature = "std")]
+pub fn tee<S, Item: Clone>(inner: S) -> (InspectSink<S, ItemsHandle<Item>>, ItemsHandle<Item>) {
+    let handle = ItemsHandle {
+        items: Arc::new(Mutex::new(Vec::new())),
+    };
+    (InspectSink::new(inner, handle.clone()), handle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SinkMock;
+    use futures::{
+        executor::block_on,
+        stream::{self, StreamExt},
+    };
+    use std::iter;
+    use std::task::Poll;
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn adds_no_polls() {
+        use crate::slow_consumer;
+        use core::num::NonZeroUsize;
+
+        let items = || stream::iter((0..20u8).map(Ok));
+        let polls = NonZeroUsize::new(3).unwrap();
+
+        let mut plain = slow_consumer(polls, 4);
+        assert_eq!(Ok(()), block_on(items().forward(&mut plain)));
+
+        let (mut wrapped, seen) = tee(slow_consumer(polls, 4));
+        assert_eq!(Ok(()), block_on(items().forward(&mut wrapped)));
+
+        assert_eq!(wrapped.get_ref().poll_count(), plain.poll_count());
+        assert_eq!(wrapped.get_ref().flush_count(), plain.flush_count());
+        assert_eq!(seen.items(), (0..20).collect::<Vec<_>>());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn errors_pass_through() {
+        let feedback = vec![Poll::Ready(Ok(())), Poll::Pending, Poll::Ready(Err("io"))]
+            .into_iter()
+            .cycle();
+        let (sink, seen) = tee(SinkMock::with_flush_feedback(feedback));
+        let stream = stream::iter(vec![Ok(5u8), Ok(7), Ok(9), Ok(77), Ok(79)]);
+        assert_eq!(Err("io"), block_on(stream.forward(sink)));
+        // items accepted before the error, in order
+        let seen = seen.items();
+        assert!(!seen.is_empty());
+        assert_eq!(seen[..], [5, 7, 9, 77, 79][..seen.len()]);
+    }
+
+    #[test]
+    fn inspect_closure() {
+        let mut cnt = 0;
+        let mock = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, ()>(()))));
+        let mut sink = InspectSink::new(mock, |_: &u8| cnt += 1);
+        let stream = stream::iter(vec![Ok(5u8), Ok(7)]);
+        assert_eq!(Ok(()), block_on(stream.forward(&mut sink)));
+        assert_eq!(cnt, 2);
+    }
+}
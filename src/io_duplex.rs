@@ -0,0 +1,529 @@
+use futures::io::{AsyncRead, AsyncWrite};
+use std::collections::VecDeque;
+use std::io;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::{
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+/// Outcomes of reads or writes of one [MockIo] half.
+type IoScript = Box<dyn Iterator<Item = Poll<io::Result<usize>>> + Send>;
+
+/// Outcomes of `poll_close()` of one [MockIo] half.
+type CloseScript = Box<dyn Iterator<Item = Poll<io::Result<()>>> + Send>;
+
+// one direction of the duplex
+struct Pipe {
+    buf: VecDeque<u8>,
+    capacity: usize,
+    // writing half was closed or dropped
+    is_closed: bool,
+    // reading half was dropped
+    reader_gone: bool,
+    read_waker: Option<Waker>,
+    write_waker: Option<Waker>,
+}
+
+impl Pipe {
+    fn new(capacity: usize) -> Arc<Mutex<Self>> {
+        Arc::new(Mutex::new(Self {
+            buf: VecDeque::new(),
+            capacity,
+            is_closed: false,
+            reader_gone: false,
+            read_waker: None,
+            write_waker: None,
+        }))
+    }
+
+    /// Returns the waker of the reading half to wake once the lock is released.
+    fn close(&mut self) -> Option<Waker> {
+        self.is_closed = true;
+        self.read_waker.take()
+    }
+}
+
+/// One half of [io_duplex()].
+///
+/// Bytes written to this half are read from the other one and vice versa.
+pub struct MockIo {
+    read: Arc<Mutex<Pipe>>,
+    write: Arc<Mutex<Pipe>>,
+    read_script: Option<IoScript>,
+    write_script: Option<IoScript>,
+    close_script: Option<CloseScript>,
+    close_cnt: usize,
+}
+
+/// Create a pair of connected in-memory byte streams.
+///
+/// Bytes written to one half become readable on the other, like `tokio::io::duplex()`. Every
+/// direction buffers at most `capacity` bytes. A write to a full buffer returns `Poll::Pending`
+/// until the other half reads, and a read from an empty buffer returns `Poll::Pending` until the
+/// other half writes. The waiting task is woken then.
+///
+/// `poll_close()` or drop of a half is end of file for the other half after it reads all
+/// buffered bytes. Writing to a closed half or to a half whose peer was dropped returns
+/// `io::ErrorKind::BrokenPipe`.
+///
+/// Faults can be injected into both directions with [MockIo::set_read_script()],
+/// [MockIo::set_write_script()] and [MockIo::set_close_script()].
+///
+/// Requires `std` feature.
+///
+/// # Panics
+/// When `capacity` is 0.
+///
+/// # Examples
+/// ```
+/// use futures::{executor::block_on, future, AsyncReadExt, AsyncWriteExt};
+/// use futures_test_sink::io_duplex;
+/// use std::task::Poll;
+///
+/// let (mut client, mut server) = io_duplex(4);
+/// // short writes and a stall of the reader
+/// client.set_write_script(vec![Poll::Ready(Ok(1)), Poll::Pending, Poll::Ready(Ok(3))]);
+/// server.set_read_script(vec![Poll::Pending, Poll::Ready(Ok(2))]);
+///
+/// let write = async {
+///     client.write_all(b"hello world").await.unwrap();
+///     client.close().await.unwrap();
+/// };
+/// let mut received = Vec::new();
+/// let read = server.read_to_end(&mut received);
+/// block_on(future::join(write, read)).1.unwrap();
+/// assert_eq!(received, b"hello world");
+/// ```
+pub fn io_duplex(capacity: usize) -> (MockIo, MockIo) {
+    assert!(capacity > 0, "`capacity` must be greater than 0");
+    let a_to_b = Pipe::new(capacity);
+    let b_to_a = Pipe::new(capacity);
+    (
+        MockIo::new(b_to_a.clone(), a_to_b.clone()),
+        MockIo::new(a_to_b, b_to_a),
+    )
+}
+
+impl MockIo {
+    fn new(read: Arc<Mutex<Pipe>>, write: Arc<Mutex<Pipe>>) -> Self {
+        Self {
+            read,
+            write,
+            read_script: None,
+            write_script: None,
+            close_script: None,
+            close_cnt: 0,
+        }
+    }
+
+    /// Script outcomes of `poll_read()` calls of this half.
+    ///
+    /// Each call takes the next item:
+    /// - `Poll::Ready(Ok(n))` - read at most `n` bytes, i.e. a short read. `n == 0` looks like
+    ///   end of file to the reader.
+    /// - `Poll::Ready(Err(e))` - return the error.
+    /// - `Poll::Pending` - stall; the waker is woken before returning like in
+    ///   [from_iter()](crate::from_iter).
+    ///
+    /// When the script ends reads aren't limited anymore.
+    pub fn set_read_script<I>(&mut self, script: I) -> &mut Self
+    where
+        I: IntoIterator<Item = Poll<io::Result<usize>>>,
+        I::IntoIter: Send + 'static,
+    {
+        self.read_script = Some(Box::new(script.into_iter()));
+        self
+    }
+
+    /// Script outcomes of `poll_write()` calls of this half.
+    ///
+    /// Each call takes the next item:
+    /// - `Poll::Ready(Ok(n))` - write at most `n` bytes, i.e. a partial write.
+    /// - `Poll::Ready(Err(e))` - return the error.
+    /// - `Poll::Pending` - stall; the waker is woken before returning.
+    ///
+    /// When the script ends writes aren't limited anymore.
+    pub fn set_write_script<I>(&mut self, script: I) -> &mut Self
+    where
+        I: IntoIterator<Item = Poll<io::Result<usize>>>,
+        I::IntoIter: Send + 'static,
+    {
+        self.write_script = Some(Box::new(script.into_iter()));
+        self
+    }
+
+    /// Script outcomes of `poll_close()` calls of this half.
+    ///
+    /// Each call takes the next item. `Poll::Ready(Ok(()))` closes the half, `Poll::Ready(Err(e))`
+    /// returns the error and `Poll::Pending` wakes the waker and returns `Poll::Pending`. When the
+    /// script ends `poll_close()` closes the half.
+    pub fn set_close_script<I>(&mut self, script: I) -> &mut Self
+    where
+        I: IntoIterator<Item = Poll<io::Result<()>>>,
+        I::IntoIter: Send + 'static,
+    {
+        self.close_script = Some(Box::new(script.into_iter()));
+        self
+    }
+
+    /// How many times `poll_close()` was called.
+    pub fn close_count(&self) -> usize {
+        self.close_cnt
+    }
+
+    /// Number of bytes written by the other half, but not read by this half yet.
+    pub fn readable(&self) -> usize {
+        self.read.lock().unwrap().buf.len()
+    }
+}
+
+/// Take next step of `script`, returning limit for the number of bytes.
+fn scripted(script: &mut Option<IoScript>, cx: &mut Context<'_>) -> Poll<io::Result<usize>> {
+    match script.as_mut().and_then(Iterator::next) {
+        Some(Poll::Pending) => {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+        Some(ready) => ready,
+        None => Poll::Ready(Ok(usize::MAX)),
+    }
+}
+
+impl Unpin for MockIo {}
+
+impl AsyncRead for MockIo {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = Pin::into_inner(self);
+        let limit = match scripted(&mut this.read_script, cx) {
+            Poll::Ready(Ok(limit)) => limit,
+            other => return other,
+        };
+        let mut pipe = this.read.lock().unwrap();
+        if pipe.buf.is_empty() && !buf.is_empty() {
+            if pipe.is_closed {
+                return Poll::Ready(Ok(0));
+            }
+            pipe.read_waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        let n = buf.len().min(limit).min(pipe.buf.len());
+        for (dst, src) in buf.iter_mut().zip(pipe.buf.drain(..n)) {
+            *dst = src;
+        }
+        let waker = pipe.write_waker.take();
+        // woken outside of the lock, a waker could poll the other half inline
+        drop(pipe);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        Poll::Ready(Ok(n))
+    }
+}
+
+impl AsyncWrite for MockIo {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = Pin::into_inner(self);
+        let limit = match scripted(&mut this.write_script, cx) {
+            Poll::Ready(Ok(limit)) => limit,
+            other => return other,
+        };
+        let mut pipe = this.write.lock().unwrap();
+        if pipe.is_closed || pipe.reader_gone {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+        let free = pipe.capacity - pipe.buf.len();
+        if free == 0 && !buf.is_empty() {
+            pipe.write_waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+        let n = buf.len().min(limit).min(free);
+        pipe.buf.extend(&buf[..n]);
+        let waker = pipe.read_waker.take();
+        drop(pipe);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = Pin::into_inner(self);
+        this.close_cnt += 1;
+        match this.close_script.as_mut().and_then(Iterator::next) {
+            Some(Poll::Pending) => {
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+            Some(Poll::Ready(Err(e))) => return Poll::Ready(Err(e)),
+            Some(Poll::Ready(Ok(()))) | None => (),
+        }
+        let waker = this.write.lock().unwrap().close();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for MockIo {
+    fn drop(&mut self) {
+        // a test could have panicked holding the lock, panicking again here would abort
+        let read_waker = self
+            .write
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .close();
+        let write_waker = {
+            let mut read = self.read.lock().unwrap_or_else(PoisonError::into_inner);
+            read.reader_gone = true;
+            read.write_waker.take()
+        };
+        read_waker
+            .into_iter()
+            .chain(write_waker)
+            .for_each(Waker::wake);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::{count_waker, noop_context};
+    use futures::{
+        executor::block_on,
+        future,
+        io::{AsyncReadExt, AsyncWriteExt},
+    };
+    use std::thread;
+
+    #[test]
+    fn reader_woken_by_write() {
+        let (waker, wakes) = count_waker();
+        let mut cx = Context::from_waker(&waker);
+        let (mut a, mut b) = io_duplex(8);
+        let mut buf = [0u8; 8];
+        assert!(Pin::new(&mut b).poll_read(&mut cx, &mut buf).is_pending());
+        assert_eq!(wakes.get(), 0);
+
+        let mut noop = noop_context();
+        assert!(matches!(
+            Pin::new(&mut a).poll_write(&mut noop, b"abc"),
+            Poll::Ready(Ok(3))
+        ));
+        assert_eq!(wakes.get(), 1);
+        assert_eq!(b.readable(), 3);
+        assert!(matches!(
+            Pin::new(&mut b).poll_read(&mut cx, &mut buf),
+            Poll::Ready(Ok(3))
+        ));
+        assert_eq!(&buf[..3], b"abc");
+    }
+
+    #[test]
+    fn writer_woken_by_read() {
+        let (waker, wakes) = count_waker();
+        let mut cx = Context::from_waker(&waker);
+        let (mut a, mut b) = io_duplex(2);
+        assert!(matches!(
+            Pin::new(&mut a).poll_write(&mut cx, b"abc"),
+            Poll::Ready(Ok(2))
+        ));
+        assert!(Pin::new(&mut a).poll_write(&mut cx, b"c").is_pending());
+        assert_eq!(wakes.get(), 0);
+
+        let mut buf = [0u8; 1];
+        let mut noop = noop_context();
+        assert!(matches!(
+            Pin::new(&mut b).poll_read(&mut noop, &mut buf),
+            Poll::Ready(Ok(1))
+        ));
+        assert_eq!(wakes.get(), 1);
+        assert!(matches!(
+            Pin::new(&mut a).poll_write(&mut cx, b"c"),
+            Poll::Ready(Ok(1))
+        ));
+    }
+
+    #[test]
+    fn close_is_eof_after_buffered_bytes() {
+        let (waker, wakes) = count_waker();
+        let mut cx = Context::from_waker(&waker);
+        let (mut a, mut b) = io_duplex(8);
+        block_on(a.write_all(b"ab")).unwrap();
+        let mut buf = [0u8; 1];
+        assert!(matches!(
+            Pin::new(&mut b).poll_read(&mut cx, &mut buf),
+            Poll::Ready(Ok(1))
+        ));
+        block_on(a.close()).unwrap();
+        assert!(matches!(
+            Pin::new(&mut b).poll_read(&mut cx, &mut buf),
+            Poll::Ready(Ok(1))
+        ));
+        assert!(matches!(
+            Pin::new(&mut b).poll_read(&mut cx, &mut buf),
+            Poll::Ready(Ok(0))
+        ));
+        // write after close
+        let err = block_on(a.write(b"c")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+        // other direction still works
+        block_on(b.write_all(b"z")).unwrap();
+        assert_eq!(block_on(a.read(&mut buf)).unwrap(), 1);
+        assert_eq!(wakes.get(), 0);
+    }
+
+    #[test]
+    fn close_wakes_pending_reader() {
+        let (waker, wakes) = count_waker();
+        let mut cx = Context::from_waker(&waker);
+        let (a, mut b) = io_duplex(8);
+        let mut buf = [0u8; 1];
+        assert!(Pin::new(&mut b).poll_read(&mut cx, &mut buf).is_pending());
+        drop(a);
+        assert_eq!(wakes.get(), 1);
+        assert!(matches!(
+            Pin::new(&mut b).poll_read(&mut cx, &mut buf),
+            Poll::Ready(Ok(0))
+        ));
+        let err = block_on(b.write(b"c")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+    }
+
+    #[test]
+    fn scripted_errors() {
+        let (mut a, mut b) = io_duplex(8);
+        a.set_write_script(vec![Poll::Ready(
+            Err(io::ErrorKind::ConnectionReset.into()),
+        )]);
+        b.set_read_script(vec![Poll::Ready(Err(io::ErrorKind::TimedOut.into()))]);
+        let err = block_on(a.write_all(b"data")).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionReset);
+        // nothing was written, the script ended
+        assert_eq!(b.readable(), 0);
+        block_on(a.write_all(b"data")).unwrap();
+
+        let mut buf = [0u8; 4];
+        let err = block_on(b.read_exact(&mut buf)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        block_on(b.read_exact(&mut buf)).unwrap();
+        assert_eq!(&buf, b"data");
+    }
+
+    // length prefixed frames
+    async fn encode(io: &mut MockIo, frames: &[&[u8]]) -> io::Result<()> {
+        for frame in frames {
+            io.write_all(&[frame.len() as u8]).await?;
+            io.write_all(frame).await?;
+        }
+        io.close().await
+    }
+
+    async fn decode(io: &mut MockIo) -> io::Result<Vec<Vec<u8>>> {
+        let mut frames = Vec::new();
+        let mut len = [0u8; 1];
+        while io.read(&mut len).await? == 1 {
+            let mut frame = vec![0; len[0] as usize];
+            io.read_exact(&mut frame).await?;
+            frames.push(frame);
+        }
+        Ok(frames)
+    }
+
+    #[test]
+    fn codec_round_trip_with_faults() {
+        let frames: &[&[u8]] = &[b"hello", b"", b"fault injection", b"!"];
+        let (mut a, mut b) = io_duplex(3);
+        a.set_write_script((0..).map(|i| match i % 3 {
+            0 => Poll::Ready(Ok(1)),
+            1 => Poll::Pending,
+            _ => Poll::Ready(Ok(2)),
+        }));
+        b.set_read_script((0..).map(|i| match i % 2 {
+            0 => Poll::Pending,
+            _ => Poll::Ready(Ok(1)),
+        }));
+        let (written, decoded) = block_on(future::join(encode(&mut a, frames), decode(&mut b)));
+        written.unwrap();
+        assert_eq!(decoded.unwrap(), frames);
+    }
+
+    #[test]
+    fn halves_in_threads() {
+        let (mut a, mut b) = io_duplex(16);
+        let data: Vec<u8> = (0..=255).cycle().take(10_000).collect();
+        let expected = data.clone();
+        let writer = thread::spawn(move || {
+            block_on(async {
+                a.write_all(&data).await?;
+                a.close().await
+            })
+        });
+        let mut received = Vec::new();
+        block_on(b.read_to_end(&mut received)).unwrap();
+        writer.join().unwrap().unwrap();
+        assert_eq!(received, expected);
+    }
+
+    #[test]
+    fn scripted_close() {
+        let (mut a, mut b) = io_duplex(8);
+        a.set_close_script(vec![
+            Poll::Pending,
+            Poll::Ready(Err(io::ErrorKind::TimedOut.into())),
+        ]);
+        let err = block_on(a.close()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(a.close_count(), 2);
+        // not closed yet
+        block_on(a.write_all(b"x")).unwrap();
+        block_on(a.close()).unwrap();
+        let mut received = Vec::new();
+        block_on(b.read_to_end(&mut received)).unwrap();
+        assert_eq!(received, b"x");
+    }
+
+    #[test]
+    fn waker_reading_inline() {
+        let (mut a, b) = io_duplex(8);
+        let b = Arc::new(Mutex::new(b));
+        let reader = b.clone();
+        let waker = async_task::waker_fn(move || {
+            assert_eq!(reader.lock().unwrap().readable(), 3);
+        });
+        let mut cx = Context::from_waker(&waker);
+        let mut buf = [0u8; 8];
+        let pending = Pin::new(&mut *b.lock().unwrap()).poll_read(&mut cx, &mut buf);
+        assert!(pending.is_pending());
+        assert!(matches!(
+            Pin::new(&mut a).poll_write(&mut noop_context(), b"abc"),
+            Poll::Ready(Ok(3))
+        ));
+    }
+
+    #[test]
+    fn drop_after_poisoned_lock() {
+        let (a, b) = io_duplex(8);
+        let read = a.read.clone();
+        let poisoned = std::panic::catch_unwind(move || {
+            let _pipe = read.lock().unwrap();
+            panic!("test panicked holding the lock");
+        });
+        assert!(poisoned.is_err());
+        drop(a);
+        drop(b);
+    }
+}
@@ -2,6 +2,16 @@
 //!
 //! # Examples
 //!
+//! ## Scripted sink
+//! The simplest start is [SinkMock::scripted()] taking a [Step] of the script on every flush.
+//! ```
+//! use futures::{executor::block_on, SinkExt};
+//! use futures_test_sink::{SinkMock, Step};
+//!
+//! let mut sink = SinkMock::scripted(vec![Step::Pending, Step::Err("broken pipe")]);
+//! assert_eq!(block_on(sink.send(1u8)), Err("broken pipe"));
+//! ```
+//!
 //! ## `SinkMock` allow to create a handy tests
 //! This example contains a 3 tests. See documentation of `SinkMock` for details.
 //! ```
@@ -54,24 +64,18 @@
 //!
 //! ## `SinkFeedback` mock provide a full control of returned items.
 //!
-//! You should first use `SinkMock` if this doesn't this one may be useful.
+//! You should first use `SinkMock` if this doesn't this one may be useful. Macros like
+//! [assert_sink_ready!] poll a sink by hand and check the result.
 //!
 //! ```
-//! use async_task::waker_fn;
-//! use futures::sink::Sink;
-//! use futures_test_sink::from_iter;
-//! use std::{
-//!     pin::Pin,
-//!     sync::{atomic, Arc},
-//!     task::{Context, Poll},
+//! use futures_test_sink::{
+//!     assert_sink_err, assert_sink_pending, assert_sink_ready, assert_start_send_ok, from_iter,
+//!     task::count_waker,
 //! };
+//! use std::task::{Context, Poll};
 //!
 //! // create a Context
-//! let wake_cnt = Arc::new(atomic::AtomicUsize::new(0));
-//! let cnt = wake_cnt.clone();
-//! let waker = waker_fn(move || {
-//!     wake_cnt.fetch_add(1, atomic::Ordering::SeqCst);
-//! });
+//! let (waker, cnt) = count_waker();
 //! let mut cx = Context::from_waker(&waker);
 //! // actual test
 //! let poll_fallback = vec![
@@ -85,49 +89,34 @@
 //! // ours sink implementation
 //! let mut s = from_iter(poll_fallback, start_send_fallback);
 //!
-//! let r1 = Pin::new(&mut s).poll_ready(&mut cx);
-//! assert_eq!(r1, Poll::Ready(Ok(())));
-//! let s1 = Pin::new(&mut s).start_send(1);
-//! assert_eq!(s1, Ok(()));
+//! assert_sink_ready!(s, cx);
+//! assert_start_send_ok!(s, 1);
 //!
-//! let r2 = Pin::new(&mut s).poll_ready(&mut cx);
-//! assert_eq!(r2, Poll::Ready(Ok(())));
+//! assert_sink_ready!(s, cx);
 //! // start send don't panic because start_send_fallback is cycle
-//! let s2 = Pin::new(&mut s).start_send(2);
-//! assert_eq!(s2, Ok(()));
+//! assert_start_send_ok!(s, 2);
 //!
 //! // ctx.wake() wasn't called.
-//! assert_eq!(0, cnt.load(atomic::Ordering::SeqCst));
+//! cnt.expect_none();
 //!
-//! let r3 = Pin::new(&mut s).poll_ready(&mut cx);
-//! assert_eq!(r3, Poll::Pending);
-//! assert_eq!(1, cnt.load(atomic::Ordering::SeqCst));
+//! assert_sink_pending!(s, cx);
+//! cnt.expect_wakes(1);
 //!
-//! let r4 = Pin::new(&mut s).poll_ready(&mut cx);
-//! assert_eq!(r4, Poll::Ready(Err(12)));
-//! assert_eq!(1, cnt.load(atomic::Ordering::SeqCst));
+//! assert_sink_err!(s, cx, 12);
+//! cnt.expect_wakes(1);
 //! ```
 //!
 //! You can be interested in [FuseLast](fuse_last::FuseLast) container for Iterator.
 //!
 //!
 //! ```
-//! use async_task::waker_fn;
-//! use futures::sink::Sink;
-//! use futures_test_sink::{from_iter, fuse_last::IteratorExt};
-//! use std::{
-//!     pin::Pin,
-//!     sync::{atomic, Arc},
-//!     task::{Context, Poll},
+//! use futures_test_sink::{
+//!     assert_sink_err, assert_sink_ready, assert_start_send_ok, from_iter, fuse_last::IteratorExt,
+//!     task::noop_context,
 //! };
+//! use std::task::Poll;
 //!
-//! // create a Context
-//! let wake_cnt = Arc::new(atomic::AtomicUsize::new(0));
-//! let cnt = wake_cnt.clone();
-//! let waker = waker_fn(move || {
-//!     wake_cnt.fetch_add(1, atomic::Ordering::SeqCst);
-//! });
-//! let mut cx = Context::from_waker(&waker);
+//! let mut cx = noop_context();
 //! // actual test
 //! let poll_fallback = vec![
 //!     Poll::Ready(Ok(())),
@@ -140,40 +129,224 @@
 //! // ours sink implementation
 //! let mut s = from_iter(poll_fallback, start_send_fallback);
 //!
-//! let r1 = Pin::new(&mut s).poll_ready(&mut cx);
-//! assert_eq!(r1, Poll::Ready(Ok(())));
-//! let s1 = Pin::new(&mut s).start_send(1);
-//! assert_eq!(s1, Ok(()));
+//! assert_sink_ready!(s, cx);
+//! assert_start_send_ok!(s, 1);
 //!
-//! let r2 = Pin::new(&mut s).poll_ready(&mut cx);
-//! assert_eq!(r2, Poll::Ready(Err(12)));
+//! assert_sink_err!(s, cx, 12);
 //!
-//! let r3 = Pin::new(&mut s).poll_ready(&mut cx);
-//! assert_eq!(r3, Poll::Ready(Ok(())));
+//! assert_sink_ready!(s, cx);
 //!
 //! // if not `fuse_last` this would panic!
-//! let r4 = Pin::new(&mut s).poll_ready(&mut cx);
-//! assert_eq!(r3, Poll::Ready(Ok(())));
+//! assert_sink_ready!(s, cx);
 //!
-//! let r5 = Pin::new(&mut s).poll_ready(&mut cx);
-//! assert_eq!(r3, Poll::Ready(Ok(())));
+//! assert_sink_ready!(s, cx);
 //! ```
+//!
+//! Wakers for polling by hand, like [count_waker()](task::count_waker), are in [task] module.
+//! Mocks wake the task before returning `Poll::Pending`; [WakePolicy] changes that, e.g. to leave
+//! the waking to the test, and a script of [WakeWhen] sets it for every `Poll::Pending`.
+//!
+//! The most common items can be imported at once with `use futures_test_sink::prelude::*;`.
+//!
+//! # Features
+//!
+//! - **`std`** (enabled by default) - APIs that need the standard library. Without it this crate
+//!   is `#![no_std]` and needs only `alloc`. `SinkMock`, `SinkFeedback`, `fuse_last` and most of
+//!   the other mocks are available without `std`. APIs that need `std`:
+//!   - `detecting_context()` and `SinkMock::detect_lost_wakes()`
+//!   - `exactly_once()`
+//!   - `flush_pending_wakes()`
+//!   - `gated()`
+//!   - `io_duplex()`
+//!   - `loopback()`
+//!   - `PollGapSink`
+//!   - `scenario_mock()`
+//!   - `sink_conformance_tests!`
+//!   - `SinkMock::event_stream()`
+//!   - `TestRegistry`
+//!   - `tee()`
+//!   - `ThreadTrackingSink`
+//!   - `TimingSink`
+//!   - `WakeOrder`
+//!   - `WakePolicy::Delayed`
+//! - **`anyhow`** - `into_anyhow()` on `SinkFeedback` and `SinkMock` converting errors into
+//!   [anyhow::Error](https://docs.rs/anyhow). Implies `std`.
+//! - **`failpoints`** - global registry of named faults in [failpoints] module. Implies `std`.
+//! - **`serde`** - `Serialize` and `Deserialize` for `Script`, `Event` and `Response` and
+//!   `Script::to_json()`/`Script::from_json()` for golden-file tests. Implies `std`.
+//! - **`tokio`** - mocks driven by [tokio](https://docs.rs/tokio) time. Implies `std`.
+//!   - `delayed_ready()`
+//! - **`tokio-compat`** - [compat] module adapting `futures::io` mocks like `io_duplex()` to
+//!   `tokio::io` traits. Implies `std`.
+//! - **`tracing`** - debug level [tracing](https://docs.rs/tracing) events from `SinkMock`,
+//!   `SinkFeedback` and wrappers (`AssertItems`, `ExactlyOnce`, `InspectSink`, `PollGapSink`,
+//!   `Recorder`, `ThreadTrackingSink`) with target `<sink>::<method>`, e.g.
+//!   `sink_mock::poll_flush`. Name a mock with `named()` to tell its events apart. Without this
+//!   feature no code is emitted.
 
 #![deny(missing_docs)]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
+extern crate alloc;
+
+// must be declared before the modules using their macros
+#[macro_use]
+mod events;
+#[macro_use]
+mod wake_policy;
+#[macro_use]
+mod wrapper;
+
+#[cfg(feature = "anyhow")]
+mod anyhow_sink;
+mod assert_items;
+mod assert_sink;
+mod assert_unmoved;
+mod boxed;
+mod close_mode;
+#[cfg(feature = "tokio-compat")]
+pub mod compat;
+#[cfg(feature = "std")]
+mod conformance;
+mod control_sink;
+mod countdown;
+mod counters;
+mod dedup_sink;
+#[cfg(feature = "tokio")]
+mod delayed_ready;
+mod diff_sink;
+#[cfg(feature = "std")]
+mod exactly_once;
+#[cfg(feature = "failpoints")]
+pub mod failpoints;
+mod fanout;
+mod flaky;
+mod flush_amount;
 pub mod fuse_last;
+#[cfg(feature = "std")]
+mod gate_sink;
+mod gated_by;
+mod inspect_sink;
+#[cfg(feature = "std")]
+mod io_duplex;
+#[cfg(feature = "std")]
+mod loopback;
 mod mock_sink;
+mod ordering_sink;
+mod panicking_sink;
+mod pinned_sink;
+#[cfg(feature = "std")]
+mod poll_gap;
+pub mod prelude;
+mod priority_sink;
+mod reconnecting;
+mod recorder;
+#[cfg(feature = "std")]
+mod registry;
+mod render;
+mod replay_sink;
+#[cfg(feature = "std")]
+mod scenario;
+mod slow_consumer;
+mod step;
+pub mod stream_mock;
+pub mod task;
+#[cfg(feature = "std")]
+mod thread_tracking;
+#[cfg(feature = "std")]
+mod timer;
+mod timestamp_sink;
+#[cfg(feature = "std")]
+mod timing_sink;
+mod trace_diff;
+#[cfg(feature = "serde")]
+mod trace_json;
+mod trace_matcher;
+mod unfold;
+mod wake_script;
+mod window_sink;
 
+#[cfg(feature = "anyhow")]
+pub use anyhow_sink::AnyhowSink;
+pub use assert_items::{AssertItems, OrderedBy, Validate};
+#[doc(hidden)]
+pub use assert_sink::__private;
+pub use assert_unmoved::AssertUnmoved;
+pub use boxed::{
+    from_iter_boxed, from_iter_boxed_local, interleave_pending_boxed, ok_boxed, BoxSink,
+    LocalBoxSink,
+};
+pub use close_mode::CloseMode;
+#[cfg(feature = "std")]
+#[doc(hidden)]
+pub use conformance::__conformance;
+pub use control_sink::{from_control_stream, Control, ControlSink};
+pub use countdown::{countdown, CountdownEnd, CountdownSink};
+pub use counters::{CallCounters, MockCounters};
+pub use dedup_sink::{dedup_mock, DedupSink};
+#[cfg(feature = "tokio")]
+pub use delayed_ready::{delayed_ready, DelayedReady};
+pub use diff_sink::DiffSink;
+#[cfg(feature = "std")]
+pub use exactly_once::{exactly_once, ExactlyOnce};
+pub use fanout::{fanout, Fanout, FanoutError};
+pub use flaky::Flaky;
+pub use flush_amount::FlushAmount;
+#[cfg(feature = "std")]
+pub use gate_sink::{gated, Gate, GateSink};
+pub use gated_by::{gated_by, GatedBy};
+#[cfg(feature = "std")]
+pub use inspect_sink::{tee, ItemsHandle};
+pub use inspect_sink::{Inspect, InspectSink};
+#[cfg(feature = "std")]
+pub use io_duplex::{io_duplex, MockIo};
+#[cfg(feature = "std")]
+pub use loopback::{loopback, LoopbackSink, LoopbackStream};
 pub use mock_sink::SinkMock;
+pub use ordering_sink::{ordering_sink, ordering_sink_by_key, OrderingSink};
+pub use panicking_sink::{panicking_sink, PanickingSink};
+pub use pinned_sink::PinnedSink;
+#[cfg(feature = "std")]
+pub use poll_gap::PollGapSink;
+pub use priority_sink::{priority_sink, PrioritySink};
+pub use reconnecting::{reconnecting, Outage, ReconnectingSink};
+pub use recorder::{Recorder, Script};
+#[cfg(feature = "std")]
+pub use registry::TestRegistry;
+pub use replay_sink::ReplaySink;
+#[cfg(feature = "std")]
+pub use scenario::{scenario_mock, Scenario, ScenarioHandle, ScenarioSink, ScenarioStream};
+pub use slow_consumer::{slow_consumer, SlowConsumer};
+pub use step::{Step, Steps};
+#[cfg(feature = "std")]
+pub use thread_tracking::ThreadTrackingSink;
+#[cfg(feature = "std")]
+pub use timer::flush_pending_wakes;
+pub use timestamp_sink::{timestamp_sink, TimestampSink};
+#[cfg(feature = "std")]
+pub use timing_sink::{Operation, Report, TimingSink};
+pub use trace_diff::{DiffOptions, TraceDiff};
+#[cfg(feature = "serde")]
+pub use trace_json::TRACE_FORMAT_VERSION;
+pub use trace_matcher::{Mismatch, StepPattern, TraceMatcher};
+pub use unfold::{sink_unfold, Event, Response, SinkUnfold};
+#[cfg(feature = "std")]
+pub use wake_policy::WakeOrder;
+pub use wake_policy::{WakeCoalescing, WakeHandle, WakePolicy, WakeStyle, WakeTarget};
+pub use wake_script::{PollClock, WakeWhen};
+pub use window_sink::{window_sink, window_sink_with, WindowHandle, WindowSink};
 
-use futures::never::Never;
-use futures::sink::Sink;
-use std::iter::{repeat, successors, Repeat};
-use std::marker::PhantomData;
-use std::{
+use alloc::string::String;
+use core::iter::{repeat, successors, Repeat};
+use core::marker::PhantomData;
+use core::{
     pin::Pin,
     task::{Context, Poll},
 };
+use counters::Method;
+use futures::never::Never;
+use futures::sink::Sink;
+use wake_policy::Waking;
 
 fn reverse<E>(poll: &Poll<Result<(), E>>) -> Option<Poll<Result<(), E>>> {
     match poll {
@@ -190,7 +363,11 @@ fn reverse<E>(poll: &Poll<Result<(), E>>) -> Option<Poll<Result<(), E>>> {
 pub struct SinkFeedback<E, FI, SSI, Item> {
     poll_fallback: FI,
     start_send_fallback: SSI,
-    item_type: PhantomData<Item>,
+    poll_cnt: usize,
+    name: Option<String>,
+    calls: Option<CallCounters>,
+    waking: Waking,
+    item_type: PhantomData<fn(Item)>,
     err_typpe: PhantomData<E>,
 }
 
@@ -202,6 +379,10 @@ pub fn ok<Item>() -> Drain<Item> {
     Drain {
         poll_fallback: repeat(Poll::Ready(Ok(()))),
         start_send_fallback: repeat(Ok(())),
+        poll_cnt: 0,
+        name: None,
+        calls: None,
+        waking: Waking::default(),
         item_type: Default::default(),
         err_typpe: Default::default(),
     }
@@ -211,10 +392,7 @@ pub fn ok<Item>() -> Drain<Item> {
 ///
 /// Inspirited by
 /// [InterleavePending](https://docs.rs/futures-test/0.3.3/futures_test/stream/struct.InterleavePending.html) from futures-test crate.
-pub fn interleave_pending<Item>() -> impl Sink<Item, Error = Never>
-where
-    Item: Unpin,
-{
+pub fn interleave_pending<Item>() -> impl Sink<Item, Error = Never> {
     let poll_fallback = successors(Some(Poll::Ready(Ok(()))), reverse);
     let ss_value: Result<(), Never> = Ok(());
     let start_send_fallback = repeat(ss_value);
@@ -225,7 +403,9 @@ where
 /// This method allows to create Sink from iterators.
 ///
 /// Any time you call `poll_ready`, `poll_flush` or `push_close` the [next] method will be called on `poll_fallback` iterator.
-/// If iterator return `Poll::Pending` the `cx.waker().clone().wake()` will be additionally called.
+/// If iterator return `Poll::Pending` the `cx.waker().wake_by_ref()` will be additionally
+/// called, unless another [WakePolicy] is set with [wake_policy()](SinkFeedback::wake_policy) or
+/// another [WakeStyle] with [wake_style()](SinkFeedback::wake_style).
 /// Where `cx` is `std::task::Context` passed to `poll_ready`, `poll_flush` or `poll_close` function.
 ///
 /// Any time you call `start_send` the inner implementation will discard `item` and return
@@ -241,21 +421,126 @@ where
 pub fn from_iter<Item, FI, SSI, E>(
     poll_fallback: FI,
     start_send_fallback: SSI,
-) -> impl Sink<Item, Error = E>
+) -> SinkFeedback<E, FI, SSI, Item>
 where
     FI: Iterator<Item = Poll<Result<(), E>>> + Unpin,
     SSI: Iterator<Item = Result<(), E>> + Unpin,
     E: Unpin,
-    Item: Unpin,
 {
     SinkFeedback {
         poll_fallback,
         start_send_fallback,
+        poll_cnt: 0,
+        name: None,
+        calls: None,
+        waking: Waking::default(),
         item_type: Default::default(),
         err_typpe: Default::default(),
     }
 }
 
+/// [from_iter()] with [WakePolicy::Never] returning the handle waking the sink.
+///
+/// See [WakeHandle] for an example.
+pub fn from_iter_manual_wake<Item, FI, SSI, E>(
+    poll_fallback: FI,
+    start_send_fallback: SSI,
+) -> (SinkFeedback<E, FI, SSI, Item>, WakeHandle)
+where
+    FI: Iterator<Item = Poll<Result<(), E>>> + Unpin,
+    SSI: Iterator<Item = Result<(), E>> + Unpin,
+    E: Unpin,
+{
+    let mut sink = from_iter(poll_fallback, start_send_fallback);
+    sink.wake_policy(WakePolicy::Never);
+    let handle = sink.wake_handle();
+    (sink, handle)
+}
+
+impl<E, FI, SSI, Item> SinkFeedback<E, FI, SSI, Item> {
+    /// Name this sink, so its `tracing` events can be told apart from events of other mocks.
+    ///
+    /// With `tracing` feature every call emits a debug event with target
+    /// `sink_feedback::<method>` and fields `mock` (the name), `result` (`ok`, `err` or
+    /// `pending`) and `script_index` (how many items were taken from `poll_fallback`).
+    pub fn named(&mut self, name: impl Into<String>) -> &mut Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Name set by [named()](SinkFeedback::named).
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Count calls of every method in lock-free [CallCounters].
+    ///
+    /// Meant for benchmarks: after this call no method allocates and counting costs one relaxed
+    /// atomic increment per call. Get the handle with
+    /// [call_counters()](SinkFeedback::call_counters).
+    pub fn counters_only(&mut self) -> &mut Self {
+        self.calls = Some(CallCounters::new());
+        self
+    }
+
+    /// Handle to the counters, `None` unless [counters_only()](SinkFeedback::counters_only) was
+    /// set.
+    pub fn call_counters(&self) -> Option<CallCounters> {
+        self.calls.clone()
+    }
+
+    waking_setters! {
+        /// Set how the waker is woken when `poll_fallback` yields `Poll::Pending`.
+    }
+
+    fn count(&self, method: Method) {
+        if let Some(calls) = &self.calls {
+            calls.count(method);
+        }
+    }
+}
+
+impl<E, FI, SSI, Item> SinkFeedback<E, FI, SSI, Item>
+where
+    FI: Iterator<Item = Poll<Result<(), E>>>,
+{
+    fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), E>> {
+        self.poll_cnt += 1;
+        self.waking.polled();
+        match self.poll_fallback.next().unwrap() {
+            Poll::Ready(t) => {
+                self.waking.ready(cx.waker());
+                Poll::Ready(t)
+            }
+            Poll::Pending => {
+                self.waking.pending(cx.waker());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Emit event of a `poll_*()` method of [SinkFeedback].
+macro_rules! feedback_event {
+    ($sink:expr, $method:literal, $poll:expr) => {
+        debug_event!(
+            target: concat!("sink_feedback::", $method),
+            mock = $sink.name.as_deref(),
+            result = crate::events::outcome($poll),
+            script_index = $sink.poll_cnt
+        )
+    };
+}
+
+impl<E, FI, SSI, Item> core::fmt::Debug for SinkFeedback<E, FI, SSI, Item> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SinkFeedback")
+            .field("name", &self.name)
+            .field("polls", &self.poll_cnt)
+            .finish()
+    }
+}
+
 impl<E, FI, SSI, Item> Sink<Item> for SinkFeedback<E, FI, SSI, Item>
 where
     Self: Sized + Unpin,
@@ -266,31 +551,47 @@ where
 
     fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
         let this = Pin::into_inner(self);
-        match this.poll_fallback.next().unwrap() {
-            Poll::Ready(t) => Poll::Ready(t),
-            Poll::Pending => {
-                cx.waker().clone().wake();
-                Poll::Pending
-            }
-        }
+        this.count(Method::Ready);
+        let poll = this.poll_next(cx);
+        feedback_event!(this, "poll_ready", &poll);
+        poll
     }
 
     fn start_send(self: Pin<&mut Self>, _item: Item) -> Result<(), Self::Error> {
-        Pin::into_inner(self).start_send_fallback.next().unwrap()
+        let this = Pin::into_inner(self);
+        this.count(Method::Send);
+        let res = this.start_send_fallback.next().unwrap();
+        debug_event!(
+            target: "sink_feedback::start_send",
+            mock = this.name.as_deref(),
+            result = crate::events::send_outcome(&res)
+        );
+        res
     }
 
     fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        self.poll_ready(cx)
+        let this = Pin::into_inner(self);
+        this.count(Method::Flush);
+        let poll = this.poll_next(cx);
+        feedback_event!(this, "poll_flush", &poll);
+        poll
     }
+
     fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        self.poll_ready(cx)
+        let this = Pin::into_inner(self);
+        this.count(Method::Close);
+        let poll = this.poll_next(cx);
+        feedback_event!(this, "poll_close", &poll);
+        poll
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::task::count_waker;
     use async_task::waker_fn;
+    use futures::{executor::block_on, stream, SinkExt};
     use std::sync::{atomic, Arc};
 
     #[test]
@@ -403,4 +704,51 @@ mod tests {
         // now it should panic
         let _ = Pin::new(&mut s).poll_ready(&mut cx);
     }
+
+    #[test]
+    fn borrowed_items() {
+        let waker = waker_fn(|| {});
+        let mut cx = Context::from_waker(&waker);
+        let data = String::from("zero copy");
+
+        let mut s = interleave_pending();
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(Pin::new(&mut s).start_send(&data[..4]), Ok(()));
+
+        let mut d = super::ok();
+        let mut stream = stream::iter(data.split(' ').map(Ok));
+        assert_eq!(Ok(()), block_on(d.send_all(&mut stream)));
+    }
+
+    #[test]
+    fn wake_policy() {
+        let (waker, cnt) = count_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut s = from_iter::<u8, _, _, _>(
+            vec![
+                Poll::Pending,
+                Poll::Pending,
+                Poll::Pending,
+                Poll::Ready(Ok(())),
+            ]
+            .into_iter(),
+            core::iter::empty::<Result<(), ()>>(),
+        );
+        s.wake_policy(WakePolicy::Never);
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Pending);
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Pending);
+        cnt.expect_none();
+        // only the latest waker is kept
+        assert!(s.wake_stored());
+        assert!(!s.wake_stored());
+        cnt.expect_wakes(1);
+
+        s.wake_policy(WakePolicy::Spurious {
+            extra: 1,
+            also_on_ready: false,
+        });
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Pending);
+        cnt.expect_wakes(3);
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+    }
 }
@@ -0,0 +1,275 @@
+use futures::{never::Never, sink::Sink, stream::Stream};
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::{
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+use crate::counters::Lock;
+
+struct Link<Item> {
+    delay_flushes: usize,
+    flush_cnt: usize,
+    // items sent but not flushed yet
+    buffered: Vec<Item>,
+    // flushed items with number of flush that sent them
+    in_flight: VecDeque<(usize, Item)>,
+    // items visible for the stream half
+    delivered: VecDeque<Item>,
+    is_closed: bool,
+    stream_waker: Option<Waker>,
+}
+
+impl<Item> Link<Item> {
+    /// Returns the waker of the stream half to wake once the lock is released.
+    fn flush(&mut self) -> Option<Waker> {
+        self.flush_cnt += 1;
+        let flush_cnt = self.flush_cnt;
+        self.in_flight
+            .extend(self.buffered.drain(..).map(|item| (flush_cnt, item)));
+        while let Some((sent_at, _)) = self.in_flight.front() {
+            if sent_at + self.delay_flushes > self.flush_cnt {
+                break;
+            }
+            let (_, item) = self.in_flight.pop_front().unwrap();
+            self.delivered.push_back(item);
+        }
+        self.stream_waker()
+    }
+
+    /// Like [flush()](Link::flush).
+    fn close(&mut self) -> Option<Waker> {
+        self.flush();
+        self.delivered
+            .extend(self.in_flight.drain(..).map(|(_, item)| item));
+        self.is_closed = true;
+        self.stream_waker()
+    }
+
+    fn stream_waker(&mut self) -> Option<Waker> {
+        if !self.delivered.is_empty() || self.is_closed {
+            self.stream_waker.take()
+        } else {
+            None
+        }
+    }
+}
+
+/// Sink half of [loopback()].
+///
+/// [loopback()]:loopback
+pub struct LoopbackSink<Item> {
+    link: Arc<Lock<Link<Item>>>,
+}
+
+/// Stream half of [loopback()].
+///
+/// [loopback()]:loopback
+pub struct LoopbackStream<Item> {
+    link: Arc<Lock<Link<Item>>>,
+}
+
+/// Create a transport mock where items sent to the sink half are received by the stream half.
+///
+/// Items accepted by `start_send` are buffered until `poll_flush`. Items flushed by one
+/// `poll_flush` appear on the stream after `delay_flushes` further flush completions. This models
+/// requests in flight without any real I/O. With `delay_flushes == 0` flushed items are available
+/// immediately.
+///
+/// `poll_close` releases all items still in flight. The stream ends after they are received.
+///
+/// `poll_ready`, `poll_flush` and `poll_close` always return `Poll::Ready(Ok(()))`. A task waiting
+/// on the stream is woken when a flush releases items or the sink is closed.
+///
+/// Requires `std` feature.
+///
+/// # Examples
+/// ```
+/// use futures::{executor::block_on, SinkExt, StreamExt};
+/// use futures_test_sink::loopback;
+///
+/// let (mut sink, mut stream) = loopback(1);
+/// block_on(async {
+///     sink.send("request 1").await.unwrap();
+///     // still in flight
+///     assert_eq!(stream.in_flight(), 1);
+///     sink.send("request 2").await.unwrap();
+///     assert_eq!(stream.next().await, Some("request 1"));
+///     sink.close().await.unwrap();
+///     assert_eq!(stream.next().await, Some("request 2"));
+///     assert_eq!(stream.next().await, None);
+/// });
+/// ```
+pub fn loopback<Item>(delay_flushes: usize) -> (LoopbackSink<Item>, LoopbackStream<Item>) {
+    let link = Arc::new(Lock::new(Link {
+        delay_flushes,
+        flush_cnt: 0,
+        buffered: Vec::new(),
+        in_flight: VecDeque::new(),
+        delivered: VecDeque::new(),
+        is_closed: false,
+        stream_waker: None,
+    }));
+    (LoopbackSink { link: link.clone() }, LoopbackStream { link })
+}
+
+impl<Item> LoopbackSink<Item> {
+    /// How many items were flushed but haven't reached the stream half yet.
+    pub fn in_flight(&self) -> usize {
+        self.link.with(|link| link.in_flight.len())
+    }
+}
+
+impl<Item> LoopbackStream<Item> {
+    /// How many items were flushed but haven't reached the stream half yet.
+    pub fn in_flight(&self) -> usize {
+        self.link.with(|link| link.in_flight.len())
+    }
+}
+
+impl<Item> Unpin for LoopbackSink<Item> {}
+impl<Item> Unpin for LoopbackStream<Item> {}
+
+impl<Item> Sink<Item> for LoopbackSink<Item> {
+    type Error = Never;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        if self.link.with(|link| link.is_closed) {
+            panic!("Trying use closed sink");
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Item) -> Result<(), Self::Error> {
+        self.link.with(|link| link.buffered.push(item));
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        // woken outside of the lock, a waker could poll the stream inline
+        if let Some(waker) = self.link.with(Link::flush) {
+            waker.wake();
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        if let Some(waker) = self.link.with(Link::close) {
+            waker.wake();
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<Item> Stream for LoopbackStream<Item> {
+    type Item = Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.link.with(|link| {
+            if let Some(item) = link.delivered.pop_front() {
+                Poll::Ready(Some(item))
+            } else if link.is_closed {
+                Poll::Ready(None)
+            } else {
+                link.stream_waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_task::waker_fn;
+    use futures::{
+        executor::block_on,
+        future,
+        stream::{self, StreamExt},
+        SinkExt,
+    };
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+
+    #[test]
+    fn delayed_by_flushes() {
+        // create a Context
+        let wake_cnt = Arc::new(AtomicUsize::new(0));
+        let cnt = wake_cnt.clone();
+        let waker = waker_fn(move || {
+            wake_cnt.fetch_add(1, Ordering::SeqCst);
+        });
+        let mut cx = Context::from_waker(&waker);
+        // actual test
+        let (mut sink, mut stream) = loopback(2);
+        assert_eq!(Pin::new(&mut sink).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(Pin::new(&mut sink).start_send(1), Ok(()));
+        assert_eq!(Pin::new(&mut stream).poll_next(&mut cx), Poll::Pending);
+        // not flushed
+        assert_eq!(sink.in_flight(), 0);
+
+        assert_eq!(Pin::new(&mut sink).poll_flush(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(sink.in_flight(), 1);
+        assert_eq!(Pin::new(&mut sink).poll_flush(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(0, cnt.load(Ordering::SeqCst));
+        assert_eq!(Pin::new(&mut sink).poll_flush(&mut cx), Poll::Ready(Ok(())));
+        // stream was woken
+        assert_eq!(1, cnt.load(Ordering::SeqCst));
+        assert_eq!(sink.in_flight(), 0);
+        assert_eq!(
+            Pin::new(&mut stream).poll_next(&mut cx),
+            Poll::Ready(Some(1))
+        );
+    }
+
+    #[test]
+    fn close_drains_and_ends_stream() {
+        let (sink, stream) = loopback(10);
+        let items = stream::iter(vec![Ok(5u8), Ok(7), Ok(9)]);
+        assert_eq!(Ok(()), block_on(items.forward(sink)));
+        assert_eq!(vec![5, 7, 9], block_on(stream.collect::<Vec<_>>()));
+    }
+
+    #[test]
+    fn pipelining_in_one_task() {
+        let (mut sink, stream) = loopback(1);
+        let send = async move {
+            for request in 0..5 {
+                sink.send(request).await.unwrap();
+            }
+            sink.close().await.unwrap();
+        };
+        let (_, received) = block_on(future::join(send, stream.collect::<Vec<_>>()));
+        assert_eq!(received, vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn stream_in_other_thread() {
+        let (sink, stream) = loopback(0);
+        let receiver = thread::spawn(move || block_on(stream.collect::<Vec<_>>()));
+        let items = stream::iter((0..100).map(Ok));
+        assert_eq!(Ok(()), block_on(items.forward(sink)));
+        assert_eq!(receiver.join().unwrap(), (0..100).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn waker_polling_stream_inline() {
+        let (mut sink, stream) = loopback(0);
+        let stream = Arc::new(std::sync::Mutex::new(stream));
+        let polled = stream.clone();
+        let waker = waker_fn(move || {
+            let mut cx = Context::from_waker(futures::task::noop_waker_ref());
+            let mut stream = polled.lock().unwrap();
+            assert_eq!(
+                Pin::new(&mut *stream).poll_next(&mut cx),
+                Poll::Ready(Some(1))
+            );
+        });
+        let mut cx = Context::from_waker(&waker);
+        let pending = Pin::new(&mut *stream.lock().unwrap()).poll_next(&mut cx);
+        assert_eq!(pending, Poll::Pending);
+        assert_eq!(Pin::new(&mut sink).start_send(1), Ok(()));
+        assert_eq!(Pin::new(&mut sink).poll_flush(&mut cx), Poll::Ready(Ok(())));
+    }
+}
@@ -1,25 +1,151 @@
-use futures::{ready, sink::Sink};
-use std::iter;
-use std::marker::PhantomData;
-use std::num::NonZeroUsize;
-use std::{
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+use core::iter;
+use core::marker::PhantomData;
+use core::num::NonZeroUsize;
+use core::option;
+use core::{
     pin::Pin,
-    task::{Context, Poll},
+    task::{Context, Poll, Waker},
 };
+use futures::{ready, sink::Sink};
+
+use crate::counters::{Method, MockCounters};
+use crate::wake_policy::{EventWaker, Waking};
+use crate::{CloseMode, Flaky, FlushAmount, Step, Steps, WakeCoalescing};
+
+#[cfg(feature = "failpoints")]
+use crate::failpoints::{self, Fault};
+#[cfg(feature = "std")]
+use crate::task::{LostWakeDetector, PollReporter};
+#[cfg(feature = "std")]
+use crate::{Event, Response, TestRegistry, WakeOrder};
+#[cfg(feature = "std")]
+use futures::channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender};
 
 const DEFAULT_MAX_ITEM: usize = 3usize;
 const DEFAULT_FLUSH_AT_ONCE: usize = 2usize;
 
+// `flush_feedback` of presets that always flush successfully
+type AlwaysReady<E> = iter::RepeatWith<fn() -> Poll<Result<(), E>>>;
+
+// returned results with the function cloning errors into them
+type PollLog<E> = (Vec<Poll<Result<(), E>>>, fn(&E) -> E);
+
+// tokens left, tokens per refill, polls between refills and polls so far
+struct RateLimit {
+    tokens: usize,
+    per_refill: usize,
+    every: NonZeroUsize,
+    poll_cnt: usize,
+}
+
+// requests allowed in flight and requests sent and not acked
+struct Pipeline {
+    window: usize,
+    in_flight: usize,
+}
+
+// `Debug` strings of buffered items with the function making them
+type DebugBuffer<Item> = (VecDeque<String>, fn(&Item) -> String);
+
+// latched error with the function cloning it
+type Latched<E> = (E, fn(&E) -> E);
+
+// sender of `event_stream()` with the function cloning errors into events
+#[cfg(feature = "std")]
+type EventSender<E> = (UnboundedSender<(Event<()>, Response<E>)>, fn(&E) -> E);
+
+// the largest accepted item size, the size function and the error with its clone function
+type FrameLimit<Item, E> = (
+    usize,
+    Box<dyn Fn(&Item) -> usize + Send + Sync>,
+    E,
+    fn(&E) -> E,
+);
+
+// predicate of refused items with the error and the function cloning it
+type Refusal<Item, E> = (Box<dyn Fn(&Item) -> bool + Send + Sync>, E, fn(&E) -> E);
+
+// items refused by `start_send()` with their errors and the functions cloning them
+type Rejections<Item, E> = (Vec<(Item, E)>, fn(&Item) -> Item, fn(&E) -> E);
+
+// sequence number of an item and the number of the last accepted item
+type Sequence<Item> = (fn(&Item) -> u64, Option<u64>);
+
+/// Emit event of a `poll_*()` method of [SinkMock].
+macro_rules! poll_event {
+    ($mock:expr, $method:literal, $poll:expr) => {
+        debug_event!(
+            target: concat!("sink_mock::", $method),
+            mock = $mock.name.as_deref(),
+            result = crate::events::outcome($poll),
+            buffered = $mock.item_cnt,
+            script_index = $mock.flush_feedback_consumed
+        )
+    };
+}
+
+fn always_ready<E>() -> AlwaysReady<E> {
+    iter::repeat_with(|| Poll::Ready(Ok(())))
+}
+
+/// Addresses of the data and vtable of a waker, what [Waker::will_wake()] compares, without a
+/// reference keeping the waker alive.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct WakerToken(usize, usize);
+
+impl WakerToken {
+    fn of(waker: &Waker) -> Self {
+        Self(waker.data() as usize, waker.vtable() as *const _ as usize)
+    }
+}
+
+/// `n` as `NonZeroUsize`, like converting `usize` into [FlushAmount].
+fn non_zero(n: usize, arg: &str) -> NonZeroUsize {
+    match NonZeroUsize::new(n) {
+        Some(n) => n,
+        None => panic!("`{}` must be greater than 0", arg),
+    }
+}
+
+#[cfg(feature = "std")]
+fn ready_result<E>(poll: &Poll<Result<(), E>>) -> Option<&Result<(), E>> {
+    match poll {
+        Poll::Ready(res) => Some(res),
+        Poll::Pending => None,
+    }
+}
+
 /// This struct represent correct implementation of sink according to [sink doc].
 ///
+/// `Item` is stored only by [record_rejections()](SinkMock::record_rejections), otherwise it can
+/// be a borrowed type like `&'a [u8]` that doesn't outlive a send.
+///
+/// The mock is meant to be driven by a single task. To share it between tasks or threads put it
+/// behind a lock like `Arc<Mutex<_>>`, and wrap it in `ThreadTrackingSink` to check which threads
+/// called it.
+///
 /// # Panics:
 ///
 /// 1. Calling `start_send` without calling '`poll_ready()` with result `Poll::Ready(Ok(()))`' panic!
 /// 2. Calling any method after `poll_close()` returned  `Poll::Ready(Ok(()))` once panic!
 /// 3. When `flush_feedback` iterator return `None`.
+/// 4. Calling `poll_flush()` with empty buffer if
+///    [assert_no_empty_flush()](SinkMock::assert_no_empty_flush) was set.
+/// 5. Calling any method after an error was returned if
+///    [fused_on_error()](SinkMock::fused_on_error) was set.
+/// 6. Calling `poll_close()` with items not acknowledged by `poll_flush()` if
+///    [require_flush_ack_before_close()](SinkMock::require_flush_ack_before_close) was set.
+/// 7. Calling `poll_close()` with buffered items if
+///    [panic_on_unflushed_close()](SinkMock::panic_on_unflushed_close) was set.
 ///
 /// [sink doc]:https://docs.rs/futures/0.3/futures/sink/trait.Sink.html
-pub struct SinkMock<FlushI, ReadyI, SendI, Item> {
+pub struct SinkMock<FlushI, ReadyI, SendI, Item, E> {
     flush_feedback: FlushI,
     ready_fallback: ReadyI,
     send_fallback: SendI,
@@ -27,273 +153,3869 @@ pub struct SinkMock<FlushI, ReadyI, SendI, Item> {
     //mock inner sink
     max_item: usize,
     item_cnt: usize,
-    flush_at_once: usize,
+    flush_at_once: FlushAmount,
+    slow_start_cap: Option<NonZeroUsize>,
+    frame: Option<NonZeroUsize>,
+    // items `poll_flush()` waits for
+    min_batch: Option<NonZeroUsize>,
+    // input items per output frame of `compressing()` and output frames so far
+    compression: Option<(NonZeroUsize, usize)>,
     is_closed: bool,
     can_start_send: bool,
+    send_cnt: usize,
+    flush_feedback_consumed: usize,
+    just_became_full: bool,
+    high_water_mark: usize,
+    // `poll_ready()` flushes when the buffer is full
+    ready_flushes: bool,
+    // `poll_flush()` is a no-op, only `poll_close()` consults `flush_feedback`
+    transactional: bool,
+
+    // panic on `poll_flush()` with empty buffer
+    no_empty_flush: bool,
+    flush_call_cnt: usize,
+    idle_flush_cnt: usize,
+
+    // `Debug` of buffered items dumped by a panic on `poll_close()` with items left
+    unflushed_close: Option<DebugBuffer<Item>>,
+
+    // whether `poll_close()` flushes or drops buffered items
+    close_mode: CloseMode,
+    dropped_on_close: usize,
+
+    // panic on `poll_close()` before `poll_flush()` acknowledged sent items
+    flush_ack_before_close: bool,
+    unacked_cnt: usize,
+
+    // spurious wakes on every Nth `Poll::Ready(Ok(()))`
+    spurious_every: Option<NonZeroUsize>,
+    ready_ok_cnt: usize,
+    spurious_wake_cnt: usize,
+
+    // every Nth accepted item is discarded
+    drop_every: Option<NonZeroUsize>,
+    dropped_cnt: usize,
+
+    // items larger than the limit are rejected by `start_send()`
+    max_frame: Option<FrameLimit<Item, E>>,
+
+    // items matching the predicate are rejected by `start_send()`
+    reject_if: Option<Refusal<Item, E>>,
+    rejections: Option<Rejections<Item, E>>,
+
+    // accepted items must be numbered consecutively, gaps are (expected, got)
+    contiguous: Option<Sequence<Item>>,
+    gaps: Vec<(u64, u64)>,
+
+    // ready-credits left, refilled by drained items
+    credits: Option<usize>,
+
+    // in-flight window of `pipelined()`, emptied by `ack()`
+    pipeline: Option<Pipeline>,
+    // waker of the full window
+    ack_waker: EventWaker,
+
+    // flushes never complete, see `half_open()`
+    half_open: bool,
+
+    // `max_item` for every `poll_ready()`
+    capacity_schedule: Option<Box<dyn Iterator<Item = usize> + Send + Sync>>,
+
+    // waker of the last `Poll::Pending` returned without waking it with wakes done outside of
+    // polls until then, and wakes before this poll
+    unwoken: Option<(Waker, usize)>,
+    wakes_before: usize,
+    unsolicited_cnt: usize,
+    unsolicited_limit: Option<usize>,
+
+    // whether each poll's waker will wake the waker of the previous poll, kept until next poll
+    continuity: Option<(Vec<bool>, Option<WakerToken>)>,
+
+    // how the waker is woken before returning `Poll::Pending`
+    waking: Waking,
+
+    // waker of the last `Poll::Pending`
+    pending_waker: Option<Waker>,
+    waker_changed_cnt: usize,
+
+    // token bucket of `rate_limited()`
+    rate_limit: Option<RateLimit>,
+
+    // item blocking the head of the buffer and the waker of the stalled flush
+    hol_block: Option<usize>,
+    hol_waker: Option<Waker>,
+
+    // panic on any call after an error was returned
+    fused_on_error: bool,
+    errored: bool,
+
+    // errors revealed by the next `poll_flush()`
+    deferred_error: Option<(usize, E)>,
+    flush_error: Option<E>,
+
+    // errors returned only by the named method
+    flush_method_error: Option<E>,
+    close_method_error: Option<E>,
+
+    // error returned by every `poll_close()` after the flush, see `close_error()`
+    close_error: Option<Latched<E>>,
+
+    #[cfg(feature = "failpoints")]
+    failpoint: Option<(alloc::string::String, failpoints::Lookup<E>)>,
+
+    // copies of results returned by `poll_*()` methods
+    poll_log: Option<PollLog<E>>,
+    #[cfg(feature = "std")]
+    event_tx: Option<EventSender<E>>,
+
+    // name in `tracing` events
+    name: Option<String>,
+
+    // method being polled, wakes are attributed to it
+    polling: Method,
+    #[cfg(feature = "std")]
+    lost_wakes: Option<PollReporter>,
+    counters: MockCounters<E>,
+    last_error_clone: Option<fn(&E) -> E>,
 
     // marker
-    item_type: PhantomData<Item>,
+    item_type: PhantomData<fn(Item)>,
+}
+
+impl<FlushI, ReadyI, SendI, Item, E> Unpin for SinkMock<FlushI, ReadyI, SendI, Item, E> {}
+
+// state checked by the assertions of the mock, so errors and items needn't be `Debug`
+impl<FlushI, ReadyI, SendI, Item, E> Debug for SinkMock<FlushI, ReadyI, SendI, Item, E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("SinkMock")
+            .field("name", &self.name)
+            .field("buffered", &self.item_cnt)
+            .field("max_item", &self.max_item)
+            .field("accepted", &self.send_cnt)
+            .field("can_start_send", &self.can_start_send)
+            .field("is_closed", &self.is_closed)
+            .field("errored", &self.errored)
+            .finish()
+    }
 }
 
-impl<FlushI, ReadyI, SendI, Item> Unpin for SinkMock<FlushI, ReadyI, SendI, Item> {}
+impl<FlushI, ReadyI, SendI, Item, E> SinkMock<FlushI, ReadyI, SendI, Item, E> {
+    /// Wake the waker as the [WakePolicy](crate::WakePolicy) says, attributing wakes to the
+    /// method being polled, and return `Poll::Pending`.
+    fn pending<T>(&mut self, cx: &mut Context<'_>) -> Poll<T> {
+        let wakes = self.waking.pending_at(Some(self.polling), cx.waker());
+        self.counters.woke(self.polling, wakes);
+        Poll::Pending
+    }
 
-impl<FlushI, ReadyI, SendI, Item> SinkMock<FlushI, ReadyI, SendI, Item> {
     fn check_panic(&self) {
         if self.is_closed {
             panic!("Trying use closed sink");
         }
+        if self.errored {
+            panic!("polled after error; sink should be discarded");
+        }
     }
 
     /// Change how many buffered item will be discarded when `flush_feedback` yield
     /// `Poll::Ready(Ok(()))`
-    pub fn set_flush_at_once(&mut self, flush_at_once: NonZeroUsize) -> &mut Self {
+    pub fn set_flush_at_once(&mut self, flush_at_once: impl Into<FlushAmount>) -> &mut Self {
         self.flush_at_once = flush_at_once.into();
         self
     }
 
-    /// Set how many item can be buffered by this sink before needing to flush.
-    pub fn set_max_item(&mut self, max_item: usize) -> &mut Self {
-        self.max_item = max_item;
+    /// How many buffered items are discarded when `flush_feedback` yield `Poll::Ready(Ok(()))`.
+    pub fn flush_at_once(&self) -> &FlushAmount {
+        &self.flush_at_once
+    }
+
+    /// Model TCP slow-start: `flush_at_once` begins at 1 and doubles after every
+    /// `Poll::Ready(Ok(()))` taken from `flush_feedback` up to `cap`.
+    ///
+    /// Only a [FlushAmount::Fixed] amount doubles. Setting [FlushAmount::All] or
+    /// [FlushAmount::PerEvent] by [set_flush_at_once()](SinkMock::set_flush_at_once) afterwards
+    /// stops the ramp up.
+    ///
+    /// # Panics
+    /// If `cap` is `0`.
+    pub fn slow_start(&mut self, cap: usize) -> &mut Self {
+        let cap = non_zero(cap, "cap");
+        self.flush_at_once = FlushAmount::Fixed(NonZeroUsize::new(1).unwrap());
+        self.slow_start_cap = Some(cap);
         self
     }
-}
 
-impl<FlushI, E, ReadyI, SendI, Item> SinkMock<FlushI, ReadyI, SendI, Item>
-where
-    FlushI: Iterator<Item = Poll<Result<(), E>>>,
-    // ReadyI accept iterator that can only return Error None.
-    // This because Poll::Ready(Ok(())) and Poll::Pending can be determined
-    // from inner implementation.
-    ReadyI: Iterator<Item = E>,
-    // Similar reason to ReadyI
-    SendI: Iterator<Item = E>,
-{
-    /// Create a new instance of SinkMock
+    /// Model a fixed-frame protocol: items are flushed only in whole frames of `batch` items.
     ///
-    /// # Arguments
-    /// - **`flush_feedback`** - an iterator that represent reaction on flushing data to it. Every time
-    /// when `poll_flush()` is called the item from iterator is taken and unwrapped and depends on
-    /// result an special action is taken:
-    ///   - `Poll::Ready(Ok(()))` discard `flush_at_once` items from buffer. Repeat
-    ///   - `Poll::Ready(Err(e))` forward error
-    ///   - `Poll::Pending` wake up Waker from Context and return Poll::Pending
+    /// `Poll::Ready(Ok(()))` taken from `flush_feedback` removes one frame from the buffer
+    /// instead of `flush_at_once` items. A flush with less than `batch` items buffered returns
+    /// `Poll::Ready(Ok(()))` without removing anything, so a partial frame stays in the buffer
+    /// even after `poll_close()`. See [items_until_frame()](SinkMock::items_until_frame).
     ///
-    /// - **`send_fallback`** - an iterator that represent when start `start_send()` called with `item: Item` should
-    /// return error. If iterator yield `iter_item` `start_send()` returns `Err(iter_item)` discarding `item`.
-    /// If iterator return `None` `item` is buffered.
+    /// `max_item` is raised to `batch` if it's lower, so a full frame can be buffered.
     ///
-    /// - **`ready_fallback`** - an iterator that represent when `poll_ready()` should return
-    /// error. If iterator yield `iter_item` `poll_ready()` returns `Err(iter_item)`. Otherwise
-    /// return `Poll::Ready(Ok(())` if can buffer next item else return result from calling
-    /// `poll_flush()`
+    /// # Panics
+    /// If `batch` is `0`.
+    pub fn frame_aligned(&mut self, batch: usize) -> &mut Self {
+        let batch = non_zero(batch, "batch");
+        self.frame = Some(batch);
+        self.max_item = self.max_item.max(batch.get());
+        self
+    }
+
+    /// How many items must be sent to complete the current frame. It's 0 when the buffer holds
+    /// only whole frames or [frame_aligned()](SinkMock::frame_aligned) wasn't set.
+    pub fn items_until_frame(&self) -> usize {
+        let frame = self.frame.map_or(1, NonZeroUsize::get);
+        (frame - self.item_cnt % frame) % frame
+    }
+
+    /// Make `poll_flush()` return `Poll::Pending` until at least `n` items are buffered, then
+    /// flush as usual. A flush with nothing buffered doesn't wait either.
     ///
-    /// - **`max_item`** - how many item this sink can buffer
+    /// The task is woken before `Poll::Pending` as set by [wake_policy()](SinkMock::wake_policy),
+    /// so a producer flushing too early spins until it sends more items. `poll_close()` flushes
+    /// whatever is buffered regardless of the minimum, and so does `poll_ready()` with a full
+    /// buffer. `max_item` is raised to `n` if it's lower, so a whole batch can be buffered.
     ///
-    /// - **`flush_at_once`** - how many item will be removed from buffer when `flush_feedback`
-    /// return `Poll::Ready(Ok(()))`
-    pub fn new(
-        flush_feedback: FlushI,
-        ready_fallback: ReadyI,
-        send_fallback: SendI,
-        max_item: usize,
-        flush_at_once: usize,
-    ) -> Self {
-        Self {
-            flush_feedback,
-            ready_fallback,
-            send_fallback,
-            max_item,
-            item_cnt: 0,
-            flush_at_once,
-            is_closed: false,
-            can_start_send: false,
-            item_type: Default::default(),
-        }
+    /// # Examples
+    /// ```
+    /// use futures::{executor::block_on, FutureExt, SinkExt};
+    /// use futures_test_sink::SinkMock;
+    /// use std::{iter, task::Poll};
+    ///
+    /// let mut sink = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, ()>(()))));
+    /// sink.min_batch(2);
+    /// block_on(sink.feed(1u8)).unwrap();
+    /// assert!(!sink.reached_min_batch());
+    /// assert_eq!(sink.flush().now_or_never(), None);
+    /// block_on(sink.feed(2u8)).unwrap();
+    /// assert!(sink.reached_min_batch());
+    /// assert_eq!(block_on(sink.flush()), Ok(()));
+    /// ```
+    ///
+    /// # Panics
+    /// If `n` is `0`.
+    pub fn min_batch(&mut self, n: usize) -> &mut Self {
+        let n = non_zero(n, "n");
+        self.min_batch = Some(n);
+        self.max_item = self.max_item.max(n.get());
+        self
     }
-}
 
-impl<FlushI, E, Item> SinkMock<FlushI, iter::Empty<E>, iter::Empty<E>, Item>
-where
-    FlushI: Iterator<Item = Poll<Result<(), E>>>,
-{
-    /// Create a sink that will never return `Error` on `start_send` and `poll_ready`.
+    /// `true` when enough items are buffered for `poll_flush()` to flush them, always `true`
+    /// unless [min_batch()](SinkMock::min_batch) was set.
+    pub fn reached_min_batch(&self) -> bool {
+        self.item_cnt >= self.min_batch.map_or(0, NonZeroUsize::get)
+    }
+
+    /// Model a compressing transport: up to `ratio` items flushed at once are coalesced into one
+    /// output frame.
     ///
-    /// This method will use
-    pub fn with_flush_feedback(flush_feedback: FlushI) -> Self {
-        SinkMock::new(
-            flush_feedback,
-            iter::empty(),
-            iter::empty(),
-            DEFAULT_MAX_ITEM,
-            DEFAULT_FLUSH_AT_ONCE,
-        )
+    /// `Poll::Ready(Ok(()))` taken from `flush_feedback` flushes all buffered items, like
+    /// [FlushAmount::All], and emits `ceil(flushed / ratio)` frames counted by
+    /// [output_frames()](SinkMock::output_frames). So a producer that flushes after every item
+    /// gets a frame per item, while one letting items accumulate gets fewer frames.
+    ///
+    /// # Examples
+    /// ```
+    /// use futures::{executor::block_on, stream, SinkExt, StreamExt};
+    /// use futures_test_sink::SinkMock;
+    /// use std::{iter, task::Poll};
+    ///
+    /// let mut sink = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, ()>(()))));
+    /// sink.compressing(4).set_max_item(8);
+    /// let items = stream::iter(vec![Ok(1u8); 10]);
+    /// assert_eq!(Ok(()), block_on(items.forward(&mut sink)));
+    /// // flushed 8 items when the buffer got full, then 2 on close
+    /// assert_eq!(sink.output_frames(), 3);
+    /// ```
+    ///
+    /// # Panics
+    /// If `ratio` is `0`.
+    pub fn compressing(&mut self, ratio: usize) -> &mut Self {
+        self.compression = Some((non_zero(ratio, "ratio"), 0));
+        self.flush_at_once = FlushAmount::All;
+        self
     }
-}
 
-impl<Item, FlushI, ReadyI, SendI, E> Sink<Item> for SinkMock<FlushI, ReadyI, SendI, Item>
-where
-    FlushI: Iterator<Item = Poll<Result<(), E>>>,
-    // ReadyI accept iterator that can only return Error None.
-    // This because Poll::Ready(Ok(())) and Poll::Pending can be determined
-    // from inner implementation.
-    ReadyI: Iterator<Item = E>,
-    // Similar reason to ReadyI
-    SendI: Iterator<Item = E>,
-{
-    type Error = E;
-    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        self.check_panic();
-        let mut this = Pin::into_inner(self);
-        this.can_start_send = false;
-        if let Some(e) = this.ready_fallback.next() {
-            return Poll::Ready(Err(e));
-        }
+    /// Output frames emitted so far, 0 unless [compressing()](SinkMock::compressing) was set.
+    pub fn output_frames(&self) -> usize {
+        self.compression.map_or(0, |(_, frames)| frames)
+    }
 
-        if this.max_item > this.item_cnt {
-            this.can_start_send = true;
-            Poll::Ready(Ok(()))
-        } else {
-            match Pin::new(&mut this).poll_flush(cx) {
-                Poll::Ready(Ok(())) => {
-                    this.can_start_send = true;
-                    Poll::Ready(Ok(()))
-                }
-                forward => forward,
-            }
-        }
+    /// Set how many item can be buffered by this sink before needing to flush.
+    pub fn set_max_item(&mut self, max_item: usize) -> &mut Self {
+        self.max_item = max_item;
+        self
     }
 
-    fn start_send(self: Pin<&mut Self>, _item: Item) -> Result<(), Self::Error> {
-        self.check_panic();
+    /// How many items were taken from `flush_feedback` iterator.
+    pub fn flush_feedback_consumed(&self) -> usize {
+        self.flush_feedback_consumed
+    }
 
-        if !self.can_start_send {
-            panic!("`start_send()` called without correct call of `poll_ready()`");
-        }
+    /// Return `true` if the last `start_send()` filled the buffer up to `max_item`.
+    ///
+    /// It's reset by next `start_send()`, also one that fails or whose item is dropped by
+    /// [lossy()](SinkMock::lossy), or when `poll_flush()` remove items from the buffer.
+    pub fn just_became_full(&self) -> bool {
+        self.just_became_full
+    }
 
-        let this = Pin::into_inner(self);
-        if let Some(e) = this.send_fallback.next() {
-            return Err(e);
-        }
+    /// The maximum number of items buffered at once during the lifetime of this sink.
+    ///
+    /// It's updated by every successful `start_send()`. Useful to check how deep the buffer got
+    /// or that a producer respects backpressure.
+    pub fn high_water_mark(&self) -> usize {
+        self.high_water_mark
+    }
 
-        this.item_cnt += 1;
-        Ok(())
+    /// Panic when `poll_flush()` is called while nothing was sent since the last flush.
+    ///
+    /// Flushing an empty buffer should be cheap, so a producer doing it in a hot loop is likely a
+    /// bug. Only calls to `poll_flush()` are checked, flushes done by `poll_ready()` and
+    /// `poll_close()` are not. Repeated `poll_flush()` after `Poll::Pending` or an error is fine
+    /// while items are still buffered.
+    pub fn assert_no_empty_flush(&mut self) -> &mut Self {
+        self.no_empty_flush = true;
+        self
     }
 
-    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        self.check_panic();
-        let this = Pin::into_inner(self);
-        this.can_start_send = false;
-        // we can think about it like an I/O that returned it was able to take items.
-        // (And how many - `flush_at_once` parameter)
-        loop {
-            match this
-                .flush_feedback
-                .next()
-                .expect("Unexpected end of `flush_feedback` iterator!")
-            {
-                // mocked I/O took `flush_at_once` buffered items.
-                Poll::Ready(Ok(())) => {
-                    this.item_cnt = this.item_cnt.saturating_sub(this.flush_at_once);
-                    if this.item_cnt == 0 {
-                        return Poll::Ready(Ok(()));
-                    }
-                }
-                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
-                Poll::Pending => {
-                    cx.waker().clone().wake();
-                    return Poll::Pending;
-                }
-            }
-        }
+    /// Panic when `poll_close()` is called with items still buffered, listing them with `Debug`.
+    ///
+    /// `poll_close()` flushes by itself, so this catches producers that close without flushing
+    /// first. The panic message shows exactly which payloads would be stranded, e.g.
+    /// ``"`poll_close()` called with 2 unflushed items: [3, 4]"``. To know them the mock keeps
+    /// `Debug` strings of buffered items, which otherwise it never does.
+    pub fn panic_on_unflushed_close(&mut self) -> &mut Self
+    where
+        Item: Debug,
+    {
+        self.unflushed_close = Some((VecDeque::new(), |item| format!("{:?}", item)));
+        self
     }
 
-    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        self.check_panic();
-        ready!(self.as_mut().poll_flush(cx))?;
-        let this = Pin::into_inner(self);
-        this.is_closed = true;
-        Poll::Ready(Ok(()))
+    /// Panic when `poll_close()` is called before `poll_flush()` returned `Poll::Ready(Ok(()))`
+    /// for all items sent so far.
+    ///
+    /// Some sinks require an explicit flush acknowledgement before close, so closing right after
+    /// `start_send()` loses data. Flushes done by `poll_ready()` don't count as acknowledgement.
+    /// Closing a sink that never received an item is fine.
+    pub fn require_flush_ack_before_close(&mut self) -> &mut Self {
+        self.flush_ack_before_close = true;
+        self
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use async_task::waker_fn;
-    use futures::{
-        never::Never,
-        stream::{self, StreamExt},
-    };
+    /// Name this sink, so it consults [failpoints] registry before its own scripts.
+    ///
+    /// Requires `failpoints` feature.
+    #[cfg(feature = "failpoints")]
+    pub fn failpoint(&mut self, name: &str) -> &mut Self
+    where
+        E: Clone + 'static,
+    {
+        self.failpoint = Some((name.into(), failpoints::get::<E>));
+        self
+    }
 
-    #[test]
-    #[should_panic(expected = "`start_send()` called without correct call of `poll_ready()`")]
-    fn panic_no_call_poll_ready_test() {
-        let e = iter::empty::<Poll<Result<(), Never>>>();
-        let mut s = SinkMock::with_flush_feedback(e);
-        Pin::new(&mut s).start_send(1).unwrap();
+    #[cfg(feature = "failpoints")]
+    fn fault(&self) -> Option<Fault<E>> {
+        let (name, get) = self.failpoint.as_ref()?;
+        get(name)
     }
 
-    #[test]
-    #[should_panic(expected = "Trying use closed sink")]
-    fn panic_after_close() {
-        // create a Context
-        let waker = waker_fn(move || {});
-        let mut cx = Context::from_waker(&waker);
-        let e = iter::once::<Poll<Result<(), Never>>>(Poll::Ready(Ok(())));
-        let mut s = SinkMock::with_flush_feedback(e);
-        let _ = Pin::new(&mut s).poll_close(&mut cx);
-        let _ = Pin::new(&mut s).poll_ready(&mut cx);
-        // just to inform compiler about Item type.
-        let _ = Pin::new(&mut s).start_send(1);
+    /// Wake the waker on every `every`th `Poll::Ready(Ok(()))` returned by `poll_ready()`,
+    /// `poll_flush()` or `poll_close()`.
+    ///
+    /// Such wake doesn't mean any progress. It simulates spurious wakeups that robust
+    /// executors and adapters must tolerate. This is independent of waking before returning
+    /// `Poll::Pending`.
+    ///
+    /// # Panics
+    /// If `every` is `0`.
+    pub fn spurious_wakes(&mut self, every: usize) -> &mut Self {
+        self.spurious_every = Some(non_zero(every, "every"));
+        self
     }
 
-    #[test]
-    #[should_panic(expected = "Unexpected end of `flush_feedback` iterator!")]
-    fn panic_when_flus_feedback_ends() {
-        let waker = waker_fn(move || {});
-        let mut cx = Context::from_waker(&waker);
-        let e = iter::once::<Poll<Result<(), Never>>>(Poll::Ready(Ok(())));
-        let mut s = SinkMock::with_flush_feedback(e);
-        let _ = Pin::new(&mut s).poll_flush(&mut cx);
-        // this will panic
-        let _ = Pin::new(&mut s).poll_flush(&mut cx);
-        // just to inform compiler about Item type.
-        let _ = Pin::new(&mut s).start_send(1);
+    waking_setters! {
+        /// Set how the waker is woken before returning `Poll::Pending`.
+        ///
+        /// The default [WakePolicy::Immediate](crate::WakePolicy::Immediate) wakes it right
+        /// away. Wakes done during a poll are attributed to the polled method in
+        /// [counters()](SinkMock::counters). Policies storing the waker store one for every
+        /// method that returned `Poll::Pending`.
+        ///
+        /// # Examples
+        /// ```
+        /// use futures::{executor::block_on, FutureExt, SinkExt};
+        /// use futures_test_sink::{SinkMock, WakePolicy};
+        /// use std::{iter, task::Poll};
+        ///
+        /// let feedback = vec![Poll::Pending, Poll::Ready(Ok::<_, ()>(()))];
+        /// let mut sink = SinkMock::with_flush_feedback(feedback.into_iter());
+        /// sink.wake_policy(WakePolicy::Never);
+        /// block_on(sink.feed(1u8)).unwrap();
+        ///
+        /// // nobody wakes the flush, so it would hang in `block_on()`
+        /// assert_eq!(sink.flush().now_or_never(), None);
+        ///
+        /// assert!(sink.wake_stored());
+        /// assert_eq!(block_on(sink.flush()), Ok(()));
+        /// ```
     }
 
-    #[test]
+    /// Set when the task is woken relative to returning from the poll. See [WakeOrder].
+    ///
+    /// Requires `std` feature.
+    #[cfg(feature = "std")]
+    pub fn wake_order(&mut self, order: WakeOrder) -> &mut Self {
+        self.waking.set_order(order);
+        self
+    }
+
+    /// Report every poll to `detector`, so wakes of the waker of
+    /// [detecting_context()](crate::task::detecting_context) not followed by a poll of this mock
+    /// fail the test.
+    ///
+    /// Requires `std` feature.
+    #[cfg(feature = "std")]
+    pub fn detect_lost_wakes(&mut self, detector: &LostWakeDetector) -> &mut Self {
+        self.lost_wakes = Some(detector.reporter());
+        self
+    }
+
+    /// Record every result returned by `poll_ready()`, `poll_flush()` and `poll_close()`.
+    ///
+    /// Read them with [returned_polls()](SinkMock::returned_polls). Errors are cloned into the log.
+    pub fn record_polls(&mut self) -> &mut Self
+    where
+        E: Clone,
+    {
+        self.poll_log = Some((Vec::new(), E::clone));
+        self
+    }
+
+    /// Results returned by `poll_ready()`, `poll_flush()` and `poll_close()` in order.
+    ///
+    /// This is a lighter alternative to [Recorder](crate::Recorder) when only poll outcomes
+    /// matter, e.g. to compare them with scripted `flush_feedback`. It's empty unless
+    /// [record_polls()](SinkMock::record_polls) was set.
+    pub fn returned_polls(&self) -> &[Poll<Result<(), E>>] {
+        self.poll_log.as_ref().map_or(&[], |(log, _)| log)
+    }
+
+    /// Stream yielding every call of this mock with its outcome as it happens.
+    ///
+    /// Items aren't stored by the mock, so `start_send()` is reported as `Event::Send(())`.
+    /// Errors are cloned into the events. The stream ends when the mock is dropped or
+    /// `poll_close()` succeeded. Calling this again ends the previous stream.
+    ///
+    /// This allows a test to `await` a particular interaction instead of sleeping and checking
+    /// counters. The channel is unbounded, so every event not consumed yet stays in memory. Drop
+    /// the stream when it's no longer needed, events are discarded then.
+    ///
+    /// Requires `std` feature.
+    ///
+    /// # Examples
+    /// ```
+    /// use futures::{executor::block_on, SinkExt, StreamExt};
+    /// use futures_test_sink::{Event, Response, SinkMock};
+    /// use std::{iter, task::Poll};
+    ///
+    /// let mut sink = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, ()>(()))));
+    /// let events = sink.event_stream();
+    /// block_on(sink.send(1u8)).unwrap();
+    /// drop(sink);
+    /// assert_eq!(
+    ///     block_on(events.collect::<Vec<_>>()),
+    ///     vec![
+    ///         (Event::Ready, Response::Ok),
+    ///         (Event::Send(()), Response::Ok),
+    ///         (Event::Flush, Response::Ok),
+    ///     ]
+    /// );
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn event_stream(&mut self) -> UnboundedReceiver<(Event<()>, Response<E>)>
+    where
+        E: Clone,
+    {
+        let (tx, rx) = unbounded();
+        self.event_tx = Some((tx, E::clone));
+        rx
+    }
+
+    /// Send `event` with `result` to [event_stream()](SinkMock::event_stream), `None` is pending.
+    #[cfg(feature = "std")]
+    fn emit(&mut self, event: Event<()>, result: Option<&Result<(), E>>) {
+        if let Some((tx, clone)) = &self.event_tx {
+            let response = match result {
+                Some(Ok(())) => Response::Ok,
+                Some(Err(e)) => Response::Err(clone(e)),
+                None => Response::Pending,
+            };
+            // the stream may be dropped already
+            let _ = tx.unbounded_send((event, response));
+        }
+        if self.is_closed {
+            self.event_tx = None;
+        }
+    }
+
+    /// Name this mock, so its `tracing` events can be told apart from events of other mocks.
+    ///
+    /// With `tracing` feature every call emits a debug event with target
+    /// `sink_mock::<method>` and fields `mock` (the name), `result` (`ok`, `err` or `pending`),
+    /// `buffered` and `script_index` (how many items were taken from `flush_feedback`).
+    pub fn named(&mut self, name: impl Into<String>) -> &mut Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Name this mock and add it to `registry`, so it's included in the registry report.
+    ///
+    /// The registry keeps a handle to [counters()](SinkMock::counters), not the mock, so the
+    /// mock is dropped as usual and its last state stays in the report.
+    ///
+    /// Requires `std` feature.
+    #[cfg(feature = "std")]
+    pub fn register(&mut self, registry: &TestRegistry, name: impl Into<String>) -> &mut Self
+    where
+        E: 'static,
+    {
+        let name = name.into();
+        registry.add(name.clone(), self.counters.stats());
+        self.name = Some(name);
+        self
+    }
+
+    /// Name set by [named()](SinkMock::named).
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Handle to counters of this mock that can be read after the mock was moved, e.g. into
+    /// `forward()`.
+    pub fn counters(&self) -> MockCounters<E> {
+        self.counters.clone()
+    }
+
+    /// Keep a clone of the last returned error in [counters()](SinkMock::counters).
+    ///
+    /// Errors are counted per method either way, the last error is available only with this
+    /// set, because it must be cloned.
+    pub fn track_last_error(&mut self) -> &mut Self
+    where
+        E: Clone,
+    {
+        self.last_error_clone = Some(E::clone);
+        self
+    }
+
+    fn count_error(&self, method: Method, e: &E) {
+        self.counters
+            .error(method, self.last_error_clone.map(|clone| (clone(e), clone)));
+    }
+
+    /// How many spurious wakes were done. See [spurious_wakes()](SinkMock::spurious_wakes).
+    pub fn spurious_wake_count(&self) -> usize {
+        self.spurious_wake_cnt
+    }
+
+    /// Silently discard every `drop_every`th item passed to `start_send()`.
+    ///
+    /// `start_send()` returns `Ok(())` for the discarded item, but it's never buffered, so the
+    /// producer can't notice the loss. This models a UDP-like transport and tests that the
+    /// receiving side copes with gaps. Unlike `send_fallback` errors the item counts as sent.
+    ///
+    /// # Panics
+    /// If `drop_every` is `0`.
+    pub fn lossy(&mut self, drop_every: usize) -> &mut Self {
+        self.drop_every = Some(non_zero(drop_every, "drop_every"));
+        self
+    }
+
+    /// Set whether `poll_close()` flushes buffered items first, the default, or drops them.
+    ///
+    /// With [CloseMode::Abrupt] `poll_close()` returns `Poll::Ready(Ok(()))` without consulting
+    /// `flush_feedback`, unless a `close_err` of
+    /// [with_method_errors()](SinkMock::with_method_errors) is pending. The
+    /// dropped items are counted by [items_dropped_on_close()](SinkMock::items_dropped_on_close).
+    pub fn close_mode(&mut self, mode: CloseMode) -> &mut Self {
+        self.close_mode = mode;
+        self
+    }
+
+    /// How many buffered items `poll_close()` dropped. See [close_mode()](SinkMock::close_mode).
+    pub fn items_dropped_on_close(&self) -> usize {
+        self.dropped_on_close
+    }
+
+    /// How many items were discarded. See [lossy()](SinkMock::lossy).
+    pub fn dropped_count(&self) -> usize {
+        self.dropped_cnt
+    }
+
+    /// Reject items larger than `bytes` as measured by `size_fn`.
+    ///
+    /// `start_send()` returns `Err(err.clone())` for an item with `size_fn(&item) > bytes`. The
+    /// item is discarded and doesn't count as sent. Smaller items are buffered as usual. This is
+    /// a per-item gate like a datagram or a frame size limit of a protocol, it tests producers
+    /// that must fragment large messages before sending them. `size_fn` must be `Send + Sync`, so
+    /// the mock can still be shared between threads.
+    ///
+    /// # Examples
+    /// ```
+    /// use futures::{executor::block_on, SinkExt};
+    /// use futures_test_sink::SinkMock;
+    /// use std::{iter, task::Poll};
+    ///
+    /// let mut sink = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok(()))));
+    /// sink.max_frame(4, |frame: &Vec<u8>| frame.len(), "frame too large");
+    /// assert_eq!(block_on(sink.send(vec![0; 4])), Ok(()));
+    /// assert_eq!(block_on(sink.send(vec![0; 5])), Err("frame too large"));
+    /// ```
+    pub fn max_frame(
+        &mut self,
+        bytes: usize,
+        size_fn: impl Fn(&Item) -> usize + Send + Sync + 'static,
+        err: E,
+    ) -> &mut Self
+    where
+        E: Clone,
+    {
+        self.max_frame = Some((bytes, Box::new(size_fn), err, E::clone));
+        self
+    }
+
+    /// The error for `item` if it's larger than [max_frame()](SinkMock::max_frame).
+    fn oversized(&self, item: &Item) -> Option<E> {
+        match &self.max_frame {
+            Some((bytes, size_fn, err, clone)) if size_fn(item) > *bytes => Some(clone(err)),
+            _ => None,
+        }
+    }
+
+    /// Reject items matching `predicate`, e.g. by content.
+    ///
+    /// `start_send()` returns `Err(err.clone())` for an item with `predicate(&item)`. Like with
+    /// [max_frame()](SinkMock::max_frame) the item is discarded and doesn't count as sent and
+    /// `predicate` must be `Send + Sync`.
+    ///
+    /// # Examples
+    /// ```
+    /// use futures::{executor::block_on, SinkExt};
+    /// use futures_test_sink::SinkMock;
+    /// use std::{iter, task::Poll};
+    ///
+    /// let mut sink = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok(()))));
+    /// sink.reject_if(|item: &&str| item.is_empty(), "empty").record_rejections();
+    /// assert_eq!(block_on(sink.send("a")), Ok(()));
+    /// assert_eq!(block_on(sink.send("")), Err("empty"));
+    /// assert_eq!(sink.rejections(), &[("", "empty")]);
+    /// ```
+    pub fn reject_if(
+        &mut self,
+        predicate: impl Fn(&Item) -> bool + Send + Sync + 'static,
+        err: E,
+    ) -> &mut Self
+    where
+        E: Clone,
+    {
+        self.reject_if = Some((Box::new(predicate), err, E::clone));
+        self
+    }
+
+    /// The error for `item` if it matches the predicate of [reject_if()](SinkMock::reject_if).
+    fn refused(&self, item: &Item) -> Option<E> {
+        match &self.reject_if {
+            Some((predicate, err, clone)) if predicate(item) => Some(clone(err)),
+            _ => None,
+        }
+    }
+
+    /// Record clones of items refused by `start_send()` with the returned errors, whatever the
+    /// reason: [reject_if()](SinkMock::reject_if), [max_frame()](SinkMock::max_frame) or a
+    /// `start_send()` error of the mock.
+    pub fn record_rejections(&mut self) -> &mut Self
+    where
+        Item: Clone,
+        E: Clone,
+    {
+        self.rejections = Some((Vec::new(), Item::clone, E::clone));
+        self
+    }
+
+    /// Items refused by `start_send()` with their errors, in order, empty unless
+    /// [record_rejections()](SinkMock::record_rejections) was set.
+    pub fn rejections(&self) -> &[(Item, E)] {
+        self.rejections
+            .as_ref()
+            .map_or(&[], |(rejections, _, _)| rejections)
+    }
+
+    /// Require accepted items to be numbered consecutively, each item's sequence number taken by
+    /// `extractor` must be the previous one plus 1.
+    ///
+    /// The first accepted item sets the start. An item breaking the sequence is accepted and
+    /// recorded in [gaps()](SinkMock::gaps), the sequence continues from it. Unlike checking that
+    /// the numbers only grow, a skipped number or a repeated one is a gap too. Items refused by
+    /// `start_send()` aren't checked.
+    ///
+    /// # Examples
+    /// ```
+    /// use futures::{executor::block_on, stream, StreamExt};
+    /// use futures_test_sink::SinkMock;
+    /// use std::{iter, task::Poll};
+    ///
+    /// let mut sink = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, ()>(()))));
+    /// sink.expect_contiguous(|seq: &u64| *seq);
+    /// let items = stream::iter(vec![Ok(7u64), Ok(8), Ok(10), Ok(11), Ok(11)]);
+    /// block_on(items.forward(&mut sink)).unwrap();
+    /// assert_eq!(sink.gaps(), vec![(9, 10), (12, 11)]);
+    /// ```
+    pub fn expect_contiguous(&mut self, extractor: fn(&Item) -> u64) -> &mut Self {
+        self.contiguous = Some((extractor, None));
+        self
+    }
+
+    /// Breaks of the sequence as `(expected, got)` sequence numbers, in order. Empty unless
+    /// [expect_contiguous()](SinkMock::expect_contiguous) was set.
+    pub fn gaps(&self) -> Vec<(u64, u64)> {
+        self.gaps.clone()
+    }
+
+    /// Check the sequence number of an accepted item.
+    fn sequenced(&mut self, item: &Item) {
+        if let Some((extractor, last)) = &mut self.contiguous {
+            let got = extractor(item);
+            if let Some(expected) = last.map(|last| last.wrapping_add(1)) {
+                if got != expected {
+                    self.gaps.push((expected, got));
+                }
+            }
+            *last = Some(got);
+        }
+    }
+
+    /// Model window-based backpressure: the sink grants `capacity` ready-credits.
+    ///
+    /// Every item accepted by `start_send()` consumes one credit and every item drained from the
+    /// buffer by `poll_flush()` or `poll_close()` gives it back. With no credits left
+    /// `poll_ready()` wakes the waker and returns `Poll::Pending` without flushing, so a producer
+    /// must flush to make progress. A producer that waits for `poll_ready()` without ever flushing
+    /// spins forever, which is how a real peer that never gets data behaves.
+    ///
+    /// `max_item` is set to `capacity`, so the buffer never limits the producer before credits do.
+    /// Items discarded by [lossy()](SinkMock::lossy) never give their credit back.
+    ///
+    /// # Examples
+    /// ```
+    /// use futures::{executor::block_on, FutureExt, SinkExt};
+    /// use futures_test_sink::SinkMock;
+    /// use std::{iter, task::Poll};
+    ///
+    /// let mut sink = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, ()>(()))));
+    /// sink.credited(2).set_flush_at_once(1);
+    /// block_on(sink.feed(1u8)).unwrap();
+    /// block_on(sink.feed(2u8)).unwrap();
+    /// assert_eq!(sink.credits(), Some(0));
+    /// // no credits until the producer flushes
+    /// assert_eq!(sink.feed(3u8).now_or_never(), None);
+    /// block_on(sink.flush()).unwrap();
+    /// assert_eq!(sink.credits(), Some(2));
+    /// ```
+    ///
+    /// # Panics
+    /// If `capacity` is `0`.
+    pub fn credited(&mut self, capacity: usize) -> &mut Self {
+        let capacity = non_zero(capacity, "capacity");
+        self.credits = Some(capacity.get());
+        self.max_item = capacity.get();
+        self
+    }
+
+    /// Ready-credits left, `None` unless [credited()](SinkMock::credited) was set.
+    pub fn credits(&self) -> Option<usize> {
+        self.credits
+    }
+
+    /// Model request/response pipelining with at most `window` requests in flight.
+    ///
+    /// Every item accepted by `start_send()` is a request in flight until the test acknowledges
+    /// it with [ack()](SinkMock::ack). With `window` requests in flight `poll_ready()` stores the
+    /// waker and returns `Poll::Pending` without waking it, `ack()` wakes it. Flushing doesn't
+    /// help, it only moves items out of the buffer.
+    ///
+    /// `max_item` is set to `window`, so the buffer never limits the producer before the window
+    /// does.
+    ///
+    /// # Examples
+    /// ```
+    /// use futures::{executor::block_on, FutureExt, SinkExt};
+    /// use futures_test_sink::SinkMock;
+    /// use std::{iter, task::Poll};
+    ///
+    /// let mut sink = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, ()>(()))));
+    /// sink.pipelined(2);
+    /// block_on(sink.send(1u8)).unwrap();
+    /// block_on(sink.send(2u8)).unwrap();
+    /// assert_eq!(sink.in_flight(), 2);
+    /// // the window is full until a response comes
+    /// assert_eq!(sink.send(3u8).now_or_never(), None);
+    /// sink.ack(1);
+    /// block_on(sink.send(3u8)).unwrap();
+    /// assert_eq!(sink.in_flight(), 2);
+    /// ```
+    ///
+    /// # Panics
+    /// If `window` is `0`.
+    pub fn pipelined(&mut self, window: usize) -> &mut Self {
+        let window = non_zero(window, "window");
+        self.pipeline = Some(Pipeline {
+            window: window.get(),
+            in_flight: 0,
+        });
+        self.max_item = window.get();
+        self
+    }
+
+    /// Acknowledge `n` requests in flight and wake the waker of `poll_ready()` waiting for the
+    /// window. See [pipelined()](SinkMock::pipelined).
+    ///
+    /// Every ack wakes the waiting task until it polls the mock again, unless
+    /// [WakeCoalescing::PerProgress] is set by [wake_coalescing()](SinkMock::wake_coalescing).
+    ///
+    /// # Panics
+    /// If [pipelined()](SinkMock::pipelined) wasn't set or fewer than `n` requests are in flight.
+    pub fn ack(&mut self, n: usize) {
+        let pipeline = match &mut self.pipeline {
+            Some(pipeline) => pipeline,
+            None => panic!("`ack()` called, but the mock isn't `pipelined()`"),
+        };
+        if n > pipeline.in_flight {
+            panic!("acked {} requests with {} in flight", n, pipeline.in_flight);
+        }
+        pipeline.in_flight -= n;
+        self.unwoken = None;
+        if let Some(waker) = self.ack_waker.event() {
+            waker.wake();
+        }
+    }
+
+    /// Set how many wakes acks between two polls cause, see [ack()](SinkMock::ack).
+    pub fn wake_coalescing(&mut self, coalescing: WakeCoalescing) -> &mut Self {
+        self.ack_waker.set(coalescing);
+        self
+    }
+
+    /// How many acks didn't wake the task because of [WakeCoalescing::PerProgress].
+    pub fn coalesced_wakes(&self) -> usize {
+        self.ack_waker.coalesced()
+    }
+
+    /// Requests sent and not acknowledged by [ack()](SinkMock::ack), 0 unless
+    /// [pipelined()](SinkMock::pipelined) was set.
+    pub fn in_flight(&self) -> usize {
+        self.pipeline
+            .as_ref()
+            .map_or(0, |pipeline| pipeline.in_flight)
+    }
+
+    /// Current `max_item`, changed over time by
+    /// [with_capacity_schedule()](SinkMock::with_capacity_schedule).
+    pub fn capacity(&self) -> usize {
+        self.max_item
+    }
+
+    /// Model a half-open connection: the peer is gone, but nobody told us.
+    ///
+    /// `poll_ready()` and `start_send()` keep succeeding, items enter the buffer like data enters
+    /// the kernel buffer of a TCP socket, but ACKs never arrive. `poll_flush()` and graceful
+    /// `poll_close()` return `Poll::Pending` forever without waking the task, regardless of the
+    /// [WakePolicy](crate::WakePolicy). Unlike a sink that is never ready, only code with a flush
+    /// timeout or a keepalive notices it.
+    ///
+    /// `max_item` is set to `usize::MAX`, so `poll_ready()` never needs a flush.
+    ///
+    /// # Examples
+    /// ```
+    /// use futures::{executor::block_on, FutureExt, SinkExt};
+    /// use futures_test_sink::SinkMock;
+    /// use std::iter;
+    ///
+    /// let mut sink = SinkMock::<_, _, _, u8, ()>::with_flush_feedback(iter::empty());
+    /// sink.half_open();
+    /// block_on(sink.feed(1)).unwrap();
+    /// block_on(sink.feed(2)).unwrap();
+    /// assert_eq!(sink.flush().now_or_never(), None);
+    /// assert_eq!(sink.stuck_items(), 2);
+    /// ```
+    pub fn half_open(&mut self) -> &mut Self {
+        self.half_open = true;
+        self.max_item = usize::MAX;
+        self
+    }
+
+    /// Items sent and never flushed because of [half_open()](SinkMock::half_open), 0 unless it
+    /// was set.
+    pub fn stuck_items(&self) -> usize {
+        if self.half_open {
+            self.item_cnt
+        } else {
+            0
+        }
+    }
+
+    /// Model a token-bucket rate limiter granting `tokens_per_refill` items every
+    /// `refill_every_polls` calls of `poll_ready()`, `poll_flush()` and `poll_close()`.
+    ///
+    /// The bucket starts full. Every item accepted by `start_send()` takes a token. With no tokens
+    /// left `poll_ready()` wakes the waker and returns `Poll::Pending` like a rate limiter asking
+    /// to come back later. Every `refill_every_polls`th poll call fills the bucket again, unused
+    /// tokens don't accumulate over `tokens_per_refill`. Unlike
+    /// [credited()](SinkMock::credited) flushing doesn't help, only polling again does.
+    ///
+    /// # Examples
+    /// ```
+    /// use futures::{executor::block_on, SinkExt};
+    /// use futures_test_sink::SinkMock;
+    /// use std::{iter, task::Poll};
+    ///
+    /// let mut sink = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, ()>(()))));
+    /// sink.rate_limited(1, 3);
+    /// block_on(sink.feed(1u8)).unwrap();
+    /// assert_eq!(sink.tokens(), Some(0));
+    /// // the second poll is pending, the third one gets a new token
+    /// assert_eq!(block_on(sink.feed(2u8)), Ok(()));
+    /// assert_eq!(sink.tokens(), Some(0));
+    /// ```
+    ///
+    /// # Panics
+    /// If `tokens_per_refill` or `refill_every_polls` is `0`.
+    pub fn rate_limited(
+        &mut self,
+        tokens_per_refill: usize,
+        refill_every_polls: usize,
+    ) -> &mut Self {
+        let tokens_per_refill = non_zero(tokens_per_refill, "tokens_per_refill");
+        self.rate_limit = Some(RateLimit {
+            tokens: tokens_per_refill.get(),
+            per_refill: tokens_per_refill.get(),
+            every: non_zero(refill_every_polls, "refill_every_polls"),
+            poll_cnt: 0,
+        });
+        self
+    }
+
+    /// How many times the task was polled again after `Poll::Pending` although the mock didn't
+    /// wake it.
+    ///
+    /// The mock usually wakes the waker before it returns `Poll::Pending`, then any following poll
+    /// is expected. When it doesn't, e.g. while blocked by
+    /// [hol_block_at()](SinkMock::hol_block_at), it remembers the waker. The next poll with a
+    /// waker that [will_wake()](Waker::will_wake) the remembered one, before the mock woke it, is
+    /// counted. A driver spinning in a loop instead of yielding to the executor is caught this
+    /// way without relying on timing. Polls with another waker, e.g. from another task, aren't
+    /// counted. `will_wake()` is a best-effort check, so a waker cloned in a way the executor
+    /// doesn't recognize isn't counted either. If other futures of the same task can wake it,
+    /// re-polls caused by them are counted too.
+    pub fn unsolicited_repolls(&self) -> usize {
+        self.unsolicited_cnt
+    }
+
+    /// Panic when [unsolicited_repolls()](SinkMock::unsolicited_repolls) exceeds `threshold`.
+    pub fn panic_on_unsolicited_repolls(&mut self, threshold: usize) -> &mut Self {
+        self.unsolicited_limit = Some(threshold);
+        self
+    }
+
+    /// Record for every `poll_*()` call whether its waker [will_wake()](Waker::will_wake) the
+    /// waker of the previous call.
+    ///
+    /// Read the record with [waker_continuity()](SinkMock::waker_continuity). No waker is kept,
+    /// only the addresses [will_wake()](Waker::will_wake) compares are remembered for the next
+    /// call.
+    pub fn record_waker_continuity(&mut self) -> &mut Self {
+        self.continuity = Some((Vec::new(), None));
+        self
+    }
+
+    /// For every `poll_*()` call after the first one, `true` if its waker
+    /// [will_wake()](Waker::will_wake) the waker of the previous call.
+    ///
+    /// Some executors create a new waker for every poll, others reuse one. Together these allow
+    /// to check that code under test works either way. The result is best-effort: `will_wake()`
+    /// may return `false` for wakers waking the same task, e.g. when a combinator wraps the
+    /// waker. Since no waker is kept, `true` can also mean a new waker allocated at the address of
+    /// a dropped previous one, otherwise it means the same task. It's empty unless
+    /// [record_waker_continuity()](SinkMock::record_waker_continuity) was set.
+    ///
+    /// # Examples
+    /// ```
+    /// use futures::sink::Sink;
+    /// use futures_test_sink::{task::count_waker, SinkMock};
+    /// use std::{iter, pin::Pin, task::{Context, Poll}};
+    ///
+    /// let feedback = iter::repeat(Poll::Ready(Ok::<_, ()>(())));
+    /// let mut sink = SinkMock::<_, _, _, u8, _>::with_flush_feedback(feedback);
+    /// sink.record_waker_continuity();
+    /// let (first, _) = count_waker();
+    /// let (second, _) = count_waker();
+    /// for waker in [&first, &first.clone(), &second] {
+    ///     let mut cx = Context::from_waker(waker);
+    ///     let _ = Pin::new(&mut sink).poll_ready(&mut cx);
+    /// }
+    /// // a clone wakes the same task, another waker doesn't
+    /// assert_eq!(sink.waker_continuity(), [true, false]);
+    /// ```
+    pub fn waker_continuity(&self) -> &[bool] {
+        self.continuity
+            .as_ref()
+            .map_or(&[], |(continuity, _)| continuity)
+    }
+
+    /// How many times `Poll::Pending` was returned with a different waker than the previous
+    /// `Poll::Pending`.
+    ///
+    /// A task moved to another executor or a combinator polling with its own waker changes the
+    /// waker legally, but an unexpected change or none at all where one was expected can point
+    /// to a lost wakeup: the producer registers a waker that is never woken. Wakers are compared
+    /// with [will_wake()](Waker::will_wake), which is best-effort: it may return `false` for
+    /// wakers that wake the same task, so a count greater than zero isn't a bug by itself,
+    /// compare it with the count expected from the executor used in the test.
+    pub fn waker_changed_count(&self) -> usize {
+        self.waker_changed_cnt
+    }
+
+    fn wake_total(&self) -> usize {
+        self.counters.wakes_from_ready()
+            + self.counters.wakes_from_flush()
+            + self.counters.wakes_from_close()
+    }
+
+    /// Tokens left, `None` unless [rate_limited()](SinkMock::rate_limited) was set.
+    pub fn tokens(&self) -> Option<usize> {
+        self.rate_limit.as_ref().map(|rate| rate.tokens)
+    }
+
+    /// Called at the start of every `poll_*()` method.
+    ///
+    /// Counts unsolicited re-polls and refills the token bucket on every `every`th call.
+    fn polled(&mut self, cx: &mut Context<'_>) {
+        #[cfg(feature = "std")]
+        if let Some(reporter) = &self.lost_wakes {
+            reporter.polled(match self.polling {
+                Method::Ready => "poll_ready",
+                Method::Flush => "poll_flush",
+                Method::Close => "poll_close",
+                Method::Send => unreachable!("`start_send()` isn't polled"),
+            });
+        }
+        self.ack_waker.polled();
+        if let Some((waker, fired)) = self.unwoken.take() {
+            // not woken by a `WakeHandle` or a background thread either
+            if fired == self.waking.fired() && waker.will_wake(cx.waker()) {
+                self.unsolicited_cnt += 1;
+                if let Some(limit) = self.unsolicited_limit {
+                    if self.unsolicited_cnt > limit {
+                        panic!(
+                            "{} unsolicited re-polls, the task was polled again without being woken",
+                            self.unsolicited_cnt
+                        );
+                    }
+                }
+            }
+        }
+        self.wakes_before = self.wake_total();
+        let wakes = self.waking.polled();
+        self.counters.woke(self.polling, wakes);
+        if let Some((continuity, last)) = &mut self.continuity {
+            let token = WakerToken::of(cx.waker());
+            if let Some(last) = last.replace(token) {
+                continuity.push(last == token);
+            }
+        }
+        if let Some(rate) = &mut self.rate_limit {
+            rate.poll_cnt += 1;
+            if rate.poll_cnt.is_multiple_of(rate.every.get()) {
+                rate.tokens = rate.per_refill;
+            }
+        }
+    }
+
+    /// Simulate head-of-line blocking: item number `index` (counting items accepted by
+    /// `start_send()` from 0) gets stuck at the head of the buffer.
+    ///
+    /// Items before it are flushed normally. Once it's the first buffered item every flush,
+    /// including flushes done by `poll_ready()` and `poll_close()`, returns `Poll::Pending`
+    /// without consulting `flush_feedback`. The waker isn't woken then, it's stored and woken by
+    /// [release_block()](SinkMock::release_block). This tests how a producer behaves when a
+    /// single stuck message stalls the whole connection.
+    ///
+    /// # Examples
+    /// ```
+    /// use futures::{executor::block_on, FutureExt, SinkExt};
+    /// use futures_test_sink::SinkMock;
+    /// use std::{iter, task::Poll};
+    ///
+    /// let mut sink = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, ()>(()))));
+    /// sink.hol_block_at(1);
+    /// block_on(sink.feed(0u8)).unwrap();
+    /// block_on(sink.feed(1u8)).unwrap();
+    /// assert_eq!(sink.flush().now_or_never(), None);
+    /// assert!(sink.is_blocked());
+    ///
+    /// sink.release_block();
+    /// assert!(!sink.is_blocked());
+    /// assert_eq!(block_on(sink.flush()), Ok(()));
+    /// ```
+    pub fn hol_block_at(&mut self, index: usize) -> &mut Self {
+        self.hol_block = Some(index);
+        self
+    }
+
+    /// Unblock the item set by [hol_block_at()](SinkMock::hol_block_at) and wake the stalled flush.
+    pub fn release_block(&mut self) {
+        self.hol_block = None;
+        self.unwoken = None;
+        if let Some(waker) = self.hol_waker.take() {
+            waker.wake();
+        }
+    }
+
+    /// `true` when flushes stall because the blocked item is at the head of the buffer.
+    /// See [hol_block_at()](SinkMock::hol_block_at).
+    pub fn is_blocked(&self) -> bool {
+        self.item_cnt > 0 && Some(self.buffer_head()) == self.hol_block
+    }
+
+    /// Index of the first buffered item counting all items that entered the buffer.
+    fn buffer_head(&self) -> usize {
+        self.send_cnt - self.dropped_cnt - self.item_cnt
+    }
+
+    /// How many items are buffered, for [transactional()](SinkMock::transactional) sink it's how
+    /// many items the next commit would take.
+    pub fn commit_size(&self) -> usize {
+        self.item_cnt
+    }
+
+    /// How many `poll_flush()` calls returned `Poll::Ready(Ok(()))` without removing any item
+    /// from the buffer.
+    ///
+    /// Flushing an empty buffer is the usual case. A high count reveals a producer that flushes
+    /// needlessly, e.g. after every item or in a loop. Flushes done by `poll_ready()` and
+    /// `poll_close()` aren't counted. See also [assert_no_empty_flush()](SinkMock::assert_no_empty_flush)
+    /// which panics on the first flush with nothing sent.
+    ///
+    /// # Examples
+    /// ```
+    /// use futures::{executor::block_on, SinkExt};
+    /// use futures_test_sink::SinkMock;
+    /// use std::{iter, task::Poll};
+    ///
+    /// let mut sink = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, ()>(()))));
+    /// block_on(sink.send(1u8)).unwrap();
+    /// block_on(sink.flush()).unwrap();
+    /// block_on(sink.flush()).unwrap();
+    /// assert_eq!(sink.idle_flush_count(), 2);
+    /// ```
+    pub fn idle_flush_count(&self) -> usize {
+        self.idle_flush_cnt
+    }
+
+    /// Panic on any call after an error was returned by any method.
+    ///
+    /// An errored sink usually shouldn't be used again. This catches code that ignores errors
+    /// and keeps using the sink instead of discarding it.
+    pub fn fused_on_error(&mut self) -> &mut Self {
+        self.fused_on_error = true;
+        self
+    }
+
+    /// Accept item number `after_send` (counting accepted items from 0) but return `err` from
+    /// the next `poll_flush()`.
+    ///
+    /// This models a write that was buffered successfully but failed when it was flushed. The
+    /// error is returned before `flush_feedback` is consulted and the buffered items are kept. Since
+    /// `poll_ready()` and `poll_close()` flush, they can return the error as well.
+    pub fn deferred_error(&mut self, after_send: usize, err: E) -> &mut Self {
+        self.deferred_error = Some((after_send, err));
+        self
+    }
+
+    /// Fail the close handshake, e.g. a TLS `close_notify`, after all data was flushed.
+    ///
+    /// `poll_close()` flushes the buffer as usual and then returns `Poll::Ready(Err(err))`
+    /// without closing the sink. The error is latched: every later `poll_close()` flushes and
+    /// returns a clone of it again, other methods keep working. With
+    /// [fused_on_error()](SinkMock::fused_on_error) any call after the error panics instead.
+    ///
+    /// # Examples
+    /// ```
+    /// use futures::{executor::block_on, SinkExt};
+    /// use futures_test_sink::SinkMock;
+    /// use std::{iter, task::Poll};
+    ///
+    /// let mut sink = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok(()))));
+    /// sink.close_error("close_notify failed");
+    /// block_on(sink.feed(1u8)).unwrap();
+    /// assert_eq!(block_on(sink.close()), Err("close_notify failed"));
+    /// assert_eq!(sink.counters().buffered(), 0);
+    /// assert!(!sink.counters().is_closed());
+    /// assert_eq!(block_on(sink.close()), Err("close_notify failed"));
+    /// ```
+    pub fn close_error(&mut self, err: E) -> &mut Self
+    where
+        E: Clone,
+    {
+        self.close_error = Some((err, E::clone));
+        self
+    }
+}
+
+impl<FlushI, E, ReadyI, SendI, Item> SinkMock<FlushI, ReadyI, SendI, Item, E>
+where
+    FlushI: Iterator<Item = Poll<Result<(), E>>>,
+    // ReadyI accept iterator that can only return Error None.
+    // This because Poll::Ready(Ok(())) and Poll::Pending can be determined
+    // from inner implementation.
+    ReadyI: Iterator<Item = E>,
+    // Similar reason to ReadyI
+    SendI: Iterator<Item = E>,
+{
+    /// Create a new instance of SinkMock
+    ///
+    /// # Arguments
+    /// - **`flush_feedback`** - an iterator that represent reaction on flushing data to it. Every time
+    /// when `poll_flush()` is called the item from iterator is taken and unwrapped and depends on
+    /// result an special action is taken:
+    ///   - `Poll::Ready(Ok(()))` discard `flush_at_once` items from buffer. Repeat
+    ///   - `Poll::Ready(Err(e))` forward error
+    ///   - `Poll::Pending` wake up Waker from Context and return Poll::Pending
+    ///
+    /// - **`send_fallback`** - an iterator that represent when start `start_send()` called with `item: Item` should
+    /// return error. If iterator yield `iter_item` `start_send()` returns `Err(iter_item)` discarding `item`.
+    /// If iterator return `None` `item` is buffered.
+    ///
+    /// - **`ready_fallback`** - an iterator that represent when `poll_ready()` should return
+    /// error. If iterator yield `iter_item` `poll_ready()` returns `Err(iter_item)`. Otherwise
+    /// return `Poll::Ready(Ok(())` if can buffer next item else return result from calling
+    /// `poll_flush()`
+    ///
+    /// - **`max_item`** - how many item this sink can buffer
+    ///
+    /// - **`flush_at_once`** - how many item will be removed from buffer when `flush_feedback`
+    /// return `Poll::Ready(Ok(()))`. See [FlushAmount].
+    #[allow(clippy::doc_lazy_continuation)]
+    pub fn new(
+        flush_feedback: FlushI,
+        ready_fallback: ReadyI,
+        send_fallback: SendI,
+        max_item: usize,
+        flush_at_once: impl Into<FlushAmount>,
+    ) -> Self {
+        Self {
+            flush_feedback,
+            ready_fallback,
+            send_fallback,
+            max_item,
+            item_cnt: 0,
+            flush_at_once: flush_at_once.into(),
+            slow_start_cap: None,
+            frame: None,
+            min_batch: None,
+            is_closed: false,
+            can_start_send: false,
+            send_cnt: 0,
+            flush_feedback_consumed: 0,
+            just_became_full: false,
+            high_water_mark: 0,
+            ready_flushes: true,
+            transactional: false,
+            no_empty_flush: false,
+            flush_call_cnt: 0,
+            idle_flush_cnt: 0,
+            unflushed_close: None,
+            flush_ack_before_close: false,
+            unacked_cnt: 0,
+            spurious_every: None,
+            ready_ok_cnt: 0,
+            spurious_wake_cnt: 0,
+            drop_every: None,
+            dropped_cnt: 0,
+            max_frame: None,
+            reject_if: None,
+            rejections: None,
+            contiguous: None,
+            gaps: Vec::new(),
+            credits: None,
+            compression: None,
+            pipeline: None,
+            ack_waker: EventWaker::default(),
+            half_open: false,
+            capacity_schedule: None,
+            unwoken: None,
+            wakes_before: 0,
+            unsolicited_cnt: 0,
+            unsolicited_limit: None,
+            continuity: None,
+            close_mode: CloseMode::Graceful,
+            dropped_on_close: 0,
+            waking: Waking::default(),
+            pending_waker: None,
+            waker_changed_cnt: 0,
+            rate_limit: None,
+            hol_block: None,
+            hol_waker: None,
+            fused_on_error: false,
+            errored: false,
+            deferred_error: None,
+            flush_error: None,
+            flush_method_error: None,
+            close_method_error: None,
+            close_error: None,
+            #[cfg(feature = "failpoints")]
+            failpoint: None,
+            poll_log: None,
+            #[cfg(feature = "std")]
+            event_tx: None,
+            name: None,
+            polling: Method::Ready,
+            #[cfg(feature = "std")]
+            lost_wakes: None,
+            counters: MockCounters::new(),
+            last_error_clone: None,
+            item_type: Default::default(),
+        }
+    }
+}
+
+impl<FlushI, E, Item> SinkMock<FlushI, iter::Empty<E>, iter::Empty<E>, Item, E>
+where
+    FlushI: Iterator<Item = Poll<Result<(), E>>>,
+{
+    /// Create a sink that will never return `Error` on `start_send` and `poll_ready`.
+    ///
+    /// This method will use
+    pub fn with_flush_feedback(flush_feedback: FlushI) -> Self {
+        SinkMock::new(
+            flush_feedback,
+            iter::empty(),
+            iter::empty(),
+            DEFAULT_MAX_ITEM,
+            DEFAULT_FLUSH_AT_ONCE,
+        )
+    }
+
+    /// Create a sink that is always willing to buffer but slow to write.
+    ///
+    /// `poll_ready()` never flushes. It only checks capacity, which is unlimited unless changed
+    /// with [set_max_item()](SinkMock::set_max_item), and returns `Poll::Pending` when the
+    /// buffer is full. Only `poll_flush()` and `poll_close()` consult `flush_feedback`. This allows
+    /// to test buffering middleware without flushes hidden in `poll_ready()`.
+    ///
+    /// # Examples
+    /// ```
+    /// use futures::{executor::block_on, SinkExt};
+    /// use futures_test_sink::SinkMock;
+    /// use std::{iter, task::Poll};
+    ///
+    /// let flush_feedback = iter::repeat(Poll::Ready(Ok::<_, ()>(())));
+    /// let mut sink = SinkMock::buffer_fast_flush_slow(flush_feedback);
+    /// for item in 0..100u8 {
+    ///     block_on(sink.feed(item)).unwrap();
+    /// }
+    /// assert_eq!(sink.flush_feedback_consumed(), 0);
+    /// block_on(sink.flush()).unwrap();
+    /// assert_eq!(sink.high_water_mark(), 100);
+    /// ```
+    pub fn buffer_fast_flush_slow(flush_feedback: FlushI) -> Self {
+        let mut sink = SinkMock::new(
+            flush_feedback,
+            iter::empty(),
+            iter::empty(),
+            usize::MAX,
+            DEFAULT_FLUSH_AT_ONCE,
+        );
+        sink.ready_flushes = false;
+        sink
+    }
+
+    /// Create a sink committing all items at once on `poll_close()`.
+    ///
+    /// `poll_ready()` and `poll_flush()` always return `Poll::Ready(Ok(()))` and don't touch the
+    /// buffer, which is unlimited. `poll_close()` takes the next item of `commit_feedback`:
+    /// - `Poll::Ready(Ok(()))` commits the whole batch and closes the sink
+    /// - `Poll::Ready(Err(e))` fails the commit, the batch stays buffered and closing can be
+    ///   retried
+    /// - `Poll::Pending` wakes the waker like `flush_feedback` does
+    ///
+    /// This models an atomic batch like a database transaction. The number of items the next
+    /// commit would take is [commit_size()](SinkMock::commit_size).
+    ///
+    /// # Examples
+    /// ```
+    /// use futures::{executor::block_on, SinkExt};
+    /// use futures_test_sink::SinkMock;
+    /// use std::task::Poll;
+    ///
+    /// let commit_feedback = vec![Poll::Ready(Err("conflict")), Poll::Ready(Ok(()))];
+    /// let mut sink = SinkMock::transactional(commit_feedback.into_iter());
+    /// for item in 0..10u8 {
+    ///     block_on(sink.send(item)).unwrap();
+    /// }
+    /// assert_eq!(sink.commit_size(), 10);
+    /// assert_eq!(block_on(sink.close()), Err("conflict"));
+    /// assert_eq!(block_on(sink.close()), Ok(()));
+    /// assert_eq!(sink.commit_size(), 0);
+    /// ```
+    pub fn transactional(commit_feedback: FlushI) -> Self {
+        let mut sink = SinkMock::new(
+            commit_feedback,
+            iter::empty(),
+            iter::empty(),
+            usize::MAX,
+            FlushAmount::All,
+        );
+        sink.transactional = true;
+        sink
+    }
+}
+
+impl<E, Item> SinkMock<AlwaysReady<E>, iter::Empty<E>, option::IntoIter<E>, Item, E> {
+    /// Create a sink where every lifecycle method can fail with its own error.
+    ///
+    /// Each error is returned once:
+    /// - **`flush_err`** - by the first `poll_flush()` called directly. Flushes done inside
+    ///   `poll_ready()` and `poll_close()` don't return it.
+    /// - **`send_err`** - by the first `start_send()`. The item is discarded.
+    /// - **`close_err`** - by the first `poll_close()` after the buffer was flushed. The sink is not
+    ///   closed then.
+    ///
+    /// `None` means the method never fails. Otherwise the sink behaves like
+    /// [with_flush_feedback()](SinkMock::with_flush_feedback) with `flush_feedback` always
+    /// returning `Poll::Ready(Ok(()))`. This allows a test to check that errors from different
+    /// stages are handled differently.
+    ///
+    /// # Examples
+    /// ```
+    /// use futures::{executor::block_on, SinkExt};
+    /// use futures_test_sink::SinkMock;
+    ///
+    /// #[derive(Debug, PartialEq)]
+    /// enum Stage {
+    ///     Flush,
+    ///     Send,
+    ///     Close,
+    /// }
+    ///
+    /// let mut sink = SinkMock::with_method_errors(Some(Stage::Flush), None, Some(Stage::Close));
+    /// assert_eq!(block_on(sink.send(1u8)), Err(Stage::Flush));
+    /// assert_eq!(block_on(sink.send(2u8)), Ok(()));
+    /// assert_eq!(block_on(sink.close()), Err(Stage::Close));
+    /// assert_eq!(block_on(sink.close()), Ok(()));
+    /// ```
+    pub fn with_method_errors(
+        flush_err: Option<E>,
+        send_err: Option<E>,
+        close_err: Option<E>,
+    ) -> Self {
+        let mut sink = SinkMock::new(
+            always_ready(),
+            iter::empty(),
+            send_err.into_iter(),
+            DEFAULT_MAX_ITEM,
+            DEFAULT_FLUSH_AT_ONCE,
+        );
+        sink.flush_method_error = flush_err;
+        sink.close_method_error = close_err;
+        sink
+    }
+}
+
+impl<E, Item> SinkMock<AlwaysReady<E>, iter::Once<E>, iter::Empty<E>, Item, E> {
+    /// Create a sink where the first `poll_ready()` returns `Poll::Ready(Err(err))` and then the
+    /// sink recovers by itself.
+    ///
+    /// All following calls behave like [with_flush_feedback()](SinkMock::with_flush_feedback)
+    /// with `flush_feedback` always returning `Poll::Ready(Ok(()))`. This is the simplest scenario
+    /// to test retry of a transient error.
+    ///
+    /// # Examples
+    /// ```
+    /// use futures::{executor::block_on, SinkExt};
+    /// use futures_test_sink::SinkMock;
+    ///
+    /// let mut sink = SinkMock::transient_error_once("connection reset");
+    /// assert_eq!(block_on(sink.send(1u8)), Err("connection reset"));
+    /// // retry succeeds
+    /// assert_eq!(block_on(sink.send(1u8)), Ok(()));
+    /// ```
+    pub fn transient_error_once(err: E) -> Self {
+        SinkMock::new(
+            always_ready(),
+            iter::once(err),
+            iter::empty(),
+            DEFAULT_MAX_ITEM,
+            DEFAULT_FLUSH_AT_ONCE,
+        )
+    }
+}
+
+impl<E, Item> SinkMock<AlwaysReady<E>, iter::Empty<E>, iter::Empty<E>, Item, E> {
+    /// Create a sink whose capacity changes over time, e.g. under memory pressure.
+    ///
+    /// Every `poll_ready()` first takes the next value of `capacity` as the new `max_item`, also a
+    /// `poll_ready()` repeated after `Poll::Pending`, so every re-poll advances the schedule. When
+    /// `capacity` ends the last value stays. `capacity` must be `Send + Sync`, so the mock can
+    /// still be shared between threads. Flushes always succeed, like
+    /// [with_flush_feedback()](SinkMock::with_flush_feedback) with `flush_feedback` always
+    /// returning `Poll::Ready(Ok(()))`.
+    ///
+    /// When the capacity drops below the number of buffered items, the items are kept.
+    /// `poll_ready()` flushes them like with a full buffer before it accepts another item, and
+    /// [just_became_full()](SinkMock::just_became_full) isn't set for them.
+    ///
+    /// # Examples
+    /// ```
+    /// use futures_test_sink::{
+    ///     assert_sink_ready, assert_start_send_ok, task::noop_context, SinkMock,
+    /// };
+    ///
+    /// let mut sink = SinkMock::<_, _, _, u8, ()>::with_capacity_schedule(vec![4, 1].into_iter());
+    /// let mut cx = noop_context();
+    /// assert_sink_ready!(sink, cx);
+    /// assert_eq!(sink.capacity(), 4);
+    /// assert_start_send_ok!(sink, 1);
+    /// // the capacity dropped to 1, so the item is flushed first
+    /// assert_sink_ready!(sink, cx);
+    /// assert_eq!(sink.capacity(), 1);
+    /// assert_eq!(sink.counters().buffered(), 0);
+    /// ```
+    ///
+    /// # Panics
+    /// `poll_ready()` panics if `capacity` yields `0`.
+    pub fn with_capacity_schedule(
+        capacity: impl Iterator<Item = usize> + Send + Sync + 'static,
+    ) -> Self {
+        let mut sink = SinkMock::new(
+            always_ready(),
+            iter::empty(),
+            iter::empty(),
+            DEFAULT_MAX_ITEM,
+            DEFAULT_FLUSH_AT_ONCE,
+        );
+        sink.capacity_schedule = Some(Box::new(capacity));
+        sink
+    }
+}
+
+impl<E: Clone, Item> SinkMock<Flaky<E>, iter::Empty<E>, iter::Empty<E>, Item, E> {
+    /// Create a sink where every flush fails with a clone of `err` with probability `error_prob`.
+    ///
+    /// The decision is made by a deterministic generator seeded with `seed`, so a failing
+    /// randomized test is reproduced by running it with the same seed. Every consultation of
+    /// `flush_feedback` counts as a flush, including flushes done by `poll_ready()` and
+    /// `poll_close()`. Otherwise the sink behaves like
+    /// [with_flush_feedback()](SinkMock::with_flush_feedback).
+    ///
+    /// # Panics
+    /// If `error_prob` isn't in `0.0..=1.0`.
+    ///
+    /// # Examples
+    /// ```
+    /// use futures::{executor::block_on, SinkExt};
+    /// use futures_test_sink::SinkMock;
+    ///
+    /// let mut sink = SinkMock::flaky(1234, 0.5, "timeout");
+    /// let mut failed = 0;
+    /// for item in 0..20u8 {
+    ///     if block_on(sink.send(item)).is_err() {
+    ///         failed += 1;
+    ///     }
+    /// }
+    /// assert!(failed > 0);
+    /// assert!(sink.injected_errors() >= failed);
+    /// ```
+    pub fn flaky(seed: u64, error_prob: f64, err: E) -> Self {
+        SinkMock::new(
+            Flaky::new(seed, error_prob, err),
+            iter::empty(),
+            iter::empty(),
+            DEFAULT_MAX_ITEM,
+            DEFAULT_FLUSH_AT_ONCE,
+        )
+    }
+
+    /// How many errors were injected. See [flaky()](SinkMock::flaky).
+    pub fn injected_errors(&self) -> usize {
+        self.flush_feedback.error_count()
+    }
+}
+
+impl<E, Item> SinkMock<Steps<E>, iter::Empty<E>, iter::Empty<E>, Item, E> {
+    /// Create a sink following a script of flush outcomes, the easiest way to script a sink.
+    ///
+    /// Every flush takes the next [Step]: every `poll_flush()` and `poll_close()` and
+    /// `poll_ready()` when the buffer is full, which is after 3 items unless changed with
+    /// [set_max_item()](SinkMock::set_max_item). `start_send()` never fails and takes no step.
+    ///
+    /// - [Step::Ready] flushes all buffered items and the method returns `Poll::Ready(Ok(()))`,
+    ///   `poll_close()` closes the sink.
+    /// - [Step::Pending] wakes the waker and returns `Poll::Pending`.
+    /// - [Step::WouldBlock] is [Step::Ready] counted by
+    ///   [would_block_count()](SinkMock::would_block_count).
+    /// - [Step::Err] returns the error. The items stay buffered and a failed `poll_close()` leaves
+    ///   the sink open, so the next flush or close takes the next step and retries them. Use
+    ///   [fused_on_error()](SinkMock::fused_on_error) to forbid that.
+    ///
+    /// # Panics
+    /// When a flush is needed after the last step.
+    ///
+    /// # Examples
+    /// ```
+    /// use futures::{executor::block_on, SinkExt};
+    /// use futures_test_sink::{SinkMock, Step};
+    ///
+    /// let mut sink = SinkMock::scripted(vec![
+    ///     Step::Pending,
+    ///     Step::Err("connection reset"),
+    ///     Step::Ready, // retried flush
+    ///     Step::Ready, // close
+    /// ]);
+    /// assert_eq!(block_on(sink.send(1u8)), Err("connection reset"));
+    /// assert_eq!(sink.counters().buffered(), 1);
+    /// assert_eq!(block_on(sink.flush()), Ok(()));
+    /// assert_eq!(sink.counters().buffered(), 0);
+    /// assert_eq!(block_on(sink.close()), Ok(()));
+    /// assert!(sink.steps().remaining().is_empty());
+    /// ```
+    pub fn scripted(steps: Vec<Step<E>>) -> Self {
+        let mut sink = SinkMock::new(
+            Steps::new(steps),
+            iter::empty(),
+            iter::empty(),
+            DEFAULT_MAX_ITEM,
+            DEFAULT_FLUSH_AT_ONCE,
+        );
+        sink.flush_at_once = FlushAmount::All;
+        sink
+    }
+
+    /// The script of [scripted()](SinkMock::scripted).
+    pub fn steps(&self) -> &Steps<E> {
+        &self.flush_feedback
+    }
+
+    /// How many flushes returned `Poll::Ready(Ok(()))` for a [Step::WouldBlock].
+    pub fn would_block_count(&self) -> usize {
+        self.flush_feedback.would_block_count()
+    }
+}
+
+impl<FlushI, ReadyI, SendI, Item, E> SinkMock<FlushI, ReadyI, SendI, Item, E>
+where
+    FlushI: Iterator<Item = Poll<Result<(), E>>>,
+{
+    fn flush_inner(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), E>> {
+        self.can_start_send = false;
+        if let Some(e) = self.flush_error.take() {
+            return Poll::Ready(Err(e));
+        }
+        if self.half_open {
+            // ACKs never arrive, so there's nobody to wake the task
+            return Poll::Pending;
+        }
+        // we can think about it like an I/O that returned it was able to take items.
+        // (And how many - `flush_at_once` parameter)
+        loop {
+            if self.is_blocked() {
+                self.hol_waker = Some(cx.waker().clone());
+                return Poll::Pending;
+            }
+            let feedback = match self.flush_feedback.next() {
+                Some(feedback) => feedback,
+                None => panic!(
+                    "Unexpected end of `flush_feedback` iterator after {} items!",
+                    self.flush_feedback_consumed
+                ),
+            };
+            self.flush_feedback_consumed += 1;
+            match feedback {
+                // mocked I/O took `flush_at_once` buffered items.
+                Poll::Ready(Ok(())) => {
+                    let amount = match self.frame {
+                        Some(frame) if self.item_cnt >= frame.get() => frame.get(),
+                        Some(_) => 0,
+                        None => self.flush_at_once.next(self.item_cnt),
+                    };
+                    // the blocked item stays at the head of the buffer
+                    let amount = match self.hol_block {
+                        Some(index) if index >= self.buffer_head() => {
+                            amount.min(index - self.buffer_head())
+                        }
+                        _ => amount,
+                    };
+                    let drained = amount.min(self.item_cnt);
+                    self.item_cnt -= drained;
+                    if let Some((ratio, frames)) = &mut self.compression {
+                        *frames += drained.div_ceil(ratio.get());
+                    }
+                    if let Some((buffered, _)) = &mut self.unflushed_close {
+                        buffered.drain(..drained);
+                    }
+                    if let Some(credits) = &mut self.credits {
+                        *credits += drained;
+                    }
+                    self.just_became_full = false;
+                    if let (Some(cap), FlushAmount::Fixed(n)) =
+                        (self.slow_start_cap, &mut self.flush_at_once)
+                    {
+                        *n = n.saturating_mul(NonZeroUsize::new(2).unwrap()).min(cap);
+                    }
+                    // a partial frame is never flushed
+                    if self.item_cnt < self.frame.map_or(1, NonZeroUsize::get) {
+                        return Poll::Ready(Ok(()));
+                    }
+                }
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return self.pending(cx),
+            }
+        }
+    }
+}
+
+impl<FlushI, ReadyI, SendI, Item, E> SinkMock<FlushI, ReadyI, SendI, Item, E>
+where
+    FlushI: Iterator<Item = Poll<Result<(), E>>>,
+    ReadyI: Iterator<Item = E>,
+    SendI: Iterator<Item = E>,
+{
+    fn ready_mock(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), E>> {
+        self.can_start_send = false;
+        if let Some(capacity) = self.capacity_schedule.as_mut().and_then(Iterator::next) {
+            self.max_item = non_zero(capacity, "capacity").get();
+        }
+        #[cfg(feature = "failpoints")]
+        match self.fault() {
+            Some(Fault::ReadyErr(e)) => return Poll::Ready(Err(e)),
+            Some(Fault::Pending) => return self.pending(cx),
+            _ => (),
+        }
+        if let Some(e) = self.ready_fallback.next() {
+            return Poll::Ready(Err(e));
+        }
+
+        if let Some(pipeline) = &mut self.pipeline {
+            if pipeline.in_flight >= pipeline.window {
+                self.ack_waker.register(cx.waker());
+                return Poll::Pending;
+            }
+        }
+        if self.credits == Some(0) || self.tokens() == Some(0) {
+            self.pending(cx)
+        } else if self.max_item > self.item_cnt {
+            self.can_start_send = true;
+            Poll::Ready(Ok(()))
+        } else if !self.ready_flushes {
+            self.pending(cx)
+        } else {
+            match self.flush_inner(cx) {
+                Poll::Ready(Ok(())) => {
+                    self.can_start_send = true;
+                    Poll::Ready(Ok(()))
+                }
+                forward => forward,
+            }
+        }
+    }
+
+    fn flush_mock(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), E>> {
+        self.flush_call_cnt += 1;
+        if self.no_empty_flush && self.item_cnt == 0 {
+            panic!(
+                "`poll_flush()` call #{} with nothing sent since the last flush",
+                self.flush_call_cnt
+            );
+        }
+        #[cfg(feature = "failpoints")]
+        match self.fault() {
+            Some(Fault::FlushErr(e)) => {
+                self.can_start_send = false;
+                return Poll::Ready(Err(e));
+            }
+            Some(Fault::Pending) => {
+                self.can_start_send = false;
+                return self.pending(cx);
+            }
+            _ => (),
+        }
+        if let Some(e) = self.flush_method_error.take() {
+            self.can_start_send = false;
+            return Poll::Ready(Err(e));
+        }
+        if self.transactional {
+            self.can_start_send = false;
+            return Poll::Ready(Ok(()));
+        }
+        // an empty buffer isn't held back by min_batch
+        if self.item_cnt > 0 && !self.reached_min_batch() {
+            self.can_start_send = false;
+            return self.pending(cx);
+        }
+        let buffered = self.item_cnt;
+        let poll = self.flush_inner(cx);
+        if let Poll::Ready(Ok(())) = poll {
+            self.unacked_cnt = 0;
+            if self.item_cnt == buffered {
+                self.idle_flush_cnt += 1;
+            }
+        }
+        poll
+    }
+
+    fn close_mock(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), E>> {
+        if let Some((buffered, _)) = &self.unflushed_close {
+            if !buffered.is_empty() {
+                panic!(
+                    "`poll_close()` called with {} unflushed items: [{}]",
+                    buffered.len(),
+                    buffered
+                        .iter()
+                        .map(String::as_str)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+            }
+        }
+        if self.flush_ack_before_close && self.unacked_cnt > 0 {
+            panic!(
+                "`poll_close()` called before `poll_flush()` acknowledged {} sent items",
+                self.unacked_cnt
+            );
+        }
+        #[cfg(feature = "failpoints")]
+        match self.fault() {
+            Some(Fault::CloseErr(e)) => return Poll::Ready(Err(e)),
+            Some(Fault::Pending) => return self.pending(cx),
+            _ => (),
+        }
+        match self.close_mode {
+            CloseMode::Graceful => ready!(self.flush_inner(cx))?,
+            CloseMode::Abrupt => {
+                self.dropped_on_close += self.item_cnt;
+                self.item_cnt = 0;
+                if let Some((buffered, _)) = &mut self.unflushed_close {
+                    buffered.clear();
+                }
+            }
+        }
+        if let Some(e) = self.close_method_error.take() {
+            return Poll::Ready(Err(e));
+        }
+        if let Some((e, clone)) = &self.close_error {
+            return Poll::Ready(Err(clone(e)));
+        }
+        self.is_closed = true;
+        Poll::Ready(Ok(()))
+    }
+
+    fn send_mock(&mut self, item: &Item) -> Result<(), E> {
+        #[cfg(feature = "failpoints")]
+        if let Some(Fault::SendErr(e)) = self.fault() {
+            return Err(e);
+        }
+        if let Some(e) = self.send_fallback.next() {
+            return Err(e);
+        }
+
+        if let Some((after_send, _)) = self.deferred_error {
+            if after_send == self.send_cnt {
+                self.flush_error = self.deferred_error.take().map(|(_, e)| e);
+            }
+        }
+        self.send_cnt += 1;
+        self.unacked_cnt += 1;
+        if let Some(credits) = &mut self.credits {
+            *credits = credits.saturating_sub(1);
+        }
+        if let Some(pipeline) = &mut self.pipeline {
+            pipeline.in_flight += 1;
+        }
+        if let Some(rate) = &mut self.rate_limit {
+            rate.tokens = rate.tokens.saturating_sub(1);
+        }
+        if let Some(every) = self.drop_every {
+            if self.send_cnt.is_multiple_of(every.get()) {
+                self.dropped_cnt += 1;
+                return Ok(());
+            }
+        }
+        self.item_cnt += 1;
+        if let Some((buffered, debug)) = &mut self.unflushed_close {
+            buffered.push_back(debug(item));
+        }
+        self.just_became_full = self.item_cnt == self.max_item;
+        self.high_water_mark = self.high_water_mark.max(self.item_cnt);
+        Ok(())
+    }
+
+    /// Called with every result of `poll_ready()`, `poll_flush()` and `poll_close()`.
+    fn returned(&mut self, poll: Poll<Result<(), E>>, cx: &mut Context<'_>) -> Poll<Result<(), E>> {
+        if poll.is_ready() {
+            let wakes = self.waking.ready(cx.waker());
+            self.counters.woke(self.polling, wakes);
+        }
+        if let Poll::Ready(Err(e)) = &poll {
+            self.errored |= self.fused_on_error;
+            self.count_error(self.polling, e);
+        }
+        if let Some((log, clone)) = &mut self.poll_log {
+            log.push(match &poll {
+                Poll::Ready(Ok(())) => Poll::Ready(Ok(())),
+                Poll::Ready(Err(e)) => Poll::Ready(Err(clone(e))),
+                Poll::Pending => Poll::Pending,
+            });
+        }
+        if poll.is_pending() {
+            self.counters.pending();
+            if self.wake_total() == self.wakes_before {
+                self.unwoken = Some((cx.waker().clone(), self.waking.fired()));
+            }
+            match &self.pending_waker {
+                Some(last) if last.will_wake(cx.waker()) => (),
+                Some(_) => {
+                    self.waker_changed_cnt += 1;
+                    self.pending_waker = Some(cx.waker().clone());
+                }
+                None => self.pending_waker = Some(cx.waker().clone()),
+            }
+        }
+        self.counters
+            .progress(self.send_cnt, self.item_cnt, self.is_closed);
+        if let (Poll::Ready(Ok(())), Some(every)) = (&poll, self.spurious_every) {
+            self.ready_ok_cnt += 1;
+            if self.ready_ok_cnt.is_multiple_of(every.get()) {
+                self.spurious_wake_cnt += 1;
+                self.counters.woke(self.polling, 1);
+                self.waking.wake(cx.waker());
+            }
+        }
+        #[cfg(feature = "std")]
+        if let Some(reporter) = &self.lost_wakes {
+            reporter.returned(poll.is_ready());
+        }
+        poll
+    }
+}
+
+impl<Item, FlushI, ReadyI, SendI, E> Sink<Item> for SinkMock<FlushI, ReadyI, SendI, Item, E>
+where
+    FlushI: Iterator<Item = Poll<Result<(), E>>>,
+    // ReadyI accept iterator that can only return Error None.
+    // This because Poll::Ready(Ok(())) and Poll::Pending can be determined
+    // from inner implementation.
+    ReadyI: Iterator<Item = E>,
+    // Similar reason to ReadyI
+    SendI: Iterator<Item = E>,
+{
+    type Error = E;
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.check_panic();
+        let this = Pin::into_inner(self);
+        this.polling = Method::Ready;
+        this.polled(cx);
+        let poll = this.ready_mock(cx);
+        let poll = this.returned(poll, cx);
+        #[cfg(feature = "std")]
+        this.emit(Event::Ready, ready_result(&poll));
+        poll_event!(this, "poll_ready", &poll);
+        #[cfg(feature = "std")]
+        this.waking.returned();
+        poll
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Item) -> Result<(), Self::Error> {
+        self.check_panic();
+
+        if !self.can_start_send {
+            panic!("`start_send()` called without correct call of `poll_ready()`");
+        }
+
+        let this = Pin::into_inner(self);
+        // set again by `send_mock()` if the item fills the buffer
+        this.just_became_full = false;
+        let res = match this.oversized(&item).or_else(|| this.refused(&item)) {
+            Some(e) => Err(e),
+            None => this.send_mock(&item),
+        };
+        if res.is_ok() {
+            this.sequenced(&item);
+        }
+        if let Err(e) = &res {
+            this.errored |= this.fused_on_error;
+            this.count_error(Method::Send, e);
+            if let Some((rejections, clone_item, clone_err)) = &mut this.rejections {
+                rejections.push((clone_item(&item), clone_err(e)));
+            }
+        }
+        this.counters
+            .progress(this.send_cnt, this.item_cnt, this.is_closed);
+        #[cfg(feature = "std")]
+        this.emit(Event::Send(()), Some(&res));
+        debug_event!(
+            target: "sink_mock::start_send",
+            mock = this.name.as_deref(),
+            result = crate::events::send_outcome(&res),
+            buffered = this.item_cnt
+        );
+        res
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.check_panic();
+        let this = Pin::into_inner(self);
+        this.polling = Method::Flush;
+        this.polled(cx);
+        let poll = this.flush_mock(cx);
+        let poll = this.returned(poll, cx);
+        #[cfg(feature = "std")]
+        this.emit(Event::Flush, ready_result(&poll));
+        poll_event!(this, "poll_flush", &poll);
+        #[cfg(feature = "std")]
+        this.waking.returned();
+        poll
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.check_panic();
+        let this = Pin::into_inner(self);
+        this.polling = Method::Close;
+        this.polled(cx);
+        let poll = this.close_mock(cx);
+        let poll = this.returned(poll, cx);
+        #[cfg(feature = "std")]
+        this.emit(Event::Close, ready_result(&poll));
+        poll_event!(this, "poll_close", &poll);
+        #[cfg(feature = "std")]
+        this.waking.returned();
+        poll
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PollClock, WakePolicy, WakeStyle, WakeTarget, WakeWhen};
+    use async_task::waker_fn;
+    use futures::{
+        never::Never,
+        stream::{self, StreamExt},
+        SinkExt,
+    };
+    use std::sync::{atomic, Arc};
+
+    #[test]
+    #[should_panic(expected = "`start_send()` called without correct call of `poll_ready()`")]
+    fn panic_no_call_poll_ready_test() {
+        let e = iter::empty::<Poll<Result<(), Never>>>();
+        let mut s = SinkMock::with_flush_feedback(e);
+        Pin::new(&mut s).start_send(1).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "Trying use closed sink")]
+    fn panic_after_close() {
+        // create a Context
+        let waker = waker_fn(move || {});
+        let mut cx = Context::from_waker(&waker);
+        let e = iter::once::<Poll<Result<(), Never>>>(Poll::Ready(Ok(())));
+        let mut s = SinkMock::with_flush_feedback(e);
+        let _ = Pin::new(&mut s).poll_close(&mut cx);
+        let _ = Pin::new(&mut s).poll_ready(&mut cx);
+        // just to inform compiler about Item type.
+        let _ = Pin::new(&mut s).start_send(1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Unexpected end of `flush_feedback` iterator after 1 items!")]
+    fn panic_when_flus_feedback_ends() {
+        let waker = waker_fn(move || {});
+        let mut cx = Context::from_waker(&waker);
+        let e = iter::once::<Poll<Result<(), Never>>>(Poll::Ready(Ok(())));
+        let mut s = SinkMock::with_flush_feedback(e);
+        let _ = Pin::new(&mut s).poll_flush(&mut cx);
+        // this will panic
+        let _ = Pin::new(&mut s).poll_flush(&mut cx);
+        // just to inform compiler about Item type.
+        let _ = Pin::new(&mut s).start_send(1);
+    }
+
+    #[test]
+    #[allow(clippy::useless_conversion)]
     fn drain_test() {
         let e = iter::repeat::<Poll<Result<(), Never>>>(Poll::Ready(Ok(())));
         let sink = SinkMock::with_flush_feedback(e);
 
-        let stream =
-            stream::iter(vec![Ok::<u8, Never>(5u8), Ok(7), Ok(9), Ok(77), Ok(79)].into_iter());
-        let send_all = stream.forward(sink);
-        assert_eq!(Ok(()), futures::executor::block_on(send_all));
+        let stream =
+            stream::iter(vec![Ok::<u8, Never>(5u8), Ok(7), Ok(9), Ok(77), Ok(79)].into_iter());
+        let send_all = stream.forward(sink);
+        assert_eq!(Ok(()), futures::executor::block_on(send_all));
+    }
+
+    #[test]
+    #[allow(clippy::useless_conversion)]
+    fn interleave_pending() {
+        let e = vec![Poll::Ready(Ok::<_, Never>(())), Poll::Pending]
+            .into_iter()
+            .cycle();
+        let sink = SinkMock::with_flush_feedback(e);
+
+        let stream =
+            stream::iter(vec![Ok::<u8, Never>(5u8), Ok(7), Ok(9), Ok(77), Ok(79)].into_iter());
+        let send_all = stream.forward(sink);
+        assert_eq!(Ok(()), futures::executor::block_on(send_all));
+    }
+
+    #[test]
+    #[allow(clippy::useless_conversion)]
+    fn error() {
+        let e = vec![Poll::Ready(Ok(())), Poll::Pending, Poll::Ready(Err(()))]
+            .into_iter()
+            .cycle();
+        let sink = SinkMock::with_flush_feedback(e);
+
+        let stream = stream::iter(vec![Ok(5u8), Ok(7), Ok(9), Ok(77), Ok(79)].into_iter());
+        let send_all = stream.forward(sink);
+        assert_eq!(Err(()), futures::executor::block_on(send_all));
+    }
+
+    #[test]
+    fn deferred_error() {
+        let waker = waker_fn(move || {});
+        let mut cx = Context::from_waker(&waker);
+        let e = iter::repeat(Poll::Ready(Ok(())));
+        let mut s = SinkMock::with_flush_feedback(e);
+        s.deferred_error(1, "write failed");
+
+        for item in 0..2 {
+            assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+            // send with the deferred error succeeds
+            assert_eq!(Pin::new(&mut s).start_send(item), Ok(()));
+        }
+        assert_eq!(
+            Pin::new(&mut s).poll_flush(&mut cx),
+            Poll::Ready(Err("write failed"))
+        );
+        // error is returned only once
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Ready(Ok(())));
+    }
+
+    #[test]
+    fn deferred_error_forward() {
+        let e = iter::repeat(Poll::Ready(Ok(())));
+        let mut sink = SinkMock::with_flush_feedback(e);
+        sink.deferred_error(3, ());
+
+        let stream = stream::iter(vec![Ok(5u8), Ok(7), Ok(9), Ok(77), Ok(79)]);
+        let send_all = stream.forward(sink);
+        assert_eq!(Err(()), futures::executor::block_on(send_all));
+    }
+
+    #[test]
+    fn flush_feedback_consumed() {
+        let waker = waker_fn(move || {});
+        let mut cx = Context::from_waker(&waker);
+        let e = vec![Poll::Ready(Ok::<_, Never>(())), Poll::Pending]
+            .into_iter()
+            .cycle();
+        let mut s = SinkMock::with_flush_feedback(e);
+        for item in 0..3 {
+            assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+            assert_eq!(Pin::new(&mut s).start_send(item), Ok(()));
+        }
+        assert_eq!(s.flush_feedback_consumed(), 0);
+        // Ready(Ok) flush 2 items, then Pending
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Pending);
+        assert_eq!(s.flush_feedback_consumed(), 2);
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(s.flush_feedback_consumed(), 3);
+    }
+
+    #[test]
+    fn borrowed_items() {
+        let waker = waker_fn(move || {});
+        let mut cx = Context::from_waker(&waker);
+        let data = String::from("one two three four");
+
+        let e = iter::repeat(Poll::Ready(Ok::<_, Never>(())));
+        let mut s = SinkMock::with_flush_feedback(e);
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(Pin::new(&mut s).start_send(&data[..3]), Ok(()));
+
+        let mut stream = stream::iter(data.split(' ').map(Ok));
+        let send_all = s.send_all(&mut stream);
+        assert_eq!(Ok(()), futures::executor::block_on(send_all));
+    }
+
+    #[test]
+    fn just_became_full() {
+        let waker = waker_fn(move || {});
+        let mut cx = Context::from_waker(&waker);
+        let e = iter::repeat(Poll::Ready(Ok::<_, Never>(())));
+        let mut s = SinkMock::with_flush_feedback(e);
+        assert!(!s.just_became_full());
+
+        for item in 0..2 {
+            assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+            assert_eq!(Pin::new(&mut s).start_send(item), Ok(()));
+            assert!(!s.just_became_full());
+        }
+        // third item hits `max_item`
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(Pin::new(&mut s).start_send(2), Ok(()));
+        assert!(s.just_became_full());
+
+        // `poll_ready()` has to flush before next item
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        assert!(!s.just_became_full());
+        assert_eq!(Pin::new(&mut s).start_send(3), Ok(()));
+        assert!(!s.just_became_full());
+    }
+
+    #[test]
+    fn just_became_full_reset_by_refused_and_dropped_items() {
+        let mut cx = crate::task::noop_context();
+        // a grown capacity doesn't flush, so only `start_send()` resets the flag
+        let schedule = vec![1, 5, 2, 5].into_iter();
+        let mut s = SinkMock::<_, _, _, u8, &str>::with_capacity_schedule(schedule);
+        s.reject_if(|&item| item == 0, "refused").lossy(2);
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(Pin::new(&mut s).start_send(1), Ok(()));
+        assert!(s.just_became_full());
+        // dropped by `lossy()`
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(Pin::new(&mut s).start_send(2), Ok(()));
+        assert_eq!(s.dropped_count(), 1);
+        assert!(!s.just_became_full());
+
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(Pin::new(&mut s).start_send(3), Ok(()));
+        assert!(s.just_became_full());
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(Pin::new(&mut s).start_send(0), Err("refused"));
+        assert!(!s.just_became_full());
+    }
+
+    #[test]
+    fn method_errors() {
+        let waker = waker_fn(move || {});
+        let mut cx = Context::from_waker(&waker);
+        let mut s = SinkMock::with_method_errors(Some("flush"), Some("send"), Some("close"));
+        // the refused item isn't buffered, the rest fills the buffer
+        for item in 0..=DEFAULT_MAX_ITEM {
+            assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+            let expected = if item == 0 { Err("send") } else { Ok(()) };
+            assert_eq!(Pin::new(&mut s).start_send(item), expected);
+        }
+        assert_eq!(s.counters().buffered(), DEFAULT_MAX_ITEM);
+        // `poll_ready()` flush a full buffer, but doesn't return flush error
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(s.counters().buffered(), 0);
+        assert_eq!(Pin::new(&mut s).start_send(4), Ok(()));
+        assert_eq!(
+            Pin::new(&mut s).poll_flush(&mut cx),
+            Poll::Ready(Err("flush"))
+        );
+        assert_eq!(
+            Pin::new(&mut s).poll_close(&mut cx),
+            Poll::Ready(Err("close"))
+        );
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(Pin::new(&mut s).poll_close(&mut cx), Poll::Ready(Ok(())));
+    }
+
+    #[test]
+    fn method_errors_forward() {
+        type Case = (Option<u8>, Option<u8>, Option<u8>, Result<(), u8>);
+        let table: Vec<Case> = vec![
+            (None, None, None, Ok(())),
+            (Some(1), None, None, Err(1)),
+            (None, Some(2), None, Err(2)),
+            (None, None, Some(3), Err(3)),
+            (None, Some(2), Some(3), Err(2)),
+        ];
+        for (flush, send, close, expected) in table {
+            let mut sink = SinkMock::with_method_errors(flush, send, close);
+            let mut stream = stream::iter(vec![Ok(5u8), Ok(7), Ok(9)]);
+            let res = futures::executor::block_on(async {
+                sink.send_all(&mut stream).await?;
+                sink.close().await
+            });
+            assert_eq!(res, expected);
+        }
+    }
+
+    #[test]
+    fn high_water_mark() {
+        let waker = waker_fn(move || {});
+        let mut cx = Context::from_waker(&waker);
+        let e = iter::repeat(Poll::Ready(Ok::<_, Never>(())));
+        let mut s = SinkMock::with_flush_feedback(e);
+        s.set_max_item(5);
+        assert_eq!(s.high_water_mark(), 0);
+
+        for item in 0..4 {
+            assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+            assert_eq!(Pin::new(&mut s).start_send(item), Ok(()));
+        }
+        assert_eq!(s.high_water_mark(), 4);
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(Pin::new(&mut s).start_send(4), Ok(()));
+        // doesn't decrease after flush
+        assert_eq!(s.high_water_mark(), 4);
+    }
+
+    #[test]
+    fn spurious_wakes() {
+        let wake_cnt = Arc::new(atomic::AtomicUsize::new(0));
+        let cnt = wake_cnt.clone();
+        let waker = waker_fn(move || {
+            wake_cnt.fetch_add(1, atomic::Ordering::SeqCst);
+        });
+        let mut cx = Context::from_waker(&waker);
+        let e = iter::repeat(Poll::Ready(Ok::<_, Never>(())));
+        let mut s = SinkMock::with_flush_feedback(e);
+        s.spurious_wakes(2);
+
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(0, cnt.load(atomic::Ordering::SeqCst));
+        assert_eq!(Pin::new(&mut s).start_send(1), Ok(()));
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(1, cnt.load(atomic::Ordering::SeqCst));
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(Pin::new(&mut s).poll_close(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(2, cnt.load(atomic::Ordering::SeqCst));
+        assert_eq!(s.spurious_wake_count(), 2);
+    }
+
+    #[test]
+    fn spurious_wakes_forward() {
+        let e = vec![Poll::Ready(Ok::<_, Never>(())), Poll::Pending]
+            .into_iter()
+            .cycle();
+        let mut sink = SinkMock::with_flush_feedback(e);
+        sink.spurious_wakes(1);
+
+        let stream = stream::iter(vec![Ok(5u8), Ok(7), Ok(9), Ok(77), Ok(79)]);
+        let send_all = stream.forward(&mut sink);
+        assert_eq!(Ok(()), futures::executor::block_on(send_all));
+        assert!(sink.spurious_wake_count() >= 5);
+    }
+
+    #[test]
+    fn slow_start() {
+        let waker = waker_fn(move || {});
+        let mut cx = Context::from_waker(&waker);
+        let e = iter::repeat(Poll::Ready(Ok::<_, Never>(())));
+        let mut s = SinkMock::with_flush_feedback(e);
+        s.set_max_item(100).slow_start(6);
+        assert_eq!(s.flush_at_once().fixed(), Some(1));
+
+        for item in 0..20 {
+            assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+            assert_eq!(Pin::new(&mut s).start_send(item), Ok(()));
+        }
+        let mut amounts = vec![s.flush_at_once().fixed()];
+        // flush 1 + 2 + 4 + 6 + 6 + 6 items
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(s.flush_feedback_consumed(), 6);
+        amounts.push(s.flush_at_once().fixed());
+        assert_eq!(amounts, vec![Some(1), Some(6)]);
+    }
+
+    #[test]
+    fn slow_start_doubling_sequence() {
+        let waker = waker_fn(move || {});
+        let mut cx = Context::from_waker(&waker);
+        // every flush is interrupted by `Poll::Pending`
+        let e = vec![Poll::Ready(Ok::<_, Never>(())), Poll::Pending]
+            .into_iter()
+            .cycle();
+        let mut s = SinkMock::with_flush_feedback(e);
+        s.set_max_item(100).slow_start(10);
+        for item in 0..40 {
+            assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+            assert_eq!(Pin::new(&mut s).start_send(item), Ok(()));
+        }
+        let mut amounts = Vec::new();
+        while Pin::new(&mut s).poll_flush(&mut cx).is_pending() {
+            amounts.push(s.flush_at_once().fixed().unwrap());
+        }
+        assert_eq!(amounts, vec![2, 4, 8, 10, 10, 10]);
+    }
+
+    #[test]
+    fn transient_error_once() {
+        let waker = waker_fn(move || {});
+        let mut cx = Context::from_waker(&waker);
+        let mut s = SinkMock::transient_error_once(7u8);
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Err(7)));
+        for item in 0..10 {
+            assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+            assert_eq!(Pin::new(&mut s).start_send(item), Ok(()));
+        }
+        assert_eq!(Pin::new(&mut s).poll_close(&mut cx), Poll::Ready(Ok(())));
+    }
+
+    #[test]
+    fn transient_error_once_retry() {
+        let mut sink = SinkMock::transient_error_once(());
+        let mut attempts = 0;
+        let res = futures::executor::block_on(async {
+            loop {
+                attempts += 1;
+                let mut stream = stream::iter(vec![Ok(5u8), Ok(7), Ok(9)]);
+                match sink.send_all(&mut stream).await {
+                    Ok(()) => break sink.close().await,
+                    Err(()) if attempts < 3 => continue,
+                    Err(e) => break Err(e),
+                }
+            }
+        });
+        assert_eq!(res, Ok(()));
+        assert_eq!(attempts, 2);
+    }
+
+    #[test]
+    fn flush_amount() {
+        let waker = waker_fn(move || {});
+        let mut cx = Context::from_waker(&waker);
+        let fill = |s: &mut SinkMock<_, _, _, u8, Never>, cx: &mut Context<'_>| {
+            while s.item_cnt < 9 {
+                assert_eq!(Pin::new(&mut *s).poll_ready(cx), Poll::Ready(Ok(())));
+                assert_eq!(Pin::new(&mut *s).start_send(1), Ok(()));
+            }
+        };
+        let feedback = || iter::repeat(Poll::Ready(Ok(())));
+
+        let mut s = SinkMock::new(feedback(), iter::empty(), iter::empty(), 10, 4);
+        fill(&mut s, &mut cx);
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(s.flush_feedback_consumed(), 3);
+
+        let mut s = SinkMock::new(
+            feedback(),
+            iter::empty(),
+            iter::empty(),
+            10,
+            FlushAmount::All,
+        );
+        fill(&mut s, &mut cx);
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(s.flush_feedback_consumed(), 1);
+
+        let mut amounts = vec![1, 5].into_iter().cycle();
+        let per_event = FlushAmount::PerEvent(Box::new(move || amounts.next().unwrap()));
+        let mut s = SinkMock::new(feedback(), iter::empty(), iter::empty(), 10, per_event);
+        fill(&mut s, &mut cx);
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Ready(Ok(())));
+        // 1 + 5 + 1 + 5
+        assert_eq!(s.flush_feedback_consumed(), 4);
+    }
+
+    #[test]
+    fn zero_flush_amount() {
+        let mut cx = crate::task::noop_context();
+        let feedback = || iter::repeat(Poll::Ready(Ok::<_, Never>(())));
+        // taken as 1, doesn't loop forever
+        let per_event = FlushAmount::PerEvent(Box::new(|| 0));
+        let mut s = SinkMock::new(feedback(), iter::empty(), iter::empty(), 3, per_event);
+        for item in 0..3 {
+            assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+            assert_eq!(Pin::new(&mut s).start_send(item), Ok(()));
+        }
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(s.flush_feedback_consumed(), 3);
+
+        let res = std::panic::catch_unwind(|| FlushAmount::from(0));
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn no_empty_flush() {
+        let mut sink = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, Never>(()))));
+        sink.assert_no_empty_flush();
+        futures::executor::block_on(async {
+            sink.send(1u8).await.unwrap();
+            sink.send(2u8).await.unwrap();
+            let mut stream = stream::iter(vec![Ok(5u8), Ok(7), Ok(9)]);
+            sink.send_all(&mut stream).await.unwrap();
+            sink.close().await.unwrap();
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "`poll_flush()` call #2 with nothing sent since the last flush")]
+    fn no_empty_flush_panics() {
+        let mut sink = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, Never>(()))));
+        sink.assert_no_empty_flush();
+        futures::executor::block_on(async {
+            sink.send(1u8).await.unwrap();
+            // gratuitous flush
+            let _ = sink.flush().await;
+        });
+    }
+
+    #[test]
+    fn buffer_fast_flush_slow() {
+        let (waker, cnt) = crate::task::count_waker();
+        let mut cx = Context::from_waker(&waker);
+        let feedback = vec![Poll::Pending, Poll::Ready(Ok::<_, Never>(()))].into_iter();
+        let mut s = SinkMock::buffer_fast_flush_slow(feedback);
+        s.set_max_item(2);
+        for item in 0..2u8 {
+            assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+            assert_eq!(Pin::new(&mut s).start_send(item), Ok(()));
+        }
+        // full, but `poll_ready()` doesn't flush
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Pending);
+        assert_eq!(s.flush_feedback_consumed(), 0);
+        assert_eq!(cnt.get(), 1);
+
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Pending);
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(s.flush_feedback_consumed(), 2);
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+    }
+
+    #[test]
+    #[should_panic(expected = "polled after error; sink should be discarded")]
+    fn fused_on_error() {
+        let feedback = vec![Poll::Ready(Ok(())), Poll::Ready(Err(()))].into_iter();
+        let mut sink = SinkMock::with_flush_feedback(feedback);
+        sink.fused_on_error();
+        futures::executor::block_on(async {
+            sink.send(1u8).await.unwrap();
+            assert_eq!(sink.send(2u8).await, Err(()));
+            // ignores the error
+            let _ = sink.send(3u8).await;
+        });
+    }
+
+    #[test]
+    fn fused_on_send_error() {
+        let mut sink = SinkMock::new(
+            iter::repeat(Poll::Ready(Ok(()))),
+            iter::empty(),
+            iter::once("send"),
+            10,
+            2,
+        );
+        sink.fused_on_error();
+        let waker = waker_fn(|| {});
+        let mut cx = Context::from_waker(&waker);
+        assert_eq!(Pin::new(&mut sink).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(Pin::new(&mut sink).start_send(1u8), Err("send"));
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            Pin::new(&mut sink).poll_ready(&mut cx)
+        }));
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn frame_aligned() {
+        let waker = waker_fn(|| {});
+        let mut cx = Context::from_waker(&waker);
+        let mut s = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, Never>(()))));
+        s.set_max_item(10).frame_aligned(4);
+        let send = |s: &mut SinkMock<_, _, _, u8, Never>, cx: &mut Context<'_>, n| {
+            for item in 0..n {
+                assert_eq!(Pin::new(&mut *s).poll_ready(cx), Poll::Ready(Ok(())));
+                assert_eq!(Pin::new(&mut *s).start_send(item), Ok(()));
+            }
+        };
+        send(&mut s, &mut cx, 3);
+        assert_eq!(s.items_until_frame(), 1);
+        // nothing to flush before alignment
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(s.items_until_frame(), 1);
+        assert_eq!(s.item_cnt, 3);
+
+        send(&mut s, &mut cx, 6);
+        assert_eq!(s.items_until_frame(), 3);
+        // two full frames are drained, the partial one stays
+        let consumed = s.flush_feedback_consumed();
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(s.flush_feedback_consumed() - consumed, 2);
+        assert_eq!(s.item_cnt, 1);
+        assert_eq!(s.items_until_frame(), 3);
+    }
+
+    #[test]
+    fn frame_aligned_partial_frame_at_close() {
+        let mut s = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, Never>(()))));
+        s.frame_aligned(3);
+        let items = stream::iter((0..7u8).map(Ok));
+        assert_eq!(Ok(()), futures::executor::block_on(items.forward(&mut s)));
+        // the last item didn't complete a frame
+        assert_eq!(s.items_until_frame(), 2);
+        assert_eq!(s.item_cnt, 1);
+    }
+
+    #[test]
+    fn lossy() {
+        let mut cx = crate::task::noop_context();
+        let mut s = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, Never>(()))));
+        s.set_max_item(10).lossy(3);
+        for item in 1..=7u8 {
+            assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+            assert_eq!(Pin::new(&mut s).start_send(item), Ok(()));
+        }
+        // items 3 and 6 were never buffered
+        assert_eq!(s.dropped_count(), 2);
+        assert_eq!(s.item_cnt, 5);
+        assert_eq!(s.high_water_mark(), 5);
+    }
+
+    #[test]
+    fn lossy_forward() {
+        let mut s = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, Never>(()))));
+        s.lossy(1);
+        let items = stream::iter((0..5u8).map(Ok));
+        // the producer sees success even though everything is lost
+        assert_eq!(Ok(()), futures::executor::block_on(items.forward(&mut s)));
+        assert_eq!(s.dropped_count(), 5);
+        assert_eq!(s.high_water_mark(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "`drop_every` must be greater than 0")]
+    fn lossy_zero() {
+        let mut s =
+            SinkMock::<_, _, _, u8, Never>::with_flush_feedback(iter::repeat(Poll::Ready(Ok(()))));
+        s.lossy(0);
+    }
+
+    #[test]
+    fn wakes_attributed_to_methods() {
+        let (waker, wakes) = crate::task::count_waker();
+        let feedback = vec![Poll::Ready(Ok::<_, Never>(())), Poll::Pending]
+            .into_iter()
+            .cycle();
+        let mut sink = SinkMock::with_flush_feedback(feedback);
+        sink.spurious_wakes(4);
+        let counters = sink.counters();
+        let items = stream::iter((0..7u8).map(Ok));
+        let mut forward = items.forward(sink);
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            if let Poll::Ready(res) = futures::FutureExt::poll_unpin(&mut forward, &mut cx) {
+                assert_eq!(res, Ok(()));
+                break;
+            }
+        }
+        // `max_item` is 3 and `flush_at_once` is 2, so `poll_ready()` flushes before items 3 and
+        // 5. Each flush takes `Ok` and `Pending`. The 4th `Poll::Ready(Ok(()))` is spurious.
+        assert_eq!(counters.wakes_from_ready(), 2 + 1);
+        assert_eq!(counters.wakes_from_flush(), 0);
+        // `poll_close()` takes `Ok`, `Pending` and `Ok` that is the 8th `Poll::Ready(Ok(()))`
+        assert_eq!(counters.wakes_from_close(), 1 + 1);
+        assert_eq!(
+            counters.wakes_from_ready() + counters.wakes_from_flush() + counters.wakes_from_close(),
+            wakes.get()
+        );
+    }
+
+    #[test]
+    fn wakes_from_flush() {
+        let mut cx = crate::task::noop_context();
+        let feedback = vec![
+            Poll::Pending,
+            Poll::Pending,
+            Poll::Ready(Ok::<_, Never>(())),
+        ];
+        let mut s = SinkMock::with_flush_feedback(feedback.into_iter().cycle());
+        let counters = s.counters();
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(Pin::new(&mut s).start_send(1), Ok(()));
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Pending);
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Pending);
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(counters.wakes_from_flush(), 2);
+        assert_eq!(counters.wakes_from_ready(), 0);
+        assert_eq!(counters.clone().wakes_from_flush(), 2);
+    }
+
+    #[test]
+    fn returned_polls() {
+        let feedback = vec![Poll::Ready(Ok(())), Poll::Pending, Poll::Ready(Err("io"))];
+        let mut sink = SinkMock::with_flush_feedback(feedback.into_iter());
+        sink.record_polls();
+        let items = stream::iter(vec![Ok(5u8), Ok(7), Ok(9), Ok(77)]);
+        assert_eq!(
+            Err("io"),
+            futures::executor::block_on(items.forward(&mut sink))
+        );
+        assert_eq!(
+            sink.returned_polls(),
+            &[
+                Poll::Ready(Ok(())),
+                Poll::Ready(Ok(())),
+                Poll::Ready(Ok(())),
+                // `Ok` flushed 2 items, the buffer is still not empty
+                Poll::Pending,
+                Poll::Ready(Ok(())),
+                // `poll_close()`
+                Poll::Ready(Err("io")),
+            ]
+        );
     }
 
     #[test]
-    fn interleave_pending() {
-        let e = vec![Poll::Ready(Ok::<_, Never>(())), Poll::Pending]
-            .into_iter()
-            .cycle();
-        let sink = SinkMock::with_flush_feedback(e);
+    fn returned_polls_not_recorded() {
+        let mut sink = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, Never>(()))));
+        let items = stream::iter(vec![Ok(5u8), Ok(7)]);
+        assert_eq!(
+            Ok(()),
+            futures::executor::block_on(items.forward(&mut sink))
+        );
+        assert!(sink.returned_polls().is_empty());
+    }
 
-        let stream =
-            stream::iter(vec![Ok::<u8, Never>(5u8), Ok(7), Ok(9), Ok(77), Ok(79)].into_iter());
-        let send_all = stream.forward(sink);
-        assert_eq!(Ok(()), futures::executor::block_on(send_all));
+    #[test]
+    fn flush_ack_before_close() {
+        let mut sink = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, Never>(()))));
+        sink.require_flush_ack_before_close();
+        futures::executor::block_on(async {
+            sink.feed(1u8).await.unwrap();
+            sink.feed(2u8).await.unwrap();
+            sink.flush().await.unwrap();
+            sink.close().await.unwrap();
+        });
+        // nothing to acknowledge
+        let mut sink = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, Never>(()))));
+        sink.require_flush_ack_before_close();
+        futures::executor::block_on(SinkExt::<u8>::close(&mut sink)).unwrap();
     }
 
     #[test]
-    fn error() {
-        let e = vec![Poll::Ready(Ok(())), Poll::Pending, Poll::Ready(Err(()))]
+    #[should_panic(
+        expected = "`poll_close()` called before `poll_flush()` acknowledged 1 sent items"
+    )]
+    fn flush_ack_before_close_panics() {
+        let feedback = vec![
+            Poll::Ready(Ok(())),
+            Poll::Pending,
+            Poll::Ready(Ok::<_, Never>(())),
+        ];
+        let mut sink = SinkMock::with_flush_feedback(feedback.into_iter().cycle());
+        sink.require_flush_ack_before_close();
+        futures::executor::block_on(async {
+            sink.send(1u8).await.unwrap();
+            sink.feed(2u8).await.unwrap();
+            // pending flush isn't an acknowledgement
+            let mut cx = crate::task::noop_context();
+            let _ = Pin::new(&mut sink).poll_flush(&mut cx);
+            let _ = sink.close().await;
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "acknowledged 5 sent items")]
+    fn flush_ack_before_close_forward() {
+        let mut sink = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, Never>(()))));
+        sink.require_flush_ack_before_close();
+        let items = stream::iter((0..5u8).map(Ok));
+        // `forward()` closes without flushing
+        let _ = futures::executor::block_on(items.forward(&mut sink));
+    }
+
+    #[test]
+    fn errors_per_method() {
+        let mut cx = crate::task::noop_context();
+        let mut s = SinkMock::new(
+            vec![
+                Poll::Ready(Err("flush")),
+                Poll::Ready(Ok(())),
+                Poll::Ready(Err("close")),
+            ]
+            .into_iter(),
+            vec!["ready"].into_iter(),
+            vec!["send"].into_iter(),
+            2,
+            1,
+        );
+        s.track_last_error();
+        let counters = s.counters();
+        assert_eq!(counters.last_error(), None);
+        assert_eq!(
+            Pin::new(&mut s).poll_ready(&mut cx),
+            Poll::Ready(Err("ready"))
+        );
+        assert_eq!(counters.last_error(), Some("ready"));
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(Pin::new(&mut s).start_send(1u8), Err("send"));
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(Pin::new(&mut s).start_send(2), Ok(()));
+        assert_eq!(
+            Pin::new(&mut s).poll_flush(&mut cx),
+            Poll::Ready(Err("flush"))
+        );
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(
+            Pin::new(&mut s).poll_close(&mut cx),
+            Poll::Ready(Err("close"))
+        );
+        assert_eq!(counters.errors_from_ready(), 1);
+        assert_eq!(counters.errors_from_send(), 1);
+        assert_eq!(counters.errors_from_flush(), 1);
+        assert_eq!(counters.errors_from_close(), 1);
+        assert_eq!(counters.last_error(), Some("close"));
+        assert_eq!(
+            counters.to_string(),
+            "wakes from ready: 0, flush: 0, close: 0; \
+             errors from ready: 1, send: 1, flush: 1, close: 1; last error: \"close\""
+        );
+    }
+
+    #[test]
+    fn last_error_untracked() {
+        let feedback = vec![Poll::Ready(Ok(())), Poll::Ready(Err(std::fmt::Error))];
+        let sink = SinkMock::with_flush_feedback(feedback.into_iter().cycle());
+        let counters = sink.counters();
+        let items = stream::iter((0..5u8).map(Ok));
+        assert!(futures::executor::block_on(items.forward(sink)).is_err());
+        assert_eq!(counters.errors_from_ready(), 1);
+        assert_eq!(counters.last_error(), None);
+    }
+
+    #[test]
+    fn errors_read_from_other_thread() {
+        let feedback = vec![Poll::Ready(Err(1usize)), Poll::Ready(Ok(()))]
             .into_iter()
             .cycle();
-        let sink = SinkMock::with_flush_feedback(e);
+        let mut s = SinkMock::with_flush_feedback(feedback);
+        s.track_last_error();
+        let counters = s.counters();
+        let done = Arc::new(atomic::AtomicBool::new(false));
+        let reader = {
+            let done = done.clone();
+            std::thread::spawn(move || {
+                while !done.load(atomic::Ordering::SeqCst) {
+                    // every error is 1, the error is set together with the first count
+                    let line = counters.to_string();
+                    assert!(
+                        line.ends_with("send: 0, flush: 0, close: 0")
+                            || line.ends_with("last error: 1")
+                    );
+                }
+                counters
+            })
+        };
+        let mut cx = crate::task::noop_context();
+        for _ in 0..1000 {
+            let _ = Sink::<u8>::poll_flush(Pin::new(&mut s), &mut cx);
+        }
+        done.store(true, atomic::Ordering::SeqCst);
+        let counters = reader.join().unwrap();
+        assert_eq!(counters.errors_from_flush(), 500);
+    }
 
-        let stream = stream::iter(vec![Ok(5u8), Ok(7), Ok(9), Ok(77), Ok(79)].into_iter());
-        let send_all = stream.forward(sink);
-        assert_eq!(Err(()), futures::executor::block_on(send_all));
+    #[test]
+    fn hol_block_stalls_until_released() {
+        let (waker, wakes) = crate::task::count_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut s = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, Never>(()))));
+        s.hol_block_at(2);
+        for item in 0..3u8 {
+            assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+            assert_eq!(Pin::new(&mut s).start_send(item), Ok(()));
+        }
+        assert!(!s.is_blocked());
+        // items before the blocked one are flushed
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Pending);
+        assert_eq!(s.item_cnt, 1);
+        assert!(s.is_blocked());
+        // the full buffer can't be flushed by `poll_ready()` either
+        s.set_max_item(1);
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Pending);
+        assert_eq!(wakes.get(), 0);
+
+        s.release_block();
+        assert_eq!(wakes.get(), 1);
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(s.item_cnt, 0);
+        // stalls were not taken from `flush_feedback`
+        assert_eq!(s.flush_feedback_consumed(), 2);
+    }
+
+    #[test]
+    fn hol_block_released_from_other_thread() {
+        let mut s = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, Never>(()))));
+        s.hol_block_at(0);
+        let shared = std::sync::Arc::new(std::sync::Mutex::new(s));
+        let sink = shared.clone();
+        let flushing = std::thread::spawn(move || {
+            futures::executor::block_on(futures::future::poll_fn(|cx| {
+                let mut s = sink.lock().unwrap();
+                if s.send_cnt == 0 {
+                    assert_eq!(Pin::new(&mut *s).poll_ready(cx), Poll::Ready(Ok(())));
+                    Pin::new(&mut *s).start_send(1u8).unwrap();
+                }
+                Pin::new(&mut *s).poll_flush(cx)
+            }))
+        });
+        // the flush stalls on the blocked item until this thread releases it
+        while !shared.lock().unwrap().is_blocked() {
+            std::thread::yield_now();
+        }
+        shared.lock().unwrap().release_block();
+        assert_eq!(flushing.join().unwrap(), Ok(()));
+        assert!(!shared.lock().unwrap().is_blocked());
+        assert_eq!(shared.lock().unwrap().counters().accepted(), 1);
+    }
+
+    #[test]
+    fn credited_refills_by_drained_items() {
+        let (waker, wakes) = crate::task::count_waker();
+        let mut cx = Context::from_waker(&waker);
+        let feedback = vec![Poll::Ready(Ok(())), Poll::Pending, Poll::Ready(Ok(()))];
+        let mut s = SinkMock::with_flush_feedback(feedback.into_iter().cycle());
+        s.credited(3).set_flush_at_once(2);
+        for item in 0..3u8 {
+            assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+            assert_eq!(Pin::new(&mut s).start_send(item), Ok::<_, Never>(()));
+        }
+        assert_eq!(s.credits(), Some(0));
+        // `poll_ready()` doesn't flush by itself
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Pending);
+        assert_eq!(wakes.get(), 1);
+        assert_eq!(s.flush_feedback_consumed(), 0);
+
+        // credits come back even from a flush that ends pending
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Pending);
+        assert_eq!(s.credits(), Some(2));
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(s.credits(), Some(3));
+    }
+
+    #[test]
+    fn credited_producer_flushing_on_pending() {
+        let mut s = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, Never>(()))));
+        s.credited(4).set_flush_at_once(1);
+        let mut cx = crate::task::noop_context();
+        let mut flushes = 0;
+        for item in 0..10u8 {
+            while Pin::new(&mut s).poll_ready(&mut cx).is_pending() {
+                flushes += 1;
+                assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Ready(Ok(())));
+            }
+            Pin::new(&mut s).start_send(item).unwrap();
+        }
+        assert_eq!(flushes, 2);
+        assert_eq!(s.high_water_mark(), 4);
+        assert_eq!(s.credits(), Some(2));
+    }
+
+    #[test]
+    fn idle_flushes() {
+        let mut s = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, Never>(()))));
+        s.frame_aligned(2);
+        let mut cx = crate::task::noop_context();
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        Pin::new(&mut s).start_send(1u8).unwrap();
+        // a partial frame stays buffered
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(s.idle_flush_count(), 2);
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        Pin::new(&mut s).start_send(2u8).unwrap();
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(s.idle_flush_count(), 2);
+    }
+
+    #[test]
+    fn max_frame_rejects_oversized_items() {
+        let mut s = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok(()))));
+        s.max_frame(3, |item: &&str| item.len(), "too large");
+        let mut cx = crate::task::noop_context();
+        for (item, expected) in [("abc", Ok(())), ("abcd", Err("too large")), ("", Ok(()))] {
+            assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+            assert_eq!(Pin::new(&mut s).start_send(item), expected);
+        }
+        assert_eq!(s.counters().errors_from_send(), 1);
+        assert_eq!(s.high_water_mark(), 2);
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn await_events_between_phases() {
+        use futures::{channel::oneshot, StreamExt};
+
+        let feedback = vec![Poll::Pending, Poll::Ready(Ok(()))].into_iter().cycle();
+        let mut sink = SinkMock::with_flush_feedback(feedback);
+        let mut events = sink.event_stream();
+        let (next_phase, phase_started) = oneshot::channel();
+        let producer = tokio::spawn(async move {
+            sink.send(1u8).await.unwrap();
+            phase_started.await.unwrap();
+            sink.send(2u8).await.unwrap();
+            sink.close().await
+        });
+
+        // wait for the first successful flush before starting the next phase
+        let mut before = Vec::new();
+        while let Some(event) = events.next().await {
+            let done = event == (Event::Flush, Response::Ok);
+            before.push(event);
+            if done {
+                break;
+            }
+        }
+        assert_eq!(
+            before,
+            vec![
+                (Event::Ready, Response::Ok),
+                (Event::Send(()), Response::Ok),
+                (Event::Flush, Response::Pending),
+                (Event::Flush, Response::Ok),
+            ]
+        );
+        next_phase.send(()).unwrap();
+        // the stream ends when the sink is closed
+        let rest: Vec<_> = events.collect().await;
+        assert_eq!(rest.last(), Some(&(Event::Close, Response::Ok)));
+        assert_eq!(producer.await.unwrap(), Ok::<_, Never>(()));
+    }
+
+    #[test]
+    #[should_panic(expected = "`poll_close()` called with 2 unflushed items: [\"b\", \"c\"]")]
+    fn unflushed_close_dumps_items() {
+        let feedback = vec![Poll::Ready(Ok::<_, Never>(())), Poll::Pending];
+        let mut s = SinkMock::with_flush_feedback(feedback.into_iter().cycle());
+        s.panic_on_unflushed_close().set_flush_at_once(1);
+        let mut cx = crate::task::noop_context();
+        for item in ["a", "b", "c"] {
+            assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+            Pin::new(&mut s).start_send(item).unwrap();
+        }
+        // "a" is flushed, then the flush stalls
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Pending);
+        let _ = Pin::new(&mut s).poll_close(&mut cx);
+    }
+
+    #[test]
+    fn rate_limited_refills_every_n_polls() {
+        let (waker, wakes) = crate::task::count_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut s = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, Never>(()))));
+        s.rate_limited(2, 4).set_max_item(10);
+        let mut ready = Vec::new();
+        for item in 0..8u8 {
+            let poll = Pin::new(&mut s).poll_ready(&mut cx);
+            if poll.is_ready() {
+                Pin::new(&mut s).start_send(item).unwrap();
+            }
+            ready.push(poll.is_ready());
+        }
+        // polls 4 and 8 refill the bucket
+        assert_eq!(
+            ready,
+            vec![true, true, false, true, true, false, false, true]
+        );
+        assert_eq!(wakes.get(), 3);
+        assert_eq!(s.tokens(), Some(1));
+    }
+
+    type AlwaysOk = iter::Repeat<Poll<Result<(), Never>>>;
+
+    fn blocked_mock() -> SinkMock<AlwaysOk, iter::Empty<Never>, iter::Empty<Never>, u8, Never> {
+        let mut s = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok(()))));
+        s.hol_block_at(0);
+        let mut cx = crate::task::noop_context();
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        Pin::new(&mut s).start_send(0).unwrap();
+        s
+    }
+
+    #[test]
+    fn spinning_driver_repolls_unsolicited() {
+        let mut s = blocked_mock();
+        let (waker, _) = crate::task::count_waker();
+        let mut cx = Context::from_waker(&waker);
+        for _ in 0..4 {
+            assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Pending);
+        }
+        assert_eq!(s.unsolicited_repolls(), 3);
+        // another task polling isn't a re-poll
+        let (other, _) = crate::task::count_waker();
+        let _ = Pin::new(&mut s).poll_flush(&mut Context::from_waker(&other));
+        assert_eq!(s.unsolicited_repolls(), 3);
+    }
+
+    #[test]
+    fn driver_waiting_for_wake_is_fine() {
+        let mut s = blocked_mock();
+        s.panic_on_unsolicited_repolls(0);
+        let (waker, wakes) = crate::task::count_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Pending);
+        s.release_block();
+        assert_eq!(wakes.get(), 1);
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Ready(Ok(())));
+        // self-waking pending results are solicited
+        let mut s =
+            SinkMock::with_flush_feedback(vec![Poll::Pending, Poll::Ready(Ok(()))].into_iter());
+        s.panic_on_unsolicited_repolls(0);
+        assert_eq!(futures::executor::block_on(s.send(1u8)), Ok::<_, Never>(()));
+        assert_eq!(s.unsolicited_repolls(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "2 unsolicited re-polls")]
+    fn panics_over_threshold() {
+        let mut s = blocked_mock();
+        s.panic_on_unsolicited_repolls(1);
+        let (waker, _) = crate::task::count_waker();
+        let mut cx = Context::from_waker(&waker);
+        for _ in 0..3 {
+            let _ = Pin::new(&mut s).poll_flush(&mut cx);
+        }
+    }
+
+    #[test]
+    fn waker_changes_between_pendings() {
+        let feedback = iter::repeat(Poll::Pending::<Result<(), Never>>);
+        let mut s = SinkMock::<_, _, _, u8, _>::with_flush_feedback(feedback);
+        let (first, _) = crate::task::count_waker();
+        let (second, _) = crate::task::count_waker();
+        for waker in [&first, &first, &second, &second.clone(), &first] {
+            let mut cx = Context::from_waker(waker);
+            assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Pending);
+        }
+        assert_eq!(s.waker_changed_count(), 2);
+    }
+
+    #[test]
+    fn waker_continuity_across_swapped_wakers() {
+        let feedback = iter::repeat(Poll::Ready(Ok::<_, Never>(())));
+        let mut s = SinkMock::<_, _, _, u8, _>::with_flush_feedback(feedback);
+        let (first, _) = crate::task::count_waker();
+        let (second, _) = crate::task::count_waker();
+        let mut cx = Context::from_waker(&first);
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        // not recorded until asked for
+        assert!(s.waker_continuity().is_empty());
+
+        s.record_waker_continuity();
+        for waker in [&first, &first, &second, &second.clone(), &first] {
+            let mut cx = Context::from_waker(waker);
+            assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+            assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Ready(Ok(())));
+        }
+        let mut cx = Context::from_waker(&second);
+        assert_eq!(Pin::new(&mut s).poll_close(&mut cx), Poll::Ready(Ok(())));
+        // every call but the first one, a swap shows up once
+        assert_eq!(
+            s.waker_continuity(),
+            [true, true, true, false, true, true, true, false, true, false]
+        );
+        let swaps = s.waker_continuity().iter().filter(|&&same| !same).count();
+        assert_eq!(swaps, 3);
+    }
+
+    #[test]
+    fn waker_continuity_keeps_no_waker() {
+        let feedback = iter::repeat(Poll::Ready(Ok::<_, Never>(())));
+        let mut s = SinkMock::<_, _, _, u8, _>::with_flush_feedback(feedback);
+        s.record_waker_continuity();
+        let (waker, stats) = crate::task::tracking_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(s.waker_continuity(), [true]);
+        assert_eq!(stats.live_clones(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "expected 3 wakes, observed 5; wake sources: flush×4, ready×1")]
+    fn expect_wakes_lists_sources() {
+        let feedback = vec![Poll::Pending, Poll::Ready(Ok(()))].into_iter().cycle();
+        let mut s = SinkMock::<_, _, _, u8, Never>::with_flush_feedback(feedback);
+        s.set_max_item(0);
+        let mut cx = crate::task::noop_context();
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Pending);
+        for _ in 0..4 {
+            while Pin::new(&mut s).poll_flush(&mut cx).is_ready() {}
+        }
+        s.counters().expect_wakes(3);
+    }
+
+    #[test]
+    fn transactional_commits_only_on_close() {
+        let commit_feedback = vec![Poll::Pending, Poll::Ready(Ok::<_, Never>(()))];
+        let mut s = SinkMock::transactional(commit_feedback.into_iter());
+        let (waker, wakes) = crate::task::count_waker();
+        let mut cx = Context::from_waker(&waker);
+        for item in 0..3u8 {
+            assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+            Pin::new(&mut s).start_send(item).unwrap();
+            assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Ready(Ok(())));
+        }
+        assert_eq!(s.flush_feedback_consumed(), 0);
+        assert_eq!(s.commit_size(), 3);
+        assert_eq!(Pin::new(&mut s).poll_close(&mut cx), Poll::Pending);
+        wakes.expect_wakes(1);
+        assert_eq!(Pin::new(&mut s).poll_close(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(s.commit_size(), 0);
+    }
+
+    #[test]
+    fn wake_policy_never_until_woken_by_hand() {
+        let feedback = vec![Poll::Pending, Poll::Ready(Ok(()))];
+        let mut s = SinkMock::<_, _, _, u8, Never>::with_flush_feedback(feedback.into_iter());
+        s.wake_policy(WakePolicy::Never)
+            .panic_on_unsolicited_repolls(0);
+        let (waker, wakes) = crate::task::count_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Pending);
+        wakes.expect_none();
+        s.counters().expect_none();
+        assert!(s.wake_stored());
+        assert!(!s.wake_stored());
+        wakes.expect_wakes(1);
+        // not counted as the mock woke outside of a poll
+        s.counters().expect_none();
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Ready(Ok(())));
+    }
+
+    #[test]
+    fn wake_policy_after_polls() {
+        let feedback = iter::repeat(Poll::Pending);
+        let mut s = SinkMock::<_, _, _, u8, Never>::with_flush_feedback(feedback);
+        s.wake_policy(WakePolicy::AfterPolls(2));
+        let (waker, wakes) = crate::task::count_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Pending);
+        wakes.expect_none();
+        // the task is polled without a wake
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Pending);
+        wakes.expect_none();
+        assert_eq!(s.unsolicited_repolls(), 1);
+        // the second poll after the first `Poll::Pending` wakes, the count isn't restarted
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Pending);
+        wakes.expect_wakes(1);
+        assert_eq!(s.unsolicited_repolls(), 2);
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Pending);
+        wakes.expect_wakes(1);
+        s.counters().expect_wakes(1);
+        assert_eq!(s.counters().wakes_from_flush(), 1);
+    }
+
+    #[test]
+    fn wake_policy_spurious() {
+        let feedback = vec![Poll::Pending, Poll::Ready(Ok(()))].into_iter().cycle();
+        let mut s = SinkMock::<_, _, _, u8, Never>::with_flush_feedback(feedback);
+        s.wake_policy(WakePolicy::Spurious {
+            extra: 2,
+            also_on_ready: false,
+        });
+        let (waker, wakes) = crate::task::count_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert_eq!(Pin::new(&mut s).poll_close(&mut cx), Poll::Pending);
+        wakes.expect_wakes(3);
+        assert_eq!(s.counters().wakes_from_close(), 3);
+        assert_eq!(Pin::new(&mut s).poll_close(&mut cx), Poll::Ready(Ok(())));
+        wakes.expect_wakes(3);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn wake_policy_delayed() {
+        let feedback = vec![Poll::Pending, Poll::Pending, Poll::Ready(Ok(()))];
+        let mut s = SinkMock::<_, _, _, u8, Never>::with_flush_feedback(feedback.into_iter());
+        s.wake_policy(WakePolicy::Delayed(std::time::Duration::from_millis(5)));
+        futures::executor::block_on(s.send(1)).unwrap();
+        s.counters().expect_none();
+        assert_eq!(s.flush_feedback_consumed(), 3);
+    }
+
+    #[test]
+    fn abrupt_close_drops_buffered_items() {
+        let feedback = vec![Poll::Ready(Ok(()))];
+        let mut s = SinkMock::<_, _, _, u8, Never>::with_flush_feedback(feedback.into_iter());
+        s.set_flush_at_once(1);
+        futures::executor::block_on(async {
+            s.send(1).await.unwrap();
+            s.feed(2).await.unwrap();
+            s.feed(3).await.unwrap();
+        });
+        s.close_mode(CloseMode::Abrupt);
+        let waker = crate::task::panic_waker("abrupt close doesn't wait");
+        let mut cx = Context::from_waker(&waker);
+        assert_eq!(Pin::new(&mut s).poll_close(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(s.items_dropped_on_close(), 2);
+        assert_eq!(s.flush_feedback_consumed(), 1);
+        assert!(s.counters().is_closed());
+        assert_eq!(s.counters().buffered(), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn wake_handle_releases_task_on_another_thread() {
+        let feedback = vec![Poll::Pending, Poll::Ready(Ok(()))];
+        let mut s = SinkMock::<_, _, _, u8, Never>::with_flush_feedback(feedback.into_iter());
+        s.wake_policy(WakePolicy::Never);
+        let handle = s.wake_handle();
+        let done = Arc::new(atomic::AtomicBool::new(false));
+        let task = std::thread::spawn({
+            let done = done.clone();
+            move || {
+                let res = futures::executor::block_on(s.send(1));
+                done.store(true, atomic::Ordering::SeqCst);
+                res
+            }
+        });
+        while !handle.has_pending_waker() {
+            std::thread::yield_now();
+        }
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert!(!done.load(atomic::Ordering::SeqCst));
+        assert!(handle.wake());
+        assert_eq!(task.join().unwrap(), Ok(()));
+        assert!(done.load(atomic::Ordering::SeqCst));
+        assert!(!handle.wake());
+    }
+
+    #[test]
+    fn pipelined_window_released_by_ack() {
+        let mut s = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, Never>(()))));
+        s.pipelined(2).panic_on_unsolicited_repolls(0);
+        let (waker, wakes) = crate::task::count_waker();
+        let mut cx = Context::from_waker(&waker);
+        for item in 0..2u8 {
+            assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+            Pin::new(&mut s).start_send(item).unwrap();
+        }
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(s.in_flight(), 2);
+        // flushed, but still in flight
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Pending);
+        wakes.expect_none();
+
+        s.ack(2);
+        wakes.expect_wakes(1);
+        assert_eq!(s.in_flight(), 0);
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        // nothing waits anymore
+        s.ack(0);
+        wakes.expect_wakes(1);
+    }
+
+    #[test]
+    #[should_panic(expected = "acked 2 requests with 1 in flight")]
+    fn pipelined_ack_more_than_in_flight() {
+        let mut s = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, Never>(()))));
+        s.pipelined(4);
+        futures::executor::block_on(s.send(1u8)).unwrap();
+        s.ack(2);
+    }
+
+    #[test]
+    fn scripted_step_per_flush() {
+        let mut s = SinkMock::scripted(vec![
+            Step::Ready,
+            Step::Err("full"),
+            Step::Pending,
+            Step::Ready,
+        ]);
+        s.set_max_item(2);
+        let (waker, wakes) = crate::task::count_waker();
+        let mut cx = Context::from_waker(&waker);
+        for item in 0..2u8 {
+            assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+            Pin::new(&mut s).start_send(item).unwrap();
+        }
+        // the full buffer is flushed by `poll_ready()`
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        Pin::new(&mut s).start_send(2).unwrap();
+        assert_eq!(s.steps().remaining().len(), 3);
+        assert_eq!(
+            Pin::new(&mut s).poll_close(&mut cx),
+            Poll::Ready(Err("full"))
+        );
+        assert!(!s.counters().is_closed());
+        assert_eq!(Pin::new(&mut s).poll_close(&mut cx), Poll::Pending);
+        wakes.expect_wakes(1);
+        assert_eq!(Pin::new(&mut s).poll_close(&mut cx), Poll::Ready(Ok(())));
+        assert!(s.counters().is_closed());
+        assert_eq!(s.flush_feedback_consumed(), 4);
+    }
+
+    /// Drive `feed()` and `flush()` of a combinator around the mock by hand and return the
+    /// wakes the task got and the wakes done by the mock.
+    fn wakes_through_buffer(policy: WakePolicy) -> (usize, usize) {
+        let feedback = vec![
+            Poll::Pending,
+            Poll::Ready(Ok(())),
+            Poll::Pending,
+            Poll::Ready(Ok(())),
+        ];
+        let mut mock = SinkMock::<_, _, _, u8, Never>::with_flush_feedback(feedback.into_iter());
+        mock.wake_policy(policy).record_polls();
+        let counters = mock.counters();
+        let mut sink = (&mut mock).buffer(1);
+        let (waker, wakes) = crate::task::count_waker();
+        let mut cx = Context::from_waker(&waker);
+        for item in 0..2 {
+            let mut flush = Box::pin(async {
+                sink.feed(item).await?;
+                sink.flush().await
+            });
+            let mut polls = 0;
+            while futures::Future::poll(flush.as_mut(), &mut cx).is_pending() {
+                polls += 1;
+                assert!(polls < 10, "livelock");
+            }
+        }
+        drop(sink);
+        assert_eq!(mock.flush_feedback_consumed(), 4);
+        let readies = mock.returned_polls().iter().filter(|p| p.is_ready());
+        assert_eq!(readies.count(), 8);
+        (
+            wakes.get(),
+            counters.wakes_from_ready() + counters.wakes_from_flush(),
+        )
+    }
+
+    #[test]
+    fn spurious_wakes_not_amplified() {
+        let spurious = |extra, also_on_ready| WakePolicy::Spurious {
+            extra,
+            also_on_ready,
+        };
+        // two `Poll::Pending` of flush
+        assert_eq!(wakes_through_buffer(spurious(0, false)), (2, 2));
+        assert_eq!(wakes_through_buffer(spurious(3, false)), (8, 8));
+        // and one for each of 8 `Poll::Ready`
+        assert_eq!(wakes_through_buffer(spurious(0, true)), (10, 10));
+        assert_eq!(
+            wakes_through_buffer(spurious(0, false)),
+            wakes_through_buffer(WakePolicy::Immediate)
+        );
+    }
+
+    #[test]
+    fn compressing_counts_output_frames() {
+        let ratio = 3;
+        let mut s = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, Never>(()))));
+        s.compressing(ratio).set_max_item(usize::MAX);
+        futures::executor::block_on(async {
+            for item in 0..7u8 {
+                s.feed(item).await.unwrap();
+            }
+            // 3 + 3 + 1
+            s.flush().await.unwrap();
+            assert_eq!(s.output_frames(), 3);
+            // nothing to flush, no frame
+            s.flush().await.unwrap();
+            s.send(7).await.unwrap();
+            assert_eq!(s.output_frames(), 4);
+            s.close().await.unwrap();
+        });
+        assert_eq!(s.output_frames(), 4);
+    }
+
+    /// Two tasks flush halves of a shared sink, both get `Poll::Pending` and the test wakes the
+    /// stored wakers once. Returns how many tasks completed.
+    fn tasks_done_after_wake(target: WakeTarget) -> usize {
+        use futures::{executor::LocalPool, future::poll_fn, task::LocalSpawnExt};
+        use std::{cell::RefCell, rc::Rc};
+
+        let sink = Rc::new(RefCell::new(SinkMock::<_, _, _, u8, _>::scripted(vec![
+            Step::<()>::Pending,
+            Step::Pending,
+            Step::Ready,
+            Step::Ready,
+        ])));
+        sink.borrow_mut()
+            .wake_policy(WakePolicy::Never)
+            .wake_target(target);
+        let done = Rc::new(atomic::AtomicUsize::new(0));
+        let mut pool = LocalPool::new();
+        for _ in 0..2 {
+            let (sink, done) = (sink.clone(), done.clone());
+            pool.spawner()
+                .spawn_local(async move {
+                    poll_fn(|cx| Pin::new(&mut *sink.borrow_mut()).poll_flush(cx))
+                        .await
+                        .unwrap();
+                    done.fetch_add(1, atomic::Ordering::SeqCst);
+                })
+                .unwrap();
+        }
+        pool.run_until_stalled();
+        assert_eq!(done.load(atomic::Ordering::SeqCst), 0);
+
+        assert!(sink.borrow_mut().wake_stored());
+        pool.run_until_stalled();
+        done.load(atomic::Ordering::SeqCst)
+    }
+
+    #[test]
+    fn wake_target_latest_strands_older_task() {
+        assert_eq!(tasks_done_after_wake(WakeTarget::Latest), 1);
+    }
+
+    #[test]
+    fn wake_target_all_wakes_every_task() {
+        assert_eq!(tasks_done_after_wake(WakeTarget::All), 2);
+    }
+
+    #[test]
+    fn half_open_accepts_sends_and_never_flushes() {
+        let mut sink = SinkMock::<_, _, _, u8, ()>::with_flush_feedback(iter::empty());
+        sink.half_open();
+        let (waker, cnt) = crate::task::count_waker();
+        let mut cx = Context::from_waker(&waker);
+        for i in 0..10 {
+            assert_eq!(Pin::new(&mut sink).poll_ready(&mut cx), Poll::Ready(Ok(())));
+            Pin::new(&mut sink).start_send(i).unwrap();
+        }
+        for _ in 0..3 {
+            assert_eq!(Pin::new(&mut sink).poll_flush(&mut cx), Poll::Pending);
+        }
+        assert_eq!(Pin::new(&mut sink).poll_close(&mut cx), Poll::Pending);
+        cnt.expect_none();
+        assert_eq!(sink.stuck_items(), 10);
+        // still accepting
+        assert_eq!(Pin::new(&mut sink).poll_ready(&mut cx), Poll::Ready(Ok(())));
+    }
+
+    #[test]
+    fn wake_style_doesnt_change_behavior() {
+        let run = |style| {
+            let (waker, cnt) = crate::task::count_waker();
+            let mut cx = Context::from_waker(&waker);
+            let feedback = vec![Poll::Pending, Poll::Ready(Ok(())), Poll::Ready(Ok(()))];
+            let mut sink = SinkMock::<_, _, _, u8, ()>::with_flush_feedback(feedback.into_iter());
+            sink.wake_style(style).spurious_wakes(1);
+            assert_eq!(Pin::new(&mut sink).poll_ready(&mut cx), Poll::Ready(Ok(())));
+            Pin::new(&mut sink).start_send(1).unwrap();
+            assert_eq!(Pin::new(&mut sink).poll_flush(&mut cx), Poll::Pending);
+            assert_eq!(Pin::new(&mut sink).poll_flush(&mut cx), Poll::Ready(Ok(())));
+            assert_eq!(Pin::new(&mut sink).poll_close(&mut cx), Poll::Ready(Ok(())));
+            (cnt.get(), sink.counters().wakes_from_flush())
+        };
+        assert_eq!(run(WakeStyle::ByRef), (4, 2));
+        assert_eq!(run(WakeStyle::ByRef), run(WakeStyle::CloneAndWake));
+    }
+
+    #[test]
+    fn close_error_is_latched() {
+        let mut sink = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok(()))));
+        sink.close_error("close");
+        let mut cx = crate::task::noop_context();
+        assert_eq!(Pin::new(&mut sink).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        Pin::new(&mut sink).start_send(1u8).unwrap();
+        for _ in 0..3 {
+            assert_eq!(
+                Pin::new(&mut sink).poll_close(&mut cx),
+                Poll::Ready(Err("close"))
+            );
+        }
+        assert_eq!(sink.counters().buffered(), 0);
+        assert!(!sink.counters().is_closed());
+        assert_eq!(sink.counters().errors_from_close(), 3);
+        // the sink isn't closed, so it still accepts items
+        assert_eq!(Pin::new(&mut sink).poll_ready(&mut cx), Poll::Ready(Ok(())));
+    }
+
+    #[test]
+    #[should_panic(expected = "polled after error; sink should be discarded")]
+    fn close_error_fused() {
+        let mut sink =
+            SinkMock::<_, _, _, u8, _>::with_flush_feedback(iter::repeat(Poll::Ready(Ok(()))));
+        sink.close_error("close").fused_on_error();
+        let mut cx = crate::task::noop_context();
+        assert_eq!(
+            Pin::new(&mut sink).poll_close(&mut cx),
+            Poll::Ready(Err("close"))
+        );
+        let _ = Pin::new(&mut sink).poll_close(&mut cx);
+    }
+
+    /// The first wake fires after 2 polls of any mock, the second is lost, the third is
+    /// immediate, and the test drives the interleaving instead of the scheduler.
+    #[test]
+    fn wake_script_reproduces_lost_wake() {
+        let clock = PollClock::new();
+        let feedback = vec![Poll::Pending, Poll::Pending, Poll::Pending];
+        let mut a = SinkMock::<_, _, _, u8, ()>::with_flush_feedback(
+            feedback
+                .into_iter()
+                .chain(iter::repeat(Poll::Ready(Ok(())))),
+        );
+        a.wake_script(
+            vec![
+                WakeWhen::AfterPolls(2),
+                WakeWhen::Never,
+                WakeWhen::Immediately,
+            ]
+            .into_iter(),
+            &clock,
+        );
+        let mut b =
+            SinkMock::<_, _, _, u8, ()>::with_flush_feedback(iter::repeat(Poll::Ready(Ok(()))));
+        b.wake_script(iter::empty(), &clock);
+        let (waker_a, cnt_a) = crate::task::count_waker();
+        let mut cx_a = Context::from_waker(&waker_a);
+        let mut cx_b = crate::task::noop_context();
+
+        assert_eq!(Pin::new(&mut a).poll_flush(&mut cx_a), Poll::Pending);
+        assert_eq!(clock.scheduled(), 1);
+        assert_eq!(Pin::new(&mut b).poll_flush(&mut cx_b), Poll::Ready(Ok(())));
+        cnt_a.expect_none();
+        assert_eq!(Pin::new(&mut b).poll_flush(&mut cx_b), Poll::Ready(Ok(())));
+        cnt_a.expect_wakes(1);
+
+        // the lost wake: nothing ever wakes `a` again
+        assert_eq!(Pin::new(&mut a).poll_flush(&mut cx_a), Poll::Pending);
+        for _ in 0..10 {
+            assert_eq!(Pin::new(&mut b).poll_flush(&mut cx_b), Poll::Ready(Ok(())));
+        }
+        assert!(!a.wake_stored());
+        cnt_a.expect_wakes(1);
+        assert_eq!(clock.scheduled(), 0);
+
+        assert_eq!(Pin::new(&mut a).poll_flush(&mut cx_a), Poll::Pending);
+        cnt_a.expect_wakes(2);
+        // the script ended, the default policy applies again
+        assert_eq!(Pin::new(&mut a).poll_flush(&mut cx_a), Poll::Ready(Ok(())));
+        assert_eq!(clock.now(), 16);
+        assert_eq!(a.unsolicited_repolls(), 1);
+    }
+
+    #[test]
+    fn rejections_record_every_refused_item() {
+        let mut s = SinkMock::new(
+            iter::repeat(Poll::Ready(Ok(()))),
+            iter::empty(),
+            vec!["send"].into_iter(),
+            10,
+            2,
+        );
+        s.reject_if(|item: &String| item.starts_with('#'), "comment")
+            .max_frame(5, String::len, "too large")
+            .record_rejections();
+        let mut cx = crate::task::noop_context();
+        for item in &["a", "#b", "c", "dddddd", "#eeeeee", "f"] {
+            assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+            let _ = Pin::new(&mut s).start_send(item.to_string());
+        }
+        let rejections: Vec<_> = s
+            .rejections()
+            .iter()
+            .map(|(item, e)| (item.as_str(), *e))
+            .collect();
+        assert_eq!(
+            rejections,
+            vec![
+                // `start_send()` error of the mock
+                ("a", "send"),
+                ("#b", "comment"),
+                ("dddddd", "too large"),
+                // the size is checked first
+                ("#eeeeee", "too large"),
+            ]
+        );
+        assert_eq!(s.counters().errors_from_send(), 4);
+        assert_eq!(s.counters().buffered(), 2);
+    }
+
+    /// Wakes racing with the return of the poll, or landing strictly after it, while the
+    /// combinators in between are polled by another worker.
+    #[cfg(feature = "std")]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn wake_orders_on_multi_thread_runtime() {
+        let orders = vec![
+            WakeOrder::BeforeReturn,
+            WakeOrder::RacingReturn,
+            WakeOrder::AfterReturn,
+        ];
+        for order in orders {
+            let feedback = vec![Poll::Pending, Poll::Ready(Ok(()))].into_iter().cycle();
+            let mut sink =
+                SinkMock::<_, _, _, u16, ()>::new(feedback, iter::empty(), iter::empty(), 2, 1);
+            sink.wake_order(order)
+                .wake_policy(WakePolicy::Spurious {
+                    extra: 1,
+                    also_on_ready: true,
+                })
+                .panic_on_unsolicited_repolls(0);
+            let sending = tokio::spawn(async move {
+                let mut buffered = (&mut sink).buffer(3);
+                let items = stream::iter(0..500).map(Ok);
+                items.forward(&mut buffered).await.unwrap();
+                sink
+            });
+            let sink = sending.await.unwrap();
+            assert!(sink.counters().is_closed());
+            assert_eq!(sink.counters().accepted(), 500);
+            assert_eq!(sink.unsolicited_repolls(), 0);
+        }
+    }
+
+    #[test]
+    fn capacity_schedule_shrinks_and_grows() {
+        let schedule = vec![3, 3, 3, 1, 5].into_iter();
+        let mut sink = SinkMock::<_, _, _, u8, ()>::with_capacity_schedule(schedule);
+        let mut cx = crate::task::noop_context();
+        for i in 0..3 {
+            assert_eq!(Pin::new(&mut sink).poll_ready(&mut cx), Poll::Ready(Ok(())));
+            Pin::new(&mut sink).start_send(i).unwrap();
+        }
+        assert_eq!(sink.counters().buffered(), 3);
+
+        // shrunk below the buffer, the buffered items are flushed first
+        assert_eq!(Pin::new(&mut sink).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(sink.capacity(), 1);
+        assert_eq!(sink.counters().buffered(), 0);
+        Pin::new(&mut sink).start_send(3).unwrap();
+        assert!(sink.just_became_full());
+
+        // grown, the schedule ended and the last capacity stays
+        for i in 4..8 {
+            assert_eq!(Pin::new(&mut sink).poll_ready(&mut cx), Poll::Ready(Ok(())));
+            Pin::new(&mut sink).start_send(i).unwrap();
+        }
+        assert_eq!(sink.capacity(), 5);
+        assert_eq!(sink.counters().buffered(), 5);
+        assert_eq!(sink.high_water_mark(), 5);
+    }
+
+    #[test]
+    fn sync_with_boxed_functions() {
+        fn assert_sync<T: Sync>(_: &T) {}
+
+        let schedule = iter::repeat(2);
+        let mut sink = SinkMock::<_, _, _, String, &str>::with_capacity_schedule(schedule);
+        sink.max_frame(5, String::len, "too large")
+            .reject_if(String::is_empty, "empty")
+            .set_flush_at_once(FlushAmount::PerEvent(Box::new(|| 2)));
+        assert_sync(&sink);
+    }
+
+    fn acks_between_polls(coalescing: WakeCoalescing) -> (usize, usize) {
+        let mut s = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, Never>(()))));
+        s.pipelined(3).wake_coalescing(coalescing);
+        let (waker, wakes) = crate::task::count_waker();
+        let mut cx = Context::from_waker(&waker);
+        for item in 0..3u8 {
+            assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+            Pin::new(&mut s).start_send(item).unwrap();
+        }
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Pending);
+        for _ in 0..3 {
+            s.ack(1);
+        }
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        // acks after the poll wake nobody
+        s.ack(0);
+        (wakes.get(), s.coalesced_wakes())
+    }
+
+    #[test]
+    fn acks_wake_per_event() {
+        assert_eq!(acks_between_polls(WakeCoalescing::PerEvent), (3, 0));
+    }
+
+    #[test]
+    fn acks_coalesced_per_progress() {
+        assert_eq!(acks_between_polls(WakeCoalescing::PerProgress), (1, 2));
+    }
+
+    #[test]
+    fn min_batch_close_flushes_partial_batch() {
+        let mut s = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, Never>(()))));
+        s.min_batch(3);
+        let (waker, wakes) = crate::task::count_waker();
+        let mut cx = Context::from_waker(&waker);
+        for item in 0..2u8 {
+            assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+            Pin::new(&mut s).start_send(item).unwrap();
+        }
+        // self-waking until the batch is complete
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Pending);
+        wakes.expect_wakes(1);
+        assert!(!s.reached_min_batch());
+
+        assert_eq!(Pin::new(&mut s).poll_close(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(s.counters().buffered(), 0);
+        assert!(s.counters().is_closed());
+        wakes.expect_wakes(1);
+    }
+
+    #[test]
+    fn would_block_is_ready_and_counted() {
+        let mut s = SinkMock::scripted(vec![Step::WouldBlock, Step::Ready, Step::<()>::WouldBlock]);
+        let (waker, wakes) = crate::task::count_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        Pin::new(&mut s).start_send(1u8).unwrap();
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(s.would_block_count(), 1);
+        assert_eq!(s.counters().buffered(), 0);
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(Pin::new(&mut s).poll_close(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(s.would_block_count(), 2);
+        assert_eq!(s.steps().would_block_count(), 2);
+        wakes.expect_none();
+    }
+
+    #[test]
+    fn contiguous_gaps_skip_refused_items() {
+        let mut s = SinkMock::new(
+            iter::repeat(Poll::Ready(Ok(()))),
+            iter::empty(),
+            vec!["send"].into_iter(),
+            10,
+            2,
+        );
+        s.reject_if(|&(seq, _): &(u64, char)| seq == 3, "refused")
+            .expect_contiguous(|&(seq, _)| seq);
+        assert!(s.gaps().is_empty());
+        let mut cx = crate::task::noop_context();
+        for &item in &[
+            (0, 'a'),
+            (1, 'b'),
+            (2, 'c'),
+            (3, 'd'),
+            (3, 'e'),
+            (5, 'f'),
+            (2, 'g'),
+        ] {
+            assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+            let _ = Pin::new(&mut s).start_send(item);
+        }
+        // the first item failed, both items numbered 3 were refused
+        assert_eq!(s.gaps(), vec![(3, 5), (6, 2)]);
+    }
+
+    #[test]
+    fn min_batch_empty_flush_is_ready() {
+        let e = iter::repeat(Poll::Ready(Ok::<_, Never>(())));
+        let mut s = SinkMock::<_, _, _, u8, _>::with_flush_feedback(e);
+        s.min_batch(3);
+        let (waker, wakes) = crate::task::count_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Ready(Ok(())));
+        wakes.expect_none();
     }
 }
@@ -0,0 +1,164 @@
+use core::{
+    convert::identity,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures::{never::Never, sink::Sink};
+
+/// Sink that counts items sent out of order.
+///
+/// Items are compared by a key, the item itself for [ordering_sink()]. For details see
+/// [ordering_sink()] and [ordering_sink_by_key()].
+///
+/// [ordering_sink()]:ordering_sink
+/// [ordering_sink_by_key()]:ordering_sink_by_key
+pub struct OrderingSink<K, F = fn(K) -> K> {
+    key: F,
+    last: Option<K>,
+    send_cnt: usize,
+    out_of_order_cnt: usize,
+}
+
+/// Create a sink that checks items are sent in non-decreasing order.
+///
+/// Items are usually sequence numbers (or carry one and order by it). Every item passed to
+/// `start_send` is compared with the item sent just before it. If it's less than the previous
+/// one, it's counted as out of order. Equal items are in order. Only neighbours are compared, so
+/// one late item in `1, 3, 2, 4` is counted once (`2 < 3`), but `4` is in order again.
+///
+/// Every method returns `Poll::Ready(Ok(()))` like [ok()](crate::ok).
+///
+/// # Examples
+/// ```
+/// use futures::{executor::block_on, SinkExt};
+/// use futures_test_sink::ordering_sink;
+///
+/// let mut sink = ordering_sink();
+/// for seq in vec![1u32, 2, 2, 4, 3, 5] {
+///     block_on(sink.send(seq)).unwrap();
+/// }
+/// assert_eq!(sink.out_of_order_count(), 1);
+/// assert_eq!(sink.send_count(), 6);
+/// ```
+pub fn ordering_sink<Item: Ord>() -> OrderingSink<Item> {
+    ordering_sink_by_key(identity)
+}
+
+/// Create a sink that checks keys of items are non-decreasing.
+///
+/// `key` returns the key of an item. It takes the item by value, the sink keeps only the key of
+/// the last item. Keys are compared like the items of [ordering_sink()], only neighbours are
+/// compared and equal keys are in order.
+///
+/// # Examples
+/// ```
+/// use futures::{executor::block_on, stream, StreamExt};
+/// use futures_test_sink::ordering_sink_by_key;
+///
+/// let mut sink = ordering_sink_by_key(|(seq, _payload): (u32, &str)| seq);
+/// let items = vec![(1, "a"), (3, "c"), (2, "b")];
+/// assert_eq!(Ok(()), block_on(stream::iter(items.into_iter().map(Ok)).forward(&mut sink)));
+/// assert_eq!(sink.out_of_order_count(), 1);
+/// assert_eq!(sink.last(), Some(&2));
+/// ```
+pub fn ordering_sink_by_key<Item, K, F>(key: F) -> OrderingSink<K, F>
+where
+    K: Ord,
+    F: FnMut(Item) -> K,
+{
+    OrderingSink {
+        key,
+        last: None,
+        send_cnt: 0,
+        out_of_order_cnt: 0,
+    }
+}
+
+impl<K, F> Unpin for OrderingSink<K, F> {}
+
+impl<K, F> OrderingSink<K, F> {
+    /// How many items had a key less than the key of the item sent before them.
+    pub fn out_of_order_count(&self) -> usize {
+        self.out_of_order_cnt
+    }
+
+    /// How many items were sent.
+    pub fn send_count(&self) -> usize {
+        self.send_cnt
+    }
+
+    /// Key of the last item sent, the item itself for [ordering_sink()].
+    pub fn last(&self) -> Option<&K> {
+        self.last.as_ref()
+    }
+}
+
+impl<Item, K, F> Sink<Item> for OrderingSink<K, F>
+where
+    K: Ord,
+    F: FnMut(Item) -> K,
+{
+    type Error = Never;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Item) -> Result<(), Self::Error> {
+        let this = Pin::into_inner(self);
+        let key = (this.key)(item);
+        if matches!(&this.last, Some(last) if key < *last) {
+            this.out_of_order_cnt += 1;
+        }
+        this.last = Some(key);
+        this.send_cnt += 1;
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{
+        executor::block_on,
+        stream::{self, StreamExt},
+    };
+
+    #[test]
+    fn detects_violations() {
+        let table = vec![
+            (vec![], 0),
+            (vec![1u32, 2, 3, 4], 0),
+            (vec![1, 1, 1], 0),
+            (vec![1, 3, 2, 4], 1),
+            (vec![4, 3, 2, 1], 3),
+            (vec![1, 2, 1, 2, 1], 2),
+        ];
+        for (items, expected) in table {
+            let mut sink = ordering_sink();
+            let len = items.len();
+            let items = stream::iter(items.into_iter().map(Ok));
+            assert_eq!(Ok(()), block_on(items.forward(&mut sink)));
+            assert_eq!(sink.out_of_order_count(), expected);
+            assert_eq!(sink.send_count(), len);
+        }
+    }
+
+    #[test]
+    fn ordered_by_sequence_number() {
+        // tuples are ordered by the first field
+        let mut sink = ordering_sink();
+        let items = vec![Ok((1, "a")), Ok((2, "b")), Ok((2, "a")), Ok((3, "z"))];
+        assert_eq!(Ok(()), block_on(stream::iter(items).forward(&mut sink)));
+        assert_eq!(sink.out_of_order_count(), 1);
+        assert_eq!(sink.last(), Some(&(3, "z")));
+    }
+}
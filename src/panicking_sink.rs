@@ -0,0 +1,173 @@
+use core::marker::PhantomData;
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures::{never::Never, sink::Sink};
+
+/// Sink that panics at a precisely chosen interaction.
+///
+/// For details see [panicking_sink()].
+///
+/// [panicking_sink()]:panicking_sink
+pub struct PanickingSink<P, Item> {
+    plan: P,
+    item_type: PhantomData<fn(Item)>,
+}
+
+/// Create a sink that panics at the interaction chosen by `plan`.
+///
+/// Every call to `poll_ready`, `start_send`, `poll_flush` or `poll_close` takes the [next] element
+/// from `plan`:
+/// - `Some(msg)` - panic with `msg`
+/// - `None` - behave like [`ok()`](crate::ok), so `poll_*` return `Poll::Ready(Ok(()))` and
+///   `start_send` discard `item`.
+///
+/// When `plan` is exhausted the sink never panics again.
+///
+/// This is useful to check that a supervisor converts the panic into a task failure.
+///
+/// # Examples
+/// ```
+/// use futures::{executor::block_on, SinkExt};
+/// use futures_test_sink::panicking_sink;
+/// use std::panic::{catch_unwind, AssertUnwindSafe};
+///
+/// // second `poll_flush` panics (the first one is called by `send()`)
+/// let plan = vec![None, None, None, None, None, Some("second flush")].into_iter();
+/// let mut sink = panicking_sink::<u8, _>(plan);
+/// block_on(sink.send(1)).unwrap();
+/// let result = catch_unwind(AssertUnwindSafe(|| block_on(sink.send(2))));
+/// assert!(result.is_err());
+/// ```
+///
+/// [next]: https://doc.rust-lang.org/std/iter/trait.Iterator.html#tymethod.next
+pub fn panicking_sink<Item, P>(plan: P) -> PanickingSink<P, Item>
+where
+    P: Iterator<Item = Option<&'static str>>,
+{
+    PanickingSink {
+        plan,
+        item_type: Default::default(),
+    }
+}
+
+impl<P, Item> Unpin for PanickingSink<P, Item> {}
+
+impl<P, Item> PanickingSink<P, Item>
+where
+    P: Iterator<Item = Option<&'static str>>,
+{
+    fn check_plan(&mut self) {
+        if let Some(Some(msg)) = self.plan.next() {
+            panic!("{}", msg);
+        }
+    }
+}
+
+impl<P, Item> Sink<Item> for PanickingSink<P, Item>
+where
+    P: Iterator<Item = Option<&'static str>>,
+{
+    type Error = Never;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::into_inner(self).check_plan();
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, _item: Item) -> Result<(), Self::Error> {
+        Pin::into_inner(self).check_plan();
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::into_inner(self).check_plan();
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::into_inner(self).check_plan();
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_task::waker_fn;
+    use futures::{
+        executor::block_on,
+        future::FutureExt,
+        stream::{self, FuturesUnordered, StreamExt},
+    };
+    use std::iter;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn panic_at_second_flush() {
+        let waker = waker_fn(move || {});
+        let mut cx = Context::from_waker(&waker);
+        let plan = vec![None, Some("second flush")].into_iter();
+        let mut s = panicking_sink::<u8, _>(plan);
+
+        let r1 = Pin::new(&mut s).poll_flush(&mut cx);
+        assert_eq!(r1, Poll::Ready(Ok(())));
+        let result = catch_unwind(AssertUnwindSafe(|| Pin::new(&mut s).poll_flush(&mut cx)));
+        let msg = *result.unwrap_err().downcast::<String>().unwrap();
+        assert_eq!(msg, "second flush");
+
+        // plan is exhausted, so sink works like `ok()`
+        let r3 = Pin::new(&mut s).poll_ready(&mut cx);
+        assert_eq!(r3, Poll::Ready(Ok(())));
+        assert_eq!(Pin::new(&mut s).start_send(1), Ok(()));
+    }
+
+    #[test]
+    fn never_panic_without_plan() {
+        let sink = panicking_sink(iter::repeat(None));
+        let stream = stream::iter(vec![Ok::<u8, Never>(5u8), Ok(7), Ok(9)]);
+        assert_eq!(Ok(()), block_on(stream.forward(sink)));
+    }
+
+    #[test]
+    fn block_on_propagates_panic() {
+        let plan = iter::repeat_n(None, 3).chain(iter::once(Some("boom")));
+        let sink = panicking_sink(plan);
+        let stream = stream::iter(vec![Ok::<u8, Never>(5u8), Ok(7), Ok(9)]);
+        let result = catch_unwind(AssertUnwindSafe(|| block_on(stream.forward(sink))));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn futures_unordered_isolates_panic() {
+        // shared state is updated only by tasks that didn't panic
+        let shared = Arc::new(Mutex::new(Vec::new()));
+        let tasks = FuturesUnordered::new();
+        for (id, plan) in [
+            (0, vec![None; 8]),
+            (1, vec![None, None, Some("task 1 failed")]),
+            (2, vec![None; 8]),
+        ] {
+            let shared = shared.clone();
+            let sink = panicking_sink(plan.into_iter().chain(iter::repeat(None)));
+            let stream = stream::iter(vec![Ok::<u8, Never>(5u8), Ok(7)]);
+            let task = async move {
+                stream.forward(sink).await.unwrap();
+                shared.lock().unwrap().push(id);
+            };
+            tasks.push(AssertUnwindSafe(task).catch_unwind().map(move |r| (id, r)));
+        }
+
+        let mut results: Vec<_> = block_on(tasks.collect());
+        results.sort_by_key(|(id, _)| *id);
+        assert!(results[0].1.is_ok());
+        assert!(results[1].1.is_err());
+        assert!(results[2].1.is_ok());
+
+        let mut shared = shared.lock().unwrap().clone();
+        shared.sort_unstable();
+        assert_eq!(shared, vec![0, 2]);
+    }
+}
@@ -0,0 +1,153 @@
+use core::marker::PhantomPinned;
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures::sink::Sink;
+
+/// Wrapper that makes any sink `!Unpin`.
+///
+/// All mocks in this crate are `Unpin`, so code tested with them can accidentally require `Unpin`
+/// from the sink (e.g. by calling `Pin::new(&mut sink)`). Most of the ecosystem assumes `Unpin`,
+/// but sinks built with `async` blocks or self-references are not. Wrap a mock in `PinnedSink`
+/// to check that your adapter works with sinks pinned in place.
+///
+/// The inner sink is structurally pinned, so every method is forwarded to it unchanged.
+///
+/// # Examples
+/// ```
+/// use futures::{executor::block_on, never::Never, pin_mut, SinkExt};
+/// use futures_test_sink::{PinnedSink, SinkMock};
+/// use std::{iter, task::Poll};
+///
+/// let mock = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, Never>(()))));
+/// let sink = PinnedSink::new(mock);
+/// // `sink` is `!Unpin` so it has to be pinned before use
+/// pin_mut!(sink);
+/// block_on(sink.send(5u8)).unwrap();
+/// ```
+pub struct PinnedSink<S> {
+    inner: S,
+    _pin: PhantomPinned,
+}
+
+impl<S> PinnedSink<S> {
+    /// Wrap `inner` sink.
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            _pin: PhantomPinned,
+        }
+    }
+
+    /// Get a reference to the inner sink.
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Get a pinned mutable reference to the inner sink.
+    pub fn get_pin_mut(self: Pin<&mut Self>) -> Pin<&mut S> {
+        // SAFETY: `inner` is structurally pinned. It's never moved out of pinned `self`,
+        // `PinnedSink` doesn't implement `Drop` and is never `Unpin`.
+        unsafe { self.map_unchecked_mut(|this| &mut this.inner) }
+    }
+
+    /// Consume `PinnedSink` returning the inner sink.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S, Item> Sink<Item> for PinnedSink<S>
+where
+    S: Sink<Item>,
+{
+    type Error = S::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.get_pin_mut().poll_ready(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Item) -> Result<(), Self::Error> {
+        self.get_pin_mut().start_send(item)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.get_pin_mut().poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.get_pin_mut().poll_close(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SinkMock;
+    use futures::{
+        executor::block_on,
+        never::Never,
+        pin_mut,
+        stream::{self, StreamExt},
+        SinkExt,
+    };
+    use std::iter;
+
+    // Compile-pass check that `PinnedSink` is `!Unpin`: `some_item` is ambiguous for `Unpin` types.
+    trait AmbiguousIfUnpin<A> {
+        fn some_item() {}
+    }
+    impl<T: ?Sized> AmbiguousIfUnpin<()> for T {}
+    impl<T: ?Sized + Unpin> AmbiguousIfUnpin<u8> for T {}
+
+    type Mock = SinkMock<
+        iter::Repeat<Poll<Result<(), Never>>>,
+        iter::Empty<Never>,
+        iter::Empty<Never>,
+        u8,
+        Never,
+    >;
+
+    #[test]
+    fn is_not_unpin() {
+        let _ = <PinnedSink<Mock> as AmbiguousIfUnpin<_>>::some_item;
+    }
+
+    fn mock() -> Mock {
+        SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok(()))))
+    }
+
+    // adapter under test doesn't require `Unpin`
+    async fn send_twice<S, T>(mut sink: Pin<&mut S>) -> Result<(), S::Error>
+    where
+        S: Sink<T>,
+        T: From<u8>,
+    {
+        sink.as_mut().send(T::from(1)).await?;
+        sink.as_mut().send(T::from(2)).await
+    }
+
+    #[test]
+    fn pinned_on_stack() {
+        let sink = PinnedSink::new(mock());
+        pin_mut!(sink);
+        assert_eq!(Ok(()), block_on(send_twice::<_, u8>(sink.as_mut())));
+        assert_eq!(sink.get_ref().flush_feedback_consumed(), 2);
+    }
+
+    #[test]
+    fn pinned_in_box() {
+        let mut sink = Box::pin(PinnedSink::new(mock()));
+        let items = stream::iter(vec![Ok(5u8), Ok(7), Ok(9), Ok(77), Ok(79)]);
+        assert_eq!(Ok(()), block_on(items.forward(sink.as_mut())));
+    }
+
+    #[test]
+    fn combinators() {
+        let sink =
+            PinnedSink::new(mock()).with(|item: u16| async move { Ok::<_, Never>(item as u8) });
+        pin_mut!(sink);
+        assert_eq!(Ok(()), block_on(send_twice::<_, u16>(sink)));
+    }
+}
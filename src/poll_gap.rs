@@ -0,0 +1,110 @@
+use futures::sink::Sink;
+use pin_project_lite::pin_project;
+use std::time::{Duration, Instant};
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+pin_project! {
+    /// Wrapper that measures wall-clock time between consecutive `poll_ready()` and `poll_flush()`
+    /// calls of the inner sink.
+    ///
+    /// Every method is delegated to the inner sink unchanged. In tests with real timers a long gap
+    /// reveals that the code under test didn't poll again promptly after it was woken.
+    ///
+    /// Requires `std` feature.
+    ///
+    /// # Examples
+    /// ```
+    /// use futures::{executor::block_on, stream, StreamExt};
+    /// use futures_test_sink::{interleave_pending, PollGapSink};
+    /// use std::time::Duration;
+    ///
+    /// let mut sink = PollGapSink::new(Box::pin(interleave_pending()));
+    /// let items = stream::iter(vec![Ok(1u8), Ok(2), Ok(3)]);
+    /// assert_eq!(Ok(()), block_on(items.forward(&mut sink)));
+    /// assert!(sink.max_poll_gap() < Duration::from_secs(1));
+    /// ```
+    pub struct PollGapSink<S> {
+        #[pin]
+        inner: S,
+        last_poll: Option<Instant>,
+        max_gap: Duration,
+    }
+}
+
+impl<S> PollGapSink<S> {
+    /// Wrap `inner` sink.
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            last_poll: None,
+            max_gap: Duration::from_secs(0),
+        }
+    }
+
+    /// The longest time between two consecutive `poll_ready()` or `poll_flush()` calls.
+    ///
+    /// It's zero until the second call.
+    pub fn max_poll_gap(&self) -> Duration {
+        self.max_gap
+    }
+
+    inner_accessors!(S, "the inner sink");
+
+    fn polled(self: Pin<&mut Self>) -> Pin<&mut S> {
+        let this = self.project();
+        let now = Instant::now();
+        if let Some(last) = *this.last_poll {
+            *this.max_gap = (*this.max_gap).max(now - last);
+        }
+        *this.last_poll = Some(now);
+        this.inner
+    }
+}
+
+impl<S, Item> Sink<Item> for PollGapSink<S>
+where
+    S: Sink<Item>,
+{
+    type Error = S::Error;
+
+    forward_sink!("poll_gap", start_send, poll_close);
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let poll = self.polled().poll_ready(cx);
+        wrapper_event!("poll_gap", "poll_ready", &poll);
+        poll
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let poll = self.polled().poll_flush(cx);
+        wrapper_event!("poll_gap", "poll_flush", &poll);
+        poll
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ok, task::noop_context};
+    use std::thread;
+
+    #[test]
+    fn records_longest_gap() {
+        let mut cx = noop_context();
+        let mut sink = PollGapSink::new(ok::<u8>());
+        assert_eq!(Pin::new(&mut sink).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(sink.max_poll_gap(), Duration::from_secs(0));
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(Pin::new(&mut sink).poll_flush(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(Pin::new(&mut sink).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        let gap = sink.max_poll_gap();
+        assert!(gap >= Duration::from_millis(20));
+        // `poll_close()` isn't measured
+        thread::sleep(Duration::from_millis(40));
+        assert_eq!(Pin::new(&mut sink).poll_close(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(sink.max_poll_gap(), gap);
+    }
+}
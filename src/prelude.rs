@@ -0,0 +1,26 @@
+//! Items used in most tests.
+//!
+//! ```
+//! use futures_test_sink::prelude::*;
+//! ```
+//!
+//! # Examples
+//! ```
+//! use futures::{executor::block_on, never::Never, stream, StreamExt};
+//! use futures_test_sink::prelude::*;
+//! use std::task::Poll;
+//!
+//! let flush_feedback = vec![Poll::Pending, Poll::Ready(Ok::<_, Never>(()))]
+//!     .into_iter()
+//!     .fuse_last();
+//! let sink = SinkMock::with_flush_feedback(flush_feedback);
+//! let items = stream::iter(vec![Ok(5u8), Ok(7)]);
+//! assert_eq!(Ok(()), block_on(items.forward(sink)));
+//! ```
+
+pub use crate::fuse_last::IteratorExt;
+pub use crate::{
+    assert_close_ready, assert_flush_pending, assert_flush_ready, assert_sink_err,
+    assert_sink_pending, assert_sink_ready, assert_start_send_ok,
+};
+pub use crate::{from_iter, interleave_pending, ok, SinkMock};
@@ -0,0 +1,99 @@
+use core::marker::PhantomData;
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures::{never::Never, sink::Sink};
+
+/// Sink that accept `(priority, item)` tuples and counts items sent with every priority.
+///
+/// For details see [priority_sink()].
+///
+/// [priority_sink()]:priority_sink
+pub struct PrioritySink<Item> {
+    counts: [usize; 256],
+    item_type: PhantomData<fn(Item)>,
+}
+
+/// Create a sink that discards `(priority, item)` tuples but counts how many items were sent with
+/// every priority.
+///
+/// Every method returns `Poll::Ready(Ok(()))` like [ok()](crate::ok).
+///
+/// # Examples
+/// ```
+/// use futures::{executor::block_on, SinkExt};
+/// use futures_test_sink::priority_sink;
+///
+/// let mut sink = priority_sink();
+/// block_on(sink.send((0, "control"))).unwrap();
+/// block_on(sink.send((7, "bulk"))).unwrap();
+/// block_on(sink.send((7, "bulk"))).unwrap();
+///
+/// assert_eq!(sink.count_for(0), 1);
+/// assert_eq!(sink.count_for(7), 2);
+/// assert_eq!(sink.count_for(1), 0);
+/// ```
+pub fn priority_sink<Item>() -> PrioritySink<Item> {
+    PrioritySink {
+        counts: [0; 256],
+        item_type: Default::default(),
+    }
+}
+
+impl<Item> Unpin for PrioritySink<Item> {}
+
+impl<Item> PrioritySink<Item> {
+    /// How many items were sent with `priority`.
+    pub fn count_for(&self, priority: u8) -> usize {
+        self.counts[usize::from(priority)]
+    }
+
+    /// How many items were sent with any priority.
+    pub fn total_count(&self) -> usize {
+        self.counts.iter().sum()
+    }
+}
+
+impl<Item> Sink<(u8, Item)> for PrioritySink<Item> {
+    type Error = Never;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, (priority, _item): (u8, Item)) -> Result<(), Self::Error> {
+        Pin::into_inner(self).counts[usize::from(priority)] += 1;
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{
+        executor::block_on,
+        stream::{self, StreamExt},
+    };
+
+    #[test]
+    fn counts_per_priority() {
+        let mut sink = priority_sink();
+        let items = (0..30u32).map(|i| Ok(((i % 3) as u8, i)));
+        assert_eq!(Ok(()), block_on(stream::iter(items).forward(&mut sink)));
+
+        assert_eq!(sink.count_for(0), 10);
+        assert_eq!(sink.count_for(1), 10);
+        assert_eq!(sink.count_for(2), 10);
+        assert_eq!(sink.count_for(u8::MAX), 0);
+        assert_eq!(sink.total_count(), 30);
+    }
+}
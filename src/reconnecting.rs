@@ -0,0 +1,264 @@
+use alloc::vec::Vec;
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures::sink::Sink;
+
+/// One failure window of [reconnecting()] sink.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outage<E> {
+    /// The outage starts with the first operation after this many items were accepted in total.
+    pub starts_after_items: usize,
+    /// How many operations fail during the outage.
+    pub broken_for_ops: usize,
+    /// Error returned by every failing operation.
+    pub error: E,
+    /// Items accepted but not flushed yet are lost when the outage starts.
+    pub loses_buffered: bool,
+}
+
+/// Sink that fails during scripted outages.
+///
+/// For details see [reconnecting()].
+///
+/// [reconnecting()]:reconnecting
+pub struct ReconnectingSink<I, Item, E> {
+    outages: I,
+    next: Option<Outage<E>>,
+    // operations left to fail when an outage is in progress
+    broken_left: Option<usize>,
+    accepted: usize,
+    buffered: Vec<Item>,
+    items: Vec<Item>,
+    outage_cnt: usize,
+    lost_cnt: usize,
+}
+
+/// Create a sink that breaks and recovers as `outages` say.
+///
+/// Outside of outages the sink is always ready and `poll_flush()` and `poll_close()` move
+/// buffered items to [items()](ReconnectingSink::items). When the number of accepted items
+/// reaches [Outage::starts_after_items] the next [Outage::broken_for_ops] calls of any method
+/// fail with [Outage::error]. Then the sink works again until the next outage. Outages are taken
+/// from the iterator one by one, so they should be ordered by `starts_after_items`.
+///
+/// This allows to test reconnect and retry logic of a client.
+///
+/// # Examples
+/// ```
+/// use futures::{executor::block_on, SinkExt};
+/// use futures_test_sink::{reconnecting, Outage};
+///
+/// let outage = Outage {
+///     starts_after_items: 1,
+///     broken_for_ops: 2,
+///     error: "connection reset",
+///     loses_buffered: true,
+/// };
+/// let mut sink = reconnecting(vec![outage]);
+/// block_on(sink.feed(1u8)).unwrap();
+/// assert_eq!(block_on(sink.send(2)), Err("connection reset"));
+/// assert_eq!(block_on(sink.send(2)), Err("connection reset"));
+/// assert_eq!(block_on(sink.send(2)), Ok(()));
+/// // the first item was buffered when the outage started
+/// assert_eq!(sink.items(), &[2]);
+/// assert_eq!(sink.lost_count(), 1);
+/// ```
+pub fn reconnecting<Item, E, I>(outages: I) -> ReconnectingSink<I::IntoIter, Item, E>
+where
+    I: IntoIterator<Item = Outage<E>>,
+    E: Clone,
+{
+    let mut outages = outages.into_iter();
+    ReconnectingSink {
+        next: outages.next(),
+        outages,
+        broken_left: None,
+        accepted: 0,
+        buffered: Vec::new(),
+        items: Vec::new(),
+        outage_cnt: 0,
+        lost_cnt: 0,
+    }
+}
+
+impl<I, Item, E> Unpin for ReconnectingSink<I, Item, E> {}
+
+impl<I, Item, E> ReconnectingSink<I, Item, E> {
+    /// Flushed items.
+    pub fn items(&self) -> &[Item] {
+        &self.items
+    }
+
+    /// Consume the sink returning flushed items.
+    pub fn into_items(self) -> Vec<Item> {
+        self.items
+    }
+
+    /// How many outages are over.
+    pub fn outage_count(&self) -> usize {
+        self.outage_cnt
+    }
+
+    /// How many accepted items were lost by outages.
+    pub fn lost_count(&self) -> usize {
+        self.lost_cnt
+    }
+
+    /// `true` when the sink is failing now.
+    pub fn is_broken(&self) -> bool {
+        self.broken_left.is_some()
+    }
+}
+
+impl<I, Item, E> ReconnectingSink<I, Item, E>
+where
+    I: Iterator<Item = Outage<E>>,
+    E: Clone,
+{
+    /// Error of the current operation if it's part of an outage.
+    fn outage(&mut self) -> Option<E> {
+        loop {
+            let outage = self.next.as_ref()?;
+            if self.accepted < outage.starts_after_items {
+                return None;
+            }
+            let left = match self.broken_left {
+                Some(left) => left,
+                // the outage starts
+                None => {
+                    if outage.loses_buffered {
+                        self.lost_cnt += self.buffered.len();
+                        self.buffered.clear();
+                    }
+                    outage.broken_for_ops
+                }
+            };
+            if left > 0 {
+                self.broken_left = Some(left - 1);
+                return Some(outage.error.clone());
+            }
+            self.broken_left = None;
+            self.outage_cnt += 1;
+            self.next = self.outages.next();
+        }
+    }
+
+    fn flush_buffered(&mut self) -> Poll<Result<(), E>> {
+        match self.outage() {
+            Some(e) => Poll::Ready(Err(e)),
+            None => {
+                self.items.append(&mut self.buffered);
+                Poll::Ready(Ok(()))
+            }
+        }
+    }
+}
+
+impl<I, Item, E> Sink<Item> for ReconnectingSink<I, Item, E>
+where
+    I: Iterator<Item = Outage<E>>,
+    E: Clone,
+{
+    type Error = E;
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match Pin::into_inner(self).outage() {
+            Some(e) => Poll::Ready(Err(e)),
+            None => Poll::Ready(Ok(())),
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Item) -> Result<(), Self::Error> {
+        let this = Pin::into_inner(self);
+        if let Some(e) = this.outage() {
+            return Err(e);
+        }
+        this.accepted += 1;
+        this.buffered.push(item);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::into_inner(self).flush_buffered()
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::into_inner(self).flush_buffered()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{executor::block_on, SinkExt};
+
+    // send every item, reconnecting until it's flushed
+    async fn send_all_retrying<S>(sink: &mut S, items: &[u8]) -> usize
+    where
+        S: Sink<u8, Error = &'static str> + Unpin,
+    {
+        let mut retries = 0;
+        for item in items {
+            while sink.send(*item).await.is_err() {
+                retries += 1;
+            }
+        }
+        retries
+    }
+
+    #[test]
+    fn retrying_across_two_outages() {
+        let mut sink = reconnecting(vec![
+            Outage {
+                starts_after_items: 2,
+                broken_for_ops: 3,
+                error: "reset",
+                loses_buffered: false,
+            },
+            Outage {
+                starts_after_items: 5,
+                broken_for_ops: 1,
+                error: "timeout",
+                loses_buffered: false,
+            },
+        ]);
+        let items: Vec<u8> = (0..8).collect();
+        let retries = block_on(send_all_retrying(&mut sink, &items));
+        assert_eq!(retries, 4);
+        assert_eq!(sink.outage_count(), 2);
+        assert_eq!(sink.lost_count(), 0);
+        // outages started at flushes, so the items were buffered and sent again
+        assert_eq!(sink.items(), &[0, 1, 1, 2, 3, 3, 4, 5, 6, 7]);
+    }
+
+    #[test]
+    fn buffered_items_lost() {
+        let outage = |starts_after_items, loses_buffered| Outage {
+            starts_after_items,
+            broken_for_ops: 1,
+            error: "reset",
+            loses_buffered,
+        };
+        let mut sink = reconnecting(vec![outage(3, true), outage(6, false)]);
+        block_on(async {
+            sink.feed(0).await.unwrap();
+            sink.flush().await.unwrap();
+            sink.feed(1).await.unwrap();
+            sink.feed(2).await.unwrap();
+            // outage starts with this operation, 1 and 2 are lost
+            assert_eq!(sink.feed(3).await, Err("reset"));
+            assert!(sink.is_broken());
+            sink.feed(3).await.unwrap();
+            assert!(!sink.is_broken());
+            sink.feed(4).await.unwrap();
+            sink.feed(5).await.unwrap();
+            assert_eq!(sink.flush().await, Err("reset"));
+            sink.close().await.unwrap();
+        });
+        assert_eq!(sink.lost_count(), 2);
+        assert_eq!(sink.outage_count(), 2);
+        assert_eq!(sink.into_items(), vec![0, 3, 4, 5]);
+    }
+}
@@ -0,0 +1,399 @@
+use crate::{Event, Response, SinkFeedback};
+use alloc::format;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::task::Wake;
+use alloc::vec::{self, Vec};
+use core::fmt::Debug;
+use core::iter::FromIterator;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::{
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use futures::sink::Sink;
+use pin_project_lite::pin_project;
+
+type Replay<Item, E> =
+    SinkFeedback<E, vec::IntoIter<Poll<Result<(), E>>>, vec::IntoIter<Result<(), E>>, Item>;
+
+/// Recorded interaction with a sink: every operation with its outcome.
+///
+/// It's created by [Recorder] or written by hand (e.g. with `collect()`). Turn it into a mock
+/// reproducing the outcomes with [into_mock()](Script::into_mock) or check that a driver performs
+/// exactly the same operations with [ReplaySink](crate::ReplaySink).
+///
+/// A script recorded by [Recorder] also knows when the waker was woken, which is shown by
+/// [render()](Script::render). Wakes aren't compared by `==`, nor serialized.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Script<Item, E> {
+    steps: Vec<(Event<Item>, Response<E>)>,
+    // number of steps performed before every wake
+    #[cfg_attr(feature = "serde", serde(skip))]
+    wakes: Vec<usize>,
+}
+
+impl<Item: PartialEq, E: PartialEq> PartialEq for Script<Item, E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.steps == other.steps
+    }
+}
+
+impl<Item: Eq, E: Eq> Eq for Script<Item, E> {}
+
+impl<Item, E> Script<Item, E> {
+    /// Create a script from operations and their outcomes.
+    pub fn new(steps: Vec<(Event<Item>, Response<E>)>) -> Self {
+        Self {
+            steps,
+            wakes: Vec::new(),
+        }
+    }
+
+    /// For every wake the number of steps performed before it.
+    pub(crate) fn wakes(&self) -> &[usize] {
+        &self.wakes
+    }
+
+    /// Operations and their outcomes in order they were performed.
+    pub fn steps(&self) -> &[(Event<Item>, Response<E>)] {
+        &self.steps
+    }
+
+    /// Consume the script returning its steps.
+    pub fn into_steps(self) -> Vec<(Event<Item>, Response<E>)> {
+        self.steps
+    }
+
+    /// Script with items and errors replaced by their `Debug` representation.
+    ///
+    /// This is the default representation for [to_json()](Script::to_json) when items or errors
+    /// don't implement serde traits. Two scripts can be compared this way as well, even if
+    /// `Item` isn't `PartialEq`.
+    pub fn debug_repr(&self) -> Script<String, String>
+    where
+        Item: Debug,
+        E: Debug,
+    {
+        let steps = self
+            .steps
+            .iter()
+            .map(|(event, response)| {
+                let event = match event {
+                    Event::Ready => Event::Ready,
+                    Event::Send(item) => Event::Send(format!("{:?}", item)),
+                    Event::Flush => Event::Flush,
+                    Event::Close => Event::Close,
+                };
+                let response = match response {
+                    Response::Ok => Response::Ok,
+                    Response::Err(e) => Response::Err(format!("{:?}", e)),
+                    Response::Pending => Response::Pending,
+                };
+                (event, response)
+            })
+            .collect();
+        Script {
+            steps,
+            wakes: self.wakes.clone(),
+        }
+    }
+
+    /// Create a mock that returns the recorded outcomes in order.
+    ///
+    /// Outcomes of `poll_ready()`, `poll_flush()` and `poll_close()` are returned by the next
+    /// call to any of them and outcomes of `start_send()` by the next `start_send()`, like in
+    /// [from_iter()](crate::from_iter). On `Response::Pending` the waker is woken. Items sent to
+    /// the mock are discarded.
+    ///
+    /// # Panics
+    /// The mock panics when the driver performs more operations than recorded.
+    ///
+    /// `start_send()` recorded with `Response::Pending` can't be reproduced and panics here.
+    pub fn into_mock(self) -> Replay<Item, E> {
+        let mut polls = Vec::new();
+        let mut sends = Vec::new();
+        for (event, response) in self.steps {
+            match (event, response) {
+                (Event::Send(_), Response::Ok) => sends.push(Ok(())),
+                (Event::Send(_), Response::Err(e)) => sends.push(Err(e)),
+                (Event::Send(_), Response::Pending) => {
+                    panic!("`start_send()` can't return `Response::Pending`")
+                }
+                (_, Response::Ok) => polls.push(Poll::Ready(Ok(()))),
+                (_, Response::Err(e)) => polls.push(Poll::Ready(Err(e))),
+                (_, Response::Pending) => polls.push(Poll::Pending),
+            }
+        }
+        SinkFeedback {
+            poll_fallback: polls.into_iter(),
+            start_send_fallback: sends.into_iter(),
+            poll_cnt: 0,
+            name: None,
+            calls: None,
+            waking: Default::default(),
+            item_type: Default::default(),
+            err_typpe: Default::default(),
+        }
+    }
+}
+
+impl<Item, E> FromIterator<(Event<Item>, Response<E>)> for Script<Item, E> {
+    fn from_iter<I: IntoIterator<Item = (Event<Item>, Response<E>)>>(iter: I) -> Self {
+        Self::new(iter.into_iter().collect())
+    }
+}
+
+pin_project! {
+    /// Wrapper that records every operation on the inner sink and its outcome into a [Script].
+    ///
+    /// Every method is delegated to the inner sink unchanged. This allows to record an interaction
+    /// with a real transport once and replay it deterministically later with
+    /// [Script::into_mock()].
+    ///
+    /// Items are cloned into the script, so `Item: Clone` is required. Errors are cloned as well.
+    ///
+    /// The inner sink is polled with a waker wrapping the one from the context, so wakes are
+    /// recorded too. The wrapper is reused while the task's waker doesn't change.
+    ///
+    /// # Examples
+    /// ```
+    /// use futures::{executor::block_on, stream, StreamExt};
+    /// use futures_test_sink::{interleave_pending, Recorder};
+    ///
+    /// let mut recorder = Recorder::new(Box::pin(interleave_pending()));
+    /// let items = stream::iter(vec![Ok(1u8), Ok(2)]);
+    /// assert_eq!(Ok(()), block_on(items.clone().forward(&mut recorder)));
+    /// let script = recorder.into_script();
+    ///
+    /// // replay recorded outcomes to the same driver
+    /// let mut replay = Recorder::new(script.clone().into_mock());
+    /// assert_eq!(Ok(()), block_on(items.forward(&mut replay)));
+    /// assert_eq!(replay.into_script(), script);
+    /// ```
+    pub struct Recorder<S, Item, E> {
+        #[pin]
+        inner: S,
+        recording: Recording<Item, E>,
+    }
+}
+
+// everything but the inner sink
+struct Recording<Item, E> {
+    script: Script<Item, E>,
+    // wakes of wakers passed to the inner sink
+    woken: Arc<AtomicUsize>,
+    seen_wakes: usize,
+    // the last waker from the context and its counting wrapper
+    waker: Option<(Waker, Waker)>,
+}
+
+struct CountingWaker {
+    inner: Waker,
+    woken: Arc<AtomicUsize>,
+}
+
+impl Wake for CountingWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.woken.fetch_add(1, Ordering::SeqCst);
+        self.inner.wake_by_ref();
+    }
+}
+
+impl<S, Item, E> Recorder<S, Item, E> {
+    /// Wrap `inner` sink recording every operation.
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            recording: Recording {
+                script: Script::new(Vec::new()),
+                woken: Arc::new(AtomicUsize::new(0)),
+                seen_wakes: 0,
+                waker: None,
+            },
+        }
+    }
+
+    /// Script recorded so far.
+    pub fn script(&self) -> &Script<Item, E> {
+        &self.recording.script
+    }
+
+    /// Consume the recorder returning recorded script.
+    pub fn into_script(self) -> Script<Item, E> {
+        self.recording.script
+    }
+
+    inner_accessors!(S, "the inner sink");
+
+    /// Poll the inner sink with a waker counting wakes.
+    fn poll_inner<F>(self: Pin<&mut Self>, cx: &mut Context<'_>, f: F) -> Poll<Result<(), E>>
+    where
+        F: FnOnce(Pin<&mut S>, &mut Context<'_>) -> Poll<Result<(), E>>,
+    {
+        let this = self.project();
+        let waker = this.recording.waker(cx.waker());
+        f(this.inner, &mut Context::from_waker(waker))
+    }
+}
+
+impl<Item, E> Recording<Item, E> {
+    /// Note wakes since the last step.
+    fn wakes(&mut self) {
+        let woken = self.woken.load(Ordering::SeqCst);
+        for _ in self.seen_wakes..woken {
+            self.script.wakes.push(self.script.steps.len());
+        }
+        self.seen_wakes = woken;
+    }
+
+    /// Waker counting wakes of the one from the context.
+    fn waker(&mut self, cx_waker: &Waker) -> &Waker {
+        self.wakes();
+        match self.waker {
+            // reused, so the inner sink can skip replacing a stored waker
+            Some((ref inner, _)) if inner.will_wake(cx_waker) => (),
+            _ => {
+                let counting = Waker::from(Arc::new(CountingWaker {
+                    inner: cx_waker.clone(),
+                    woken: self.woken.clone(),
+                }));
+                self.waker = Some((cx_waker.clone(), counting));
+            }
+        }
+        &self.waker.as_ref().unwrap().1
+    }
+
+    fn record(&mut self, event: Event<Item>, poll: &Poll<Result<(), E>>)
+    where
+        E: Clone,
+    {
+        let response = match poll {
+            Poll::Ready(Ok(())) => Response::Ok,
+            Poll::Ready(Err(e)) => Response::Err(e.clone()),
+            Poll::Pending => Response::Pending,
+        };
+        self.script.steps.push((event, response));
+        self.wakes();
+    }
+}
+
+impl<S, Item, E> Sink<Item> for Recorder<S, Item, E>
+where
+    S: Sink<Item, Error = E>,
+    Item: Clone,
+    E: Clone,
+{
+    type Error = E;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let poll = self
+            .as_mut()
+            .poll_inner(cx, |inner, cx| inner.poll_ready(cx));
+        self.project().recording.record(Event::Ready, &poll);
+        wrapper_event!("recorder", "poll_ready", &poll);
+        poll
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Item) -> Result<(), Self::Error> {
+        let this = self.project();
+        let event = Event::Send(item.clone());
+        this.recording.wakes();
+        let res = this.inner.start_send(item);
+        this.recording.record(event, &Poll::Ready(res.clone()));
+        wrapper_event!("recorder", "start_send", &res);
+        res
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let poll = self
+            .as_mut()
+            .poll_inner(cx, |inner, cx| inner.poll_flush(cx));
+        self.project().recording.record(Event::Flush, &poll);
+        wrapper_event!("recorder", "poll_flush", &poll);
+        poll
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let poll = self
+            .as_mut()
+            .poll_inner(cx, |inner, cx| inner.poll_close(cx));
+        self.project().recording.record(Event::Close, &poll);
+        wrapper_event!("recorder", "poll_close", &poll);
+        poll
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SinkMock;
+    use futures::{
+        executor::block_on,
+        stream::{self, StreamExt},
+    };
+    use std::iter;
+
+    #[test]
+    fn record_replay_round_trip() {
+        let feedback = vec![Poll::Ready(Ok(())), Poll::Pending, Poll::Ready(Err("io"))]
+            .into_iter()
+            .cycle();
+        let items = stream::iter(vec![Ok(5u8), Ok(7), Ok(9), Ok(77), Ok(79)]);
+
+        let mut recorder = Recorder::new(SinkMock::with_flush_feedback(feedback));
+        assert_eq!(Err("io"), block_on(items.clone().forward(&mut recorder)));
+        let script = recorder.into_script();
+        assert_eq!(script.steps()[0], (Event::Ready, Response::Ok));
+        assert_eq!(script.steps()[1], (Event::Send(5), Response::Ok));
+        // `forward()` flushes on close
+        assert_eq!(
+            script.steps().last(),
+            Some(&(Event::Close, Response::Err("io")))
+        );
+
+        let mut replay = Recorder::new(script.clone().into_mock());
+        assert_eq!(Err("io"), block_on(items.forward(&mut replay)));
+        assert_eq!(replay.into_script(), script);
+    }
+
+    #[test]
+    fn hand_written_script() {
+        let script: Script<u8, ()> = vec![
+            (Event::Ready, Response::Pending),
+            (Event::Ready, Response::Ok),
+            (Event::Send(1), Response::Ok),
+            (Event::Close, Response::Ok),
+        ]
+        .into_iter()
+        .collect();
+        let mut replay = Recorder::new(script.clone().into_mock());
+        let items = stream::iter(vec![Ok(1u8)]);
+        assert_eq!(Ok(()), block_on(items.forward(&mut replay)));
+        assert_eq!(replay.into_script(), script);
+    }
+
+    #[test]
+    fn delegates_unchanged() {
+        let mock = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, ()>(()))));
+        let mut recorder = Recorder::new(mock);
+        let items = stream::iter((0..10u8).map(Ok));
+        assert_eq!(Ok(()), block_on(items.forward(&mut recorder)));
+        let sends = recorder
+            .script()
+            .steps()
+            .iter()
+            .filter(|(event, _)| matches!(event, Event::Send(_)))
+            .count();
+        assert_eq!(sends, 10);
+        assert_eq!(
+            recorder.script().steps().last(),
+            Some(&(Event::Close, Response::Ok))
+        );
+    }
+}
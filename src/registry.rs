@@ -0,0 +1,139 @@
+use crate::counters::Stats;
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+
+/// Collection of named mocks summarized in one report.
+///
+/// Add a mock with [SinkMock::register()](crate::SinkMock::register). The registry holds only the
+/// counters of a mock, so mocks are moved and dropped as usual and a mock dropped during the test
+/// still shows its last state. [report()](TestRegistry::report) prints a table with a row per
+/// mock, [run()](TestRegistry::run) adds it to the message of a failing test.
+///
+/// Requires `std` feature.
+///
+/// # Examples
+/// ```
+/// use futures::{executor::block_on, stream, StreamExt};
+/// use futures_test_sink::{SinkMock, TestRegistry};
+/// use std::{iter, task::Poll};
+///
+/// let registry = TestRegistry::new();
+/// let mut primary = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Ok::<_, ()>(()))));
+/// primary.register(&registry, "primary");
+/// let mut replica = SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(Err("down"))));
+/// replica.register(&registry, "replica");
+///
+/// registry.run(|| {
+///     let items = stream::iter(vec![Ok(1u8), Ok(2)]);
+///     assert_eq!(Ok(()), block_on(items.forward(primary)));
+///     let items = stream::iter(vec![Ok(1u8)]);
+///     assert_eq!(Err("down"), block_on(items.forward(replica)));
+/// });
+/// assert_eq!(
+///     registry.report(),
+///     "\
+/// name     accepted  buffered  errors  pendings  closed
+/// primary         2         0       0         0  yes
+/// replica         1         1       1         0  no"
+/// );
+/// ```
+#[derive(Default)]
+pub struct TestRegistry {
+    mocks: RefCell<Vec<(String, Arc<dyn Stats>)>>,
+}
+
+impl TestRegistry {
+    /// Create an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn add(&self, name: String, stats: Arc<dyn Stats>) {
+        self.mocks.borrow_mut().push((name, stats));
+    }
+
+    /// Table with name, items accepted, items buffered, errors returned, `Poll::Pending` returned
+    /// and whether it's closed for every mock in order of registration.
+    pub fn report(&self) -> String {
+        let mocks = self.mocks.borrow();
+        let width = mocks
+            .iter()
+            .map(|(name, _)| name.chars().count())
+            .fold("name".len(), usize::max);
+        let mut out = format!(
+            "{:<width$}  accepted  buffered  errors  pendings  closed",
+            "name",
+            width = width
+        );
+        for (name, stats) in mocks.iter() {
+            let row = stats.row();
+            let _ = write!(
+                out,
+                "\n{:<width$}  {:>8}  {:>8}  {:>6}  {:>8}  {}",
+                name,
+                row.accepted,
+                row.buffered,
+                row.errors,
+                row.pendings,
+                if row.closed { "yes" } else { "no" },
+                width = width
+            );
+        }
+        out
+    }
+
+    /// Run `f` and if it panics, panic again with the [report()](TestRegistry::report) appended
+    /// to the message. A payload that isn't a string is replaced by the message `Box<dyn Any>`.
+    ///
+    /// Tests generated by [sink_conformance_tests!](crate::sink_conformance_tests) run in a
+    /// registry with the inner mock, so their failures end with its report too.
+    pub fn run<R>(&self, f: impl FnOnce() -> R) -> R {
+        match panic::catch_unwind(AssertUnwindSafe(f)) {
+            Ok(r) => r,
+            Err(payload) => {
+                // named like the payload in the standard panic message
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .map(|s| s.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_else(|| "Box<dyn Any>".to_string());
+                panic!("{}\n\nmocks:\n{}", message, self.report())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SinkMock;
+    use futures::{executor::block_on, FutureExt, SinkExt};
+    use std::{iter, task::Poll};
+
+    #[test]
+    #[should_panic(
+        expected = "closed too early\n\nmocks:\nname  accepted  buffered  errors  \
+                               pendings  closed\nslow         1         1       0         1  no"
+    )]
+    fn report_in_failure_message() {
+        let registry = TestRegistry::new();
+        let mut sink = SinkMock::with_flush_feedback(iter::repeat(Poll::Pending::<Result<_, ()>>));
+        sink.register(&registry, "slow");
+        registry.run(|| {
+            block_on(sink.feed(1u8)).unwrap();
+            assert_eq!(sink.flush().now_or_never(), None);
+            assert!(sink.counters().is_closed(), "closed too early");
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "Box<dyn Any>\n\nmocks:\nname  accepted")]
+    fn report_with_non_string_payload() {
+        let registry = TestRegistry::new();
+        let mut sink = SinkMock::<_, _, _, u8, ()>::with_flush_feedback(iter::empty());
+        sink.register(&registry, "idle");
+        registry.run(|| panic::panic_any(42));
+    }
+}
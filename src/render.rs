@@ -0,0 +1,159 @@
+use crate::{Event, Response, Script};
+use alloc::string::String;
+use core::fmt::{Debug, Write};
+
+/// Render `steps` one per line with `[wake]` lines at `wakes` and ` <-` after step `marker`.
+///
+/// `item` and `err` append the representation of an item or an error, if any.
+pub(crate) fn render_steps<Item, E>(
+    steps: &[(Event<Item>, Response<E>)],
+    wakes: &[usize],
+    marker: Option<usize>,
+    item: &dyn Fn(&mut String, &Item),
+    err: &dyn Fn(&mut String, &E),
+) -> String {
+    let mut out = String::new();
+    let mut wakes = wakes.iter().peekable();
+    let mut sent = 0;
+    for (i, (event, response)) in steps.iter().enumerate() {
+        while wakes.next_if(|&&at| at <= i).is_some() {
+            out.push_str("     [wake]\n");
+        }
+        let _ = write!(out, "{:>3}  ", i);
+        match event {
+            Event::Ready => out.push_str("ready"),
+            Event::Send(it) => {
+                let _ = write!(out, "send item#{}", sent);
+                item(&mut out, it);
+                sent += 1;
+            }
+            Event::Flush => out.push_str("flush"),
+            Event::Close => out.push_str("close"),
+        }
+        match response {
+            Response::Ok => out.push_str(" ✔"),
+            Response::Err(e) => {
+                out.push_str(" ✘");
+                err(&mut out, e);
+            }
+            Response::Pending => out.push_str(" … (pending)"),
+        }
+        if marker == Some(i) {
+            out.push_str(" <-");
+        }
+        out.push('\n');
+    }
+    for _ in wakes {
+        out.push_str("     [wake]\n");
+    }
+    out.pop();
+    out
+}
+
+impl<Item, E> Script<Item, E> {
+    /// Render the script as a compact timeline for failure messages.
+    ///
+    /// Every step is one line with its index, the operation and the outcome: `✔` for success,
+    /// `✘` for an error and `… (pending)`. Sent items are numbered as `item#N`. Wakes recorded
+    /// by [Recorder](crate::Recorder) are shown as `[wake]` lines between the steps. Items and
+    /// errors are not shown, use [render_debug()](Script::render_debug) for that.
+    ///
+    /// The format is meant for humans and may change.
+    ///
+    /// # Examples
+    /// ```
+    /// use futures::{executor::block_on, stream, StreamExt};
+    /// use futures_test_sink::{interleave_pending, Recorder};
+    ///
+    /// let mut recorder = Recorder::new(Box::pin(interleave_pending()));
+    /// let items = stream::iter(vec![Ok(1u8)]);
+    /// assert_eq!(Ok(()), block_on(items.forward(&mut recorder)));
+    /// assert_eq!(
+    ///     recorder.script().render(),
+    ///     "  0  ready ✔
+    ///   1  send item#0 ✔
+    ///   2  close … (pending)
+    ///      [wake]
+    ///   3  close ✔"
+    /// );
+    /// ```
+    pub fn render(&self) -> String {
+        render_steps(self.steps(), self.wakes(), None, &|_, _| (), &|_, _| ())
+    }
+
+    /// Like [render()](Script::render), but shows items and errors with `Debug`.
+    pub fn render_debug(&self) -> String
+    where
+        Item: Debug,
+        E: Debug,
+    {
+        render_steps(
+            self.steps(),
+            self.wakes(),
+            None,
+            &|out, item| {
+                let _ = write!(out, " {:?}", item);
+            },
+            &|out, e| {
+                let _ = write!(out, " {:?}", e);
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Recorder, SinkMock};
+    use futures::{executor::block_on, SinkExt};
+    use std::task::Poll;
+
+    #[test]
+    fn render_snapshot() {
+        let feedback = vec![
+            Poll::Pending,
+            Poll::Ready(Ok(())),
+            Poll::Ready(Err("reset")),
+        ];
+        let mut recorder = Recorder::new(SinkMock::with_flush_feedback(feedback.into_iter()));
+        block_on(async {
+            recorder.feed(5u8).await.unwrap();
+            recorder.feed(7).await.unwrap();
+            recorder.flush().await.unwrap();
+            assert_eq!(recorder.close().await, Err("reset"));
+        });
+        let script = recorder.into_script();
+        assert_eq!(
+            script.render(),
+            "  0  ready ✔
+  1  send item#0 ✔
+  2  ready ✔
+  3  send item#1 ✔
+  4  flush … (pending)
+     [wake]
+  5  flush ✔
+  6  close ✘"
+        );
+        assert_eq!(
+            script.render_debug(),
+            "  0  ready ✔
+  1  send item#0 5 ✔
+  2  ready ✔
+  3  send item#1 7 ✔
+  4  flush … (pending)
+     [wake]
+  5  flush ✔
+  6  close ✘ \"reset\""
+        );
+    }
+
+    #[test]
+    fn hand_written_script_has_no_wakes() {
+        let script: crate::Script<u8, ()> = vec![
+            (crate::Event::Ready, crate::Response::Pending),
+            (crate::Event::Ready, crate::Response::Ok),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(script.render(), "  0  ready … (pending)\n  1  ready ✔");
+    }
+}
@@ -0,0 +1,281 @@
+use crate::trace_diff::{rendered_step, RenderedStep, TraceDiff};
+use crate::wake_policy::Waking;
+use crate::{Event, Response, Script};
+use alloc::vec::{self, Vec};
+use core::fmt::Debug;
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures::sink::Sink;
+
+/// Sink that checks the driver performs exactly the operations of a recorded [Script].
+///
+/// Every operation is compared with the next recorded step, including the item passed to
+/// `start_send()`. On match the recorded outcome is returned, on `Response::Pending` the waker is
+/// woken before returning. Unlike [Script::into_mock()], which only reproduces outcomes, any
+/// difference fails the test immediately with the step index and the [diff](Script::diff) of the
+/// recording and the operations performed. Call [verify()](ReplaySink::verify) at the end to
+/// check that no recorded operation is missing.
+///
+/// # Panics
+/// - Operation or item differs from the recording.
+/// - The driver performs more operations than recorded.
+/// - `start_send()` recorded with `Response::Pending`.
+///
+/// # Examples
+/// ```
+/// use futures::{executor::block_on, stream, StreamExt};
+/// use futures_test_sink::{interleave_pending, Recorder, ReplaySink};
+///
+/// let items = stream::iter(vec![Ok(1u8), Ok(2)]);
+/// let mut recorder = Recorder::new(Box::pin(interleave_pending()));
+/// assert_eq!(Ok(()), block_on(items.clone().forward(&mut recorder)));
+///
+/// // the driver must do exactly the same again
+/// let mut replay = ReplaySink::new(recorder.into_script());
+/// assert_eq!(Ok(()), block_on(items.forward(&mut replay)));
+/// replay.verify();
+/// ```
+pub struct ReplaySink<Item, E> {
+    steps: vec::IntoIter<(Event<Item>, Response<E>)>,
+    position: usize,
+    len: usize,
+    waking: Waking,
+    // the recording for the report
+    recorded: Vec<RenderedStep>,
+}
+
+impl<Item, E> Unpin for ReplaySink<Item, E> {}
+
+impl<Item, E> ReplaySink<Item, E> {
+    /// Create a sink replaying `script`.
+    pub fn new(script: Script<Item, E>) -> Self
+    where
+        Item: Debug,
+        E: Debug,
+    {
+        let steps: Vec<_> = script.into_steps();
+        Self {
+            len: steps.len(),
+            recorded: steps
+                .iter()
+                .map(|(event, response)| rendered_step(event, response))
+                .collect(),
+            steps: steps.into_iter(),
+            position: 0,
+            waking: Waking::default(),
+        }
+    }
+
+    /// Diff of the recording and the steps performed so far followed by `event`.
+    fn diff(&self, event: Option<RenderedStep>) -> TraceDiff {
+        let mut performed = self.recorded[..self.position].to_vec();
+        performed.extend(event);
+        Script::new(self.recorded.clone()).diff(&Script::new(performed))
+    }
+
+    /// Index of the next expected step.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// `true` when all recorded steps were performed.
+    pub fn is_finished(&self) -> bool {
+        self.position == self.len
+    }
+
+    waking_setters! {
+        /// Set how the waker is woken before returning `Poll::Pending`.
+    }
+}
+
+impl<Item: Debug, E> ReplaySink<Item, E> {
+    /// Check that the whole recording was performed.
+    ///
+    /// # Panics
+    /// When some recorded steps are left, with the index and operation of the first of them.
+    pub fn verify(&self) {
+        if let Some((expected, _)) = self.steps.as_slice().first() {
+            panic!(
+                "ReplaySink missing operations: {} of {} recorded steps left, step {} expected `{:?}`\n{}",
+                self.len - self.position,
+                self.len,
+                self.position,
+                expected,
+                self.diff(None)
+            );
+        }
+    }
+}
+
+impl<Item: PartialEq + Debug, E: Debug> ReplaySink<Item, E> {
+    fn next_step(&mut self, event: Event<Item>) -> Response<E> {
+        let position = self.position;
+        match self.steps.next() {
+            Some((expected, response)) if expected == event => {
+                self.position += 1;
+                response
+            }
+            // the recorded outcome, only the operation differs
+            Some((expected, response)) => panic!(
+                "ReplaySink divergence at step {}: expected `{:?}`, but `{:?}` was performed\n{}",
+                position,
+                expected,
+                event,
+                self.diff(Some(rendered_step(&event, &response)))
+            ),
+            None => panic!(
+                "ReplaySink recording ended at step {}, but `{:?}` was performed\n{}",
+                position,
+                event,
+                self.diff(Some(rendered_step(&event, &Response::<E>::Ok)))
+            ),
+        }
+    }
+
+    fn poll_event(&mut self, event: Event<Item>, cx: &mut Context<'_>) -> Poll<Result<(), E>> {
+        self.waking.polled();
+        let res = match self.next_step(event) {
+            Response::Ok => Ok(()),
+            Response::Err(e) => Err(e),
+            Response::Pending => {
+                self.waking.pending(cx.waker());
+                return Poll::Pending;
+            }
+        };
+        self.waking.ready(cx.waker());
+        Poll::Ready(res)
+    }
+}
+
+impl<Item: PartialEq + Debug, E: Debug> Sink<Item> for ReplaySink<Item, E> {
+    type Error = E;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::into_inner(self).poll_event(Event::Ready, cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Item) -> Result<(), Self::Error> {
+        match Pin::into_inner(self).next_step(Event::Send(item)) {
+            Response::Ok => Ok(()),
+            Response::Err(e) => Err(e),
+            Response::Pending => panic!("`start_send()` can't return `Response::Pending`"),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::into_inner(self).poll_event(Event::Flush, cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::into_inner(self).poll_event(Event::Close, cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Recorder, SinkMock};
+    use futures::{
+        executor::block_on,
+        stream::{self, StreamExt},
+        SinkExt,
+    };
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    fn recorded() -> Script<u8, &'static str> {
+        let feedback = vec![Poll::Ready(Ok(())), Poll::Pending].into_iter().cycle();
+        let mut recorder = Recorder::new(SinkMock::with_flush_feedback(feedback));
+        let items = stream::iter(vec![Ok(1u8), Ok(2), Ok(3)]);
+        assert_eq!(Ok(()), block_on(items.forward(&mut recorder)));
+        recorder.into_script()
+    }
+
+    #[test]
+    fn same_driver_passes() {
+        let mut replay = ReplaySink::new(recorded());
+        let items = stream::iter(vec![Ok(1u8), Ok(2), Ok(3)]);
+        assert_eq!(Ok(()), block_on(items.forward(&mut replay)));
+        assert!(replay.is_finished());
+        replay.verify();
+    }
+
+    #[test]
+    fn changed_driver_reports_divergence() {
+        // the driver was changed to flush after every item
+        let mut replay = ReplaySink::new(recorded());
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            block_on(async {
+                for item in 1..=3u8 {
+                    replay.feed(item).await?;
+                    replay.flush().await?;
+                }
+                replay.close().await
+            })
+        }));
+        let msg = *result.unwrap_err().downcast::<String>().unwrap();
+        assert_eq!(
+            msg,
+            "ReplaySink divergence at step 2: expected `Ready`, but `Flush` was performed
+traces differ at step 2: 6 removed, 1 added
+    0  ready ✔
+    1  send item#0 1 ✔
+-   2  ready ✔
+-   3  send item#1 2 ✔
+-   4  ready ✔
+-   5  send item#2 3 ✔
+-   6  close … (pending)
+-   7  close ✔
++   2  flush ✔"
+        );
+    }
+
+    #[test]
+    fn changed_item_reports_divergence() {
+        let mut replay = ReplaySink::new(recorded());
+        let items = stream::iter(vec![Ok(1u8), Ok(5), Ok(3)]);
+        let result = catch_unwind(AssertUnwindSafe(|| block_on(items.forward(&mut replay))));
+        let msg = *result.unwrap_err().downcast::<String>().unwrap();
+        assert_eq!(
+            msg,
+            "ReplaySink divergence at step 3: expected `Send(2)`, but `Send(5)` was performed
+traces differ at step 3: 5 removed, 1 added
+    0  ready ✔
+    1  send item#0 1 ✔
+    2  ready ✔
+-   3  send item#1 2 ✔
+-   4  ready ✔
+-   5  send item#2 3 ✔
+-   6  close … (pending)
+-   7  close ✔
++   3  send item#1 5 ✔"
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ReplaySink recording ended at step 2, but `Ready` was performed")]
+    fn extra_operation_panics() {
+        let script = Script::<u8, ()>::new(vec![
+            (Event::Ready, Response::Ok),
+            (Event::Send(1), Response::Ok),
+        ]);
+        let mut replay = ReplaySink::new(script);
+        let _ = block_on(replay.send_all(&mut stream::iter(vec![Ok(1u8), Ok(2)])));
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "ReplaySink missing operations: 1 of 3 recorded steps left, step 2 expected `Close`"
+    )]
+    fn verify_missing_operations() {
+        let script = Script::<u8, ()>::new(vec![
+            (Event::Ready, Response::Ok),
+            (Event::Send(1), Response::Ok),
+            (Event::Close, Response::Ok),
+        ]);
+        let mut replay = ReplaySink::new(script);
+        assert_eq!(Ok(()), block_on(replay.feed(1)));
+        replay.verify();
+    }
+}
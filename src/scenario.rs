@@ -0,0 +1,336 @@
+use futures::{sink::Sink, stream::Stream};
+use std::collections::VecDeque;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// One step of the script shared by [ScenarioStream] and [ScenarioSink].
+///
+/// `Emit*`, `SourcePending` and `SourceEnd` are consumed by `poll_next()` of the stream half.
+/// `Sink*` steps are consumed by the method of the sink half they are named after.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Scenario<Item, E> {
+    /// `poll_next()` returns `Poll::Ready(Some(Ok(item)))`.
+    EmitItem(Item),
+    /// `poll_next()` returns `Poll::Ready(Some(Err(e)))`.
+    EmitError(E),
+    /// `poll_next()` returns `Poll::Pending`.
+    SourcePending,
+    /// `poll_next()` returns `Poll::Ready(None)`.
+    SourceEnd,
+    /// `poll_ready()` returns `Poll::Ready(Ok(()))`.
+    SinkReadyOk,
+    /// `poll_ready()` returns `Poll::Pending`.
+    SinkReadyPending,
+    /// `poll_ready()` returns `Poll::Ready(Err(e))`.
+    SinkReadyErr(E),
+    /// `start_send()` returns `Ok(())`.
+    SinkSendOk,
+    /// `start_send()` returns `Err(e)`.
+    SinkSendErr(E),
+    /// `poll_flush()` returns `Poll::Ready(Ok(()))`.
+    SinkFlushOk,
+    /// `poll_flush()` returns `Poll::Pending`.
+    SinkFlushPending,
+    /// `poll_flush()` returns `Poll::Ready(Err(e))`.
+    SinkFlushErr(E),
+    /// `poll_close()` returns `Poll::Ready(Ok(()))`.
+    SinkCloseOk,
+    /// `poll_close()` returns `Poll::Pending`.
+    SinkClosePending,
+    /// `poll_close()` returns `Poll::Ready(Err(e))`.
+    SinkCloseErr(E),
+}
+
+impl<Item, E> Scenario<Item, E> {
+    fn method(&self) -> &'static str {
+        match self {
+            Scenario::EmitItem(_)
+            | Scenario::EmitError(_)
+            | Scenario::SourcePending
+            | Scenario::SourceEnd => "poll_next",
+            Scenario::SinkReadyOk | Scenario::SinkReadyPending | Scenario::SinkReadyErr(_) => {
+                "poll_ready"
+            }
+            Scenario::SinkSendOk | Scenario::SinkSendErr(_) => "start_send",
+            Scenario::SinkFlushOk | Scenario::SinkFlushPending | Scenario::SinkFlushErr(_) => {
+                "poll_flush"
+            }
+            Scenario::SinkCloseOk | Scenario::SinkClosePending | Scenario::SinkCloseErr(_) => {
+                "poll_close"
+            }
+        }
+    }
+}
+
+struct Script<Item, E> {
+    steps: VecDeque<Scenario<Item, E>>,
+    position: usize,
+}
+
+type Shared<Item, E> = Arc<Mutex<Script<Item, E>>>;
+
+/// Take next step of the script checking it's expected by `method`.
+fn next_step<Item, E>(script: &Shared<Item, E>, method: &str) -> Scenario<Item, E> {
+    let divergence = {
+        let mut script = script.lock().unwrap();
+        let position = script.position;
+        match script.steps.pop_front() {
+            Some(step) if step.method() == method => {
+                script.position += 1;
+                return step;
+            }
+            Some(step) => format!(
+                "ScenarioMock divergence at script position {}: expected `{}`, but `{}` was called",
+                position,
+                step.method(),
+                method
+            ),
+            None => format!(
+                "ScenarioMock script ended at position {}, but `{}` was called",
+                position, method
+            ),
+        }
+    };
+    // panic after the lock is released, so the handle can still report the position
+    panic!("{}", divergence)
+}
+
+/// Stream half of [scenario_mock()].
+///
+/// [scenario_mock()]:scenario_mock
+pub struct ScenarioStream<Item, E> {
+    script: Shared<Item, E>,
+}
+
+/// Sink half of [scenario_mock()].
+///
+/// [scenario_mock()]:scenario_mock
+pub struct ScenarioSink<Item, E> {
+    script: Shared<Item, E>,
+    item_type: PhantomData<fn(Item)>,
+}
+
+/// Create a stream and a sink that consume one shared script.
+///
+/// Every call to `poll_next()` of the stream or any method of the sink takes the next [Scenario]
+/// step. If the step belongs to a different method, or the script ended, it panics with the script
+/// position. This makes interleaving of the source and the destination explicit in tests of
+/// combinators like [forward()](futures::stream::StreamExt::forward).
+///
+/// When a step returns `Poll::Pending` the waker is woken before returning like in
+/// [from_iter()](crate::from_iter).
+///
+/// Items sent to the sink are discarded. Use [ScenarioHandle::is_finished()] to check that the whole
+/// script was consumed.
+///
+/// Requires `std` feature.
+///
+/// # Examples
+/// ```
+/// use futures::{executor::block_on, never::Never, stream::StreamExt};
+/// use futures_test_sink::{scenario_mock, Scenario::*};
+///
+/// let (stream, sink) = scenario_mock::<u8, Never>(vec![
+///     EmitItem(1),
+///     SinkReadyOk,
+///     SinkSendOk,
+///     // forward flush the sink when source is pending
+///     SourcePending,
+///     SinkFlushPending,
+///     SourcePending,
+///     SinkFlushOk,
+///     SourceEnd,
+///     SinkCloseOk,
+/// ]);
+/// let check = stream.handle();
+/// assert_eq!(Ok(()), block_on(stream.forward(sink)));
+/// assert!(check.is_finished());
+/// ```
+pub fn scenario_mock<Item, E>(
+    script: impl IntoIterator<Item = Scenario<Item, E>>,
+) -> (ScenarioStream<Item, E>, ScenarioSink<Item, E>) {
+    let script = Arc::new(Mutex::new(Script {
+        steps: script.into_iter().collect(),
+        position: 0,
+    }));
+    (
+        ScenarioStream {
+            script: script.clone(),
+        },
+        ScenarioSink {
+            script,
+            item_type: Default::default(),
+        },
+    )
+}
+
+/// Handle to check progress of [scenario_mock()] script after both halves were moved.
+///
+/// [scenario_mock()]:scenario_mock
+pub struct ScenarioHandle<Item, E> {
+    script: Shared<Item, E>,
+}
+
+impl<Item, E> ScenarioStream<Item, E> {
+    /// Create handle that can check progress of the script after this half is moved.
+    pub fn handle(&self) -> ScenarioHandle<Item, E> {
+        ScenarioHandle {
+            script: self.script.clone(),
+        }
+    }
+}
+
+impl<Item, E> ScenarioSink<Item, E> {
+    /// Create handle that can check progress of the script after this half is moved.
+    pub fn handle(&self) -> ScenarioHandle<Item, E> {
+        ScenarioHandle {
+            script: self.script.clone(),
+        }
+    }
+}
+
+impl<Item, E> ScenarioHandle<Item, E> {
+    /// Position of the next step in the script.
+    pub fn position(&self) -> usize {
+        self.script.lock().unwrap().position
+    }
+
+    /// `true` when all steps of the script were consumed.
+    pub fn is_finished(&self) -> bool {
+        self.script.lock().unwrap().steps.is_empty()
+    }
+}
+
+impl<Item, E> Unpin for ScenarioStream<Item, E> {}
+impl<Item, E> Unpin for ScenarioSink<Item, E> {}
+
+fn pending<T>(cx: &mut Context<'_>) -> Poll<T> {
+    cx.waker().wake_by_ref();
+    Poll::Pending
+}
+
+impl<Item, E> Stream for ScenarioStream<Item, E> {
+    type Item = Result<Item, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match next_step(&self.script, "poll_next") {
+            Scenario::EmitItem(item) => Poll::Ready(Some(Ok(item))),
+            Scenario::EmitError(e) => Poll::Ready(Some(Err(e))),
+            Scenario::SourceEnd => Poll::Ready(None),
+            _ => pending(cx),
+        }
+    }
+}
+
+impl<Item, E> Sink<Item> for ScenarioSink<Item, E> {
+    type Error = E;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match next_step(&self.script, "poll_ready") {
+            Scenario::SinkReadyOk => Poll::Ready(Ok(())),
+            Scenario::SinkReadyErr(e) => Poll::Ready(Err(e)),
+            _ => pending(cx),
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, _item: Item) -> Result<(), Self::Error> {
+        match next_step(&self.script, "start_send") {
+            Scenario::SinkSendErr(e) => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match next_step(&self.script, "poll_flush") {
+            Scenario::SinkFlushOk => Poll::Ready(Ok(())),
+            Scenario::SinkFlushErr(e) => Poll::Ready(Err(e)),
+            _ => pending(cx),
+        }
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match next_step(&self.script, "poll_close") {
+            Scenario::SinkCloseOk => Poll::Ready(Ok(())),
+            Scenario::SinkCloseErr(e) => Poll::Ready(Err(e)),
+            _ => pending(cx),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Scenario::*;
+    use super::*;
+    use futures::{executor::block_on, never::Never, stream::StreamExt};
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    type Case = (Vec<Scenario<u8, &'static str>>, Result<(), &'static str>);
+
+    #[test]
+    fn forward_table() {
+        let table: Vec<Case> = vec![
+            (vec![SourceEnd, SinkCloseOk], Ok(())),
+            (
+                vec![
+                    EmitItem(1),
+                    SinkReadyPending,
+                    SinkReadyOk,
+                    SinkSendOk,
+                    SourceEnd,
+                    SinkClosePending,
+                    SinkCloseOk,
+                ],
+                Ok(()),
+            ),
+            (
+                vec![EmitItem(1), SinkReadyOk, SinkSendErr("send")],
+                Err("send"),
+            ),
+            (
+                vec![EmitItem(1), SinkReadyOk, SinkSendOk, EmitError("source")],
+                Err("source"),
+            ),
+            (vec![SourcePending, SinkFlushErr("flush")], Err("flush")),
+        ];
+
+        for (script, expected) in table {
+            let (stream, sink) = scenario_mock(script);
+            let handle = sink.handle();
+            assert_eq!(expected, block_on(stream.forward(sink)));
+            assert!(handle.is_finished());
+        }
+    }
+
+    #[test]
+    fn divergence_panics_with_position() {
+        let (stream, sink) = scenario_mock::<u8, Never>(vec![EmitItem(1), SinkFlushOk]);
+        let result = catch_unwind(AssertUnwindSafe(|| block_on(stream.forward(sink))));
+        let msg = *result.unwrap_err().downcast::<String>().unwrap();
+        assert_eq!(
+            msg,
+            "ScenarioMock divergence at script position 1: expected `poll_flush`, but `poll_ready` was called"
+        );
+    }
+
+    #[test]
+    fn handle_reports_position_after_divergence() {
+        let (stream, sink) = scenario_mock::<u8, Never>(vec![EmitItem(1), SinkFlushOk]);
+        let handle = sink.handle();
+        let result = catch_unwind(AssertUnwindSafe(|| block_on(stream.forward(sink))));
+        assert!(result.is_err());
+        assert_eq!(handle.position(), 1);
+    }
+
+    #[test]
+    #[should_panic(
+        expected = "ScenarioMock script ended at position 2, but `poll_next` was called"
+    )]
+    fn script_end_panics() {
+        let (stream, sink) = scenario_mock::<u8, Never>(vec![SourcePending, SinkFlushOk]);
+        let _ = block_on(stream.forward(sink));
+    }
+}
@@ -0,0 +1,204 @@
+use core::marker::PhantomData;
+use core::num::NonZeroUsize;
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures::{never::Never, sink::Sink};
+
+use crate::wake_policy::Waking;
+
+/// Sink that needs a fixed number of polls to complete every flush.
+///
+/// For details see [slow_consumer()].
+///
+/// [slow_consumer()]:slow_consumer
+pub struct SlowConsumer<Item> {
+    polls_per_flush: usize,
+    capacity: usize,
+    countdown: usize,
+    buffered: usize,
+    poll_cnt: usize,
+    flush_cnt: usize,
+    is_closed: bool,
+    waking: Waking,
+    item_type: PhantomData<fn(Item)>,
+}
+
+/// Create a sink modeling I/O device with fixed service time.
+///
+/// Up to `capacity` items are buffered. Flushing buffered items takes `polls_per_flush` calls
+/// to `poll_flush()`: every call decrements a countdown, wakes the waker as set by
+/// [wake_policy()](SlowConsumer::wake_policy) and returns `Poll::Pending`. The call that brings
+/// the countdown to zero drains the whole buffer and returns `Poll::Ready(Ok(()))`. Flush of an
+/// empty buffer is ready immediately.
+///
+/// `poll_ready()` flushes the same way when the buffer is full and `poll_close()` flushes before
+/// closing. Their polls are counted as flush polls too.
+///
+/// This gives deterministic "work per flush" without authoring long `Poll::Pending` scripts.
+///
+/// # Examples
+/// ```
+/// use futures::{executor::block_on, stream, StreamExt};
+/// use futures_test_sink::slow_consumer;
+/// use std::num::NonZeroUsize;
+///
+/// let mut sink = slow_consumer(NonZeroUsize::new(3).unwrap(), 2);
+/// let items = stream::iter(vec![Ok(1u8), Ok(2), Ok(3), Ok(4)]);
+/// assert_eq!(Ok(()), block_on(items.forward(&mut sink)));
+/// // buffer full after 2 items and then flushed on close
+/// assert_eq!(sink.flush_count(), 2);
+/// assert_eq!(sink.poll_count(), 6);
+/// ```
+pub fn slow_consumer<Item>(polls_per_flush: NonZeroUsize, capacity: usize) -> SlowConsumer<Item> {
+    SlowConsumer {
+        polls_per_flush: polls_per_flush.get(),
+        capacity,
+        countdown: polls_per_flush.get(),
+        buffered: 0,
+        poll_cnt: 0,
+        flush_cnt: 0,
+        is_closed: false,
+        waking: Waking::default(),
+        item_type: Default::default(),
+    }
+}
+
+impl<Item> Unpin for SlowConsumer<Item> {}
+
+impl<Item> SlowConsumer<Item> {
+    /// How many times the buffer was flushed (polled).
+    pub fn poll_count(&self) -> usize {
+        self.poll_cnt
+    }
+
+    /// How many flushes completed and drained the buffer.
+    pub fn flush_count(&self) -> usize {
+        self.flush_cnt
+    }
+
+    /// How many items are buffered now.
+    pub fn buffered(&self) -> usize {
+        self.buffered
+    }
+
+    waking_setters! {
+        /// Set how the waker is woken before returning `Poll::Pending`.
+    }
+
+    fn check_panic(&self) {
+        if self.is_closed {
+            panic!("Trying use closed sink");
+        }
+    }
+
+    fn flush_inner(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Never>> {
+        self.poll_cnt += 1;
+        if self.buffered == 0 {
+            self.waking.ready(cx.waker());
+            return Poll::Ready(Ok(()));
+        }
+        self.countdown -= 1;
+        if self.countdown == 0 {
+            self.countdown = self.polls_per_flush;
+            self.buffered = 0;
+            self.flush_cnt += 1;
+            self.waking.ready(cx.waker());
+            Poll::Ready(Ok(()))
+        } else {
+            self.waking.pending(cx.waker());
+            Poll::Pending
+        }
+    }
+}
+
+impl<Item> Sink<Item> for SlowConsumer<Item> {
+    type Error = Never;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.check_panic();
+        let this = Pin::into_inner(self);
+        this.waking.polled();
+        if this.buffered < this.capacity {
+            this.waking.ready(cx.waker());
+            Poll::Ready(Ok(()))
+        } else {
+            this.flush_inner(cx)
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, _item: Item) -> Result<(), Self::Error> {
+        self.check_panic();
+        let this = Pin::into_inner(self);
+        if this.buffered >= this.capacity {
+            panic!("`start_send()` called when the buffer is full");
+        }
+        this.buffered += 1;
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.check_panic();
+        let this = Pin::into_inner(self);
+        this.waking.polled();
+        this.flush_inner(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.check_panic();
+        let this = Pin::into_inner(self);
+        this.waking.polled();
+        futures::ready!(this.flush_inner(cx))?;
+        this.is_closed = true;
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_task::waker_fn;
+    use std::sync::{atomic, Arc};
+
+    #[test]
+    fn countdown_per_flush() {
+        // create a Context
+        let wake_cnt = Arc::new(atomic::AtomicUsize::new(0));
+        let cnt = wake_cnt.clone();
+        let waker = waker_fn(move || {
+            wake_cnt.fetch_add(1, atomic::Ordering::SeqCst);
+        });
+        let mut cx = Context::from_waker(&waker);
+        // actual test
+        let mut s = slow_consumer(NonZeroUsize::new(3).unwrap(), 1);
+        // empty buffer
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(s.flush_count(), 0);
+
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(Pin::new(&mut s).start_send(1), Ok(()));
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Pending);
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Pending);
+        assert_eq!(2, cnt.load(atomic::Ordering::SeqCst));
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(s.buffered(), 0);
+        assert_eq!(s.flush_count(), 1);
+        assert_eq!(s.poll_count(), 4);
+
+        // countdown restarted
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(Pin::new(&mut s).start_send(2), Ok(()));
+        assert_eq!(Pin::new(&mut s).poll_close(&mut cx), Poll::Pending);
+        assert_eq!(Pin::new(&mut s).poll_close(&mut cx), Poll::Pending);
+        assert_eq!(Pin::new(&mut s).poll_close(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(s.flush_count(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "`start_send()` called when the buffer is full")]
+    fn send_to_full_buffer() {
+        let mut s = slow_consumer(NonZeroUsize::new(1).unwrap(), 0);
+        let _ = Pin::new(&mut s).start_send(1);
+    }
+}
@@ -0,0 +1,76 @@
+use alloc::vec::{self, Vec};
+use core::task::Poll;
+
+/// Outcome of one flush of [SinkMock::scripted()](crate::SinkMock::scripted).
+///
+/// Non-blocking I/O maps to the steps as:
+///
+/// | I/O                               | step                  | returned                  |
+/// |-----------------------------------|-----------------------|---------------------------|
+/// | wrote the items                   | [Step::Ready]         | `Poll::Ready(Ok(()))`     |
+/// | `WouldBlock`, task woken later    | [Step::Pending]       | `Poll::Pending`           |
+/// | `WouldBlock`, queued for a retry  | [Step::WouldBlock]    | `Poll::Ready(Ok(()))`     |
+/// | failed                            | [Step::Err]           | `Poll::Ready(Err(e))`     |
+///
+/// Code under test can't tell [Step::WouldBlock] from [Step::Ready] by the result, the test can
+/// with [SinkMock::would_block_count()](crate::SinkMock::would_block_count).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step<E> {
+    /// Flush all buffered items and return `Poll::Ready(Ok(()))`.
+    Ready,
+    /// Wake the waker and return `Poll::Pending`, the items stay buffered.
+    Pending,
+    /// Return `Poll::Ready(Err(e))`, the items stay buffered.
+    Err(E),
+    /// Flush all buffered items and return `Poll::Ready(Ok(()))` like [Step::Ready], but count
+    /// that the I/O would have blocked, e.g. a writer queueing the items to retry them itself.
+    WouldBlock,
+}
+
+impl<E> From<Step<E>> for Poll<Result<(), E>> {
+    fn from(step: Step<E>) -> Self {
+        match step {
+            Step::Ready | Step::WouldBlock => Poll::Ready(Ok(())),
+            Step::Pending => Poll::Pending,
+            Step::Err(e) => Poll::Ready(Err(e)),
+        }
+    }
+}
+
+/// `flush_feedback` of [SinkMock::scripted()](crate::SinkMock::scripted).
+#[derive(Debug, Clone)]
+pub struct Steps<E> {
+    steps: vec::IntoIter<Step<E>>,
+    would_block_cnt: usize,
+}
+
+impl<E> Steps<E> {
+    pub(crate) fn new(steps: Vec<Step<E>>) -> Self {
+        Self {
+            steps: steps.into_iter(),
+            would_block_cnt: 0,
+        }
+    }
+
+    /// Steps not taken yet.
+    pub fn remaining(&self) -> &[Step<E>] {
+        self.steps.as_slice()
+    }
+
+    /// How many [Step::WouldBlock] steps were taken.
+    pub fn would_block_count(&self) -> usize {
+        self.would_block_cnt
+    }
+}
+
+impl<E> Iterator for Steps<E> {
+    type Item = Poll<Result<(), E>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let step = self.steps.next()?;
+        if let Step::WouldBlock = step {
+            self.would_block_cnt += 1;
+        }
+        Some(step.into())
+    }
+}
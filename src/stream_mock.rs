@@ -0,0 +1,234 @@
+//! Scripted streams for the source side of tests.
+//!
+//! [StreamExt::forward()](futures::stream::StreamExt::forward) and similar combinators take items
+//! from a `TryStream`. Mocks in this module script that side, so both halves of the contract can
+//! be tested together with sink mocks.
+//!
+//! # Examples
+//! ```
+//! use futures::{executor::block_on, SinkExt, StreamExt};
+//! use futures_test_sink::{ok, stream_mock::try_from_results};
+//! use std::task::Poll;
+//!
+//! let script = vec![
+//!     Poll::Ready(Some(Ok(1u8))),
+//!     Poll::Pending,
+//!     Poll::Ready(Some(Err("broken source"))),
+//! ];
+//! let sink = ok().sink_map_err(|e| match e {});
+//! assert_eq!(
+//!     Err("broken source"),
+//!     block_on(try_from_results(script.into_iter()).forward(sink))
+//! );
+//! ```
+
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures::stream::{FusedStream, Stream};
+
+use crate::wake_policy::Waking;
+
+/// Stream returned by [try_from_results()].
+pub struct TryFromResults<I> {
+    script: I,
+    polled: usize,
+    is_terminated: bool,
+    waking: Waking,
+}
+
+impl<I> Unpin for TryFromResults<I> {}
+
+impl<I> TryFromResults<I> {
+    /// How many times `poll_next()` was called.
+    pub fn poll_count(&self) -> usize {
+        self.polled
+    }
+
+    waking_setters! {
+        /// Set how the waker is woken before returning `Poll::Pending`.
+    }
+}
+
+/// Create a `TryStream` that returns outcomes of `poll_next()` from `script`.
+///
+/// When the script yields `Poll::Pending` the waker is woken before returning like in
+/// [from_iter()](crate::from_iter), or as set by [wake_policy()](TryFromResults::wake_policy).
+/// After `Poll::Ready(None)` the stream is terminated and the script is not consulted anymore.
+///
+/// # Panics
+/// When `script` ends before the stream returned `Poll::Ready(None)`.
+pub fn try_from_results<Item, E, I>(script: I) -> TryFromResults<I>
+where
+    I: Iterator<Item = Poll<Option<Result<Item, E>>>>,
+{
+    TryFromResults {
+        script,
+        polled: 0,
+        is_terminated: false,
+        waking: Waking::default(),
+    }
+}
+
+impl<Item, E, I> Stream for TryFromResults<I>
+where
+    I: Iterator<Item = Poll<Option<Result<Item, E>>>>,
+{
+    type Item = Result<Item, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = Pin::into_inner(self);
+        if this.is_terminated {
+            return Poll::Ready(None);
+        }
+        this.polled += 1;
+        this.waking.polled();
+        match this.script.next() {
+            Some(Poll::Ready(item)) => {
+                this.is_terminated = item.is_none();
+                this.waking.ready(cx.waker());
+                Poll::Ready(item)
+            }
+            Some(Poll::Pending) => {
+                this.waking.pending(cx.waker());
+                Poll::Pending
+            }
+            None => panic!(
+                "Unexpected end of `script` iterator after {} polls!",
+                this.polled - 1
+            ),
+        }
+    }
+}
+
+impl<Item, E, I> FusedStream for TryFromResults<I>
+where
+    I: Iterator<Item = Poll<Option<Result<Item, E>>>>,
+{
+    fn is_terminated(&self) -> bool {
+        self.is_terminated
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Event, Recorder, SinkMock, WakePolicy};
+    use futures::{executor::block_on, SinkExt, StreamExt};
+    use std::iter;
+
+    type Mock = SinkMock<
+        iter::Repeat<Poll<Result<(), &'static str>>>,
+        iter::Empty<&'static str>,
+        iter::Empty<&'static str>,
+        u8,
+        &'static str,
+    >;
+
+    fn recorder() -> Recorder<Mock, u8, &'static str> {
+        Recorder::new(SinkMock::with_flush_feedback(iter::repeat(Poll::Ready(
+            Ok(()),
+        ))))
+    }
+
+    fn script() -> impl Iterator<Item = Poll<Option<Result<u8, &'static str>>>> {
+        vec![
+            Poll::Ready(Some(Ok(1))),
+            Poll::Ready(Some(Ok(2))),
+            Poll::Ready(Some(Err("source"))),
+        ]
+        .into_iter()
+    }
+
+    #[test]
+    fn forward_doesnt_flush_on_stream_error() {
+        let mut sink = recorder();
+        let stream = try_from_results(script());
+        assert_eq!(Err("source"), block_on(stream.forward(&mut sink)));
+        let script = sink.into_script();
+        let (last, _) = script.steps().last().unwrap();
+        // the last item was sent, but never flushed
+        assert_eq!(last, &Event::Send(2));
+        assert!(!script
+            .steps()
+            .iter()
+            .any(|(event, _)| event == &Event::Flush || event == &Event::Close));
+    }
+
+    #[test]
+    fn driver_flushing_before_error() {
+        let mut sink = recorder();
+        let mut stream = try_from_results(script());
+        let res: Result<(), &str> = block_on(async {
+            while let Some(item) = stream.next().await {
+                match item {
+                    Ok(item) => sink.feed(item).await?,
+                    Err(e) => {
+                        sink.flush().await?;
+                        return Err(e);
+                    }
+                }
+            }
+            Ok(())
+        });
+        assert_eq!(res, Err("source"));
+        assert_eq!(
+            sink.into_script().steps().last().map(|(event, _)| event),
+            Some(&Event::Flush)
+        );
+    }
+
+    #[test]
+    fn pending_wakes_and_terminates() {
+        let (waker, wakes) = crate::task::count_waker();
+        let mut cx = Context::from_waker(&waker);
+        let script = vec![
+            Poll::Pending,
+            Poll::Ready(Some(Ok::<_, ()>(1u8))),
+            Poll::Ready(None),
+        ];
+        let mut stream = try_from_results(script.into_iter());
+        assert_eq!(Pin::new(&mut stream).poll_next(&mut cx), Poll::Pending);
+        assert_eq!(wakes.get(), 1);
+        assert_eq!(
+            Pin::new(&mut stream).poll_next(&mut cx),
+            Poll::Ready(Some(Ok(1)))
+        );
+        assert!(!stream.is_terminated());
+        assert_eq!(Pin::new(&mut stream).poll_next(&mut cx), Poll::Ready(None));
+        assert!(stream.is_terminated());
+        // script isn't consulted anymore
+        assert_eq!(Pin::new(&mut stream).poll_next(&mut cx), Poll::Ready(None));
+        assert_eq!(stream.poll_count(), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "Unexpected end of `script` iterator after 1 polls!")]
+    fn script_end_panics() {
+        let mut stream = try_from_results(iter::once(Poll::Ready(Some(Ok::<u8, ()>(1)))));
+        let _ = block_on(stream.next());
+        let _ = block_on(stream.next());
+    }
+
+    #[test]
+    fn wake_policy_after_polls() {
+        let (waker, wakes) = crate::task::count_waker();
+        let mut cx = Context::from_waker(&waker);
+        let script = vec![
+            Poll::Pending,
+            Poll::Pending,
+            Poll::Ready(None::<Result<u8, ()>>),
+        ];
+        let mut stream = try_from_results(script.into_iter());
+        stream.wake_policy(WakePolicy::AfterPolls(1));
+        assert_eq!(Pin::new(&mut stream).poll_next(&mut cx), Poll::Pending);
+        wakes.expect_none();
+        // woken when polled again, before the script returns `Poll::Pending` again
+        assert_eq!(Pin::new(&mut stream).poll_next(&mut cx), Poll::Pending);
+        wakes.expect_wakes(1);
+        assert_eq!(Pin::new(&mut stream).poll_next(&mut cx), Poll::Ready(None));
+        wakes.expect_wakes(2);
+        assert!(!stream.wake_stored());
+    }
+}
@@ -0,0 +1,717 @@
+//! Wakers and contexts for polling sinks by hand.
+//!
+//! These mirror helpers of `futures-test` without depending on it. All wakers are `Send + Sync`.
+//! Cloning them only increments a reference count, except the waker of [tracking_context()]
+//! that tracks every clone separately.
+//!
+//! # Examples
+//! ```
+//! use futures_test_sink::{
+//!     assert_flush_pending, assert_sink_ready, assert_start_send_ok, interleave_pending,
+//!     task::count_waker,
+//! };
+//! use std::task::Context;
+//!
+//! let (waker, wake_count) = count_waker();
+//! let mut cx = Context::from_waker(&waker);
+//! let mut s = interleave_pending();
+//!
+//! assert_sink_ready!(s, cx);
+//! assert_start_send_ok!(s, 1);
+//! assert_flush_pending!(s, cx);
+//! wake_count.expect_wakes(1);
+//! ```
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::task::Wake;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use core::task::{Context, RawWaker, RawWakerVTable, Waker};
+
+#[cfg(feature = "std")]
+use crate::counters::Lock;
+#[cfg(feature = "std")]
+use alloc::vec::Vec;
+
+#[derive(Debug)]
+struct Counter(AtomicUsize);
+
+impl Wake for Counter {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Number of wakes of a waker created by [count_waker()].
+#[derive(Debug, Clone)]
+pub struct WakeCount {
+    inner: Arc<Counter>,
+}
+
+impl WakeCount {
+    /// How many times the waker or any of its clones was woken.
+    ///
+    /// `wake()` and `wake_by_ref()` are counted the same way.
+    pub fn get(&self) -> usize {
+        self.inner.0.load(Ordering::SeqCst)
+    }
+
+    /// Panic unless the waker was woken exactly `n` times.
+    ///
+    /// The message shows both numbers, e.g. `expected 3 wakes, observed 5`.
+    #[track_caller]
+    pub fn expect_wakes(&self, n: usize) {
+        check_wakes(ExpectedWakes::Exactly(n), self.get(), None);
+    }
+
+    /// Panic unless the waker was woken at least `n` times.
+    #[track_caller]
+    pub fn expect_at_least(&self, n: usize) {
+        check_wakes(ExpectedWakes::AtLeast(n), self.get(), None);
+    }
+
+    /// Panic if the waker was woken.
+    #[track_caller]
+    pub fn expect_none(&self) {
+        check_wakes(ExpectedWakes::Exactly(0), self.get(), None);
+    }
+}
+
+/// Expectation of `expect_*()` methods of wake counters.
+pub(crate) enum ExpectedWakes {
+    Exactly(usize),
+    AtLeast(usize),
+}
+
+/// Panic if `observed` wakes don't meet `expected`, listing `sources` of the wakes if known.
+#[track_caller]
+pub(crate) fn check_wakes(expected: ExpectedWakes, observed: usize, sources: Option<String>) {
+    let wakes = |n: usize| match n {
+        0 => "no wakes".into(),
+        1 => "1 wake".into(),
+        n => alloc::format!("{} wakes", n),
+    };
+    let expected: String = match expected {
+        ExpectedWakes::Exactly(n) if n == observed => return,
+        ExpectedWakes::AtLeast(n) if n <= observed => return,
+        ExpectedWakes::Exactly(n) => wakes(n),
+        ExpectedWakes::AtLeast(n) => alloc::format!("at least {}", wakes(n)),
+    };
+    match sources {
+        Some(sources) => panic!(
+            "expected {}, observed {}; wake sources: {}",
+            expected, observed, sources
+        ),
+        None => panic!("expected {}, observed {}", expected, observed),
+    }
+}
+
+/// Create a waker that counts how many times it was woken.
+pub fn count_waker() -> (Waker, WakeCount) {
+    let inner = Arc::new(Counter(AtomicUsize::new(0)));
+    (Waker::from(inner.clone()), WakeCount { inner })
+}
+
+struct Panicking(&'static str);
+
+impl Wake for Panicking {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        panic!("{}", self.0);
+    }
+}
+
+/// Create a waker that panics with `msg` when it's woken.
+///
+/// Use it to check that polling doesn't wake the task, e.g. that a sink returning
+/// `Poll::Ready` doesn't wake spuriously.
+///
+/// # Examples
+/// ```
+/// use futures_test_sink::{
+///     assert_flush_ready, assert_sink_ready, assert_start_send_ok, ok, task::panic_waker,
+/// };
+/// use std::task::Context;
+///
+/// let waker = panic_waker("ready sink must not wake");
+/// let mut cx = Context::from_waker(&waker);
+/// let mut s = ok();
+/// assert_sink_ready!(s, cx);
+/// assert_start_send_ok!(s, 1);
+/// assert_flush_ready!(s, cx);
+/// ```
+pub fn panic_waker(msg: &'static str) -> Waker {
+    Waker::from(Arc::new(Panicking(msg)))
+}
+
+/// Create a context with a waker that does nothing when woken.
+///
+/// # Examples
+/// ```
+/// use futures_test_sink::{assert_sink_ready, assert_start_send_ok, ok, task::noop_context};
+///
+/// let mut s = ok::<u8>();
+/// assert_sink_ready!(s, noop_context());
+/// assert_start_send_ok!(s, 1);
+/// ```
+pub fn noop_context() -> Context<'static> {
+    Context::from_waker(futures::task::noop_waker_ref())
+}
+
+#[derive(Debug, Default)]
+struct Stats {
+    clones: AtomicUsize,
+    wakes: AtomicUsize,
+    wake_by_refs: AtomicUsize,
+    released_clones: AtomicUsize,
+    dropped_unwoken: AtomicUsize,
+}
+
+// Every clone is a separate allocation, so it knows if it was woken before it's dropped.
+struct Tracked {
+    stats: Arc<Stats>,
+    is_clone: bool,
+    woken: AtomicBool,
+}
+
+static TRACKED_VTABLE: RawWakerVTable = RawWakerVTable::new(
+    tracked_clone,
+    tracked_wake,
+    tracked_wake_by_ref,
+    tracked_drop,
+);
+
+fn tracked_raw(stats: Arc<Stats>, is_clone: bool) -> RawWaker {
+    let tracked = Box::new(Tracked {
+        stats,
+        is_clone,
+        woken: AtomicBool::new(false),
+    });
+    RawWaker::new(Box::into_raw(tracked) as *const (), &TRACKED_VTABLE)
+}
+
+// SAFETY (all vtable functions): `data` was created by `Box::into_raw()` in `tracked_raw()` and
+// the waker contract guarantees `wake` or `drop` is called exactly once for it, after all other
+// uses. `Tracked` is only accessed through shared references and atomics, so it's `Sync`.
+unsafe fn tracked_clone(data: *const ()) -> RawWaker {
+    let tracked = &*(data as *const Tracked);
+    tracked.stats.clones.fetch_add(1, Ordering::SeqCst);
+    tracked_raw(tracked.stats.clone(), true)
+}
+
+unsafe fn tracked_wake(data: *const ()) {
+    let tracked = Box::from_raw(data as *mut Tracked);
+    tracked.stats.wakes.fetch_add(1, Ordering::SeqCst);
+    if tracked.is_clone {
+        tracked.stats.released_clones.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+unsafe fn tracked_wake_by_ref(data: *const ()) {
+    let tracked = &*(data as *const Tracked);
+    tracked.woken.store(true, Ordering::SeqCst);
+    tracked.stats.wake_by_refs.fetch_add(1, Ordering::SeqCst);
+}
+
+unsafe fn tracked_drop(data: *const ()) {
+    let tracked = Box::from_raw(data as *mut Tracked);
+    if tracked.is_clone {
+        tracked.stats.released_clones.fetch_add(1, Ordering::SeqCst);
+        if !tracked.woken.load(Ordering::SeqCst) {
+            tracked.stats.dropped_unwoken.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+}
+
+/// What happened to a waker created by [tracking_waker()] or [tracking_context()] and its clones.
+///
+/// The counters can be read while the waker is used, also from other threads.
+#[derive(Debug, Clone)]
+pub struct WakerStats {
+    inner: Arc<Stats>,
+}
+
+impl WakerStats {
+    /// How many clones were created, including clones of clones.
+    pub fn clones(&self) -> usize {
+        self.inner.clones.load(Ordering::SeqCst)
+    }
+
+    /// How many times `wake()` was called on the waker or any clone.
+    pub fn wakes(&self) -> usize {
+        self.inner.wakes.load(Ordering::SeqCst)
+    }
+
+    /// How many times `wake_by_ref()` was called on the waker or any clone.
+    pub fn wake_by_refs(&self) -> usize {
+        self.inner.wake_by_refs.load(Ordering::SeqCst)
+    }
+
+    /// How many clones were dropped without being woken by `wake()` or `wake_by_ref()`.
+    ///
+    /// A sink storing a clone and dropping it un-woken likely loses a wakeup.
+    pub fn dropped_unwoken(&self) -> usize {
+        self.inner.dropped_unwoken.load(Ordering::SeqCst)
+    }
+
+    /// How many clones are alive, i.e. neither woken by `wake()` nor dropped yet.
+    ///
+    /// A clone alive after the sink was dropped was leaked.
+    pub fn live_clones(&self) -> usize {
+        self.clones() - self.inner.released_clones.load(Ordering::SeqCst)
+    }
+}
+
+/// Create a waker recording clones, wakes and clones dropped without a wake.
+///
+/// Unlike other wakers every clone is a distinct waker, so `will_wake()` is `false` between the
+/// waker and its clones. Code that stores a clone only when `will_wake()` is `false` (like
+/// `AtomicWaker`) clones again on every poll, which shows up in [WakerStats::clones()] and,
+/// for replaced clones, in [WakerStats::dropped_unwoken()].
+///
+/// # Examples
+/// ```
+/// use futures_test_sink::task::tracking_waker;
+///
+/// let (waker, stats) = tracking_waker();
+/// let stored = waker.clone();
+/// waker.wake_by_ref();
+/// drop(stored);
+/// assert_eq!(stats.clones(), 1);
+/// assert_eq!(stats.wake_by_refs(), 1);
+/// // the stored clone was never woken
+/// assert_eq!(stats.dropped_unwoken(), 1);
+/// assert_eq!(stats.live_clones(), 0);
+/// ```
+pub fn tracking_waker() -> (Waker, WakerStats) {
+    let stats = Arc::new(Stats::default());
+    // SAFETY: `tracked_raw()` creates a `RawWaker` upholding the vtable contract.
+    let waker = unsafe { Waker::from_raw(tracked_raw(stats.clone(), false)) };
+    (waker, WakerStats { inner: stats })
+}
+
+/// Create a context with a [tracking_waker()].
+///
+/// The waker is leaked, so the context can be `'static` like [noop_context()].
+///
+/// # Examples
+/// ```
+/// use futures_test_sink::{
+///     assert_sink_pending, assert_sink_ready, task::tracking_context, window_sink,
+/// };
+///
+/// let (mut cx, stats) = tracking_context();
+/// let mut s = window_sink::<&[u8]>(0);
+/// assert_sink_pending!(s, cx);
+/// assert_eq!(stats.live_clones(), 1);
+/// s.add_window(4);
+/// assert_eq!(stats.wakes(), 1);
+/// // the sink keeps its clone for later updates and drops it when it's polled again
+/// assert_eq!(stats.live_clones(), 1);
+/// assert_sink_ready!(s, cx);
+/// assert_eq!(stats.live_clones(), 0);
+/// ```
+pub fn tracking_context() -> (Context<'static>, WakerStats) {
+    let (waker, stats) = tracking_waker();
+    (Context::from_waker(Box::leak(Box::new(waker))), stats)
+}
+
+#[cfg(feature = "std")]
+#[derive(Default)]
+struct Detection {
+    wakes: usize,
+    next_id: usize,
+    // mock and method polled last
+    polling: Option<(usize, &'static str)>,
+    // generation of every wake not followed by a poll yet, with the poll it happened in
+    unhonored: Vec<(usize, Option<(usize, &'static str)>)>,
+}
+
+#[cfg(feature = "std")]
+struct Detecting(Lock<Detection>);
+
+#[cfg(feature = "std")]
+impl Wake for Detecting {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.with(|detection| {
+            detection.wakes += 1;
+            let wake = (detection.wakes, detection.polling);
+            detection.unhonored.push(wake);
+        });
+    }
+}
+
+/// Fails the test if a wake of the waker of [detecting_context()] isn't followed by a poll.
+///
+/// Mocks report their polls after `detect_lost_wakes()`, e.g.
+/// [SinkMock::detect_lost_wakes()](crate::SinkMock::detect_lost_wakes). A wake is attributed to
+/// the mock polled last and honored by the next poll of that mock, or of any mock if no mock
+/// was polled before the wake. Wakes in a poll returning `Poll::Ready` need no further poll.
+/// Unhonored wakes panic when the detector is dropped, unless the thread is already panicking.
+///
+/// Requires `std` feature.
+#[cfg(feature = "std")]
+pub struct LostWakeDetector {
+    inner: Arc<Detecting>,
+}
+
+#[cfg(feature = "std")]
+impl LostWakeDetector {
+    /// How many times the waker was woken.
+    pub fn wakes(&self) -> usize {
+        self.inner.0.with(|detection| detection.wakes)
+    }
+
+    /// Panic if a wake wasn't followed by a poll, e.g. with
+    /// `wake #3 from poll_flush was never honored`.
+    #[track_caller]
+    pub fn assert_all_wakes_honored(&self) {
+        let unhonored = self.inner.0.with(|detection| detection.unhonored.clone());
+        let source = |polling: Option<(usize, &str)>| match polling {
+            Some((_, method)) => alloc::format!("from {}", method),
+            None => "before any poll".into(),
+        };
+        match unhonored.as_slice() {
+            [] => (),
+            [(wake, polling)] => panic!("wake #{} {} was never honored", wake, source(*polling)),
+            [(wake, polling), rest @ ..] => panic!(
+                "wake #{} {} was never honored, {} more unhonored",
+                wake,
+                source(*polling),
+                rest.len()
+            ),
+        }
+    }
+
+    /// Hook reporting the polls of one mock.
+    pub(crate) fn reporter(&self) -> PollReporter {
+        let id = self.inner.0.with(|detection| {
+            detection.next_id += 1;
+            detection.next_id
+        });
+        PollReporter {
+            inner: self.inner.clone(),
+            id,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Drop for LostWakeDetector {
+    fn drop(&mut self) {
+        if !std::thread::panicking() {
+            self.assert_all_wakes_honored();
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl core::fmt::Debug for LostWakeDetector {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let (wakes, unhonored) = self
+            .inner
+            .0
+            .with(|detection| (detection.wakes, detection.unhonored.len()));
+        f.debug_struct("LostWakeDetector")
+            .field("wakes", &wakes)
+            .field("unhonored", &unhonored)
+            .finish()
+    }
+}
+
+/// Polls of one mock reported to a [LostWakeDetector].
+#[cfg(feature = "std")]
+#[derive(Clone)]
+pub(crate) struct PollReporter {
+    inner: Arc<Detecting>,
+    id: usize,
+}
+
+#[cfg(feature = "std")]
+impl PollReporter {
+    /// Called at the start of every poll, honors the wakes attributed to the mock.
+    pub(crate) fn polled(&self, method: &'static str) {
+        self.honor(Some(method));
+    }
+
+    /// Called when the poll returns, a `Poll::Ready` honors the wakes done in it.
+    pub(crate) fn returned(&self, ready: bool) {
+        if ready {
+            self.honor(None);
+        }
+    }
+
+    // drop the wakes attributed to the mock or to no mock, and start attributing to `method`
+    fn honor(&self, method: Option<&'static str>) {
+        let id = self.id;
+        self.inner.0.with(|detection| {
+            detection
+                .unhonored
+                .retain(|(_, polling)| matches!(polling, Some((mock, _)) if *mock != id));
+            if let Some(method) = method {
+                detection.polling = Some((id, method));
+            }
+        });
+    }
+}
+
+/// Create a context with a waker that records every wake for a [LostWakeDetector].
+///
+/// The waker is leaked like the one of [tracking_context()]. A wake not followed by a poll of
+/// the mock that caused it turns a hang into a failure naming the wake.
+///
+/// Requires `std` feature.
+///
+/// # Examples
+/// ```
+/// use futures_test_sink::{
+///     assert_flush_pending, assert_flush_ready, task::detecting_context, SinkMock, WakePolicy,
+/// };
+/// use std::task::Poll;
+///
+/// let (mut cx, detector) = detecting_context();
+/// let feedback = vec![Poll::Pending, Poll::Ready(Ok::<_, ()>(()))];
+/// let mut s = SinkMock::<_, _, _, u8, _>::with_flush_feedback(feedback.into_iter());
+/// s.wake_policy(WakePolicy::Immediate).detect_lost_wakes(&detector);
+///
+/// assert_flush_pending!(s, cx);
+/// // dropping the detector here would panic with "wake #1 from poll_flush was never honored"
+/// assert_flush_ready!(s, cx);
+/// detector.assert_all_wakes_honored();
+/// ```
+#[cfg(feature = "std")]
+pub fn detecting_context() -> (Context<'static>, LostWakeDetector) {
+    let inner = Arc::new(Detecting(Lock::new(Detection::default())));
+    let waker = Waker::from(inner.clone());
+    (
+        Context::from_waker(Box::leak(Box::new(waker))),
+        LostWakeDetector { inner },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::thread;
+
+    fn is_send_sync<T: Send + Sync>(_: &T) {}
+
+    #[test]
+    fn wake_and_wake_by_ref_count_the_same() {
+        let (waker, count) = count_waker();
+        waker.wake_by_ref();
+        assert_eq!(count.get(), 1);
+        let clone = waker.clone();
+        clone.wake();
+        assert_eq!(count.get(), 2);
+        waker.wake();
+        assert_eq!(count.get(), 3);
+    }
+
+    #[test]
+    fn expect_wake_counts() {
+        let (waker, count) = count_waker();
+        count.expect_none();
+        count.expect_at_least(0);
+        waker.wake_by_ref();
+        count.expect_wakes(1);
+        let message = |f: &dyn Fn()| {
+            let err = catch_unwind(AssertUnwindSafe(f)).unwrap_err();
+            *err.downcast::<String>().unwrap()
+        };
+        assert_eq!(
+            message(&|| count.expect_none()),
+            "expected no wakes, observed 1"
+        );
+        assert_eq!(
+            message(&|| count.expect_at_least(2)),
+            "expected at least 2 wakes, observed 1"
+        );
+        waker.wake_by_ref();
+        assert_eq!(
+            message(&|| count.expect_wakes(1)),
+            "expected 1 wake, observed 2"
+        );
+    }
+
+    #[test]
+    fn count_from_other_thread() {
+        let (waker, count) = count_waker();
+        is_send_sync(&waker);
+        is_send_sync(&count);
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let waker = waker.clone();
+                thread::spawn(move || waker.wake_by_ref())
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(count.clone().get(), 4);
+    }
+
+    #[test]
+    fn panic_waker_panics_on_both_wakes() {
+        let waker = panic_waker("woken");
+        is_send_sync(&waker);
+        let clone = waker.clone();
+        let by_ref = catch_unwind(AssertUnwindSafe(|| waker.wake_by_ref()));
+        assert_eq!(*by_ref.unwrap_err().downcast::<String>().unwrap(), "woken");
+        let by_value = catch_unwind(AssertUnwindSafe(|| clone.wake()));
+        assert_eq!(
+            *by_value.unwrap_err().downcast::<String>().unwrap(),
+            "woken"
+        );
+    }
+
+    #[test]
+    fn noop_context_can_be_woken() {
+        let cx = noop_context();
+        cx.waker().wake_by_ref();
+        let waker = cx.waker().clone();
+        waker.wake();
+    }
+
+    #[test]
+    fn tracking_counts() {
+        let (waker, stats) = tracking_waker();
+        is_send_sync(&waker);
+        is_send_sync(&stats);
+        let a = waker.clone();
+        let b = a.clone();
+        let c = waker.clone();
+        assert_eq!(stats.clones(), 3);
+        assert_eq!(stats.live_clones(), 3);
+        a.wake();
+        b.wake_by_ref();
+        drop(b);
+        drop(c);
+        waker.wake_by_ref();
+        assert_eq!(stats.wakes(), 1);
+        assert_eq!(stats.wake_by_refs(), 2);
+        assert_eq!(stats.dropped_unwoken(), 1);
+        assert_eq!(stats.live_clones(), 0);
+        // the original isn't a clone
+        drop(waker);
+        assert_eq!(stats.dropped_unwoken(), 1);
+        assert!(!tracking_waker().0.will_wake(&tracking_waker().0));
+    }
+
+    #[test]
+    fn tracking_from_many_threads() {
+        let (waker, stats) = tracking_waker();
+        let handles: Vec<_> = (0..8)
+            .map(|i| {
+                let waker = waker.clone();
+                thread::spawn(move || {
+                    let clones: Vec<_> = (0..100).map(|_| waker.clone()).collect();
+                    for (j, clone) in clones.into_iter().enumerate() {
+                        match (i + j) % 4 {
+                            0 => clone.wake(),
+                            1 => clone.wake_by_ref(),
+                            2 => drop(clone.clone()),
+                            _ => (),
+                        }
+                    }
+                    waker.wake_by_ref();
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        // 8 thread clones, 800 clones in threads and 200 clones of them
+        assert_eq!(stats.clones(), 8 + 800 + 200);
+        assert_eq!(stats.wakes(), 200);
+        assert_eq!(stats.wake_by_refs(), 200 + 8);
+        // the thread clones woken by ref, clones dropped in arms 2 and 3
+        assert_eq!(stats.dropped_unwoken(), 200 + 200 + 200);
+        assert_eq!(stats.live_clones(), 0);
+    }
+
+    #[test]
+    fn tracking_context_with_mocks() {
+        use core::task::Poll;
+        use futures::sink::Sink;
+
+        let (mut cx, stats) = tracking_context();
+        let s = crate::interleave_pending::<u8>();
+        futures::pin_mut!(s);
+        assert_eq!(s.as_mut().poll_ready(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(s.as_mut().poll_flush(&mut cx), Poll::Pending);
+        // mocks wake by reference and never store the waker
+        assert_eq!(stats.wake_by_refs(), 1);
+        assert_eq!(stats.clones(), 0);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn detector_names_the_lost_wake() {
+        use core::{pin::Pin, task::Poll};
+        use futures::sink::Sink;
+
+        let (mut cx, detector) = detecting_context();
+        let feedback = vec![Poll::Ready(Ok(())), Poll::Pending];
+        let mut s = crate::SinkMock::<_, _, _, u8, ()>::with_flush_feedback(feedback.into_iter());
+        s.wake_policy(crate::WakePolicy::Immediate)
+            .detect_lost_wakes(&detector);
+        let mut other = crate::SinkMock::<_, _, _, u8, ()>::with_flush_feedback(
+            core::iter::repeat(Poll::Pending),
+        );
+        other
+            .wake_policy(crate::WakePolicy::Immediate)
+            .detect_lost_wakes(&detector);
+
+        cx.waker().wake_by_ref();
+        // wakes before any poll are honored by a poll of any mock
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(Pin::new(&mut other).poll_flush(&mut cx), Poll::Pending);
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Pending);
+        // honors the wake of `other` only
+        assert_eq!(Pin::new(&mut other).poll_flush(&mut cx), Poll::Pending);
+        assert_eq!(detector.wakes(), 4);
+        let err = catch_unwind(AssertUnwindSafe(|| detector.assert_all_wakes_honored()));
+        assert_eq!(
+            *err.unwrap_err().downcast::<String>().unwrap(),
+            "wake #3 from poll_flush was never honored, 1 more unhonored"
+        );
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        // the last wake of `other` is left, the detector panics when dropped
+        let err = catch_unwind(AssertUnwindSafe(move || drop(detector)));
+        assert_eq!(
+            *err.unwrap_err().downcast::<String>().unwrap(),
+            "wake #4 from poll_flush was never honored"
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn detector_ignores_wakes_of_ready_polls() {
+        use core::{pin::Pin, task::Poll};
+        use futures::sink::Sink;
+
+        let (mut cx, detector) = detecting_context();
+        let mut s = crate::SinkMock::<_, _, _, u8, ()>::with_flush_feedback(core::iter::repeat(
+            Poll::Ready(Ok(())),
+        ));
+        s.spurious_wakes(1).detect_lost_wakes(&detector);
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        Pin::new(&mut s).start_send(1).unwrap();
+        assert_eq!(Pin::new(&mut s).poll_close(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(detector.wakes(), 2);
+    }
+}
@@ -0,0 +1,149 @@
+use futures::sink::Sink;
+use pin_project_lite::pin_project;
+use std::collections::HashSet;
+use std::thread::{self, ThreadId};
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+pin_project! {
+    /// Wrapper recording the thread every method of the inner sink was called from.
+    ///
+    /// Every method is delegated to the inner sink unchanged. A sink shared between tasks, e.g. a
+    /// mock behind `Arc<Mutex<_>>`, or a task moved between the threads of a multi-threaded runtime
+    /// shows up with several threads in [calling_threads()](ThreadTrackingSink::calling_threads).
+    /// A sink expected to be driven by a single task on a single thread should show just one.
+    ///
+    /// Requires `std` feature.
+    ///
+    /// # Examples
+    /// ```
+    /// use futures::{executor::block_on, SinkExt};
+    /// use futures_test_sink::{ok, ThreadTrackingSink};
+    /// use std::thread;
+    ///
+    /// let mut sink = ThreadTrackingSink::new(ok::<u8>());
+    /// block_on(sink.send(1)).unwrap();
+    /// let mut sink = thread::spawn(move || {
+    ///     block_on(sink.send(2)).unwrap();
+    ///     sink
+    /// })
+    /// .join()
+    /// .unwrap();
+    /// assert_eq!(sink.calling_threads().len(), 2);
+    /// assert!(sink.calling_threads().contains(&thread::current().id()));
+    /// ```
+    pub struct ThreadTrackingSink<S> {
+        #[pin]
+        inner: S,
+        threads: HashSet<ThreadId>,
+    }
+}
+
+impl<S> ThreadTrackingSink<S> {
+    /// Wrap `inner` sink.
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            threads: HashSet::new(),
+        }
+    }
+
+    /// Threads any of `poll_ready()`, `start_send()`, `poll_flush()` and `poll_close()` was
+    /// called from.
+    pub fn calling_threads(&self) -> HashSet<ThreadId> {
+        self.threads.clone()
+    }
+
+    inner_accessors!(S, "the inner sink");
+
+    fn called(self: Pin<&mut Self>) -> Pin<&mut S> {
+        let this = self.project();
+        this.threads.insert(thread::current().id());
+        this.inner
+    }
+}
+
+impl<S, Item> Sink<Item> for ThreadTrackingSink<S>
+where
+    S: Sink<Item>,
+{
+    type Error = S::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let poll = self.called().poll_ready(cx);
+        wrapper_event!("thread_tracking", "poll_ready", &poll);
+        poll
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Item) -> Result<(), Self::Error> {
+        let res = self.called().start_send(item);
+        wrapper_event!("thread_tracking", "start_send", &res);
+        res
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let poll = self.called().poll_flush(cx);
+        wrapper_event!("thread_tracking", "poll_flush", &poll);
+        poll
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let poll = self.called().poll_close(cx);
+        wrapper_event!("thread_tracking", "poll_close", &poll);
+        poll
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SinkMock;
+    use futures::{executor::block_on, SinkExt};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn shared_mock_called_from_many_threads() {
+        let mock = SinkMock::<_, _, _, u8, ()>::with_flush_feedback(std::iter::repeat_with(|| {
+            Poll::Ready(Ok(()))
+        }));
+        let sink = Arc::new(Mutex::new(ThreadTrackingSink::new(mock)));
+        block_on(sink.lock().unwrap().send(0)).unwrap();
+        assert_eq!(
+            sink.lock().unwrap().calling_threads(),
+            std::iter::once(thread::current().id()).collect()
+        );
+
+        let handles: Vec<_> = (1..=3)
+            .map(|i| {
+                let sink = sink.clone();
+                thread::spawn(move || block_on(sink.lock().unwrap().send(i)).unwrap())
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        let sink = sink.lock().unwrap();
+        assert_eq!(sink.calling_threads().len(), 4);
+        assert_eq!(sink.get_ref().counters().accepted(), 4);
+    }
+
+    #[test]
+    fn pinned_inner_sink() {
+        let received = Mutex::new(Vec::new());
+        // `!Unpin`, it holds the `async` block
+        let sink = ThreadTrackingSink::new(futures::sink::unfold((), |(), item: u8| {
+            let received = &received;
+            async move {
+                received.lock().unwrap().push(item);
+                Ok::<_, ()>(())
+            }
+        }));
+        futures::pin_mut!(sink);
+        block_on(sink.as_mut().send(1)).unwrap();
+        block_on(sink.as_mut().send(2)).unwrap();
+        assert_eq!(*received.lock().unwrap(), [1, 2]);
+        assert_eq!(sink.calling_threads().len(), 1);
+    }
+}
@@ -0,0 +1,336 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::iter;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::task::Waker;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::WakeHandle;
+
+/// Polls returned by a mock and the poll an entry waits for.
+pub(crate) type Gate = (Arc<AtomicUsize>, usize);
+
+// waker due at an instant with the handle of the mock that scheduled it
+struct Entry {
+    due: Instant,
+    seq: u64,
+    waker: Waker,
+    handle: WakeHandle,
+    gate: Option<Gate>,
+}
+
+impl Entry {
+    /// `true` if the entry waits for a poll that didn't return yet.
+    fn is_gated(&self) -> bool {
+        match &self.gate {
+            Some((returned, poll)) => returned.load(AtomicOrdering::SeqCst) < *poll,
+            None => false,
+        }
+    }
+
+    /// Wake the waker, or give the entry back if its poll didn't return yet.
+    fn fire(self) -> Option<Entry> {
+        if self.is_gated() {
+            return Some(self);
+        }
+        // a panicking waker mustn't take the wakes of other mocks down with it
+        let (handle, waker) = (self.handle, self.waker);
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| handle.fire(iter::once(waker))));
+        None
+    }
+}
+
+// entries due at the same instant fire in order of scheduling
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.due, self.seq).cmp(&(other.due, other.seq))
+    }
+}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Entry {}
+
+struct Queue {
+    entries: BinaryHeap<Reverse<Entry>>,
+    // gated entries waiting for `release()`
+    parked: Vec<Entry>,
+    seq: u64,
+    // whether the timer thread is alive
+    running: bool,
+}
+
+static QUEUE: Mutex<Queue> = Mutex::new(Queue {
+    entries: BinaryHeap::new(),
+    parked: Vec::new(),
+    seq: 0,
+    running: false,
+});
+static CHANGED: Condvar = Condvar::new();
+
+// a panic while holding the lock can't leave the queue inconsistent
+fn lock() -> MutexGuard<'static, Queue> {
+    QUEUE.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Wake `waker` after `delay` from the timer thread, starting the thread if it isn't running.
+/// With a `gate` the wake waits until the poll returned too.
+pub(crate) fn schedule(delay: Duration, waker: Waker, handle: WakeHandle, gate: Option<Gate>) {
+    let mut queue = lock();
+    let seq = queue.seq;
+    queue.seq += 1;
+    queue.entries.push(Reverse(Entry {
+        due: Instant::now() + delay,
+        seq,
+        waker,
+        handle,
+        gate,
+    }));
+    start(queue);
+}
+
+// Park entries whose poll didn't return yet until `release()`. The poll may have returned since
+// they were checked outside of the lock, those are due again.
+fn park(queue: &mut Queue, entries: Vec<Entry>) {
+    for entry in entries {
+        if entry.is_gated() {
+            queue.parked.push(entry);
+        } else {
+            queue.entries.push(Reverse(entry));
+        }
+    }
+}
+
+/// A poll counted by `returned` returned, schedule the parked wakes waiting for it.
+pub(crate) fn release(returned: &Arc<AtomicUsize>) {
+    let mut queue = lock();
+    let now = Instant::now();
+    let (gated, released) = queue.parked.drain(..).partition::<Vec<_>, _>(|entry| {
+        !matches!(&entry.gate, Some((gate, _)) if Arc::ptr_eq(gate, returned)) || entry.is_gated()
+    });
+    queue.parked = gated;
+    if released.is_empty() {
+        return;
+    }
+    queue.entries.extend(
+        released
+            .into_iter()
+            .map(|entry| Reverse(Entry { due: now, ..entry })),
+    );
+    start(queue);
+}
+
+fn start(mut queue: MutexGuard<'static, Queue>) {
+    if queue.entries.is_empty() {
+        return;
+    }
+    if queue.running {
+        CHANGED.notify_one();
+    } else {
+        queue.running = true;
+        thread::Builder::new()
+            .name("futures-test-sink-timer".into())
+            .spawn(run)
+            .expect("failed to spawn the timer thread");
+    }
+}
+
+/// Drop the wakes scheduled with `handle`, its mock is gone and nobody waits for them.
+pub(crate) fn cancel(handle: &WakeHandle) {
+    let mut queue = lock();
+    queue.entries.retain(|Reverse(e)| !e.handle.same(handle));
+    queue.parked.retain(|e| !e.handle.same(handle));
+}
+
+// The thread ends as soon as the queue is empty, so it never outlives the delayed wakes and is
+// started again by the next `schedule()`.
+fn run() {
+    let mut queue = lock();
+    loop {
+        let now = Instant::now();
+        let mut due = Vec::new();
+        while queue.entries.peek().is_some_and(|Reverse(e)| e.due <= now) {
+            due.extend(queue.entries.pop().map(|Reverse(e)| e));
+        }
+        if !due.is_empty() {
+            // wakers run outside of the lock, they may schedule again
+            drop(queue);
+            let closed = due.into_iter().filter_map(Entry::fire).collect();
+            queue = lock();
+            park(&mut queue, closed);
+            continue;
+        }
+        let timeout = match queue.entries.peek() {
+            Some(Reverse(next)) => next.due - now,
+            None => {
+                queue.running = false;
+                return;
+            }
+        };
+        queue = CHANGED
+            .wait_timeout(queue, timeout)
+            .unwrap_or_else(PoisonError::into_inner)
+            .0;
+    }
+}
+
+/// Fire all wakes scheduled by [WakePolicy::Delayed](crate::WakePolicy::Delayed) now, without
+/// waiting for their delay. Returns how many wakes were fired.
+///
+/// Wakes of [WakeOrder::AfterReturn](crate::WakeOrder::AfterReturn) whose poll didn't return
+/// yet stay parked until it returns.
+///
+/// The queue is shared by all mocks, so this fires wakes of mocks of other tests running in
+/// parallel too. They are woken early, which is a legal spurious wake for them.
+///
+/// Requires `std` feature.
+///
+/// # Examples
+/// ```
+/// use futures_test_sink::{
+///     assert_flush_pending, flush_pending_wakes, task::count_waker, SinkMock, WakePolicy,
+/// };
+/// use std::{iter, task::{Context, Poll}, time::Duration};
+///
+/// let mut sink = SinkMock::<_, _, _, u8, ()>::with_flush_feedback(iter::repeat(Poll::Pending));
+/// sink.wake_policy(WakePolicy::Delayed(Duration::from_secs(3600)));
+/// let (waker, cnt) = count_waker();
+/// let mut cx = Context::from_waker(&waker);
+/// assert_flush_pending!(sink, cx);
+///
+/// assert!(flush_pending_wakes() >= 1);
+/// cnt.expect_wakes(1);
+/// ```
+pub fn flush_pending_wakes() -> usize {
+    let due: Vec<_> = lock().entries.drain().map(|Reverse(e)| e).collect();
+    let n = due.len();
+    let closed: Vec<_> = due.into_iter().filter_map(Entry::fire).collect();
+    let fired = n - closed.len();
+    let mut queue = lock();
+    park(&mut queue, closed);
+    start(queue);
+    fired
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::count_waker;
+    use crate::{SinkMock, WakePolicy};
+    use async_task::waker_fn;
+    use futures::{stream, StreamExt};
+    use std::sync::Arc;
+    use std::task::Poll;
+
+    #[test]
+    fn fires_in_due_order_and_stops() {
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let handle = WakeHandle::default();
+        for (i, ms) in [30, 10, 20].iter().enumerate() {
+            let order = order.clone();
+            let waker = waker_fn(move || order.lock().unwrap().push(i));
+            schedule(Duration::from_millis(*ms), waker, handle.clone(), None);
+        }
+        let start = Instant::now();
+        while handle.fired() < 3 || lock().running {
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "timer thread didn't stop"
+            );
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(*order.lock().unwrap(), [1, 2, 0]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn delayed_mock_on_multi_thread_runtime() {
+        let feedback = vec![Poll::Pending, Poll::Ready(Ok(()))].into_iter().cycle();
+        let mut sink = SinkMock::<_, _, _, u8, ()>::with_flush_feedback(feedback);
+        sink.wake_policy(WakePolicy::Delayed(Duration::from_millis(2)))
+            .set_max_item(2);
+        let items = stream::iter(0..10).map(Ok);
+        let sending = tokio::spawn(async move { items.forward(&mut sink).await.map(|()| sink) });
+        let sink = sending.await.unwrap().unwrap();
+        assert!(sink.counters().is_closed());
+        assert_eq!(sink.counters().accepted(), 10);
+        assert!(sink.counters().pendings() > 0);
+        sink.counters().expect_none();
+    }
+
+    #[test]
+    fn gated_wake_waits_for_the_poll_to_return() {
+        let (waker, cnt) = count_waker();
+        let returned = Arc::new(AtomicUsize::new(0));
+        let handle = WakeHandle::default();
+        schedule(
+            Duration::ZERO,
+            waker,
+            handle.clone(),
+            Some((returned.clone(), 1)),
+        );
+        thread::sleep(Duration::from_millis(20));
+        cnt.expect_none();
+        // parked instead of checked again and again
+        assert!(lock().parked.iter().any(|e| e.handle.same(&handle)));
+
+        returned.store(1, AtomicOrdering::SeqCst);
+        release(&returned);
+        while handle.fired() == 0 {
+            thread::yield_now();
+        }
+        cnt.expect_wakes(1);
+    }
+
+    #[test]
+    fn panicking_waker_keeps_the_timer_running() {
+        let (waker, cnt) = count_waker();
+        let handle = WakeHandle::default();
+        let panicking = waker_fn(|| panic!("waker panicked"));
+        schedule(Duration::ZERO, panicking, handle.clone(), None);
+        schedule(Duration::from_millis(5), waker, handle.clone(), None);
+        let start = Instant::now();
+        while handle.fired() < 2 {
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "timer thread died"
+            );
+            thread::sleep(Duration::from_millis(1));
+        }
+        cnt.expect_wakes(1);
+    }
+
+    #[test]
+    fn dropped_mock_cancels_its_wakes() {
+        let mut sink =
+            SinkMock::<_, _, _, u8, ()>::with_flush_feedback(iter::repeat(Poll::Pending));
+        sink.wake_policy(WakePolicy::Delayed(Duration::from_secs(3600)));
+        let (waker, cnt) = count_waker();
+        let mut cx = std::task::Context::from_waker(&waker);
+        let poll = futures::Sink::poll_flush(std::pin::Pin::new(&mut sink), &mut cx);
+        assert_eq!(poll, Poll::Pending);
+        let handle = sink.wake_handle();
+        assert!(lock()
+            .entries
+            .iter()
+            .any(|Reverse(e)| e.handle.same(&handle)));
+        drop(sink);
+        assert!(!lock()
+            .entries
+            .iter()
+            .any(|Reverse(e)| e.handle.same(&handle)));
+        cnt.expect_none();
+    }
+}
@@ -0,0 +1,72 @@
+use crate::{ordering_sink_by_key, OrderingSink};
+
+/// Sink that counts items with a timestamp older than the item before them.
+///
+/// An [OrderingSink] keyed by timestamps, for details see [timestamp_sink()].
+///
+/// [timestamp_sink()]:timestamp_sink
+pub type TimestampSink<F> = OrderingSink<u64, F>;
+
+/// Create a sink that checks timestamps of items are non-decreasing.
+///
+/// `extract` returns the timestamp of an item, in any unit. Every timestamp is compared with the
+/// timestamp of the item sent just before it. If it's less, it's counted as
+/// [out of order](OrderingSink::out_of_order_count). Equal timestamps are fine, events produced at
+/// the same instant are common with coarse clocks. It's
+/// [ordering_sink_by_key()](crate::ordering_sink_by_key) with a `u64` key, so only neighbours are
+/// compared and one late item in `10, 30, 20, 40` is counted once.
+///
+/// Every method returns `Poll::Ready(Ok(()))` like [ok()](crate::ok).
+///
+/// # Examples
+/// ```
+/// use futures::{executor::block_on, stream, StreamExt};
+/// use futures_test_sink::timestamp_sink;
+///
+/// struct Event {
+///     at_ms: u64,
+///     name: &'static str,
+/// }
+///
+/// let mut sink = timestamp_sink(|event: Event| event.at_ms);
+/// let events = vec![(10, "connect"), (10, "login"), (25, "query"), (20, "late"), (30, "quit")];
+/// let events = events.into_iter().map(|(at_ms, name)| Ok(Event { at_ms, name }));
+/// assert_eq!(Ok(()), block_on(stream::iter(events).forward(&mut sink)));
+/// assert_eq!(sink.out_of_order_count(), 1);
+/// assert_eq!(sink.last(), Some(&30));
+/// ```
+pub fn timestamp_sink<F, Item>(extract: F) -> TimestampSink<F>
+where
+    F: FnMut(Item) -> u64,
+{
+    ordering_sink_by_key(extract)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{
+        executor::block_on,
+        stream::{self, StreamExt},
+    };
+
+    #[test]
+    fn counts_regressions() {
+        let table = vec![
+            (vec![], 0),
+            (vec![1u64, 2, 3], 0),
+            // equal timestamps are not regressions
+            (vec![5, 5, 5], 0),
+            (vec![10, 30, 20, 40], 1),
+            (vec![3, 2, 1], 2),
+        ];
+        for (timestamps, expected) in table {
+            let mut sink = timestamp_sink(|(at, _): (u64, char)| at);
+            let len = timestamps.len();
+            let items = stream::iter(timestamps.into_iter().map(|at| Ok((at, 'x'))));
+            assert_eq!(Ok(()), block_on(items.forward(&mut sink)));
+            assert_eq!(sink.out_of_order_count(), expected);
+            assert_eq!(sink.send_count(), len);
+        }
+    }
+}
@@ -0,0 +1,254 @@
+use futures::sink::Sink;
+use pin_project_lite::pin_project;
+use std::fmt::Debug;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// One call of the inner sink measured by [TimingSink].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Operation {
+    /// Index of the call counting all methods from 0.
+    pub seq: usize,
+    /// `poll_ready`, `start_send`, `poll_flush` or `poll_close`.
+    pub method: &'static str,
+    /// `ok`, `pending` or `err` followed by the `Debug` representation of the error.
+    pub result: String,
+    /// Wall-clock time spent in the inner sink.
+    pub duration: Duration,
+    /// Items accepted by `start_send()` since the last successful flush, after the call.
+    pub buffered: usize,
+}
+
+/// Measurements collected by [TimingSink].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    operations: Vec<Operation>,
+}
+
+impl Report {
+    /// All measured calls in order.
+    pub fn operations(&self) -> &[Operation] {
+        &self.operations
+    }
+
+    /// Write the measurements as CSV to `w`.
+    ///
+    /// The first row is the header `seq,method,result,duration_ns,buffered`, followed by one row
+    /// per [Operation]. Rows end with `\n`. Fields containing a comma, a quote or a line break are
+    /// quoted and quotes inside them doubled as in RFC 4180.
+    ///
+    /// # Errors
+    /// Any error returned by `w`.
+    pub fn write_csv(&self, mut w: impl Write) -> io::Result<()> {
+        writeln!(w, "seq,method,result,duration_ns,buffered")?;
+        for op in &self.operations {
+            write!(w, "{},{},", op.seq, op.method)?;
+            write_field(&mut w, &op.result)?;
+            writeln!(w, ",{},{}", op.duration.as_nanos(), op.buffered)?;
+        }
+        w.flush()
+    }
+
+    /// Create a file at `path` and [write_csv()](Report::write_csv) into it.
+    ///
+    /// # Errors
+    /// If the file can't be created or written.
+    pub fn write_csv_path(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.write_csv(BufWriter::new(File::create(path)?))
+    }
+}
+
+fn write_field(w: &mut impl Write, field: &str) -> io::Result<()> {
+    if field.contains([',', '"', '\n', '\r']) {
+        write!(w, "\"{}\"", field.replace('"', "\"\""))
+    } else {
+        w.write_all(field.as_bytes())
+    }
+}
+
+pin_project! {
+    /// Wrapper that measures how long every call of the inner sink takes.
+    ///
+    /// Every method is delegated to the inner sink unchanged. The measurements are available from
+    /// [report()](TimingSink::report) and can be exported with [Report::write_csv()] for charting.
+    ///
+    /// Requires `std` feature.
+    ///
+    /// # Examples
+    /// ```
+    /// use futures::{executor::block_on, stream, StreamExt};
+    /// use futures_test_sink::{interleave_pending, TimingSink};
+    ///
+    /// let mut sink = TimingSink::new(Box::pin(interleave_pending()));
+    /// let items = stream::iter(vec![Ok(1u8), Ok(2)]);
+    /// assert_eq!(Ok(()), block_on(items.forward(&mut sink)));
+    ///
+    /// let mut csv = Vec::new();
+    /// sink.report().write_csv(&mut csv).unwrap();
+    /// let csv = String::from_utf8(csv).unwrap();
+    /// assert!(csv.starts_with("seq,method,result,duration_ns,buffered\n0,poll_ready,ok,"));
+    /// ```
+    pub struct TimingSink<S> {
+        #[pin]
+        inner: S,
+        report: Report,
+        buffered: usize,
+    }
+}
+
+impl<S> TimingSink<S> {
+    /// Wrap `inner` sink.
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            report: Report::default(),
+            buffered: 0,
+        }
+    }
+
+    /// Measurements collected so far.
+    pub fn report(&self) -> &Report {
+        &self.report
+    }
+
+    inner_accessors!(S, "the inner sink");
+
+    fn measured<R>(
+        self: Pin<&mut Self>,
+        method: &'static str,
+        call: impl FnOnce(Pin<&mut S>) -> R,
+    ) -> R
+    where
+        R: Outcome,
+    {
+        let this = self.project();
+        let start = Instant::now();
+        let res = call(this.inner);
+        let duration = start.elapsed();
+        match (method, res.succeeded()) {
+            ("start_send", Some(true)) => *this.buffered += 1,
+            ("poll_flush" | "poll_close", Some(true)) => *this.buffered = 0,
+            _ => (),
+        }
+        this.report.operations.push(Operation {
+            seq: this.report.operations.len(),
+            method,
+            result: res.describe(),
+            duration,
+            buffered: *this.buffered,
+        });
+        res
+    }
+}
+
+/// Results of sink methods as they are written to a [Report].
+trait Outcome {
+    /// `None` for `Poll::Pending`.
+    fn succeeded(&self) -> Option<bool>;
+    fn describe(&self) -> String;
+}
+
+impl<E: Debug> Outcome for Result<(), E> {
+    fn succeeded(&self) -> Option<bool> {
+        Some(Result::is_ok(self))
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Ok(()) => "ok".to_string(),
+            Err(e) => format!("err {:?}", e),
+        }
+    }
+}
+
+impl<E: Debug> Outcome for Poll<Result<(), E>> {
+    fn succeeded(&self) -> Option<bool> {
+        match self {
+            Poll::Ready(res) => Some(res.is_ok()),
+            Poll::Pending => None,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Poll::Ready(res) => res.describe(),
+            Poll::Pending => "pending".to_string(),
+        }
+    }
+}
+
+impl<S, Item> Sink<Item> for TimingSink<S>
+where
+    S: Sink<Item>,
+    S::Error: Debug,
+{
+    type Error = S::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.measured("poll_ready", |inner| inner.poll_ready(cx))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Item) -> Result<(), Self::Error> {
+        self.measured("start_send", |inner| inner.start_send(item))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.measured("poll_flush", |inner| inner.poll_flush(cx))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.measured("poll_close", |inner| inner.poll_close(cx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SinkMock;
+    use futures::{executor::block_on, SinkExt};
+
+    #[test]
+    fn csv_format() {
+        let feedback = vec![
+            Poll::Pending,
+            Poll::Ready(Ok(())),
+            Poll::Ready(Err("reset, by peer")),
+        ];
+        let mut sink = TimingSink::new(SinkMock::with_flush_feedback(feedback.into_iter()));
+        block_on(async {
+            sink.send(1u8).await.unwrap();
+            assert_eq!(sink.close().await, Err("reset, by peer"));
+        });
+
+        let mut csv = Vec::new();
+        sink.report().write_csv(&mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let rows: Vec<_> = csv.lines().collect();
+        assert_eq!(rows[0], "seq,method,result,duration_ns,buffered");
+        assert_eq!(rows.len(), 1 + sink.report().operations().len());
+        assert_eq!(rows.len(), 6);
+        assert!(rows[1].starts_with("0,poll_ready,ok,"));
+        assert!(rows[3].starts_with("2,poll_flush,pending,"));
+        assert!(rows[3].ends_with(",1"));
+        assert!(rows[4].ends_with(",0"));
+        // the error contains a comma and quotes
+        assert!(rows[5].starts_with("4,poll_close,\"err \"\"reset, by peer\"\"\","));
+    }
+
+    #[test]
+    fn write_csv_path() {
+        let mut sink = TimingSink::new(crate::ok::<u8>());
+        block_on(sink.send(1)).unwrap();
+        let path = std::env::temp_dir().join(format!("timing_sink_{}.csv", std::process::id()));
+        sink.report().write_csv_path(&path).unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(csv.lines().count(), 1 + sink.report().operations().len());
+    }
+}
@@ -0,0 +1,416 @@
+use crate::render::render_steps;
+use crate::{Event, Response, Script};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::{self, Debug, Write};
+
+/// What [Script::diff_with()] compares.
+///
+/// Scripts carry no timestamps, so they never affect the diff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiffOptions {
+    compare_items: bool,
+    compare_wakes: bool,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DiffOptions {
+    /// Compare operations, items and outcomes, but not wakes.
+    pub fn new() -> Self {
+        Self {
+            compare_items: true,
+            compare_wakes: false,
+        }
+    }
+
+    /// Treat every `start_send()` as equal regardless of the item.
+    pub fn ignore_items(self) -> Self {
+        Self {
+            compare_items: false,
+            ..self
+        }
+    }
+
+    /// Compare wakes recorded by [Recorder](crate::Recorder) as well.
+    pub fn compare_wakes(self) -> Self {
+        Self {
+            compare_wakes: true,
+            ..self
+        }
+    }
+}
+
+/// `Debug` output of an item or error, printed as is. Traces of sinks whose items and errors are
+/// only `Debug` are diffed as scripts of these.
+#[derive(Clone, PartialEq, Eq)]
+pub(crate) struct Rendered(String);
+
+impl Debug for Rendered {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+pub(crate) type RenderedStep = (Event<Rendered>, Response<Rendered>);
+
+/// A step with the item and the error replaced by their `Debug` output.
+pub(crate) fn rendered_step<Item: Debug, E: Debug>(
+    event: &Event<Item>,
+    response: &Response<E>,
+) -> RenderedStep {
+    let render = |value: &dyn Debug| Rendered(format!("{:?}", value));
+    let event = match event {
+        Event::Ready => Event::Ready,
+        Event::Send(item) => Event::Send(render(item)),
+        Event::Flush => Event::Flush,
+        Event::Close => Event::Close,
+    };
+    let response = match response {
+        Response::Ok => Response::Ok,
+        Response::Err(e) => Response::Err(render(e)),
+        Response::Pending => Response::Pending,
+    };
+    (event, response)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Line {
+    Same(String),
+    Removed(String),
+    Added(String),
+}
+
+/// Alignment of two scripts made by [Script::diff()].
+///
+/// `Display` prints a summary and both scripts merged like a line diff: steps only in the left
+/// script start with `-`, steps only in the right one with `+`. It's meant for panic messages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceDiff {
+    lines: Vec<Line>,
+    first_divergence: Option<usize>,
+}
+
+impl TraceDiff {
+    /// `true` if the scripts are equal with the options used.
+    pub fn is_same(&self) -> bool {
+        self.first_divergence.is_none()
+    }
+
+    /// Index of the first step of the left script that differs, it's the length of the left
+    /// script if only steps were added at its end.
+    pub fn first_divergence(&self) -> Option<usize> {
+        self.first_divergence
+    }
+
+    /// How many lines are only in the left script.
+    pub fn deletions(&self) -> usize {
+        self.lines
+            .iter()
+            .filter(|line| matches!(line, Line::Removed(_)))
+            .count()
+    }
+
+    /// How many lines are only in the right script.
+    pub fn insertions(&self) -> usize {
+        self.lines
+            .iter()
+            .filter(|line| matches!(line, Line::Added(_)))
+            .count()
+    }
+}
+
+impl fmt::Display for TraceDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.first_divergence {
+            None => return write!(f, "traces are the same"),
+            Some(step) => write!(
+                f,
+                "traces differ at step {}: {} removed, {} added",
+                step,
+                self.deletions(),
+                self.insertions()
+            )?,
+        }
+        for line in &self.lines {
+            match line {
+                Line::Same(line) => write!(f, "\n  {}", line)?,
+                Line::Removed(line) => write!(f, "\n- {}", line)?,
+                Line::Added(line) => write!(f, "\n+ {}", line)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Entry {
+    Step(usize),
+    Wake,
+}
+
+/// Steps and wakes in order they are rendered with the rendered lines.
+fn entries<Item: Debug, E: Debug>(
+    script: &Script<Item, E>,
+    with_wakes: bool,
+) -> (Vec<Entry>, Vec<String>) {
+    let wakes = if with_wakes { script.wakes() } else { &[] };
+    let mut entries = Vec::new();
+    let mut at = wakes.iter().peekable();
+    for i in 0..script.steps().len() {
+        while at.next_if(|&&at| at <= i).is_some() {
+            entries.push(Entry::Wake);
+        }
+        entries.push(Entry::Step(i));
+    }
+    entries.extend(at.map(|_| Entry::Wake));
+    let rendered = render_steps(
+        script.steps(),
+        wakes,
+        None,
+        &|out, item| {
+            let _ = write!(out, " {:?}", item);
+        },
+        &|out, e| {
+            let _ = write!(out, " {:?}", e);
+        },
+    );
+    let lines = if entries.is_empty() {
+        Vec::new()
+    } else {
+        rendered.split('\n').map(String::from).collect()
+    };
+    (entries, lines)
+}
+
+fn same_step<Item: PartialEq, E: PartialEq>(
+    left: &(Event<Item>, Response<E>),
+    right: &(Event<Item>, Response<E>),
+    options: DiffOptions,
+) -> bool {
+    let events = match (&left.0, &right.0) {
+        (Event::Send(l), Event::Send(r)) => !options.compare_items || l == r,
+        (l, r) => core::mem::discriminant(l) == core::mem::discriminant(r),
+    };
+    events && left.1 == right.1
+}
+
+impl<Item, E> Script<Item, E>
+where
+    Item: Debug + PartialEq,
+    E: Debug + PartialEq,
+{
+    /// Align this script with `other` and report where they diverge.
+    ///
+    /// Compares operations, items and outcomes, see [diff_with()](Script::diff_with) to change
+    /// that. The alignment is the longest common subsequence of steps, so a step missing in the
+    /// middle shows up as a single deletion rather than a mismatch of every following step.
+    ///
+    /// # Examples
+    /// ```
+    /// use futures_test_sink::{Event, Response, Script};
+    ///
+    /// let expected: Script<u8, ()> = vec![
+    ///     (Event::Ready, Response::Ok),
+    ///     (Event::Send(1), Response::Ok),
+    ///     (Event::Flush, Response::Ok),
+    /// ]
+    /// .into_iter()
+    /// .collect();
+    /// let actual: Script<u8, ()> = vec![
+    ///     (Event::Ready, Response::Ok),
+    ///     (Event::Send(1), Response::Ok),
+    ///     (Event::Flush, Response::Pending),
+    ///     (Event::Flush, Response::Ok),
+    /// ]
+    /// .into_iter()
+    /// .collect();
+    /// let diff = expected.diff(&actual);
+    /// assert_eq!(diff.first_divergence(), Some(2));
+    /// assert_eq!(
+    ///     diff.to_string(),
+    ///     "traces differ at step 2: 0 removed, 1 added
+    ///     0  ready ✔
+    ///     1  send item#0 1 ✔
+    /// +   2  flush … (pending)
+    ///     2  flush ✔"
+    /// );
+    /// ```
+    pub fn diff(&self, other: &Self) -> TraceDiff {
+        self.diff_with(other, DiffOptions::new())
+    }
+
+    /// Like [diff()](Script::diff), but compares what `options` say.
+    pub fn diff_with(&self, other: &Self, options: DiffOptions) -> TraceDiff {
+        let (left, left_lines) = entries(self, options.compare_wakes);
+        let (right, right_lines) = entries(other, options.compare_wakes);
+        let same = |l: usize, r: usize| match (left[l], right[r]) {
+            (Entry::Step(l), Entry::Step(r)) => {
+                same_step(&self.steps()[l], &other.steps()[r], options)
+            }
+            (Entry::Wake, Entry::Wake) => true,
+            _ => false,
+        };
+
+        // lcs[l][r] is the length of the longest common subsequence of left[l..] and right[r..]
+        let mut lcs = vec![vec![0usize; right.len() + 1]; left.len() + 1];
+        for l in (0..left.len()).rev() {
+            for r in (0..right.len()).rev() {
+                lcs[l][r] = if same(l, r) {
+                    lcs[l + 1][r + 1] + 1
+                } else {
+                    lcs[l + 1][r].max(lcs[l][r + 1])
+                };
+            }
+        }
+
+        let mut lines = Vec::new();
+        let mut first_divergence = None;
+        // steps of the left script consumed so far
+        let mut step = 0;
+        let (mut l, mut r) = (0, 0);
+        while l < left.len() || r < right.len() {
+            let in_both = l < left.len() && r < right.len() && same(l, r);
+            if !in_both {
+                first_divergence.get_or_insert(step);
+            }
+            let only_left = r == right.len() || (l < left.len() && lcs[l + 1][r] >= lcs[l][r + 1]);
+            if in_both || only_left {
+                lines.push(if in_both {
+                    Line::Same(left_lines[l].clone())
+                } else {
+                    Line::Removed(left_lines[l].clone())
+                });
+                if let Entry::Step(_) = left[l] {
+                    step += 1;
+                }
+                l += 1;
+                r += usize::from(in_both);
+            } else {
+                lines.push(Line::Added(right_lines[r].clone()));
+                r += 1;
+            }
+        }
+        TraceDiff {
+            lines,
+            first_divergence,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Recorder, SinkMock};
+    use futures::{executor::block_on, SinkExt};
+    use std::task::Poll;
+
+    fn script(steps: Vec<(Event<u8>, Response<&'static str>)>) -> Script<u8, &'static str> {
+        steps.into_iter().collect()
+    }
+
+    #[test]
+    fn same_scripts() {
+        let left = script(vec![
+            (Event::Ready, Response::Ok),
+            (Event::Send(1), Response::Ok),
+        ]);
+        let diff = left.diff(&left.clone());
+        assert!(diff.is_same());
+        assert_eq!(diff.first_divergence(), None);
+        assert_eq!(diff.to_string(), "traces are the same");
+    }
+
+    #[test]
+    fn different_lengths() {
+        let left = script(vec![
+            (Event::Ready, Response::Ok),
+            (Event::Send(1), Response::Ok),
+            (Event::Ready, Response::Ok),
+            (Event::Send(2), Response::Ok),
+            (Event::Close, Response::Ok),
+        ]);
+        let right = script(vec![
+            (Event::Ready, Response::Ok),
+            (Event::Send(1), Response::Ok),
+            (Event::Close, Response::Err("reset")),
+        ]);
+        let diff = left.diff(&right);
+        assert_eq!(diff.first_divergence(), Some(2));
+        assert_eq!((diff.deletions(), diff.insertions()), (3, 1));
+        assert_eq!(
+            diff.to_string(),
+            "traces differ at step 2: 3 removed, 1 added
+    0  ready ✔
+    1  send item#0 1 ✔
+-   2  ready ✔
+-   3  send item#1 2 ✔
+-   4  close ✔
++   2  close ✘ \"reset\""
+        );
+
+        // only appended steps
+        let longer = script(vec![
+            (Event::Ready, Response::Ok),
+            (Event::Send(1), Response::Ok),
+            (Event::Close, Response::Err("reset")),
+            (Event::Close, Response::Ok),
+        ]);
+        let diff = right.diff(&longer);
+        assert_eq!(diff.first_divergence(), Some(3));
+        assert_eq!((diff.deletions(), diff.insertions()), (0, 1));
+    }
+
+    #[test]
+    fn only_item_payload_differs() {
+        let left = script(vec![
+            (Event::Ready, Response::Ok),
+            (Event::Send(1), Response::Ok),
+            (Event::Ready, Response::Ok),
+            (Event::Send(2), Response::Ok),
+        ]);
+        let right = script(vec![
+            (Event::Ready, Response::Ok),
+            (Event::Send(1), Response::Ok),
+            (Event::Ready, Response::Ok),
+            (Event::Send(7), Response::Ok),
+        ]);
+        let diff = left.diff(&right);
+        assert_eq!(diff.first_divergence(), Some(3));
+        assert_eq!((diff.deletions(), diff.insertions()), (1, 1));
+        assert!(diff.to_string().ends_with(
+            "-   3  send item#1 2 ✔
++   3  send item#1 7 ✔"
+        ));
+        assert!(left
+            .diff_with(&right, DiffOptions::new().ignore_items())
+            .is_same());
+    }
+
+    #[test]
+    fn wakes_are_compared_on_request() {
+        let record = |feedback: Vec<Poll<Result<(), &'static str>>>| {
+            let mut recorder = Recorder::new(SinkMock::with_flush_feedback(feedback.into_iter()));
+            block_on(async {
+                recorder.feed(1u8).await.unwrap();
+                recorder.flush().await.unwrap();
+            });
+            recorder.into_script()
+        };
+        let left = record(vec![Poll::Pending, Poll::Ready(Ok(()))]);
+        let mut right = left.clone().into_steps();
+        // the same steps written by hand have no wakes
+        let right: Script<u8, &str> = right.drain(..).collect();
+        assert!(left.diff(&right).is_same());
+        let diff = left.diff_with(&right, DiffOptions::new().compare_wakes());
+        assert_eq!(diff.first_divergence(), Some(3));
+        assert_eq!((diff.deletions(), diff.insertions()), (1, 0));
+        assert!(diff.to_string().contains("\n-      [wake]\n"));
+    }
+}
@@ -0,0 +1,125 @@
+use crate::{Event, Response, Script};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Error;
+
+/// Version of the JSON format written by [Script::to_json()].
+///
+/// It's bumped on every change of the format, so golden files written by an older version fail
+/// to load instead of being compared against a differently shaped trace.
+pub const TRACE_FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct TraceFileRef<'a, Item, E> {
+    version: u32,
+    steps: &'a [(Event<Item>, Response<E>)],
+}
+
+#[derive(Deserialize)]
+struct TraceVersion {
+    version: u32,
+}
+
+#[derive(Deserialize)]
+struct TraceFile<Item, E> {
+    steps: Vec<(Event<Item>, Response<E>)>,
+}
+
+impl<Item, E> Script<Item, E> {
+    /// Serialize the script to pretty printed JSON with a format version.
+    ///
+    /// Items and errors are serialized as they are. If they don't implement `Serialize` export
+    /// their `Debug` representation with `script.debug_repr().to_json()`.
+    ///
+    /// The output is stable for the same script, so it can be compared against a golden file
+    /// checked into the repository.
+    ///
+    /// Requires `serde` feature.
+    ///
+    /// # Panics
+    /// If serialization of an item or an error fails.
+    ///
+    /// # Examples
+    /// ```
+    /// use futures::{executor::block_on, stream, StreamExt};
+    /// use futures_test_sink::{interleave_pending, Recorder, Script};
+    ///
+    /// let mut recorder = Recorder::new(Box::pin(interleave_pending()));
+    /// let items = stream::iter(vec![Ok(1u8), Ok(2)]);
+    /// assert_eq!(Ok(()), block_on(items.forward(&mut recorder)));
+    ///
+    /// // `Never` error isn't `Serialize`, so export `Debug` strings
+    /// let json = recorder.script().debug_repr().to_json();
+    /// assert!(json.contains(r#""version": 1"#));
+    /// assert_eq!(Script::from_json(&json).unwrap(), recorder.script().debug_repr());
+    /// ```
+    pub fn to_json(&self) -> String
+    where
+        Item: Serialize,
+        E: Serialize,
+    {
+        let file = TraceFileRef {
+            version: TRACE_FORMAT_VERSION,
+            steps: self.steps(),
+        };
+        serde_json::to_string_pretty(&file).expect("trace serialization failed")
+    }
+
+    /// Deserialize a script written by [to_json()](Script::to_json).
+    ///
+    /// Requires `serde` feature.
+    ///
+    /// # Errors
+    /// If `json` is malformed or its version isn't [TRACE_FORMAT_VERSION]. The version is checked
+    /// before the steps, so a file in an older format always fails with the version error.
+    pub fn from_json(json: &str) -> Result<Self, Error>
+    where
+        Item: DeserializeOwned,
+        E: DeserializeOwned,
+    {
+        let TraceVersion { version } = serde_json::from_str(json)?;
+        if version != TRACE_FORMAT_VERSION {
+            return Err(serde::de::Error::custom(format_args!(
+                "unsupported trace format version {}, expected {}",
+                version, TRACE_FORMAT_VERSION
+            )));
+        }
+        let TraceFile { steps } = serde_json::from_str(json)?;
+        Ok(Script::new(steps))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let script: Script<u8, String> = vec![
+            (Event::Ready, Response::Pending),
+            (Event::Ready, Response::Ok),
+            (Event::Send(7), Response::Ok),
+            (Event::Flush, Response::Err("reset".to_string())),
+            (Event::Close, Response::Ok),
+        ]
+        .into_iter()
+        .collect();
+        let json = script.to_json();
+        assert!(json.contains(r#""send": 7"#));
+        assert!(json.contains(r#""err": "reset""#));
+        assert_eq!(Script::from_json(&json).unwrap(), script);
+    }
+
+    #[test]
+    fn other_version_is_rejected() {
+        let json = r#"{"version": 0, "steps": [["ready", "ok"]]}"#;
+        let err = Script::<u8, u8>::from_json(json).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unsupported trace format version 0, expected 1"
+        );
+
+        let missing = r#"{"steps": []}"#;
+        let err = Script::<u8, u8>::from_json(missing).unwrap_err();
+        assert!(err.to_string().contains("missing field `version`"));
+    }
+}
@@ -0,0 +1,582 @@
+use crate::{Event, Recorder, Response, Script};
+use alloc::{format, string::String, vec, vec::Vec};
+use core::fmt::{self, Debug, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Method {
+    Ready,
+    Send,
+    Flush,
+    Close,
+    Any,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Ok,
+    Err,
+    Pending,
+    Any,
+}
+
+/// Pattern matching one recorded step of a [Script].
+///
+/// It's created by the method constructor and narrowed by the outcome, e.g.
+/// `StepPattern::flush().pending()`. Without an outcome any outcome matches. Items and errors are
+/// not compared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepPattern {
+    method: Method,
+    outcome: Outcome,
+}
+
+impl StepPattern {
+    fn new(method: Method) -> Self {
+        Self {
+            method,
+            outcome: Outcome::Any,
+        }
+    }
+
+    /// Matches `poll_ready()`.
+    pub fn ready() -> Self {
+        Self::new(Method::Ready)
+    }
+
+    /// Matches `start_send()` with any item.
+    pub fn send() -> Self {
+        Self::new(Method::Send)
+    }
+
+    /// Matches `poll_flush()`.
+    pub fn flush() -> Self {
+        Self::new(Method::Flush)
+    }
+
+    /// Matches `poll_close()`.
+    pub fn close() -> Self {
+        Self::new(Method::Close)
+    }
+
+    /// Matches any method.
+    pub fn any() -> Self {
+        Self::new(Method::Any)
+    }
+
+    /// Match only `Response::Ok`.
+    pub fn ok(self) -> Self {
+        self.outcome(Outcome::Ok)
+    }
+
+    /// Match only `Response::Err` with any error.
+    pub fn err(self) -> Self {
+        self.outcome(Outcome::Err)
+    }
+
+    /// Match only `Response::Pending`.
+    pub fn pending(self) -> Self {
+        self.outcome(Outcome::Pending)
+    }
+
+    fn outcome(self, outcome: Outcome) -> Self {
+        Self { outcome, ..self }
+    }
+
+    /// Check if `step` matches this pattern.
+    pub fn matches<Item, E>(&self, step: &(Event<Item>, Response<E>)) -> bool {
+        let method = matches!(
+            (self.method, &step.0),
+            (Method::Any, _)
+                | (Method::Ready, Event::Ready)
+                | (Method::Send, Event::Send(_))
+                | (Method::Flush, Event::Flush)
+                | (Method::Close, Event::Close)
+        );
+        let outcome = matches!(
+            (self.outcome, &step.1),
+            (Outcome::Any, _)
+                | (Outcome::Ok, Response::Ok)
+                | (Outcome::Err, Response::Err(_))
+                | (Outcome::Pending, Response::Pending)
+        );
+        method && outcome
+    }
+}
+
+impl fmt::Display for StepPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let method = match self.method {
+            Method::Ready => "ready",
+            Method::Send => "send",
+            Method::Flush => "flush",
+            Method::Close => "close",
+            Method::Any => "any",
+        };
+        let outcome = match self.outcome {
+            Outcome::Ok => "(ok)",
+            Outcome::Err => "(err)",
+            Outcome::Pending => "(pending)",
+            Outcome::Any => "",
+        };
+        write!(f, "{}{}", method, outcome)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Element {
+    One(StepPattern),
+    Repeated(StepPattern),
+    Skip,
+}
+
+impl fmt::Display for Element {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Element::One(step) => write!(f, "{}", step),
+            Element::Repeated(step) => write!(f, "{}*", step),
+            Element::Skip => f.write_str(".."),
+        }
+    }
+}
+
+/// Where a trace stopped matching a pattern. Returned by [TraceMatcher::check()].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    /// Index of the first step that no way of matching could consume, or the trace length when
+    /// the trace ended too early.
+    pub step: usize,
+    /// Patterns that would be accepted at `step`, like `flush(ok)` or `close(ok)*`. When the trace
+    /// ended too early it's the next pattern of the longest partial match.
+    pub expected: Vec<String>,
+}
+
+/// Pattern of a whole recorded trace with wildcards. Usually written with [assert_trace!].
+///
+/// The pattern is a sequence of elements:
+/// - a [StepPattern] matches exactly one step,
+/// - a [repeated()](TraceMatcher::repeated) step pattern (`*` in the macro) matches any number
+///   of consecutive steps, including none,
+/// - [skip()](TraceMatcher::skip) (`..` in the macro) matches any number of any steps.
+///
+/// The whole trace must be matched. Repetitions and skips are tried with every possible length,
+/// so it doesn't matter whether they are greedy or lazy: the trace matches if any split of it
+/// fits. For example `[flush(pending)*, flush(pending), flush(ok)]` matches two pending flushes
+/// followed by a successful one.
+///
+/// # Examples
+/// ```
+/// use futures::{executor::block_on, stream, StreamExt};
+/// use futures_test_sink::{interleave_pending, Recorder, StepPattern, TraceMatcher};
+///
+/// let mut recorder = Recorder::new(Box::pin(interleave_pending()));
+/// let items = stream::iter(vec![Ok(1u8), Ok(2)]);
+/// block_on(items.forward(&mut recorder)).unwrap();
+///
+/// let mut matcher = TraceMatcher::new();
+/// matcher
+///     .step(StepPattern::ready().ok())
+///     .step(StepPattern::send())
+///     .skip()
+///     .repeated(StepPattern::close().pending())
+///     .step(StepPattern::close().ok());
+/// assert!(matcher.matches(&recorder));
+/// assert_eq!(matcher.to_string(), "[ready(ok), send, .., close(pending)*, close(ok)]");
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TraceMatcher {
+    elements: Vec<Element>,
+}
+
+impl TraceMatcher {
+    /// Create a pattern matching only an empty trace.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a pattern matching exactly one step.
+    pub fn step(&mut self, step: StepPattern) -> &mut Self {
+        self.elements.push(Element::One(step));
+        self
+    }
+
+    /// Append a pattern matching any number of consecutive steps matching `step`.
+    pub fn repeated(&mut self, step: StepPattern) -> &mut Self {
+        self.elements.push(Element::Repeated(step));
+        self
+    }
+
+    /// Append a pattern matching any number of any steps.
+    pub fn skip(&mut self) -> &mut Self {
+        self.elements.push(Element::Skip);
+        self
+    }
+
+    /// Check if the whole `trace` matches.
+    pub fn matches<T, Item, E>(&self, trace: &T) -> bool
+    where
+        T: AsRef<[(Event<Item>, Response<E>)]> + ?Sized,
+    {
+        self.check(trace).is_ok()
+    }
+
+    /// Match the whole `trace` returning where it diverged from the pattern.
+    pub fn check<T, Item, E>(&self, trace: &T) -> Result<(), Mismatch>
+    where
+        T: AsRef<[(Event<Item>, Response<E>)]> + ?Sized,
+    {
+        let trace = trace.as_ref();
+        // states[i] means the first `i` elements matched the steps seen so far
+        let mut states = vec![false; self.elements.len() + 1];
+        states[0] = true;
+        self.close_over_empty(&mut states);
+        for (index, step) in trace.iter().enumerate() {
+            let mut next = vec![false; states.len()];
+            for (i, element) in self.elements.iter().enumerate() {
+                if !states[i] {
+                    continue;
+                }
+                match element {
+                    Element::One(pattern) if pattern.matches(step) => next[i + 1] = true,
+                    Element::Repeated(pattern) if pattern.matches(step) => next[i] = true,
+                    Element::Skip => next[i] = true,
+                    _ => (),
+                }
+            }
+            self.close_over_empty(&mut next);
+            if !next.contains(&true) {
+                return Err(self.mismatch(index, &states));
+            }
+            states = next;
+        }
+        if states[self.elements.len()] {
+            Ok(())
+        } else {
+            // the furthest partial match is the closest to the whole pattern
+            let furthest = states.iter().rposition(|live| *live).unwrap_or(0);
+            Err(Mismatch {
+                step: trace.len(),
+                expected: vec![format!("{}", self.elements[furthest])],
+            })
+        }
+    }
+
+    /// Panic with the pattern, the trace and the first divergence if `trace` doesn't match.
+    ///
+    /// This is what [assert_trace!] calls.
+    ///
+    /// # Panics
+    /// When the trace doesn't match.
+    pub fn assert_matches<T, Item, E>(&self, trace: &T)
+    where
+        T: AsRef<[(Event<Item>, Response<E>)]> + ?Sized,
+        Item: Debug,
+        E: Debug,
+    {
+        let trace = trace.as_ref();
+        if let Err(mismatch) = self.check(trace) {
+            let rendered = crate::render::render_steps(
+                trace,
+                &[],
+                Some(mismatch.step),
+                &|out, item| {
+                    let _ = write!(out, " {:?}", item);
+                },
+                &|out, e| {
+                    let _ = write!(out, " {:?}", e);
+                },
+            );
+            let mut msg = format!("trace doesn't match\npattern: {}\n{}", self, rendered);
+            let expected = mismatch.expected.join(" or ");
+            if mismatch.step == trace.len() {
+                let _ = write!(
+                    msg,
+                    "\ntrace ended after {} steps, expected {}",
+                    mismatch.step, expected
+                );
+            } else {
+                let _ = write!(
+                    msg,
+                    "\nfirst divergence at step {}: expected {}",
+                    mismatch.step, expected
+                );
+            }
+            panic!("{}", msg);
+        }
+    }
+
+    // repetitions and skips can match no step
+    fn close_over_empty(&self, states: &mut [bool]) {
+        for (i, element) in self.elements.iter().enumerate() {
+            if states[i] && !matches!(element, Element::One(_)) {
+                states[i + 1] = true;
+            }
+        }
+    }
+
+    fn mismatch(&self, step: usize, states: &[bool]) -> Mismatch {
+        let mut expected: Vec<String> = Vec::new();
+        for (i, element) in self.elements.iter().enumerate() {
+            let element = format!("{}", element);
+            if states[i] && !expected.contains(&element) {
+                expected.push(element);
+            }
+        }
+        if states[self.elements.len()] {
+            expected.push("end of trace".into());
+        }
+        Mismatch { step, expected }
+    }
+}
+
+impl fmt::Display for TraceMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[")?;
+        for (i, element) in self.elements.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}", element)?;
+        }
+        f.write_str("]")
+    }
+}
+
+impl<Item, E> AsRef<[(Event<Item>, Response<E>)]> for Script<Item, E> {
+    fn as_ref(&self) -> &[(Event<Item>, Response<E>)] {
+        self.steps()
+    }
+}
+
+impl<S, Item, E> AsRef<[(Event<Item>, Response<E>)]> for Recorder<S, Item, E> {
+    fn as_ref(&self) -> &[(Event<Item>, Response<E>)] {
+        self.script().steps()
+    }
+}
+
+/// Assert that a recorded trace matches a pattern with wildcards.
+///
+/// The first argument is anything with recorded steps: a [Recorder](crate::Recorder), a
+/// [Script](crate::Script) or a slice of steps. The pattern is a list of:
+/// - `ready`, `send`, `flush`, `close` or `any` matching one step with any outcome,
+/// - the same followed by `(ok)`, `(err)` or `(pending)` matching only that outcome,
+/// - any of the above followed by `*` matching any number of such steps,
+/// - `..` matching any number of any steps.
+///
+/// See [TraceMatcher] for the matching rules. On failure it panics with the pattern, the whole
+/// trace and the index of the first step that couldn't be matched.
+///
+/// # Examples
+/// ```
+/// use futures::{executor::block_on, SinkExt};
+/// use futures_test_sink::{assert_trace, from_iter, Recorder};
+/// use std::{iter, task::Poll};
+///
+/// let poll_fallback = vec![
+///     Poll::Ready(Ok::<_, ()>(())),
+///     Poll::Pending,
+///     Poll::Pending,
+///     Poll::Ready(Ok(())),
+///     Poll::Ready(Ok(())),
+/// ];
+/// let sink = from_iter(poll_fallback.into_iter(), iter::repeat(Ok(())));
+/// let mut recorder = Recorder::new(sink);
+/// block_on(recorder.send(1u8)).unwrap();
+/// block_on(recorder.close()).unwrap();
+///
+/// assert_trace!(recorder, [ready(ok), send, flush(pending)*, flush(ok), close(ok)]);
+/// assert_trace!(recorder, [.., send(ok), ..]);
+/// ```
+#[macro_export]
+macro_rules! assert_trace {
+    ($trace:expr, [$($pattern:tt)*] $(,)?) => {{
+        let mut matcher = $crate::TraceMatcher::new();
+        $crate::__trace_pattern!(matcher; $($pattern)*);
+        matcher.assert_matches(&$trace);
+    }};
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __trace_pattern {
+    ($m:ident;) => {};
+    ($m:ident; .. $(, $($rest:tt)*)?) => {
+        $m.skip();
+        $crate::__trace_pattern!($m; $($($rest)*)?);
+    };
+    ($m:ident; $method:ident ($outcome:ident) * $(, $($rest:tt)*)?) => {
+        $m.repeated($crate::StepPattern::$method().$outcome());
+        $crate::__trace_pattern!($m; $($($rest)*)?);
+    };
+    ($m:ident; $method:ident ($outcome:ident) $(, $($rest:tt)*)?) => {
+        $m.step($crate::StepPattern::$method().$outcome());
+        $crate::__trace_pattern!($m; $($($rest)*)?);
+    };
+    ($m:ident; $method:ident * $(, $($rest:tt)*)?) => {
+        $m.repeated($crate::StepPattern::$method());
+        $crate::__trace_pattern!($m; $($($rest)*)?);
+    };
+    ($m:ident; $method:ident $(, $($rest:tt)*)?) => {
+        $m.step($crate::StepPattern::$method());
+        $crate::__trace_pattern!($m; $($($rest)*)?);
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SinkMock;
+    use futures::{
+        executor::block_on,
+        stream::{self, StreamExt},
+    };
+    use std::{panic, string::ToString, task::Poll};
+
+    type Step = (Event<u8>, Response<&'static str>);
+
+    fn flushes(pending: usize) -> Vec<Step> {
+        let mut trace = vec![(Event::Ready, Response::Ok), (Event::Send(1), Response::Ok)];
+        trace.extend((0..pending).map(|_| (Event::Flush, Response::Pending)));
+        trace.push((Event::Flush, Response::Ok));
+        trace.push((Event::Close, Response::Ok));
+        trace
+    }
+
+    fn panic_message(f: impl FnOnce() + panic::UnwindSafe) -> String {
+        let payload = panic::catch_unwind(f).unwrap_err();
+        payload.downcast_ref::<String>().unwrap().clone()
+    }
+
+    #[test]
+    fn repetition_matches_zero_or_more() {
+        for pending in 0..4 {
+            assert_trace!(
+                flushes(pending),
+                [ready(ok), send, flush(pending)*, flush(ok), close(ok)]
+            );
+        }
+    }
+
+    #[test]
+    fn greedy_repetition_backtracks() {
+        // a greedy `flush*` would eat the last flush needed by the next element
+        let trace = flushes(2);
+        assert_trace!(trace, [ready, send, flush*, flush(ok), close]);
+        assert_trace!(trace, [ready, send, flush(pending)*, flush(pending), flush(ok), close]);
+        assert_trace!(trace, [ready, send, flush*, flush, flush, close]);
+        // but it can't split more than there is
+        let mut matcher = TraceMatcher::new();
+        matcher
+            .step(StepPattern::ready())
+            .step(StepPattern::send())
+            .repeated(StepPattern::flush())
+            .step(StepPattern::flush().pending())
+            .step(StepPattern::flush().pending())
+            .step(StepPattern::flush().pending())
+            .step(StepPattern::flush().ok());
+        assert!(!matcher.matches(&trace));
+    }
+
+    #[test]
+    fn lazy_skip_finds_later_match() {
+        // `..` can't stop at the first `flush`, the pattern needs the second to be `ok`
+        let trace = flushes(3);
+        assert_trace!(trace, [.., flush(ok), close]);
+        assert_trace!(trace, [.., flush(pending), ..]);
+        assert_trace!(trace, [.., send, .., flush(ok), ..]);
+        assert_trace!(trace, [..]);
+        assert_trace!(Vec::<Step>::new(), [..]);
+        assert_trace!(Vec::<Step>::new(), [flush*, ..]);
+    }
+
+    #[test]
+    fn whole_trace_must_match() {
+        let trace = flushes(1);
+        let mut matcher = TraceMatcher::new();
+        matcher.step(StepPattern::ready());
+        assert_eq!(
+            matcher.check(&trace),
+            Err(Mismatch {
+                step: 1,
+                expected: vec!["end of trace".to_string()]
+            })
+        );
+        matcher.skip().step(StepPattern::ready());
+        assert!(!matcher.matches(&trace));
+        assert!(TraceMatcher::new().matches(&Vec::<Step>::new()));
+    }
+
+    #[test]
+    fn any_method_and_outcomes() {
+        let trace = vec![
+            (Event::Ready, Response::Err("broken")),
+            (Event::Send(2), Response::Pending),
+            (Event::Close, Response::Ok),
+        ];
+        assert_trace!(trace, [ready(err), any(pending), any(ok)]);
+        assert_trace!(trace, [any*]);
+        let mut matcher = TraceMatcher::new();
+        matcher.step(StepPattern::ready().ok()).skip();
+        assert!(!matcher.matches(&trace));
+    }
+
+    #[test]
+    fn mismatch_reports_divergence() {
+        let trace = flushes(1);
+        let mut matcher = TraceMatcher::new();
+        matcher
+            .step(StepPattern::ready())
+            .step(StepPattern::send())
+            .repeated(StepPattern::flush().pending())
+            .step(StepPattern::close());
+        assert_eq!(
+            matcher.check(&trace),
+            Err(Mismatch {
+                step: 3,
+                expected: vec!["flush(pending)*".to_string(), "close".to_string()]
+            })
+        );
+        let msg = panic_message(|| matcher.assert_matches(&trace));
+        assert_eq!(
+            msg,
+            "trace doesn't match
+pattern: [ready, send, flush(pending)*, close]
+  0  ready ✔
+  1  send item#0 1 ✔
+  2  flush … (pending)
+  3  flush ✔ <-
+  4  close ✔
+first divergence at step 3: expected flush(pending)* or close"
+        );
+    }
+
+    #[test]
+    fn mismatch_at_trace_end() {
+        let trace = flushes(0);
+        let msg = panic_message(|| assert_trace!(trace, [.., close(ok), flush]));
+        assert!(msg.ends_with("trace ended after 4 steps, expected flush"));
+        assert!(msg.starts_with("trace doesn't match\npattern: [.., close(ok), flush]\n"));
+    }
+
+    #[test]
+    fn recorded_forward() {
+        let feedback = vec![Poll::Ready(Ok(())), Poll::Pending].into_iter().cycle();
+        let mut recorder = Recorder::new(SinkMock::with_flush_feedback(feedback));
+        let items = stream::iter((0..5u8).map(Ok::<_, &str>));
+        block_on(items.forward(&mut recorder)).unwrap();
+        assert_trace!(recorder, [ready(ok), send(ok), .., close(pending)*, close(ok)]);
+        // the fourth `poll_ready()` has to flush a full buffer
+        assert_trace!(
+            recorder.script(),
+            [
+                ready(ok),
+                send,
+                ready(ok),
+                send,
+                ready(ok),
+                send,
+                ready(pending),
+                ..
+            ]
+        );
+    }
+}
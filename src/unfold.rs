@@ -0,0 +1,250 @@
+use core::marker::PhantomData;
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures::sink::Sink;
+
+use crate::wake_policy::Waking;
+
+/// Operation performed on [SinkUnfold].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Event<Item> {
+    /// `poll_ready()` was called.
+    Ready,
+    /// `start_send()` was called with the item.
+    Send(Item),
+    /// `poll_flush()` was called.
+    Flush,
+    /// `poll_close()` was called.
+    Close,
+}
+
+/// Response returned from the transition function of [SinkUnfold].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Response<E> {
+    /// `Poll::Ready(Ok(()))` or `Ok(())` for `start_send()`.
+    Ok,
+    /// `Poll::Ready(Err(e))` or `Err(e)` for `start_send()`.
+    Err(E),
+    /// `Poll::Pending`. The waker is woken before it's returned.
+    ///
+    /// `start_send()` can't be pending, so it panics on this response.
+    Pending,
+}
+
+/// Sink driven by a state and a transition function.
+///
+/// For details see [sink_unfold()].
+///
+/// [sink_unfold()]:sink_unfold
+pub struct SinkUnfold<S, F, Item> {
+    state: S,
+    f: F,
+    waking: Waking,
+    item_type: PhantomData<fn(Item)>,
+}
+
+/// Create a sink from `initial` state and the transition function `f`.
+///
+/// Every call to `poll_ready`, `start_send`, `poll_flush` or `poll_close` is passed to `f` as an
+/// [Event] together with the mutable state. The returned [Response] is what the method returns.
+/// On [Response::Pending] the waker is woken like in [from_iter()](crate::from_iter), or as set by
+/// [wake_policy()](SinkUnfold::wake_policy).
+///
+/// The state can be inspected with [state()] or recovered by [into_state()].
+///
+/// # Examples
+/// Sink that needs two polls to complete handshake, then streams and finally drains buffered items
+/// on close.
+/// ```
+/// use futures::{
+///     executor::block_on,
+///     stream::{self, StreamExt},
+/// };
+/// use futures_test_sink::{sink_unfold, Event, Response};
+///
+/// #[derive(Debug, PartialEq)]
+/// enum Phase {
+///     Handshake(usize),
+///     Streaming(Vec<u8>),
+///     Draining(Vec<u8>),
+///     Closed(Vec<u8>),
+/// }
+///
+/// let mut sink = sink_unfold(Phase::Handshake(2), |phase, event| match (&mut *phase, event) {
+///     (Phase::Handshake(0), Event::Ready) => {
+///         *phase = Phase::Streaming(Vec::new());
+///         Response::Ok
+///     }
+///     (Phase::Handshake(left), _) => {
+///         *left -= 1;
+///         Response::Pending
+///     }
+///     (Phase::Streaming(items), Event::Send(item)) => {
+///         items.push(item);
+///         Response::Ok
+///     }
+///     (Phase::Streaming(items), Event::Close) => {
+///         *phase = Phase::Draining(items.split_off(0));
+///         Response::Pending
+///     }
+///     (Phase::Streaming(_), _) => Response::Ok,
+///     (Phase::Draining(items), Event::Close) => {
+///         *phase = Phase::Closed(items.split_off(0));
+///         Response::Ok
+///     }
+///     (_, event) => Response::Err(format!("unexpected {:?}", event)),
+/// });
+///
+/// let items = stream::iter(vec![Ok(1u8), Ok(2), Ok(3)]);
+/// assert_eq!(Ok(()), block_on(items.forward(&mut sink)));
+/// assert_eq!(sink.into_state(), Phase::Closed(vec![1, 2, 3]));
+/// ```
+///
+/// [state()]: SinkUnfold::state
+/// [into_state()]: SinkUnfold::into_state
+pub fn sink_unfold<S, F, Item, E>(initial: S, f: F) -> SinkUnfold<S, F, Item>
+where
+    F: FnMut(&mut S, Event<Item>) -> Response<E>,
+{
+    SinkUnfold {
+        state: initial,
+        f,
+        waking: Waking::default(),
+        item_type: Default::default(),
+    }
+}
+
+impl<S, F, Item> Unpin for SinkUnfold<S, F, Item> {}
+
+impl<S, F, Item> SinkUnfold<S, F, Item> {
+    /// Current state.
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+
+    /// Consume the sink returning its state.
+    pub fn into_state(self) -> S {
+        self.state
+    }
+
+    waking_setters! {
+        /// Set how the waker is woken before returning `Poll::Pending`.
+    }
+}
+
+impl<S, F, Item, E> SinkUnfold<S, F, Item>
+where
+    F: FnMut(&mut S, Event<Item>) -> Response<E>,
+{
+    fn poll_event(&mut self, event: Event<Item>, cx: &mut Context<'_>) -> Poll<Result<(), E>> {
+        self.waking.polled();
+        let res = match (self.f)(&mut self.state, event) {
+            Response::Ok => Ok(()),
+            Response::Err(e) => Err(e),
+            Response::Pending => {
+                self.waking.pending(cx.waker());
+                return Poll::Pending;
+            }
+        };
+        self.waking.ready(cx.waker());
+        Poll::Ready(res)
+    }
+}
+
+impl<S, F, Item, E> Sink<Item> for SinkUnfold<S, F, Item>
+where
+    F: FnMut(&mut S, Event<Item>) -> Response<E>,
+{
+    type Error = E;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::into_inner(self).poll_event(Event::Ready, cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Item) -> Result<(), Self::Error> {
+        let this = Pin::into_inner(self);
+        match (this.f)(&mut this.state, Event::Send(item)) {
+            Response::Ok => Ok(()),
+            Response::Err(e) => Err(e),
+            Response::Pending => panic!("`start_send()` can't return `Response::Pending`"),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::into_inner(self).poll_event(Event::Flush, cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Pin::into_inner(self).poll_event(Event::Close, cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_task::waker_fn;
+    use std::sync::{atomic, Arc};
+
+    #[test]
+    fn events_and_responses() {
+        // create a Context
+        let wake_cnt = Arc::new(atomic::AtomicUsize::new(0));
+        let cnt = wake_cnt.clone();
+        let waker = waker_fn(move || {
+            wake_cnt.fetch_add(1, atomic::Ordering::SeqCst);
+        });
+        let mut cx = Context::from_waker(&waker);
+        // actual test
+        let mut s = sink_unfold(Vec::new(), |events, event| {
+            let response = match event {
+                Event::Ready => Response::Ok,
+                Event::Send(item) if item > 10 => Response::Err(item),
+                Event::Send(_) => Response::Ok,
+                Event::Flush if events.len() < 4 => Response::Pending,
+                Event::Flush | Event::Close => Response::Ok,
+            };
+            events.push(event);
+            response
+        });
+
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(Pin::new(&mut s).start_send(1), Ok(()));
+        assert_eq!(Pin::new(&mut s).start_send(11), Err(11));
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Pending);
+        assert_eq!(1, cnt.load(atomic::Ordering::SeqCst));
+        assert_eq!(Pin::new(&mut s).poll_flush(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(Pin::new(&mut s).poll_close(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(1, cnt.load(atomic::Ordering::SeqCst));
+
+        assert_eq!(
+            s.into_state(),
+            vec![
+                Event::Ready,
+                Event::Send(1),
+                Event::Send(11),
+                Event::Flush,
+                Event::Flush,
+                Event::Close
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "`start_send()` can't return `Response::Pending`")]
+    fn pending_send() {
+        let mut s = sink_unfold((), |_, _| Response::<()>::Pending);
+        let _ = Pin::new(&mut s).start_send(1);
+    }
+}
@@ -0,0 +1,877 @@
+/// The `wake_*()` setters of a mock, expanded in its inherent `impl` block.
+///
+/// The mock must have a `waking: Waking` field. Takes the doc comment of `wake_policy()`, which
+/// says when the mock returns `Poll::Pending`.
+macro_rules! waking_setters {
+    ($(#[$policy_doc:meta])*) => {
+        $(#[$policy_doc])*
+        pub fn wake_policy(&mut self, policy: crate::WakePolicy) -> &mut Self {
+            self.waking.set(policy);
+            self
+        }
+
+        /// Set which wakers are kept by [WakePolicy::Never](crate::WakePolicy::Never) and
+        /// [WakePolicy::AfterPolls](crate::WakePolicy::AfterPolls).
+        pub fn wake_target(&mut self, target: crate::WakeTarget) -> &mut Self {
+            self.waking.set_target(target);
+            self
+        }
+
+        /// Set how the waker of the polling context is woken.
+        pub fn wake_style(&mut self, style: crate::WakeStyle) -> &mut Self {
+            self.waking.set_style(style);
+            self
+        }
+
+        /// Wake the waker of every `Poll::Pending` as the next element of `script` says,
+        /// counting polls on `clock`. See [WakeWhen](crate::WakeWhen).
+        pub fn wake_script(
+            &mut self,
+            script: impl Iterator<Item = crate::WakeWhen> + Send + Sync + 'static,
+            clock: &crate::PollClock,
+        ) -> &mut Self {
+            self.waking.set_script(script, clock.clone());
+            self
+        }
+
+        /// Wake the wakers stored by [WakePolicy::Never](crate::WakePolicy::Never) or
+        /// [WakePolicy::AfterPolls](crate::WakePolicy::AfterPolls). Returns `false` if no waker
+        /// was stored.
+        pub fn wake_stored(&mut self) -> bool {
+            self.waking.wake_stored()
+        }
+
+        /// Handle waking the stored wakers from the test or another thread.
+        pub fn wake_handle(&self) -> crate::WakeHandle {
+            self.waking.handle()
+        }
+    };
+}
+
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::task::Waker;
+use core::{fmt, iter, mem};
+#[cfg(feature = "std")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "std")]
+use std::time::Duration;
+
+use crate::counters::{Lock, Method};
+use crate::{PollClock, WakeWhen};
+
+/// How a mock wakes the task when it returns `Poll::Pending`.
+///
+/// Set it with `wake_policy()` of [SinkMock](crate::SinkMock::wake_policy),
+/// [SinkFeedback](crate::SinkFeedback::wake_policy),
+/// [TryFromResults](crate::stream_mock::TryFromResults::wake_policy),
+/// [ControlSink](crate::ControlSink::wake_policy), [ReplaySink](crate::ReplaySink::wake_policy),
+/// [SinkUnfold](crate::SinkUnfold::wake_policy) and
+/// [SlowConsumer](crate::SlowConsumer::wake_policy). Stored wakers are woken by `wake_stored()`
+/// of the mock or a [WakeHandle]. [SinkMock](crate::SinkMock) keeps a waker per method that
+/// returned `Poll::Pending`, other mocks only the latest one, unless [WakeTarget::All] is set. A
+/// waker replaced by a later one is dropped without waking.
+///
+/// Wake counters like [MockCounters] count only wakes done during a poll, wakes done later by
+/// `wake_stored()`, a [WakeHandle] or a background thread aren't counted.
+///
+/// [MockCounters]: crate::MockCounters
+///
+/// # Examples
+/// The task isn't woken until the test decides so:
+/// ```
+/// use futures_test_sink::{
+///     assert_flush_pending, assert_flush_ready, task::count_waker, SinkMock, WakePolicy,
+/// };
+/// use std::task::{Context, Poll};
+///
+/// let feedback = vec![Poll::Pending, Poll::Ready(Ok::<_, ()>(()))];
+/// let mut sink = SinkMock::<_, _, _, u8, _>::with_flush_feedback(feedback.into_iter());
+/// sink.wake_policy(WakePolicy::Never);
+/// let (waker, cnt) = count_waker();
+/// let mut cx = Context::from_waker(&waker);
+///
+/// assert_flush_pending!(sink, cx);
+/// cnt.expect_none();
+///
+/// assert!(sink.wake_stored());
+/// cnt.expect_wakes(1);
+/// assert_flush_ready!(sink, cx);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WakePolicy {
+    /// Wake the waker before returning `Poll::Pending`.
+    #[default]
+    Immediate,
+    /// Don't wake, store the waker. The test wakes it with `wake_stored()` of the mock.
+    Never,
+    /// Store the waker and wake it when the mock is polled the `n`th time after returning
+    /// `Poll::Pending`, before the poll does anything else. Another `Poll::Pending` in between
+    /// replaces the waker but doesn't restart the count. `AfterPolls(0)` is
+    /// [Immediate](WakePolicy::Immediate).
+    ///
+    /// Only a test polling by hand or another future of the same task polls the mock then, so
+    /// this catches code that relies on being polled without a wake.
+    AfterPolls(usize),
+    /// Wake `1 + extra` times before returning `Poll::Pending` and, if `also_on_ready`, once
+    /// before returning `Poll::Ready` from a `poll_*()` method.
+    ///
+    /// Legal, but the nastiest wake storm a leaf future can cause. Code under test must not
+    /// panic, spin or complete twice under it, and shouldn't amplify it: a combinator passing the
+    /// waker through wakes the task exactly as often as the mock.
+    Spurious {
+        /// Wakes on top of the one needed.
+        extra: usize,
+        /// Wake on `Poll::Ready` too, when no wake is needed at all.
+        also_on_ready: bool,
+    },
+    /// Wake from a background thread after the delay, like a reactor would.
+    ///
+    /// One timer thread is shared by all mocks. It's started by the first delayed wake and ends
+    /// when no wakes are scheduled. Pending wakes of a dropped mock are cancelled and never fire.
+    /// [flush_pending_wakes()](crate::flush_pending_wakes) fires all scheduled wakes at once.
+    ///
+    /// Requires `std` feature.
+    #[cfg(feature = "std")]
+    Delayed(Duration),
+}
+
+/// Which wakers a mock with [WakePolicy::Never] or [WakePolicy::AfterPolls] keeps and wakes.
+///
+/// Set it with `wake_target()` of a mock, e.g. [SinkMock::wake_target()](crate::SinkMock::wake_target).
+/// With [Latest](WakeTarget::Latest) a task polling the mock replaces the waker of another task
+/// waiting on the same method, so the other task is stranded, like with a sink that has room for
+/// one waiter. [All](WakeTarget::All) keeps every distinct waker and wakes them all, which catches
+/// code that wakes only the newest waiter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WakeTarget {
+    /// Keep the latest waker of every method that returned `Poll::Pending`.
+    #[default]
+    Latest,
+    /// Keep every waker that doesn't [will_wake()](Waker::will_wake) the same task as a stored one,
+    /// up to 32. Beyond that the oldest one is dropped without waking.
+    All,
+}
+
+/// How a mock wakes the waker of the polling context.
+///
+/// Set it with `wake_style()` of a mock, e.g. [SinkMock::wake_style()](crate::SinkMock::wake_style).
+/// Both are correct, but a custom waker can behave differently, e.g. fail or be slow to clone.
+/// [WakerStats](crate::task::WakerStats) of a [tracking_waker()](crate::task::tracking_waker)
+/// shows which one was used: `wake_by_refs()` counts [ByRef](WakeStyle::ByRef), `clones()` and
+/// `wakes()` count [CloneAndWake](WakeStyle::CloneAndWake). Stored wakers are clones already and
+/// are woken the same way with either style.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WakeStyle {
+    /// `cx.waker().wake_by_ref()`, the waker isn't cloned.
+    #[default]
+    ByRef,
+    /// `cx.waker().clone().wake()`, like code that keeps the waker and wakes it later.
+    CloneAndWake,
+}
+
+/// When [SinkMock](crate::SinkMock) wakes the task relative to returning from the poll.
+///
+/// Set it with [SinkMock::wake_order()](crate::SinkMock::wake_order). It applies to all wakes
+/// done during a poll, e.g. by [WakePolicy::Immediate] or [WakePolicy::Spurious].
+///
+/// A waker woken during a poll lets a multi-threaded executor, or another task sharing state with
+/// this one, poll again on another thread before the first poll returned. The single-threaded
+/// executors of tests never do that, so code that breaks when a re-poll overlaps the end of the
+/// previous poll passes them. The orders other than [BeforeReturn](WakeOrder::BeforeReturn) hand
+/// the waker to the timer thread of [WakePolicy::Delayed] with no delay. The waker is cloned
+/// then, whatever the [WakeStyle].
+///
+/// Requires `std` feature.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WakeOrder {
+    /// Wake inside the poll, before it returns `Poll::Pending`.
+    #[default]
+    BeforeReturn,
+    /// Wake from another thread at once, racing with the return. Runs on a multi-threaded
+    /// runtime then exercise both interleavings.
+    RacingReturn,
+    /// Wake from another thread strictly after the poll of the mock returned, deterministic
+    /// where [RacingReturn](WakeOrder::RacingReturn) isn't.
+    AfterReturn,
+}
+
+/// How events between two polls wake the waiting task, e.g. acks of
+/// [SinkMock::ack()](crate::SinkMock::ack) or window updates of
+/// [WindowSink::add_window()](crate::WindowSink::add_window).
+///
+/// Set it with `wake_coalescing()` of the mock. A real reactor coalesces readiness events into
+/// one wake, while a mock waking once per event can hide a missing wake by sheer volume.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WakeCoalescing {
+    /// Every event wakes the task waiting for it, until the task polls the mock again.
+    #[default]
+    PerEvent,
+    /// Only the first event after the task waits wakes it. Later events until the task polls the
+    /// mock again are counted as coalesced.
+    PerProgress,
+}
+
+/// Waker of a `Poll::Pending` woken by events outside of polls.
+#[derive(Default)]
+pub(crate) struct EventWaker {
+    coalescing: WakeCoalescing,
+    waker: Option<Waker>,
+    // woken since the last poll
+    woken: bool,
+    coalesced: usize,
+}
+
+impl EventWaker {
+    pub(crate) fn set(&mut self, coalescing: WakeCoalescing) {
+        self.coalescing = coalescing;
+    }
+
+    /// Store the waker of a `Poll::Pending` waiting for an event.
+    pub(crate) fn register(&mut self, waker: &Waker) {
+        match &mut self.waker {
+            Some(stored) => stored.clone_from(waker),
+            None => self.waker = Some(waker.clone()),
+        }
+        self.woken = false;
+    }
+
+    /// Called at the start of every poll, the task doesn't wait anymore.
+    pub(crate) fn polled(&mut self) {
+        self.waker = None;
+        self.woken = false;
+    }
+
+    /// An event happened. Returns the waker to wake, so it can be woken outside of a lock.
+    pub(crate) fn event(&mut self) -> Option<Waker> {
+        match self.coalescing {
+            WakeCoalescing::PerEvent => self.waker.clone(),
+            WakeCoalescing::PerProgress if self.woken => {
+                self.coalesced += 1;
+                None
+            }
+            WakeCoalescing::PerProgress => {
+                let waker = self.waker.take();
+                self.woken = waker.is_some();
+                waker
+            }
+        }
+    }
+
+    /// How many events didn't wake because of [WakeCoalescing::PerProgress].
+    pub(crate) fn coalesced(&self) -> usize {
+        self.coalesced
+    }
+}
+
+/// Most wakers kept by [WakeTarget::All].
+const MAX_WAKERS: usize = 32;
+
+/// Wakers stored by a mock, shared with its [WakeHandle]s.
+#[derive(Default)]
+struct Stored {
+    // the site is the method that returned `Poll::Pending`, the latest waker is the last one
+    wakers: Vec<(Option<Method>, Waker)>,
+    // wakes done outside of polls
+    fired: usize,
+}
+
+/// Wake the waker stored by a mock with [WakePolicy::Never] or [WakePolicy::AfterPolls] from the
+/// test.
+///
+/// A mock stores one waker per method that returned `Poll::Pending`, so e.g. a waker of a pending
+/// `poll_ready()` and of a pending `poll_close()` can be stored at once, or every distinct waker
+/// with [WakeTarget::All]. Waking consumes the waker,
+/// so waking again does nothing until the mock stores another one. Dropping the mock drops stored
+/// wakers without waking them.
+///
+/// Get it from [from_iter_manual_wake()](crate::from_iter_manual_wake) or `wake_handle()` of a
+/// mock, e.g. [SinkMock::wake_handle()](crate::SinkMock::wake_handle).
+///
+/// # Examples
+/// ```
+/// use futures::{executor::block_on, SinkExt};
+/// use futures_test_sink::from_iter_manual_wake;
+/// use std::{iter, task::Poll, thread};
+///
+/// let feedback = vec![Poll::Pending, Poll::Ready(Ok::<_, ()>(()))];
+/// let (mut sink, handle) =
+///     from_iter_manual_wake::<u8, _, _, _>(feedback.into_iter(), iter::empty());
+/// let task = thread::spawn(move || block_on(sink.flush()));
+///
+/// while !handle.has_pending_waker() {
+///     thread::yield_now();
+/// }
+/// assert!(!task.is_finished());
+/// assert!(handle.wake());
+/// assert_eq!(task.join().unwrap(), Ok(()));
+/// ```
+#[derive(Clone)]
+pub struct WakeHandle {
+    shared: Arc<Lock<Stored>>,
+}
+
+impl WakeHandle {
+    /// Wake the waker stored last. Returns `false` if no waker was stored.
+    pub fn wake(&self) -> bool {
+        match self.shared.with(|stored| stored.wakers.pop()) {
+            Some((_, waker)) => {
+                self.fire(iter::once(waker));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Wake all stored wakers. Returns how many were woken.
+    pub fn wake_all(&self) -> usize {
+        let wakers = self.shared.with(|stored| mem::take(&mut stored.wakers));
+        let n = wakers.len();
+        self.fire(wakers.into_iter().map(|(_, waker)| waker));
+        n
+    }
+
+    /// How many wakes were done outside of polls.
+    pub(crate) fn fired(&self) -> usize {
+        self.shared.with(|stored| stored.fired)
+    }
+
+    /// `true` if a waker is stored.
+    pub fn has_pending_waker(&self) -> bool {
+        self.shared.with(|stored| !stored.wakers.is_empty())
+    }
+
+    /// `true` if both handles wake the wakers of the same mock.
+    #[cfg(feature = "std")]
+    pub(crate) fn same(&self, other: &WakeHandle) -> bool {
+        Arc::ptr_eq(&self.shared, &other.shared)
+    }
+
+    /// Wake `wakers` counting them as wakes done outside of polls.
+    pub(crate) fn fire(&self, wakers: impl Iterator<Item = Waker>) {
+        for waker in wakers {
+            self.shared.with(|stored| stored.fired += 1);
+            waker.wake();
+        }
+    }
+}
+
+impl fmt::Debug for WakeHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WakeHandle")
+            .field("has_pending_waker", &self.has_pending_waker())
+            .finish()
+    }
+}
+
+/// Wake state shared by all mocks: the policy and the stored wakers.
+#[derive(Default)]
+pub(crate) struct Waking {
+    policy: WakePolicy,
+    target: WakeTarget,
+    style: WakeStyle,
+    #[cfg(feature = "std")]
+    order: WakeOrder,
+    // polls that returned, for `WakeOrder::AfterReturn`
+    #[cfg(feature = "std")]
+    returned: Arc<AtomicUsize>,
+    handle: WakeHandle,
+    polls_left: usize,
+    // overrides the policy until it ends
+    script: Option<Box<dyn Iterator<Item = WakeWhen> + Send + Sync>>,
+    clock: Option<PollClock>,
+}
+
+impl Default for WakeHandle {
+    fn default() -> Self {
+        Self {
+            shared: Arc::new(Lock::new(Stored::default())),
+        }
+    }
+}
+
+impl Waking {
+    pub(crate) fn set(&mut self, policy: WakePolicy) {
+        self.policy = policy;
+        self.drop_stored();
+    }
+
+    pub(crate) fn set_target(&mut self, target: WakeTarget) {
+        self.target = target;
+        self.drop_stored();
+    }
+
+    pub(crate) fn set_style(&mut self, style: WakeStyle) {
+        self.style = style;
+    }
+
+    pub(crate) fn set_script(
+        &mut self,
+        script: impl Iterator<Item = WakeWhen> + Send + Sync + 'static,
+        clock: PollClock,
+    ) {
+        self.script = Some(Box::new(script));
+        self.clock = Some(clock);
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn set_order(&mut self, order: WakeOrder) {
+        self.order = order;
+    }
+
+    /// Called when a poll returns, after all wakes of the poll.
+    #[cfg(feature = "std")]
+    pub(crate) fn returned(&self) {
+        self.returned.fetch_add(1, Ordering::SeqCst);
+        if self.order == WakeOrder::AfterReturn {
+            crate::timer::release(&self.returned);
+        }
+    }
+
+    /// Wake the waker of the polling context in the set [WakeStyle] and [WakeOrder].
+    pub(crate) fn wake(&self, waker: &Waker) {
+        #[cfg(feature = "std")]
+        match self.order {
+            WakeOrder::BeforeReturn => (),
+            WakeOrder::RacingReturn => {
+                let handle = self.handle.clone();
+                return crate::timer::schedule(Duration::ZERO, waker.clone(), handle, None);
+            }
+            WakeOrder::AfterReturn => {
+                // this poll returns as the next one
+                let gate = (
+                    self.returned.clone(),
+                    self.returned.load(Ordering::SeqCst) + 1,
+                );
+                let handle = self.handle.clone();
+                return crate::timer::schedule(Duration::ZERO, waker.clone(), handle, Some(gate));
+            }
+        }
+        match self.style {
+            WakeStyle::ByRef => waker.wake_by_ref(),
+            // the clone is the point
+            #[allow(clippy::waker_clone_wake)]
+            WakeStyle::CloneAndWake => waker.clone().wake(),
+        }
+    }
+
+    /// [wake()](Waking::wake) a waker stored by an earlier poll, counted in
+    /// [fired()](Waking::fired) like a wake of a [WakeHandle].
+    fn wake_stored_waker(&self, waker: &Waker) {
+        // a deferred wake is counted by the timer thread firing it
+        #[cfg(feature = "std")]
+        if self.order != WakeOrder::BeforeReturn {
+            return self.wake(waker);
+        }
+        self.handle.shared.with(|stored| stored.fired += 1);
+        self.wake(waker);
+    }
+
+    pub(crate) fn handle(&self) -> WakeHandle {
+        self.handle.clone()
+    }
+
+    /// How many wakes were done outside of polls: by a [WakeHandle], `wake_stored()`, a
+    /// background thread or [AfterPolls](WakePolicy::AfterPolls) for a waker of an earlier poll.
+    pub(crate) fn fired(&self) -> usize {
+        self.handle.fired()
+    }
+
+    /// Called at the start of every poll. Wakes the wakers stored by
+    /// [AfterPolls](WakePolicy::AfterPolls) when it's due and returns how many were woken.
+    pub(crate) fn polled(&mut self) -> usize {
+        if let Some(clock) = &self.clock {
+            clock.tick();
+        }
+        if let WakePolicy::AfterPolls(_) = self.policy {
+            // a waker stored by `WakeWhen::Manual` doesn't arm the countdown
+            if self.polls_left > 0 && self.handle.has_pending_waker() {
+                self.polls_left -= 1;
+                if self.polls_left == 0 {
+                    let wakers = self
+                        .handle
+                        .shared
+                        .with(|stored| mem::take(&mut stored.wakers));
+                    wakers
+                        .iter()
+                        .for_each(|(_, waker)| self.wake_stored_waker(waker));
+                    return wakers.len();
+                }
+            }
+        }
+        0
+    }
+
+    /// Called when the mock returns `Poll::Pending`. Returns how many times `waker` was woken.
+    pub(crate) fn pending(&mut self, waker: &Waker) -> usize {
+        self.pending_at(None, waker)
+    }
+
+    /// [pending()](Waking::pending) of a mock storing a waker per method.
+    pub(crate) fn pending_at(&mut self, site: Option<Method>, waker: &Waker) -> usize {
+        if let Some(when) = self.script.as_mut().and_then(Iterator::next) {
+            return match when {
+                WakeWhen::Immediately | WakeWhen::AfterPolls(0) => {
+                    self.wake(waker);
+                    1
+                }
+                WakeWhen::AfterPolls(n) => {
+                    if let Some(clock) = &self.clock {
+                        clock.schedule(n, waker.clone(), self.handle.clone());
+                    }
+                    0
+                }
+                WakeWhen::Never => 0,
+                WakeWhen::Manual => {
+                    self.store(site, waker);
+                    0
+                }
+            };
+        }
+        match self.policy {
+            WakePolicy::Immediate | WakePolicy::AfterPolls(0) => {
+                self.wake(waker);
+                1
+            }
+            WakePolicy::Never => {
+                self.store(site, waker);
+                0
+            }
+            WakePolicy::AfterPolls(n) => {
+                if self.polls_left == 0 || !self.handle.has_pending_waker() {
+                    self.polls_left = n;
+                }
+                self.store(site, waker);
+                0
+            }
+            WakePolicy::Spurious { extra, .. } => {
+                for _ in 0..=extra {
+                    self.wake(waker);
+                }
+                1 + extra
+            }
+            #[cfg(feature = "std")]
+            WakePolicy::Delayed(delay) => {
+                crate::timer::schedule(delay, waker.clone(), self.handle.clone(), None);
+                0
+            }
+        }
+    }
+
+    /// Called when a `poll_*()` method of the mock returns `Poll::Ready`. Returns how many times
+    /// `waker` was woken.
+    pub(crate) fn ready(&mut self, waker: &Waker) -> usize {
+        match self.policy {
+            WakePolicy::Spurious {
+                also_on_ready: true,
+                ..
+            } => {
+                self.wake(waker);
+                1
+            }
+            _ => 0,
+        }
+    }
+
+    /// Wake all stored wakers. Returns `false` if there were none.
+    pub(crate) fn wake_stored(&mut self) -> bool {
+        self.handle.wake_all() > 0
+    }
+
+    fn store(&mut self, site: Option<Method>, waker: &Waker) {
+        let target = self.target;
+        let evicted = self.handle.shared.with(|stored| {
+            let same = match target {
+                WakeTarget::Latest => stored.wakers.iter().position(|(s, _)| *s == site),
+                WakeTarget::All => stored.wakers.iter().position(|(_, w)| w.will_wake(waker)),
+            };
+            match same {
+                Some(i) => {
+                    let (_, mut stored_waker) = stored.wakers.remove(i);
+                    stored_waker.clone_from(waker);
+                    stored.wakers.push((site, stored_waker));
+                    None
+                }
+                None => {
+                    stored.wakers.push((site, waker.clone()));
+                    if stored.wakers.len() > MAX_WAKERS {
+                        Some(stored.wakers.remove(0))
+                    } else {
+                        None
+                    }
+                }
+            }
+        });
+        // dropped outside of the lock
+        drop(evicted);
+    }
+
+    fn drop_stored(&mut self) {
+        // dropped outside of the lock
+        let wakers = self
+            .handle
+            .shared
+            .with(|stored| mem::take(&mut stored.wakers));
+        drop(wakers);
+    }
+}
+
+impl Drop for Waking {
+    fn drop(&mut self) {
+        // a poll that panicked never returned, release wakes waiting for it
+        #[cfg(feature = "std")]
+        self.returned.store(usize::MAX, Ordering::SeqCst);
+        // scheduled wakes of a dropped mock wake nobody waiting for it
+        #[cfg(feature = "std")]
+        crate::timer::cancel(&self.handle);
+        self.drop_stored();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::count_waker;
+
+    #[test]
+    fn immediate() {
+        let (waker, cnt) = count_waker();
+        let mut waking = Waking::default();
+        assert_eq!(waking.pending(&waker), 1);
+        cnt.expect_wakes(1);
+        assert!(!waking.wake_stored());
+    }
+
+    #[test]
+    fn never_until_woken_by_hand() {
+        let (waker, cnt) = count_waker();
+        let mut waking = Waking::default();
+        waking.set(WakePolicy::Never);
+        assert_eq!(waking.pending(&waker), 0);
+        assert_eq!(waking.polled(), 0);
+        cnt.expect_none();
+        assert!(waking.wake_stored());
+        cnt.expect_wakes(1);
+        // the waker is consumed
+        assert!(!waking.wake_stored());
+        cnt.expect_wakes(1);
+    }
+
+    #[test]
+    fn after_polls() {
+        let (waker, cnt) = count_waker();
+        let mut waking = Waking::default();
+        waking.set(WakePolicy::AfterPolls(2));
+        assert_eq!(waking.pending(&waker), 0);
+        assert_eq!(waking.polled(), 0);
+        cnt.expect_none();
+        assert_eq!(waking.polled(), 1);
+        cnt.expect_wakes(1);
+        assert_eq!(waking.fired(), 1);
+        assert_eq!(waking.polled(), 0);
+        cnt.expect_wakes(1);
+        assert_eq!(waking.fired(), 1);
+
+        waking.set(WakePolicy::AfterPolls(0));
+        assert_eq!(waking.pending(&waker), 1);
+        cnt.expect_wakes(2);
+    }
+
+    #[test]
+    fn after_polls_with_manual_script() {
+        let (waker, cnt) = count_waker();
+        let mut waking = Waking::default();
+        waking.set(WakePolicy::AfterPolls(2));
+        waking.set_script(vec![WakeWhen::Manual].into_iter(), PollClock::new());
+        assert_eq!(waking.pending(&waker), 0);
+        assert_eq!(waking.polled(), 0);
+        assert_eq!(waking.polled(), 0);
+        cnt.expect_none();
+
+        // the script ended, the policy counts down again
+        assert_eq!(waking.pending(&waker), 0);
+        assert_eq!(waking.polled(), 0);
+        assert_eq!(waking.polled(), 1);
+        cnt.expect_wakes(1);
+    }
+
+    #[test]
+    fn spurious() {
+        let (waker, cnt) = count_waker();
+        let mut waking = Waking::default();
+        waking.set(WakePolicy::Spurious {
+            extra: 3,
+            also_on_ready: false,
+        });
+        assert_eq!(waking.pending(&waker), 4);
+        assert_eq!(waking.ready(&waker), 0);
+        cnt.expect_wakes(4);
+
+        waking.set(WakePolicy::Spurious {
+            extra: 0,
+            also_on_ready: true,
+        });
+        assert_eq!(waking.pending(&waker), 1);
+        assert_eq!(waking.ready(&waker), 1);
+        cnt.expect_wakes(6);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn delayed() {
+        let (waker, cnt) = count_waker();
+        let mut waking = Waking::default();
+        waking.set(WakePolicy::Delayed(Duration::from_millis(10)));
+        assert_eq!(waking.pending(&waker), 0);
+        let start = std::time::Instant::now();
+        while cnt.get() == 0 {
+            assert!(start.elapsed() < Duration::from_secs(5), "never woken");
+            std::thread::yield_now();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(5));
+        cnt.expect_wakes(1);
+    }
+
+    #[test]
+    fn handle_wakes_per_site() {
+        let (ready, ready_cnt) = count_waker();
+        let (close, close_cnt) = count_waker();
+        let mut waking = Waking::default();
+        waking.set(WakePolicy::Never);
+        let handle = waking.handle();
+        assert!(!handle.has_pending_waker());
+        waking.pending_at(Some(Method::Ready), &ready);
+        waking.pending_at(Some(Method::Close), &close);
+        // replaces the waker of the same site
+        waking.pending_at(Some(Method::Ready), &ready);
+        assert!(handle.has_pending_waker());
+
+        // the latest first
+        assert!(handle.wake());
+        ready_cnt.expect_wakes(1);
+        close_cnt.expect_none();
+        assert_eq!(handle.wake_all(), 1);
+        close_cnt.expect_wakes(1);
+        assert!(!handle.wake());
+        assert_eq!(handle.wake_all(), 0);
+        assert_eq!(waking.fired(), 2);
+    }
+
+    #[test]
+    fn dropped_without_waking() {
+        let (waker, cnt) = count_waker();
+        let mut waking = Waking::default();
+        waking.set(WakePolicy::Never);
+        waking.pending(&waker);
+        let handle = waking.handle();
+        drop(waking);
+        assert!(!handle.has_pending_waker());
+        assert!(!handle.wake());
+        cnt.expect_none();
+    }
+
+    #[test]
+    fn all_target_dedups_by_will_wake() {
+        let (waker_a, cnt_a) = count_waker();
+        let (waker_b, cnt_b) = count_waker();
+        let mut waking = Waking::default();
+        waking.set(WakePolicy::Never);
+        waking.set_target(WakeTarget::All);
+        waking.pending(&waker_a);
+        waking.pending(&waker_b);
+        waking.pending(&waker_a.clone());
+        waking.pending(&waker_b);
+        assert_eq!(waking.handle().wake_all(), 2);
+        cnt_a.expect_wakes(1);
+        cnt_b.expect_wakes(1);
+    }
+
+    #[test]
+    fn all_target_is_bounded() {
+        let cnts: Vec<_> = (0..MAX_WAKERS + 2).map(|_| count_waker()).collect();
+        let mut waking = Waking::default();
+        waking.set(WakePolicy::Never);
+        waking.set_target(WakeTarget::All);
+        for (waker, _) in &cnts {
+            waking.pending(waker);
+        }
+        assert_eq!(waking.handle().wake_all(), MAX_WAKERS);
+        // the oldest ones were dropped
+        cnts[0].1.expect_none();
+        cnts[1].1.expect_none();
+        cnts[2..].iter().for_each(|(_, cnt)| cnt.expect_wakes(1));
+    }
+
+    #[test]
+    fn latest_target_replaces() {
+        let (waker_a, cnt_a) = count_waker();
+        let (waker_b, cnt_b) = count_waker();
+        let mut waking = Waking::default();
+        waking.set(WakePolicy::Never);
+        waking.pending(&waker_a);
+        waking.pending(&waker_b);
+        assert_eq!(waking.handle().wake_all(), 1);
+        cnt_a.expect_none();
+        cnt_b.expect_wakes(1);
+    }
+
+    #[test]
+    fn wake_style_shows_in_waker_stats() {
+        let (waker, stats) = crate::task::tracking_waker();
+        let mut waking = Waking::default();
+        assert_eq!(waking.pending(&waker), 1);
+        assert_eq!(stats.wake_by_refs(), 1);
+        assert_eq!(stats.clones(), 0);
+
+        waking.set_style(WakeStyle::CloneAndWake);
+        assert_eq!(waking.pending(&waker), 1);
+        assert_eq!(stats.wake_by_refs(), 1);
+        assert_eq!(stats.clones(), 1);
+        assert_eq!(stats.wakes(), 1);
+        assert_eq!(stats.live_clones(), 0);
+    }
+
+    // wakes like the mocks do
+    fn events(event_waker: &mut EventWaker, n: usize) {
+        for _ in 0..n {
+            if let Some(waker) = event_waker.event() {
+                waker.wake();
+            }
+        }
+    }
+
+    #[test]
+    fn events_coalesced_per_progress() {
+        let (waker, cnt) = count_waker();
+        let mut event_waker = EventWaker::default();
+        event_waker.set(WakeCoalescing::PerProgress);
+        // nobody waits
+        events(&mut event_waker, 1);
+        assert_eq!(event_waker.coalesced(), 0);
+        event_waker.register(&waker);
+        events(&mut event_waker, 3);
+        cnt.expect_wakes(1);
+        assert_eq!(event_waker.coalesced(), 2);
+
+        event_waker.polled();
+        event_waker.register(&waker);
+        events(&mut event_waker, 1);
+        cnt.expect_wakes(2);
+        assert_eq!(event_waker.coalesced(), 2);
+    }
+
+    #[test]
+    fn events_wake_per_event_until_polled() {
+        let (waker, cnt) = count_waker();
+        let mut event_waker = EventWaker::default();
+        event_waker.register(&waker);
+        events(&mut event_waker, 3);
+        cnt.expect_wakes(3);
+        event_waker.polled();
+        events(&mut event_waker, 1);
+        cnt.expect_wakes(3);
+        assert_eq!(event_waker.coalesced(), 0);
+    }
+}
@@ -0,0 +1,150 @@
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+use core::task::Waker;
+
+use crate::counters::Lock;
+use crate::WakeHandle;
+
+/// When the waker of one `Poll::Pending` is woken, one element of a wake script.
+///
+/// A script is set with `wake_script()` of a mock, e.g.
+/// [SinkMock::wake_script()](crate::SinkMock::wake_script). Every `Poll::Pending` returned by
+/// the mock consumes one element, when the script ends the [WakePolicy](crate::WakePolicy)
+/// applies again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WakeWhen {
+    /// Wake before returning `Poll::Pending`.
+    Immediately,
+    /// Wake at the start of the `n`th poll of any mock sharing the [PollClock], counting from the
+    /// poll after the `Poll::Pending`. `AfterPolls(0)` is [Immediately](WakeWhen::Immediately).
+    AfterPolls(usize),
+    /// Drop the waker without waking it. The wake is lost.
+    Never,
+    /// Store the waker, the test wakes it with `wake_stored()` of the mock or a [WakeHandle].
+    Manual,
+}
+
+/// Clock counting polls of all mocks it's passed to, for [WakeWhen::AfterPolls].
+///
+/// Every poll of such a mock ticks the clock and fires the wakes due, before the mock does
+/// anything else. So the wake of one mock can be tied to polls of another one, which makes an
+/// interleaving of wakes and polls across mocks deterministic. Clones share the clock.
+///
+/// # Examples
+/// ```
+/// use futures_test_sink::{
+///     assert_flush_pending, assert_flush_ready, task::count_waker, PollClock, SinkMock, WakeWhen,
+/// };
+/// use std::{iter, task::{Context, Poll}};
+///
+/// let clock = PollClock::new();
+/// let feedback = vec![Poll::Pending, Poll::Ready(Ok::<_, ()>(()))];
+/// let mut a = SinkMock::<_, _, _, u8, _>::with_flush_feedback(feedback.into_iter());
+/// a.wake_script(iter::once(WakeWhen::AfterPolls(2)), &clock);
+/// let mut b = SinkMock::<_, _, _, u8, ()>::with_flush_feedback(iter::repeat(Poll::Ready(Ok(()))));
+/// b.wake_script(iter::empty(), &clock);
+///
+/// let (waker, cnt) = count_waker();
+/// let mut cx = Context::from_waker(&waker);
+/// assert_flush_pending!(a, cx);
+/// assert_flush_ready!(b, cx);
+/// cnt.expect_none();
+/// // the second poll of any mock fires the wake
+/// assert_flush_ready!(b, cx);
+/// cnt.expect_wakes(1);
+/// ```
+#[derive(Clone)]
+pub struct PollClock {
+    shared: Arc<Lock<Clock>>,
+}
+
+#[derive(Default)]
+struct Clock {
+    now: usize,
+    // due time, the waker and the handle of the mock that returned `Poll::Pending`
+    due: Vec<(usize, Waker, WakeHandle)>,
+}
+
+impl PollClock {
+    /// Create a clock at 0.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How many polls were counted.
+    pub fn now(&self) -> usize {
+        self.shared.with(|clock| clock.now)
+    }
+
+    /// How many wakes are scheduled and not fired yet.
+    pub fn scheduled(&self) -> usize {
+        self.shared.with(|clock| clock.due.len())
+    }
+
+    /// Count a poll and fire the wakes due.
+    pub(crate) fn tick(&self) {
+        let due = self.shared.with(|clock| {
+            clock.now += 1;
+            let now = clock.now;
+            let (due, later): (Vec<_>, Vec<_>) =
+                clock.due.drain(..).partition(|(at, _, _)| *at <= now);
+            clock.due = later;
+            due
+        });
+        // woken outside of the lock
+        for (_, waker, handle) in due {
+            handle.fire(core::iter::once(waker));
+        }
+    }
+
+    /// Wake `waker` at the start of the `polls`th poll from now.
+    pub(crate) fn schedule(&self, polls: usize, waker: Waker, handle: WakeHandle) {
+        self.shared
+            .with(|clock| clock.due.push((clock.now + polls, waker, handle)));
+    }
+}
+
+impl Default for PollClock {
+    fn default() -> Self {
+        Self {
+            shared: Arc::new(Lock::new(Clock::default())),
+        }
+    }
+}
+
+impl fmt::Debug for PollClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PollClock")
+            .field("now", &self.now())
+            .field("scheduled", &self.scheduled())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::task::count_waker;
+
+    #[test]
+    fn fires_when_due() {
+        let clock = PollClock::new();
+        let handle = WakeHandle::default();
+        let (waker_a, cnt_a) = count_waker();
+        let (waker_b, cnt_b) = count_waker();
+        clock.schedule(1, waker_a, handle.clone());
+        clock.schedule(3, waker_b, handle.clone());
+        assert_eq!(clock.scheduled(), 2);
+
+        clock.tick();
+        cnt_a.expect_wakes(1);
+        cnt_b.expect_none();
+        clock.tick();
+        clock.tick();
+        cnt_b.expect_wakes(1);
+        assert_eq!(clock.now(), 3);
+        assert_eq!(clock.scheduled(), 0);
+        assert_eq!(handle.fired(), 2);
+    }
+}
@@ -0,0 +1,285 @@
+use alloc::sync::Arc;
+use core::convert::TryFrom;
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicU32, Ordering};
+use core::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+use futures::{never::Never, sink::Sink};
+
+use crate::counters::Lock;
+use crate::wake_policy::EventWaker;
+use crate::WakeCoalescing;
+
+struct Window {
+    window: AtomicU32,
+    waker: Lock<EventWaker>,
+}
+
+impl Window {
+    fn add(&self, n: u32) {
+        // `fetch_update` with closure returning `Some` never fails.
+        let _ = self
+            .window
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |w| {
+                Some(w.saturating_add(n))
+            });
+        // woken outside of the lock, a waker could poll the sink inline
+        if let Some(waker) = self.waker.with(EventWaker::event) {
+            waker.wake();
+        }
+    }
+
+    fn consume(&self, n: u32) {
+        let _ = self
+            .window
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |w| {
+                Some(w.saturating_sub(n))
+            });
+    }
+}
+
+/// Sink that models HTTP/2-style flow control window.
+///
+/// For details see [window_sink()].
+///
+/// [window_sink()]:window_sink
+pub struct WindowSink<Item, F> {
+    shared: Arc<Window>,
+    size_fn: F,
+    item_type: PhantomData<fn(Item)>,
+}
+
+/// Handle that allow to replenish window of [WindowSink] owned by someone else.
+#[derive(Clone)]
+pub struct WindowHandle {
+    shared: Arc<Window>,
+}
+
+/// Create a sink with flow control window of `initial` bytes.
+///
+/// `poll_ready` returns `Poll::Ready(Ok(()))` while the window is greater than zero. Otherwise it
+/// stores the waker and returns `Poll::Pending`. The waker is woken by every [add_window()] until
+/// the sink is polled again, or only by the first one with
+/// [WakeCoalescing::PerProgress](crate::WakeCoalescing::PerProgress).
+///
+/// `start_send` decrements the window by the length of the item. Like in HTTP/2 readiness only
+/// guarantees the window is open, so the window saturates at zero if the item is bigger than it.
+///
+/// `poll_flush` and `poll_close` always return `Poll::Ready(Ok(()))`.
+///
+/// # Examples
+/// ```
+/// use futures_test_sink::{
+///     assert_sink_pending, assert_sink_ready, assert_start_send_ok, task::noop_context,
+///     window_sink,
+/// };
+///
+/// let mut cx = noop_context();
+/// let mut s = window_sink::<&[u8]>(4);
+///
+/// assert_sink_ready!(s, cx);
+/// assert_start_send_ok!(s, &b"data"[..]);
+/// assert_eq!(s.window(), 0);
+/// assert_sink_pending!(s, cx);
+///
+/// s.add_window(10);
+/// assert_sink_ready!(s, cx);
+/// ```
+///
+/// [add_window()]: WindowSink::add_window
+pub fn window_sink<Item>(initial: u32) -> WindowSink<Item, fn(&Item) -> usize>
+where
+    Item: AsRef<[u8]>,
+{
+    window_sink_with(initial, |item: &Item| item.as_ref().len())
+}
+
+/// Like [window_sink()] but the size of item is computed by `size_fn`.
+///
+/// [window_sink()]:window_sink
+pub fn window_sink_with<Item, F>(initial: u32, size_fn: F) -> WindowSink<Item, F>
+where
+    F: Fn(&Item) -> usize,
+{
+    WindowSink {
+        shared: Arc::new(Window {
+            window: AtomicU32::new(initial),
+            waker: Lock::new(EventWaker::default()),
+        }),
+        size_fn,
+        item_type: Default::default(),
+    }
+}
+
+impl<Item, F> Unpin for WindowSink<Item, F> {}
+
+impl<Item, F> WindowSink<Item, F> {
+    /// Current size of the window.
+    pub fn window(&self) -> u32 {
+        self.shared.window.load(Ordering::SeqCst)
+    }
+
+    /// Increase window by `n` and wake the task waiting in `poll_ready`.
+    pub fn add_window(&self, n: u32) {
+        self.shared.add(n)
+    }
+
+    /// Set how many wakes window updates between two polls cause, see
+    /// [add_window()](WindowSink::add_window).
+    pub fn wake_coalescing(&mut self, coalescing: WakeCoalescing) -> &mut Self {
+        self.shared.waker.with(|waker| waker.set(coalescing));
+        self
+    }
+
+    /// How many window updates didn't wake the task because of
+    /// [WakeCoalescing::PerProgress].
+    pub fn coalesced_wakes(&self) -> usize {
+        self.shared.waker.with(|waker| waker.coalesced())
+    }
+
+    /// Create handle that can replenish window after this sink is moved.
+    pub fn handle(&self) -> WindowHandle {
+        WindowHandle {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl WindowHandle {
+    /// Current size of the window.
+    pub fn window(&self) -> u32 {
+        self.shared.window.load(Ordering::SeqCst)
+    }
+
+    /// Increase window by `n` and wake the task waiting in `poll_ready`.
+    pub fn add_window(&self, n: u32) {
+        self.shared.add(n)
+    }
+}
+
+impl<Item, F> Sink<Item> for WindowSink<Item, F>
+where
+    F: Fn(&Item) -> usize,
+{
+    type Error = Never;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.shared.waker.with(EventWaker::polled);
+        if self.window() > 0 {
+            return Poll::Ready(Ok(()));
+        }
+        self.shared.waker.with(|waker| waker.register(cx.waker()));
+        // window could be updated before waker was registered.
+        if self.window() > 0 {
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Item) -> Result<(), Self::Error> {
+        let size = u32::try_from((self.size_fn)(&item)).unwrap_or(u32::MAX);
+        self.shared.consume(size);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_task::waker_fn;
+    use futures::{
+        executor::block_on,
+        stream::{self, StreamExt},
+    };
+    use std::sync::atomic::AtomicUsize;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn window_decrements_and_wakes() {
+        // create a Context
+        let wake_cnt = Arc::new(AtomicUsize::new(0));
+        let cnt = wake_cnt.clone();
+        let waker = waker_fn(move || {
+            wake_cnt.fetch_add(1, Ordering::SeqCst);
+        });
+        let mut cx = Context::from_waker(&waker);
+        // actual test
+        let mut s = window_sink::<Vec<u8>>(5);
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(Pin::new(&mut s).start_send(vec![1, 2, 3]), Ok(()));
+        assert_eq!(s.window(), 2);
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        // window saturate at zero
+        assert_eq!(Pin::new(&mut s).start_send(vec![1, 2, 3]), Ok(()));
+        assert_eq!(s.window(), 0);
+
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Pending);
+        // sink doesn't wake itself
+        assert_eq!(0, cnt.load(Ordering::SeqCst));
+
+        s.handle().add_window(3);
+        assert_eq!(1, cnt.load(Ordering::SeqCst));
+        assert_eq!(s.window(), 3);
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+    }
+
+    #[test]
+    fn size_fn() {
+        let waker = waker_fn(|| {});
+        let mut cx = Context::from_waker(&waker);
+        let mut s = window_sink_with(10, |item: &u16| *item as usize);
+        assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        assert_eq!(Pin::new(&mut s).start_send(7), Ok(()));
+        assert_eq!(s.window(), 3);
+    }
+
+    #[test]
+    fn replenish_from_other_thread() {
+        let sink = window_sink::<&'static str>(4);
+        let handle = sink.handle();
+        let replenish = thread::spawn(move || {
+            for _ in 0..4 {
+                thread::sleep(Duration::from_millis(10));
+                handle.add_window(4);
+            }
+        });
+
+        let stream = stream::iter(vec![Ok("abcd"), Ok("efgh"), Ok("ijkl"), Ok("mnop")]);
+        assert_eq!(Ok(()), block_on(stream.forward(sink)));
+        replenish.join().unwrap();
+    }
+
+    #[test]
+    fn window_updates_coalesced() {
+        let cases = [
+            (WakeCoalescing::PerEvent, 3, 0),
+            (WakeCoalescing::PerProgress, 1, 2),
+        ];
+        for &(coalescing, wakes, coalesced) in &cases {
+            let (waker, cnt) = crate::task::count_waker();
+            let mut cx = Context::from_waker(&waker);
+            let mut s = window_sink::<Vec<u8>>(0);
+            s.wake_coalescing(coalescing);
+            assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Pending);
+            let handle = s.handle();
+            for _ in 0..3 {
+                handle.add_window(1);
+            }
+            cnt.expect_wakes(wakes);
+            assert_eq!(s.coalesced_wakes(), coalesced);
+            assert_eq!(Pin::new(&mut s).poll_ready(&mut cx), Poll::Ready(Ok(())));
+        }
+    }
+}
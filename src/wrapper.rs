@@ -0,0 +1,69 @@
+//! Pieces shared by the sink wrappers.
+//!
+//! A wrapper declares its struct with `pin_project!` and the inner sink in a structurally pinned
+//! `inner` field. So it's `Unpin` exactly when the inner sink is and wraps `!Unpin` sinks without
+//! `unsafe` code.
+
+/// `get_ref()`, `get_mut()`, `get_pin_mut()` and `into_inner()` of a wrapper, expanded in its
+/// inherent `impl` block. `$inner` is the type parameter of the `inner` field and `$what` names
+/// it in the docs.
+macro_rules! inner_accessors {
+    ($inner:ident, $what:literal) => {
+        #[doc = concat!("Get a reference to ", $what, ".")]
+        pub fn get_ref(&self) -> &$inner {
+            &self.inner
+        }
+
+        #[doc = concat!("Get a mutable reference to ", $what, ".")]
+        pub fn get_mut(&mut self) -> &mut $inner {
+            &mut self.inner
+        }
+
+        #[doc = concat!("Get a pinned mutable reference to ", $what, ".")]
+        pub fn get_pin_mut(self: ::core::pin::Pin<&mut Self>) -> ::core::pin::Pin<&mut $inner> {
+            self.project().inner
+        }
+
+        #[doc = concat!("Consume the wrapper returning ", $what, ".")]
+        pub fn into_inner(self) -> $inner {
+            self.inner
+        }
+    };
+}
+
+/// Sink methods of a wrapper forwarded to the inner sink unchanged, emitting the event of the
+/// wrapper. Expanded in its `Sink<Item>` impl.
+macro_rules! forward_sink {
+    (@ $wrapper:literal, $method:ident, $name:literal) => {
+        fn $method(
+            self: ::core::pin::Pin<&mut Self>,
+            cx: &mut ::core::task::Context<'_>,
+        ) -> ::core::task::Poll<::core::result::Result<(), Self::Error>> {
+            let poll = self.project().inner.$method(cx);
+            wrapper_event!($wrapper, $name, &poll);
+            poll
+        }
+    };
+    (@ $wrapper:literal, poll_ready) => {
+        forward_sink!(@ $wrapper, poll_ready, "poll_ready");
+    };
+    (@ $wrapper:literal, start_send) => {
+        fn start_send(
+            self: ::core::pin::Pin<&mut Self>,
+            item: Item,
+        ) -> ::core::result::Result<(), Self::Error> {
+            let res = self.project().inner.start_send(item);
+            wrapper_event!($wrapper, "start_send", &res);
+            res
+        }
+    };
+    (@ $wrapper:literal, poll_flush) => {
+        forward_sink!(@ $wrapper, poll_flush, "poll_flush");
+    };
+    (@ $wrapper:literal, poll_close) => {
+        forward_sink!(@ $wrapper, poll_close, "poll_close");
+    };
+    ($wrapper:literal, $($method:ident),+ $(,)?) => {
+        $(forward_sink!(@ $wrapper, $method);)+
+    };
+}
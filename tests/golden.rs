@@ -0,0 +1,50 @@
+//! Golden-file test of the trace JSON format.
+//!
+//! Run with `UPDATE_GOLDEN=1` to regenerate the golden files after an intentional change.
+#![cfg(feature = "serde")]
+
+use futures::{executor::block_on, stream, StreamExt};
+use futures_test_sink::{Recorder, Script, SinkMock};
+use std::{env, fs, path::PathBuf, task::Poll};
+
+fn check_golden(name: &str, json: &str) {
+    let path: PathBuf = [env!("CARGO_MANIFEST_DIR"), "tests", "golden", name]
+        .iter()
+        .collect();
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, json).unwrap();
+        return;
+    }
+    let golden = fs::read_to_string(&path)
+        .unwrap_or_else(|e| panic!("can't read {}: {}", path.display(), e));
+    assert!(
+        golden == json,
+        "trace differs from {}, run with UPDATE_GOLDEN=1 if the change is intended\n\
+         golden:\n{}\nactual:\n{}",
+        path.display(),
+        golden,
+        json
+    );
+}
+
+#[test]
+fn forward_with_flush_error() {
+    let feedback = vec![
+        Poll::Ready(Ok(())),
+        Poll::Pending,
+        Poll::Ready(Err("connection reset")),
+    ];
+    let mock = SinkMock::with_flush_feedback(feedback.into_iter());
+    let mut recorder = Recorder::new(mock);
+    let items = stream::iter(vec![Ok(1u8), Ok(2), Ok(3)]);
+    assert_eq!(
+        block_on(items.forward(&mut recorder)),
+        Err("connection reset")
+    );
+
+    let json = recorder.script().to_json();
+    check_golden("forward_with_flush_error.json", &json);
+    // golden files stay loadable
+    let loaded: Script<u8, String> = Script::from_json(&json).unwrap();
+    assert_eq!(loaded.steps().len(), recorder.script().steps().len());
+}